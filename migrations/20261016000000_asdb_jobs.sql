@@ -0,0 +1,41 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Job queue tables, written defensively so the migration also applies to
+-- databases set up with the old out-of-band scripts.
+
+CREATE SCHEMA IF NOT EXISTS asdb_jobs;
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.jobs (
+    id text PRIMARY KEY,
+    jobtype text NOT NULL,
+    status text NOT NULL,
+    runner text,
+    submitted_date timestamp NOT NULL DEFAULT now(),
+    data jsonb NOT NULL,
+    results jsonb NOT NULL,
+    version int NOT NULL DEFAULT 0
+);
+
+ALTER TABLE asdb_jobs.jobs
+    ADD COLUMN IF NOT EXISTS error text,
+    ADD COLUMN IF NOT EXISTS started_date timestamp,
+    ADD COLUMN IF NOT EXISTS finished_date timestamp;
+
+CREATE INDEX IF NOT EXISTS jobs_status_submitted_date_idx
+    ON asdb_jobs.jobs (status, submitted_date);
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.controls (
+    name text PRIMARY KEY,
+    status text NOT NULL,
+    stop_scheduled bool NOT NULL DEFAULT false,
+    version text NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.counters (
+    name text PRIMARY KEY,
+    value bigint NOT NULL DEFAULT 0
+);
+
+INSERT INTO asdb_jobs.counters (name, value) VALUES ('total_jobs', 0)
+    ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,11 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Optional front-end session a job was submitted from, used to list a user's jobs.
+
+ALTER TABLE asdb_jobs.jobs
+    ADD COLUMN IF NOT EXISTS session_id text;
+
+CREATE INDEX IF NOT EXISTS jobs_session_id_submitted_date_idx
+    ON asdb_jobs.jobs (session_id, submitted_date)
+    WHERE session_id IS NOT NULL;
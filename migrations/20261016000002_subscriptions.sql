@@ -0,0 +1,18 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Saved queries the job runner re-executes whenever the database contents change,
+-- or additionally every interval_hours if that is set.
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.subscriptions (
+    id text PRIMARY KEY,
+    query jsonb NOT NULL,
+    interval_hours int,
+    webhook_url text,
+    session_id text,
+    created_date timestamp NOT NULL DEFAULT now(),
+    -- database fingerprint the query last ran against
+    db_version text,
+    last_run timestamp,
+    last_job_id text
+);
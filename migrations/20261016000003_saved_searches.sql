@@ -0,0 +1,10 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Search queries stored under a short token for sharing search links.
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.saved_searches (
+    token text PRIMARY KEY,
+    query jsonb NOT NULL,
+    created_date timestamp NOT NULL DEFAULT now()
+);
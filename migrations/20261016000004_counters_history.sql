@@ -0,0 +1,12 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Daily number of job submissions, completions and failures per job type.
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.counters_history (
+    day date NOT NULL,
+    jobtype text NOT NULL,
+    event text NOT NULL,
+    count bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, jobtype, event)
+);
@@ -0,0 +1,12 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Data release information, one row per import, filled in by the import tooling.
+
+CREATE SCHEMA IF NOT EXISTS antismash;
+
+CREATE TABLE IF NOT EXISTS antismash.db_metadata (
+    release text PRIMARY KEY,
+    antismash_version text NOT NULL,
+    import_date timestamp NOT NULL DEFAULT now()
+);
@@ -0,0 +1,12 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Time an assembly was imported, filled in by the default for new imports. Assemblies
+-- imported before this column existed have no timestamp.
+
+ALTER TABLE antismash.genomes
+    ADD COLUMN IF NOT EXISTS added_on timestamp;
+ALTER TABLE antismash.genomes
+    ALTER COLUMN added_on SET DEFAULT now();
+CREATE INDEX IF NOT EXISTS genomes_added_on_idx
+    ON antismash.genomes (added_on);
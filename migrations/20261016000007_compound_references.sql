@@ -0,0 +1,20 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Known natural products, e.g. from NPAtlas, and the MIBiG entries of their gene clusters.
+-- Regions are linked to the compounds through their KnownClusterBlast hits.
+-- Filled by the import-compounds command.
+
+CREATE TABLE IF NOT EXISTS antismash.compound_references (
+    compound_reference_id serial PRIMARY KEY,
+    source text NOT NULL,
+    accession text NOT NULL,
+    name text NOT NULL,
+    smiles text,
+    mibig_accession text NOT NULL,
+    UNIQUE (source, accession, mibig_accession)
+);
+CREATE INDEX IF NOT EXISTS compound_references_mibig_accession_idx
+    ON antismash.compound_references (mibig_accession);
+CREATE INDEX IF NOT EXISTS compound_references_name_idx
+    ON antismash.compound_references (lower(name));
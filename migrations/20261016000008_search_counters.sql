@@ -0,0 +1,14 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Daily number of searches per search type, query category and return type.
+-- A search using several categories is counted once for each of them.
+
+CREATE TABLE IF NOT EXISTS asdb_jobs.search_counters (
+    day date NOT NULL,
+    search_type text NOT NULL,
+    category text NOT NULL,
+    return_type text NOT NULL,
+    count bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (day, search_type, category, return_type)
+);
@@ -0,0 +1,19 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Announce committed job updates on the asdb_job_updates channel, with the job ID
+-- as payload, so the API can push status changes to clients watching a job.
+
+CREATE OR REPLACE FUNCTION asdb_jobs.notify_job_update() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('asdb_job_updates', NEW.id);
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS jobs_notify_update ON asdb_jobs.jobs;
+CREATE TRIGGER jobs_notify_update
+    AFTER UPDATE ON asdb_jobs.jobs
+    FOR EACH ROW
+    WHEN (OLD.status IS DISTINCT FROM NEW.status OR OLD.version IS DISTINCT FROM NEW.version)
+    EXECUTE FUNCTION asdb_jobs.notify_job_update();
@@ -0,0 +1,19 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Announce newly submitted jobs on the asdb_new_jobs channel, with the job ID as payload,
+-- so idle runners pick them up right away instead of on their next poll.
+
+CREATE OR REPLACE FUNCTION asdb_jobs.notify_new_job() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('asdb_new_jobs', NEW.id);
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS jobs_notify_insert ON asdb_jobs.jobs;
+CREATE TRIGGER jobs_notify_insert
+    AFTER INSERT ON asdb_jobs.jobs
+    FOR EACH ROW
+    WHEN (NEW.status = 'pending')
+    EXECUTE FUNCTION asdb_jobs.notify_new_job();
@@ -0,0 +1,9 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Jobs failing with transient errors are re-queued, runners don't claim them again
+-- before retry_after.
+
+ALTER TABLE asdb_jobs.jobs
+    ADD COLUMN IF NOT EXISTS retries int NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS retry_after timestamp;
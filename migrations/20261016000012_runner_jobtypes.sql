@@ -0,0 +1,7 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Job types a runner picks up, NULL for runners handling all of them.
+
+ALTER TABLE asdb_jobs.controls
+    ADD COLUMN IF NOT EXISTS jobtypes text[];
@@ -0,0 +1,9 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Runner details shown by the admin workers endpoint, kept up to date by the runners.
+
+ALTER TABLE asdb_jobs.controls
+    ADD COLUMN IF NOT EXISTS host text,
+    ADD COLUMN IF NOT EXISTS current_job text,
+    ADD COLUMN IF NOT EXISTS last_heartbeat timestamp;
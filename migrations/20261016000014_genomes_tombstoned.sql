@@ -0,0 +1,8 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Tombstoned assemblies are kept in the database but hidden from searches, stats and
+-- the taxonomy trees.
+
+ALTER TABLE antismash.genomes
+    ADD COLUMN IF NOT EXISTS tombstoned bool NOT NULL DEFAULT false;
@@ -0,0 +1,216 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use serde::Serialize;
+
+use crate::{Error, Result};
+
+/// Most windows a GC skew is computed for, to keep the replies plottable
+pub const MAX_WINDOWS: usize = 10_000;
+
+/// Bases in the order of the codon table
+const BASES: [u8; 4] = [b'T', b'C', b'A', b'G'];
+
+/// Amino acids of the standard/bacterial genetic code (tables 1 and 11), codons in TCAG order
+const AMINO_ACIDS: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct GcWindows {
+    pub window: usize,
+    pub step: usize,
+    /// Start of each window, relative to the start of the sequence
+    pub positions: Vec<usize>,
+    pub gc_content: Vec<f64>,
+    /// (G - C) / (G + C) of each window
+    pub gc_skew: Vec<f64>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct CodonUsage {
+    /// Number of codons counted, codons with ambiguous bases are not counted
+    pub total: usize,
+    pub codons: Vec<String>,
+    pub amino_acids: Vec<char>,
+    pub counts: Vec<usize>,
+    pub per_thousand: Vec<f64>,
+}
+
+/// G and C counts of a sequence, and the number of unambiguous bases
+fn base_counts(sequence: &[u8]) -> (usize, usize, usize) {
+    let (mut g, mut c, mut total) = (0, 0, 0);
+    for base in sequence {
+        match base.to_ascii_uppercase() {
+            b'G' => g += 1,
+            b'C' => c += 1,
+            b'A' | b'T' | b'U' => {}
+            _ => continue,
+        }
+        total += 1;
+    }
+    (g, c, total)
+}
+
+fn skew(g: usize, c: usize) -> f64 {
+    (g as f64 - c as f64) / (g + c).max(1) as f64
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+/// Fraction of G and C among the unambiguous bases of a sequence
+pub fn gc_content(sequence: &str) -> f64 {
+    let (g, c, total) = base_counts(sequence.as_bytes());
+    ratio(g + c, total)
+}
+
+/// GC skew, (G - C) / (G + C), of a sequence
+pub fn gc_skew(sequence: &str) -> f64 {
+    let (g, c, _) = base_counts(sequence.as_bytes());
+    skew(g, c)
+}
+
+/// GC content and GC skew of sliding windows over a sequence. The last window is
+/// shorter if the windows don't fit the sequence exactly.
+pub fn sliding_gc(sequence: &str, window: usize, step: usize) -> Result<GcWindows> {
+    if window == 0 || step == 0 {
+        return Err(Error::InvalidRequest(
+            "GC skew window and step need to be positive".to_string(),
+        ));
+    }
+    let sequence = sequence.as_bytes();
+    let windows = sequence.len().saturating_sub(window).div_ceil(step) + 1;
+    if windows > MAX_WINDOWS {
+        return Err(Error::InvalidRequest(format!(
+            "GC skew would need {windows} windows, increase the step to get at most {MAX_WINDOWS}"
+        )));
+    }
+
+    let mut gc = GcWindows {
+        window,
+        step,
+        ..Default::default()
+    };
+    if sequence.is_empty() {
+        return Ok(gc);
+    }
+    for start in (0..windows).map(|i| i * step) {
+        let end = (start + window).min(sequence.len());
+        let (g, c, total) = base_counts(&sequence[start..end]);
+        gc.positions.push(start);
+        gc.gc_content.push(ratio(g + c, total));
+        gc.gc_skew.push(skew(g, c));
+    }
+    Ok(gc)
+}
+
+fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'U' => Some(0),
+        base => BASES.iter().position(|b| *b == base),
+    }
+}
+
+/// Codon usage of in-frame coding sequences, codons in TCAG order
+pub fn codon_usage<'a>(sequences: impl IntoIterator<Item = &'a str>) -> CodonUsage {
+    let mut counts = vec![0; 64];
+    for sequence in sequences {
+        for codon in sequence.as_bytes().chunks_exact(3) {
+            let index = codon.iter().try_fold(0, |index, base| {
+                base_index(*base).map(|position| index * 4 + position)
+            });
+            if let Some(index) = index {
+                counts[index] += 1;
+            }
+        }
+    }
+
+    let total = counts.iter().sum();
+    let codons = (0..64)
+        .map(|i| {
+            [i / 16, i / 4 % 4, i % 4]
+                .iter()
+                .map(|&b| BASES[b] as char)
+                .collect()
+        })
+        .collect();
+    CodonUsage {
+        total,
+        codons,
+        amino_acids: AMINO_ACIDS.iter().map(|&aa| aa as char).collect(),
+        per_thousand: counts.iter().map(|&c| ratio(c, total) * 1000.0).collect(),
+        counts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gc_content() {
+        let tests = [
+            ("GGCC", 1.0),
+            ("atgc", 0.5),
+            ("ATNNGC", 0.5),
+            ("AT", 0.0),
+            ("", 0.0),
+            ("NNN", 0.0),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(gc_content(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_gc_skew() {
+        let tests = [("GGGC", 0.5), ("ccg", -1.0 / 3.0), ("ATAT", 0.0), ("", 0.0)];
+        for (input, expected) in tests {
+            assert!((gc_skew(input) - expected).abs() < 1e-9, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_sliding_gc() {
+        let gc = sliding_gc("GGGGCCCCAT", 4, 3).unwrap();
+        assert_eq!(gc.positions, [0, 3, 6]);
+        assert_eq!(gc.gc_content, [1.0, 1.0, 0.5]);
+        assert_eq!(gc.gc_skew, [1.0, -0.5, -1.0]);
+
+        let short = sliding_gc("GGC", 10, 5).unwrap();
+        assert_eq!(short.positions, [0]);
+        assert_eq!(short.gc_skew[0], gc_skew("GGC"));
+
+        assert!(sliding_gc("", 10, 5).unwrap().positions.is_empty());
+        assert!(sliding_gc("ATGC", 0, 1).is_err());
+        assert!(sliding_gc("ATGC", 1, 0).is_err());
+        assert!(sliding_gc(&"A".repeat(MAX_WINDOWS + 1), 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_codon_usage() {
+        let usage = codon_usage(["ATGTTTtaa", "ATGNNNTT", "AUG"]);
+        assert_eq!(usage.total, 5);
+        assert_eq!(usage.codons.len(), 64);
+        assert_eq!(usage.codons[0], "TTT");
+        assert_eq!(usage.codons[63], "GGG");
+
+        let find = |codon: &str| usage.codons.iter().position(|c| c == codon).unwrap();
+        let atg = find("ATG");
+        assert_eq!(usage.amino_acids[atg], 'M');
+        assert_eq!(usage.counts[atg], 3);
+        assert_eq!(usage.per_thousand[atg], 600.0);
+        assert_eq!(usage.counts[find("TTT")], 1);
+        assert_eq!(usage.amino_acids[find("TAA")], '*');
+        assert_eq!(usage.amino_acids[find("TGG")], 'W');
+        assert_eq!(usage.counts.iter().sum::<usize>(), usage.total);
+
+        let none: [&str; 0] = [];
+        let empty = codon_usage(none);
+        assert_eq!(empty.total, 0);
+        assert!(empty.per_thousand.iter().all(|&f| f == 0.0));
+    }
+}
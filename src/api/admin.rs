@@ -0,0 +1,135 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+    routing::{get, post, put},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+
+use super::extract;
+use super::go::sanitise_id;
+use super::normalize::Routes;
+use super::region::audit::audit;
+use super::ApiConfig;
+use crate::models::control::Control;
+use crate::query::{Query, SearchType};
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/admin/audit", post(audit_query))
+        .route("/api/admin/assembly/:identifier/tombstone", put(tombstone))
+        .route("/api/admin/workers", get(workers))
+}
+
+/// Extractor guarding the admin endpoints, requires an `Authorization: Bearer <token>` header
+/// matching the configured admin token
+#[derive(Debug)]
+pub struct Admin;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Admin
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self> {
+        let Some(expected) = parts
+            .extensions
+            .get::<ApiConfig>()
+            .and_then(|c| c.admin_token.as_deref())
+        else {
+            return Err(Error::Unauthorized);
+        };
+
+        let provided = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+
+        // Compare in constant time, so response times don't leak how much of the token matched
+        let matches = provided.is_some_and(|p| bool::from(p.as_bytes().ct_eq(expected.as_bytes())));
+        if !matches {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(Admin)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct AuditPayload {
+    pub query: Query,
+}
+
+async fn audit_query(
+    _admin: Admin,
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<AuditPayload>,
+) -> Result<Json<Value>> {
+    if req.query.search_type != SearchType::Region {
+        return Err(Error::NotImplementedError(format!(
+            "{:?} search audits",
+            req.query.search_type
+        )));
+    }
+
+    let report = audit(&pool, &req.query).await?;
+    Ok(Json(json!(report)))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TombstonePayload {
+    pub tombstoned: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct TombstoneReply {
+    pub assembly_id: String,
+    pub tombstoned: bool,
+}
+
+async fn tombstone(
+    _admin: Admin,
+    Extension(pool): Extension<PgPool>,
+    extract::Path(identifier): extract::Path<String>,
+    extract::Json(req): extract::Json<TombstonePayload>,
+) -> Result<Json<Value>> {
+    let assembly_id = sanitise_id(&identifier);
+    let row = sqlx::query!(
+        r#"
+        UPDATE antismash.genomes SET tombstoned = $2
+        WHERE assembly_id = $1
+        RETURNING assembly_id, tombstoned"#,
+        assembly_id,
+        req.tombstoned,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    eprintln!(
+        "->> Set tombstoned={} on assembly {}",
+        row.tombstoned, row.assembly_id
+    );
+
+    Ok(Json(json!(TombstoneReply {
+        assembly_id: row.assembly_id,
+        tombstoned: row.tombstoned,
+    })))
+}
+
+/// Registered job runners, with their last sign of life and current job
+async fn workers(_admin: Admin, Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
+    let workers = Control::list_workers(&pool).await?;
+    Ok(Json(json!({ "workers": workers })))
+}
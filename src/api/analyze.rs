@@ -0,0 +1,587 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use axum::{
+    routing::{get, post},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::extract;
+use super::normalize::Routes;
+use super::region::{drop_tombstoned, query_ids, resolve_versions, sorted_unique};
+use super::sequence::reverse_complement;
+use super::taxa::TaxRank;
+use crate::analysis::{codon_usage, gc_content, gc_skew, sliding_gc, CodonUsage, GcWindows};
+use crate::models::location::{SimpleLocation, Strand};
+use crate::query::{Query, SearchType};
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/analyze/composition", post(composition))
+        .route("/api/analyze/go-enrichment", post(go_enrichment))
+        .route("/api/analyze/density", get(density))
+}
+
+/// Largest adjusted p-value of the returned terms, if none is requested
+pub const DEFAULT_MAX_P_VALUE: f64 = 0.05;
+
+#[derive(Debug, Deserialize)]
+pub struct EnrichmentRequest {
+    /// Region search whose results are analysed
+    pub query: Option<Query>,
+    /// Regions to analyse if no query is given
+    #[serde(default)]
+    pub region_ids: Vec<i32>,
+    /// Largest Benjamini-Hochberg adjusted p-value of the returned terms
+    pub max_p_value: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EnrichedTerm {
+    pub identifier: String,
+    pub description: String,
+    /// Regions of the set with the term
+    pub count: i64,
+    /// Regions of the database with the term
+    pub background_count: i64,
+    pub fold_enrichment: f64,
+    pub p_value: f64,
+    pub adjusted_p_value: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnrichmentResult {
+    pub set_size: usize,
+    pub background_size: usize,
+    pub terms: Vec<EnrichedTerm>,
+}
+
+/// GO terms over-represented in a set of regions compared to all regions in the database,
+/// using a one-sided Fisher's exact test on the number of regions with each term
+async fn go_enrichment(
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<EnrichmentRequest>,
+) -> Result<Json<Value>> {
+    let ids = match req.query {
+        Some(mut query) => {
+            if query.search_type != SearchType::Region {
+                return Err(Error::InvalidRequest(
+                    "GO enrichment needs a region search".to_string(),
+                ));
+            }
+            resolve_versions(&pool, &mut query.terms, query.resolve_versions).await?;
+            query_ids(&pool, &query).await?
+        }
+        None => sorted_unique(&req.region_ids),
+    };
+    // The background only has current regions, so the set can't have any others either
+    let ids = drop_tombstoned(&pool, &ids).await?;
+    if ids.is_empty() {
+        return Err(Error::InvalidRequest("No regions to analyse".to_string()));
+    }
+
+    let counts = sqlx::query!(
+        r#"
+        SELECT go_id, identifier, description, COUNT(DISTINCT region_id) AS "count!"
+        FROM antismash.cdss
+        JOIN antismash.pfam_domains USING (cds_id)
+        JOIN antismash.pfam_go_entries USING (pfam_domain_id)
+        JOIN antismash.gene_ontologies USING (go_id)
+        WHERE region_id = ANY($1)
+        GROUP BY go_id, identifier, description"#,
+        &ids,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let go_ids: Vec<i32> = counts.iter().map(|c| c.go_id).collect();
+    let background = sqlx::query!(
+        r#"
+        SELECT go_id, COUNT(DISTINCT region_id) AS "count!"
+        FROM antismash.cdss
+        JOIN antismash.regions USING (region_id)
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        JOIN antismash.pfam_domains USING (cds_id)
+        JOIN antismash.pfam_go_entries USING (pfam_domain_id)
+        WHERE go_id = ANY($1) AND tombstoned IS FALSE
+        GROUP BY go_id"#,
+        &go_ids,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let background_size = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!" FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE tombstoned IS FALSE"#,
+    )
+    .fetch_one(&pool)
+    .await? as usize;
+
+    let log_factorials = log_factorials(background_size);
+    let set_size = ids.len();
+    let mut terms: Vec<EnrichedTerm> = counts
+        .into_iter()
+        .filter_map(|c| {
+            let background_count = background.iter().find(|b| b.go_id == c.go_id)?.count;
+            Some(EnrichedTerm {
+                fold_enrichment: (c.count as f64 / set_size as f64)
+                    / (background_count as f64 / background_size as f64),
+                p_value: fisher_greater(
+                    c.count as usize,
+                    set_size,
+                    background_count as usize,
+                    background_size,
+                    &log_factorials,
+                ),
+                adjusted_p_value: 1.0,
+                identifier: c.identifier,
+                description: c.description,
+                count: c.count,
+                background_count,
+            })
+        })
+        .collect();
+
+    let p_values: Vec<f64> = terms.iter().map(|t| t.p_value).collect();
+    for (term, adjusted) in terms.iter_mut().zip(benjamini_hochberg(&p_values)) {
+        term.adjusted_p_value = adjusted;
+    }
+
+    let max_p_value = req.max_p_value.unwrap_or(DEFAULT_MAX_P_VALUE);
+    terms.retain(|t| t.fold_enrichment > 1.0 && t.adjusted_p_value <= max_p_value);
+    terms.sort_by(|a, b| {
+        a.p_value
+            .total_cmp(&b.p_value)
+            .then_with(|| a.identifier.cmp(&b.identifier))
+    });
+
+    Ok(Json(json!(EnrichmentResult {
+        set_size,
+        background_size,
+        terms,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DensityParams {
+    /// Taxonomic rank to aggregate the assemblies at
+    pub rank: String,
+}
+
+/// Size and region count of an assembly
+#[derive(Debug, sqlx::FromRow)]
+pub struct GenomeDensity {
+    pub taxon: Option<String>,
+    pub size: i64,
+    pub regions: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TaxonDensity {
+    /// Name of the taxon at the requested rank, null for unclassified assemblies
+    pub taxon: Option<String>,
+    pub genomes: usize,
+    pub regions: i64,
+    pub megabases: f64,
+    /// Regions per megabase of all assemblies together
+    pub density: f64,
+    /// Mean and median of the regions per megabase of the single assemblies
+    pub mean_density: f64,
+    pub median_density: f64,
+}
+
+/// Regions per megabase of the assemblies of every taxon at a rank
+async fn density(
+    Extension(pool): Extension<PgPool>,
+    extract::Query(params): extract::Query<DensityParams>,
+) -> Result<Json<Value>> {
+    let Ok(rank) = TaxRank::from_str(&params.rank.to_lowercase()) else {
+        return Err(Error::InvalidRequest(format!(
+            "Invalid rank {}",
+            params.rank
+        )));
+    };
+
+    let sql = format!(
+        r#"
+        WITH sizes AS (
+            SELECT genome_id, SUM(length(dna))::bigint AS size
+            FROM antismash.dna_sequences
+            GROUP BY genome_id
+        ), counts AS (
+            SELECT genome_id, COUNT(region_id) AS regions
+            FROM antismash.regions
+            JOIN antismash.dna_sequences USING (accession)
+            GROUP BY genome_id
+        )
+        SELECT {column} AS taxon, size, COALESCE(regions, 0) AS regions
+        FROM antismash.genomes
+        JOIN antismash.taxa USING (tax_id)
+        JOIN sizes USING (genome_id)
+        LEFT JOIN counts USING (genome_id)
+        WHERE tombstoned IS FALSE AND size > 0"#,
+        column = rank.column(),
+    );
+    let genomes = sqlx::query_as::<_, GenomeDensity>(&sql)
+        .fetch_all(&pool)
+        .await?;
+
+    Ok(Json(json!({
+        "rank": rank.as_ref(),
+        "taxa": summarise_density(genomes),
+    })))
+}
+
+/// Aggregate the assembly densities per taxon, ordered by taxon name
+pub fn summarise_density(genomes: Vec<GenomeDensity>) -> Vec<TaxonDensity> {
+    let mut by_taxon: BTreeMap<Option<String>, Vec<GenomeDensity>> = BTreeMap::new();
+    for genome in genomes {
+        let taxon = genome.taxon.clone().filter(|t| !t.is_empty());
+        by_taxon.entry(taxon).or_default().push(genome);
+    }
+
+    by_taxon
+        .into_iter()
+        .map(|(taxon, genomes)| {
+            let regions: i64 = genomes.iter().map(|g| g.regions).sum();
+            let megabases: f64 = genomes.iter().map(|g| g.size as f64 / 1e6).sum();
+            let mut densities: Vec<f64> = genomes
+                .iter()
+                .map(|g| g.regions as f64 / (g.size as f64 / 1e6))
+                .collect();
+            densities.sort_by(f64::total_cmp);
+            let middle = densities.len() / 2;
+            let median_density = if densities.len().is_multiple_of(2) {
+                (densities[middle - 1] + densities[middle]) / 2.0
+            } else {
+                densities[middle]
+            };
+            TaxonDensity {
+                taxon,
+                genomes: genomes.len(),
+                regions,
+                megabases,
+                density: regions as f64 / megabases,
+                mean_density: densities.iter().sum::<f64>() / densities.len() as f64,
+                median_density,
+            }
+        })
+        .collect()
+}
+
+/// Default size of the sliding GC windows over a region
+pub const DEFAULT_GC_WINDOW: usize = 1000;
+
+/// Most genes analysed in a single request
+pub const MAX_COMPOSITION_GENES: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct CompositionRequest {
+    /// Region to analyse, including all of its genes
+    pub region_id: Option<i32>,
+    /// Genes to analyse if no region is given
+    #[serde(default)]
+    pub cds_ids: Vec<i32>,
+    /// Size of the GC windows, defaults to `DEFAULT_GC_WINDOW`
+    pub window: Option<usize>,
+    /// Distance between the GC windows, defaults to a quarter of the window size
+    pub step: Option<usize>,
+}
+
+/// Per-gene values, as parallel arrays in the order of the CDS IDs
+#[derive(Debug, Default, Serialize)]
+pub struct GeneComposition {
+    pub cds_ids: Vec<i32>,
+    pub locus_tags: Vec<Option<String>>,
+    pub gc_content: Vec<f64>,
+    pub gc_skew: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Composition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_id: Option<i32>,
+    /// Length of the analysed DNA, the region or the coding sequences of the genes
+    pub length: usize,
+    pub gc_content: f64,
+    pub gc_skew: f64,
+    /// GC values along the region, with window positions relative to the record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub windows: Option<GcWindows>,
+    pub genes: GeneComposition,
+    pub codon_usage: CodonUsage,
+    /// Genes with locations that can't be analysed, like ones spanning the origin
+    pub skipped: Vec<i32>,
+}
+
+/// GC content, GC skew and codon usage of a region or a set of genes, computed from
+/// the stored DNA of the records
+async fn composition(
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<CompositionRequest>,
+) -> Result<Json<Value>> {
+    let window = req.window.unwrap_or(DEFAULT_GC_WINDOW);
+    let step = req.step.unwrap_or((window / 4).max(1));
+
+    let region = match req.region_id {
+        Some(region_id) => Some(
+            sqlx::query!(
+                r#"
+        SELECT start_pos, SUBSTRING(dna FROM start_pos + 1 FOR end_pos - start_pos) AS "sequence!"
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+                region_id,
+            )
+            .fetch_optional(&pool)
+            .await?
+            .ok_or(Error::NotFound)?,
+        ),
+        None => None,
+    };
+
+    let cds_ids = sorted_unique(&req.cds_ids);
+    if region.is_none() && (cds_ids.is_empty() || cds_ids.len() > MAX_COMPOSITION_GENES) {
+        return Err(Error::InvalidRequest(format!(
+            "Composition needs a region or 1 to {MAX_COMPOSITION_GENES} genes"
+        )));
+    }
+
+    let genes = sqlx::query!(
+        r#"
+        SELECT cds_id, locus_tag, c.location, accession
+        FROM antismash.cdss AS c
+        JOIN antismash.regions USING (region_id)
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE (region_id = $1 OR cds_id = ANY($2)) AND tombstoned IS FALSE
+        ORDER BY cds_id"#,
+        req.region_id,
+        &cds_ids,
+    )
+    .fetch_all(&pool)
+    .await?;
+    if region.is_none() && genes.is_empty() {
+        return Err(Error::InvalidRequest("No genes to analyse".to_string()));
+    }
+
+    let mut skipped = Vec::new();
+    let mut located = Vec::with_capacity(genes.len());
+    for gene in genes {
+        match SimpleLocation::parse(&gene.location) {
+            Ok(("", location)) if location.start < location.end => located.push((gene, location)),
+            _ => skipped.push(gene.cds_id),
+        }
+    }
+
+    let sequences = sqlx::query!(
+        r#"
+        SELECT t.cds_id AS "cds_id!",
+            SUBSTRING(dna FROM t.start_pos + 1 FOR t.end_pos - t.start_pos) AS "sequence!"
+        FROM unnest($1::int[], $2::text[], $3::int[], $4::int[])
+            AS t(cds_id, accession, start_pos, end_pos)
+        JOIN antismash.dna_sequences USING (accession)"#,
+        &located.iter().map(|(g, _)| g.cds_id).collect::<Vec<i32>>(),
+        &located
+            .iter()
+            .map(|(g, _)| g.accession.to_owned())
+            .collect::<Vec<String>>(),
+        &located
+            .iter()
+            .map(|(_, l)| l.start as i32)
+            .collect::<Vec<i32>>(),
+        &located
+            .iter()
+            .map(|(_, l)| l.end as i32)
+            .collect::<Vec<i32>>(),
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut genes = GeneComposition::default();
+    let mut coding = Vec::with_capacity(located.len());
+    for (gene, location) in located {
+        let Some(row) = sequences.iter().find(|s| s.cds_id == gene.cds_id) else {
+            skipped.push(gene.cds_id);
+            continue;
+        };
+        let sequence = match location.strand {
+            Strand::Reverse => reverse_complement(&row.sequence),
+            _ => row.sequence.to_owned(),
+        };
+        genes.cds_ids.push(gene.cds_id);
+        genes.locus_tags.push(gene.locus_tag);
+        genes.gc_content.push(gc_content(&sequence));
+        genes.gc_skew.push(gc_skew(&sequence));
+        coding.push(sequence);
+    }
+    skipped.sort_unstable();
+
+    let (analysed, windows) = match &region {
+        Some(region) => {
+            let mut windows = sliding_gc(&region.sequence, window, step)?;
+            for position in windows.positions.iter_mut() {
+                *position += region.start_pos as usize;
+            }
+            (region.sequence.to_owned(), Some(windows))
+        }
+        None => (coding.concat(), None),
+    };
+
+    Ok(Json(json!(Composition {
+        region_id: req.region_id,
+        length: analysed.len(),
+        gc_content: gc_content(&analysed),
+        gc_skew: gc_skew(&analysed),
+        windows,
+        codon_usage: codon_usage(coding.iter().map(String::as_str)),
+        genes,
+        skipped,
+    })))
+}
+
+/// `ln(i!)` for all `i` up to `max`
+fn log_factorials(max: usize) -> Vec<f64> {
+    let mut table = Vec::with_capacity(max + 1);
+    table.push(0.0);
+    for i in 1..=max {
+        table.push(table[i - 1] + (i as f64).ln());
+    }
+    table
+}
+
+fn log_binomial(n: usize, k: usize, log_factorials: &[f64]) -> f64 {
+    log_factorials[n] - log_factorials[k] - log_factorials[n - k]
+}
+
+/// One-sided Fisher's exact test, the probability of drawing at least `hits` elements with
+/// a property when drawing `draws` of `total` elements, `with_property` of which have it.
+/// `log_factorials` needs to reach up to `total`.
+pub fn fisher_greater(
+    hits: usize,
+    draws: usize,
+    with_property: usize,
+    total: usize,
+    log_factorials: &[f64],
+) -> f64 {
+    let without_property = total - with_property;
+    let log_all = log_binomial(total, draws, log_factorials);
+    let p: f64 = (hits..=draws.min(with_property))
+        .filter(|x| draws - x <= without_property)
+        .map(|x| {
+            (log_binomial(with_property, x, log_factorials)
+                + log_binomial(without_property, draws - x, log_factorials)
+                - log_all)
+                .exp()
+        })
+        .sum();
+    p.min(1.0)
+}
+
+/// Benjamini-Hochberg false discovery rate adjustment, in the order of the input
+pub fn benjamini_hochberg(p_values: &[f64]) -> Vec<f64> {
+    let count = p_values.len();
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by(|&a, &b| p_values[a].total_cmp(&p_values[b]));
+
+    let mut adjusted = vec![1.0; count];
+    let mut smallest: f64 = 1.0;
+    for (rank, &i) in order.iter().enumerate().rev() {
+        smallest = smallest.min(p_values[i] * count as f64 / (rank + 1) as f64);
+        adjusted[i] = smallest;
+    }
+    adjusted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fisher_greater() {
+        let table = log_factorials(100);
+        let tests = [
+            // Lady tasting tea, all four cups right
+            ((4, 4, 4, 8), 1.0 / 70.0),
+            ((3, 4, 4, 8), 17.0 / 70.0),
+            ((0, 4, 4, 8), 1.0),
+            ((1, 10, 1, 100), 0.1),
+            ((2, 3, 2, 4), 0.5),
+        ];
+        for ((hits, draws, with_property, total), expected) in tests {
+            let p = fisher_greater(hits, draws, with_property, total, &table);
+            assert!(
+                (p - expected).abs() < 1e-9,
+                "{hits} {draws}: {p} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_summarise_density() {
+        let genome = |taxon: Option<&str>, size, regions| GenomeDensity {
+            taxon: taxon.map(str::to_string),
+            size,
+            regions,
+        };
+        let genomes = vec![
+            genome(Some("Streptomyces"), 8_000_000, 32),
+            genome(None, 2_000_000, 1),
+            genome(Some("Streptomyces"), 2_000_000, 2),
+            genome(Some(""), 1_000_000, 3),
+            genome(Some("Amycolatopsis"), 10_000_000, 30),
+        ];
+        let expected = vec![
+            TaxonDensity {
+                taxon: None,
+                genomes: 2,
+                regions: 4,
+                megabases: 3.0,
+                density: 4.0 / 3.0,
+                mean_density: 1.75,
+                median_density: 1.75,
+            },
+            TaxonDensity {
+                taxon: Some("Amycolatopsis".to_string()),
+                genomes: 1,
+                regions: 30,
+                megabases: 10.0,
+                density: 3.0,
+                mean_density: 3.0,
+                median_density: 3.0,
+            },
+            TaxonDensity {
+                taxon: Some("Streptomyces".to_string()),
+                genomes: 2,
+                regions: 34,
+                megabases: 10.0,
+                density: 3.4,
+                mean_density: 2.5,
+                median_density: 2.5,
+            },
+        ];
+        assert_eq!(summarise_density(genomes), expected);
+    }
+
+    #[test]
+    fn test_benjamini_hochberg() {
+        let adjusted = benjamini_hochberg(&[0.01, 0.04, 0.03, 0.2]);
+        let expected = [0.04, 0.16 / 3.0, 0.16 / 3.0, 0.2];
+        for (a, e) in adjusted.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{adjusted:?}");
+        }
+        assert!(benjamini_hochberg(&[]).is_empty());
+    }
+}
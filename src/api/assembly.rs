@@ -0,0 +1,200 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{routing::get, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::extract;
+use super::go::{canonical_id, VersionParams};
+use super::normalize::Routes;
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/assembly/:identifier/summary", get(summary))
+        .route("/api/genomes/latest", get(latest_genomes))
+}
+
+const DEFAULT_LATEST_LIMIT: i64 = 20;
+const MAX_LATEST_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize)]
+pub struct Taxonomy {
+    pub ncbi_taxid: Option<i32>,
+    pub superkingdom: Option<String>,
+    pub phylum: Option<String>,
+    pub class: Option<String>,
+    #[serde(rename = "order")]
+    pub taxonomic_order: Option<String>,
+    pub family: Option<String>,
+    pub genus: Option<String>,
+    pub species: Option<String>,
+    pub strain: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeCount {
+    pub term: String,
+    pub description: String,
+    pub category: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssemblySummary {
+    pub assembly_id: String,
+    pub taxonomy: Taxonomy,
+    pub records: i64,
+    pub genome_length: i64,
+    pub regions: i64,
+    pub contig_edge_regions: i64,
+    pub types: Vec<TypeCount>,
+}
+
+async fn summary(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(identifier): extract::Path<String>,
+    extract::Query(params): extract::Query<VersionParams>,
+) -> Result<Json<Value>> {
+    let assembly_id = canonical_id(&pool, identifier, params.resolve_versions).await?;
+    Ok(Json(json!(assembly_summary(&pool, &assembly_id).await?)))
+}
+
+pub async fn assembly_summary(pool: &PgPool, assembly_id: &str) -> Result<AssemblySummary> {
+    let genome = sqlx::query!(
+        r#"
+        SELECT ncbi_taxid, superkingdom, phylum, class, taxonomic_order, family, genus, species, strain,
+            COUNT(accession) AS "records!", COALESCE(SUM(LENGTH(dna)), 0)::bigint AS "genome_length!"
+        FROM antismash.genomes
+        JOIN antismash.taxa USING (tax_id)
+        JOIN antismash.dna_sequences USING (genome_id)
+        WHERE assembly_id = $1 AND tombstoned IS FALSE
+        GROUP BY genomes.genome_id, taxa.tax_id"#,
+        assembly_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let regions = sqlx::query!(
+        r#"
+        SELECT COUNT(region_id) AS "total!",
+            COUNT(region_id) FILTER (WHERE contig_edge) AS "contig_edge!"
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE assembly_id = $1"#,
+        assembly_id,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let types = type_counts(pool, assembly_id).await?;
+
+    Ok(AssemblySummary {
+        assembly_id: assembly_id.to_owned(),
+        taxonomy: Taxonomy {
+            ncbi_taxid: genome.ncbi_taxid,
+            superkingdom: genome.superkingdom,
+            phylum: genome.phylum,
+            class: genome.class,
+            taxonomic_order: genome.taxonomic_order,
+            family: genome.family,
+            genus: genome.genus,
+            species: genome.species,
+            strain: genome.strain,
+        },
+        records: genome.records,
+        genome_length: genome.genome_length,
+        regions: regions.total,
+        contig_edge_regions: regions.contig_edge,
+        types,
+    })
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LatestParams {
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatestGenome {
+    pub assembly_id: String,
+    /// Unknown for assemblies imported before import times were recorded
+    pub added_on: Option<DateTime<Utc>>,
+    pub taxonomy: Taxonomy,
+    pub regions: i64,
+}
+
+/// The most recently imported assemblies, newest first
+async fn latest_genomes(
+    Extension(pool): Extension<PgPool>,
+    extract::Query(params): extract::Query<LatestParams>,
+) -> Result<Json<Value>> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LATEST_LIMIT)
+        .clamp(1, MAX_LATEST_LIMIT);
+
+    // Without import times, newer genome IDs stand in for newer imports
+    let genomes: Vec<LatestGenome> = sqlx::query!(
+        r#"
+        SELECT assembly_id, added_on, ncbi_taxid, superkingdom, phylum, class, taxonomic_order,
+            family, genus, species, strain,
+            (SELECT COUNT(region_id) FROM antismash.regions
+                JOIN antismash.dna_sequences AS d USING (accession)
+                WHERE d.genome_id = g.genome_id) AS "regions!"
+        FROM antismash.genomes AS g
+        JOIN antismash.taxa USING (tax_id)
+        WHERE tombstoned IS FALSE
+        ORDER BY added_on DESC NULLS LAST, genome_id DESC
+        LIMIT $1"#,
+        limit,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| LatestGenome {
+        assembly_id: row.assembly_id,
+        added_on: row.added_on.map(|d| d.and_utc()),
+        taxonomy: Taxonomy {
+            ncbi_taxid: row.ncbi_taxid,
+            superkingdom: row.superkingdom,
+            phylum: row.phylum,
+            class: row.class,
+            taxonomic_order: row.taxonomic_order,
+            family: row.family,
+            genus: row.genus,
+            species: row.species,
+            strain: row.strain,
+        },
+        regions: row.regions,
+    })
+    .collect();
+
+    Ok(Json(json!(genomes)))
+}
+
+/// Count the regions of an assembly per BGC type
+pub async fn type_counts(pool: &PgPool, assembly_id: &str) -> Result<Vec<TypeCount>> {
+    let counts = sqlx::query_as!(
+        TypeCount,
+        r#"
+        SELECT term, t.description, category, COUNT(region_id) AS "count!"
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        JOIN antismash.rel_regions_types USING (region_id)
+        JOIN antismash.bgc_types AS t USING (bgc_type_id)
+        WHERE assembly_id = $1
+        GROUP BY term, t.description, category
+        ORDER BY "count!" DESC, term"#,
+        assembly_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(counts)
+}
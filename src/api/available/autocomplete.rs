@@ -0,0 +1,174 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{Extension, Json};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::terms::terms_for_category;
+use super::AvailableTerm;
+use crate::api::extract;
+use crate::search::category::Category;
+use crate::{Error, Result};
+
+/// Categories searched by the autocompletion, in the order of the returned groups
+pub const AUTOCOMPLETE_CATEGORIES: [Category; 5] = [
+    Category::Type,
+    Category::Genus,
+    Category::Species,
+    Category::Pfam,
+    Category::KnownCluster,
+];
+
+/// Most suggestions returned per category
+pub const MAX_SUGGESTIONS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct AutocompleteParams {
+    pub q: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub value: String,
+    pub description: Option<String>,
+    /// Query term for the suggestion, in the format of the category snippets
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SuggestionGroup {
+    pub category: &'static str,
+    pub label: &'static str,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// Suggestions for a single search box, grouped by category. Only categories with
+/// suggestions are returned.
+pub async fn autocomplete(
+    Extension(pool): Extension<PgPool>,
+    extract::Query(params): extract::Query<AutocompleteParams>,
+) -> Result<Json<Value>> {
+    let query = params.q.trim();
+    if query.is_empty() {
+        return Err(Error::InvalidRequest(
+            "Empty autocomplete query".to_string(),
+        ));
+    }
+
+    let lookups = AUTOCOMPLETE_CATEGORIES
+        .iter()
+        .map(|category| terms_for_category(&pool, category.clone(), query));
+    let groups: Vec<SuggestionGroup> = AUTOCOMPLETE_CATEGORIES
+        .iter()
+        .zip(try_join_all(lookups).await?)
+        .filter_map(|(category, terms)| {
+            let suggestions = rank_suggestions(category, query, terms);
+            if suggestions.is_empty() {
+                return None;
+            }
+            Some(SuggestionGroup {
+                category: category.into(),
+                label: category.get_label(),
+                suggestions,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "query": query,
+        "groups": groups,
+    })))
+}
+
+/// How well a term matches the query, lower is better: exact matches come first, then
+/// values starting with the query, values containing it, and matches in the description only
+fn match_rank(query: &str, value: &str) -> u8 {
+    let value = value.to_lowercase();
+    if value == query {
+        0
+    } else if value.starts_with(query) {
+        1
+    } else if value.contains(query) {
+        2
+    } else {
+        3
+    }
+}
+
+/// Order the terms found for a category by how well they match, capped at `MAX_SUGGESTIONS`
+pub fn rank_suggestions(
+    category: &Category,
+    query: &str,
+    terms: Vec<AvailableTerm>,
+) -> Vec<Suggestion> {
+    let query = query.to_lowercase();
+    let category_name: &'static str = category.into();
+    let mut ranked: Vec<(u8, Suggestion)> = terms
+        .into_iter()
+        .filter_map(|term| {
+            let value = term.name?;
+            let rank = match_rank(&query, &value);
+            Some((
+                rank,
+                Suggestion {
+                    snippet: format!("{{[{category_name}|{value}]}}"),
+                    value,
+                    description: term.description,
+                },
+            ))
+        })
+        .collect();
+    ranked.sort_by(|(rank_a, a), (rank_b, b)| {
+        rank_a
+            .cmp(rank_b)
+            .then_with(|| a.value.len().cmp(&b.value.len()))
+            .then_with(|| a.value.cmp(&b.value))
+    });
+    ranked.dedup_by(|(_, a), (_, b)| a.value == b.value);
+    ranked.truncate(MAX_SUGGESTIONS);
+    ranked.into_iter().map(|(_, s)| s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_suggestions() {
+        let term = |name: Option<&str>, description: Option<&str>| AvailableTerm {
+            name: name.map(str::to_string),
+            description: description.map(str::to_string),
+        };
+        let terms = vec![
+            term(Some("PKS_KS"), Some("Ketosynthase")),
+            term(Some("Streptomyces_KS"), None),
+            term(None, Some("Unnamed")),
+            term(Some("ks"), None),
+            term(Some("KSB"), None),
+            term(Some("KS_long"), None),
+            term(Some("ACP"), Some("Carrier protein next to a KS")),
+        ];
+        let ranked = rank_suggestions(&Category::Pfam, "Ks", terms);
+        let values: Vec<&str> = ranked.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(
+            values,
+            ["ks", "KSB", "KS_long", "PKS_KS", "Streptomyces_KS", "ACP"]
+        );
+        assert_eq!(ranked[0].snippet, "{[pfam|ks]}");
+        assert_eq!(ranked[3].description.as_deref(), Some("Ketosynthase"));
+
+        let many = (0..2 * MAX_SUGGESTIONS)
+            .map(|i| AvailableTerm {
+                name: Some(format!("Streptomyces sp. {i}")),
+                description: None,
+            })
+            .collect();
+        assert_eq!(
+            rank_suggestions(&Category::Species, "strep", many).len(),
+            MAX_SUGGESTIONS
+        );
+    }
+}
@@ -4,24 +4,29 @@
 use std::collections::HashMap;
 use std::str::FromStr;
 
-use axum::{extract, routing::get, Extension, Json, Router};
+use axum::{middleware, routing::get, Extension, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
 use strum::IntoEnumIterator;
 
+use super::etag;
+use crate::api::extract;
+use crate::api::normalize::Routes;
 use crate::search::category::{Category, CategoryGroup, CategoryType};
 use crate::search::filters::{get_filters_by_category, AvailableFilter};
 use crate::{Error, Result};
 
-mod terms;
+pub mod autocomplete;
+pub mod terms;
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route(
             "/api/available/term/:category/:term",
             get(terms::available_terms_by_category),
         )
+        .route("/api/autocomplete", get(autocomplete::autocomplete))
         .route("/api/available/categories", get(available_categories))
         .route(
             "/api/available/filters/:category",
@@ -31,6 +36,7 @@ pub fn routes() -> Router {
             "/api/available/filter_values/:category/:filter_name",
             get(available_filter_values_by_category),
         )
+        .route_layer(middleware::from_fn(etag::conditional))
 }
 
 #[derive(Debug, Serialize)]
@@ -41,6 +47,9 @@ pub struct CategoryInfo {
     pub category_type: CategoryType,
     pub countable: bool,
     pub description: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<&'static str>,
+    pub snippet: String,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub filters: Vec<AvailableFilter>,
 }
@@ -67,6 +76,8 @@ pub fn get_available_categories() -> AvailableCategories {
         let category_type = cat.get_type();
         let countable = cat.is_countable();
         let description = cat.get_description();
+        let example = cat.get_example();
+        let snippet = cat.get_snippet();
         let filters = cat.get_filters();
 
         let info = CategoryInfo {
@@ -75,6 +86,8 @@ pub fn get_available_categories() -> AvailableCategories {
             category_type,
             countable,
             description,
+            example,
+            snippet,
             filters,
         };
 
@@ -107,7 +120,7 @@ pub fn get_available_categories() -> AvailableCategories {
     AvailableCategories { options, groups }
 }
 
-async fn available_categories(Extension(_pool): Extension<PgPool>) -> Result<Json<Value>> {
+pub async fn available_categories(Extension(_pool): Extension<PgPool>) -> Result<Json<Value>> {
     Ok(Json(json!(get_available_categories())))
 }
 
@@ -119,7 +132,7 @@ pub struct AvailableTerm {
     pub description: Option<String>,
 }
 
-async fn available_filters_by_category(
+pub async fn available_filters_by_category(
     Extension(_pool): Extension<PgPool>,
     extract::Path(raw_category): extract::Path<String>,
 ) -> Result<Json<Value>> {
@@ -127,7 +140,7 @@ async fn available_filters_by_category(
     Ok(Json(json!(get_filters_by_category(&category))))
 }
 
-async fn available_filter_values_by_category(
+pub async fn available_filter_values_by_category(
     Extension(_pool): Extension<PgPool>,
     extract::Path((_category, _filter_name)): extract::Path<(String, String)>,
 ) -> Result<Json<Value>> {
@@ -2,11 +2,13 @@
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
 use std::convert::From;
+use std::str::FromStr;
 
-use axum::{extract, Extension, Json};
+use axum::{Extension, Json};
 use serde_json::{json, Value};
 use sqlx::PgPool;
 
+use crate::api::extract;
 use crate::search::category::Category;
 use crate::{Error, Result};
 
@@ -30,11 +32,19 @@ pub async fn available_terms_by_category(
     Extension(pool): Extension<PgPool>,
     extract::Path((cat, term)): extract::Path<(String, String)>,
 ) -> Result<Json<Value>> {
-    let category = match Category::parse(&cat) {
-        Ok((_, c)) => c,
-        Err(e) => return Err(Error::InvalidRequest(format!("{e}"))),
-    };
+    let category = Category::from_str(&cat)?;
+
+    Ok(Json(json!(
+        terms_for_category(&pool, category, &term).await?
+    )))
+}
 
+/// Known values of a category starting with or, for some categories, containing the term
+pub async fn terms_for_category(
+    pool: &PgPool,
+    category: Category,
+    term: &str,
+) -> Result<Vec<AvailableTerm>> {
     let available = match category {
         Category::Acc => {
             sqlx::query_as!(
@@ -45,7 +55,7 @@ pub async fn available_terms_by_category(
         ORDER BY accession LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Assembly => {
@@ -57,7 +67,7 @@ pub async fn available_terms_by_category(
         ORDER BY assembly_id LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Type => {
@@ -69,7 +79,7 @@ pub async fn available_terms_by_category(
         ORDER BY term LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::TypeCategory => {
@@ -81,7 +91,7 @@ pub async fn available_terms_by_category(
         ORDER BY category LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::CandidateKind => {
@@ -93,7 +103,7 @@ pub async fn available_terms_by_category(
         ORDER BY description LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Substrate => sqlx::query_as!(
@@ -104,7 +114,7 @@ pub async fn available_terms_by_category(
         ORDER BY name LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?,
         Category::Monomer => {
             sqlx::query_as!(
@@ -115,7 +125,7 @@ pub async fn available_terms_by_category(
         ORDER BY name LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Profile => {
@@ -127,7 +137,7 @@ pub async fn available_terms_by_category(
         ORDER BY name LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Resfam => {
@@ -140,7 +150,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Pfam => {
@@ -153,7 +163,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Tigrfam => {
@@ -166,7 +176,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::GOTerm => {
@@ -179,7 +189,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::AsDomain => {
@@ -192,7 +202,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::AsDomainSubtype => {
@@ -205,10 +215,10 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
-        Category::ModuleQuery | Category::CrossCdsModule | Category::ContigEdge | Category::T2pksElongation => {
+        Category::ModuleQuery | Category::CrossCdsModule | Category::ContigEdge | Category::T2pksElongation | Category::TaxNode | Category::ProteinMotif | Category::DomainArchitecture => {
             return Err(Error::InvalidRequest(format!(
                 "No terms available for {category}"
             )))
@@ -222,7 +232,7 @@ pub async fn available_terms_by_category(
         ORDER BY product_class LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::T2pksStarter => {
@@ -234,7 +244,7 @@ pub async fn available_terms_by_category(
         ORDER BY name LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::T2pksProfile => {
@@ -247,7 +257,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::SmCoG => {
@@ -260,7 +270,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::Tfbs => {
@@ -273,7 +283,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         | Category::CompoundSeq => {
@@ -285,11 +295,38 @@ pub async fn available_terms_by_category(
         ORDER BY peptide_sequence LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
+            .await?.iter()
+            .map(|v| v.into())
+            .collect()
+        }
+        Category::Smiles => {
+            sqlx::query_as!(
+                PossibleTermNoDesc,
+                r#"
+        SELECT DISTINCT smiles AS name FROM antismash.candidates
+        WHERE strpos(smiles, $1) > 0
+        ORDER BY smiles LIMIT 50"#,
+                &term,
+            )
+            .fetch_all(pool)
             .await?.iter()
             .map(|v| v.into())
             .collect()
         }
+        Category::Compound => {
+            sqlx::query_as!(
+                AvailableTerm,
+                r#"
+        SELECT DISTINCT name, accession AS description FROM antismash.compound_references
+        WHERE name ILIKE $1 OR accession ILIKE $2
+        ORDER BY name LIMIT 50"#,
+                format!("%{term}%"),
+                format!("{term}%"),
+            )
+            .fetch_all(pool)
+            .await?
+        }
         Category::CompoundClass => {
             sqlx::query_as!(
                 PossibleTermNoDesc,
@@ -299,7 +336,7 @@ pub async fn available_terms_by_category(
         ORDER BY subclass LIMIT 50"#,
                 format!("{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?.iter()
             .map(|v| v.into())
             .collect()
@@ -312,7 +349,7 @@ pub async fn available_terms_by_category(
         ORDER BY strain LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -325,7 +362,7 @@ pub async fn available_terms_by_category(
         ORDER BY species LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -338,7 +375,7 @@ pub async fn available_terms_by_category(
         ORDER BY genus LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -351,7 +388,7 @@ pub async fn available_terms_by_category(
         ORDER BY family LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -364,7 +401,7 @@ pub async fn available_terms_by_category(
         ORDER BY taxonomic_order LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -377,7 +414,7 @@ pub async fn available_terms_by_category(
         ORDER BY class LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -390,7 +427,7 @@ pub async fn available_terms_by_category(
         ORDER BY phylum LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -403,7 +440,7 @@ pub async fn available_terms_by_category(
         ORDER BY superkingdom LIMIT 50"#,
             format!("{term}%"),
         )
-        .fetch_all(&pool)
+        .fetch_all(pool)
         .await?
         .iter()
         .map(|v| v.into())
@@ -418,7 +455,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::ClusterCompareRegion
@@ -432,7 +469,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         Category::ClusterBlast => {
@@ -446,7 +483,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         | Category::KnownCluster => {
@@ -460,7 +497,7 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
         | Category::SubCluster => {
@@ -474,10 +511,10 @@ pub async fn available_terms_by_category(
                 format!("{term}%"),
                 format!("%{term}%"),
             )
-            .fetch_all(&pool)
+            .fetch_all(pool)
             .await?
         }
     };
 
-    Ok(Json(json!(available)))
+    Ok(available)
 }
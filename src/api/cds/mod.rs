@@ -6,6 +6,9 @@ use sqlx::PgPool;
 
 use crate::Result;
 
+/// Functional class antiSMASH assigns to the biosynthetic core genes of a region
+pub const CORE_GENE_CLASS: &str = "biosynthetic";
+
 pub struct CdsId {
     pub cds_id: i32,
 }
@@ -51,15 +54,18 @@ pub async fn ids_to_genes(pool: &PgPool, ids: &[i32]) -> Result<Vec<Cds>> {
     Ok(genes)
 }
 
-pub async fn ids_to_faa(pool: &PgPool, ids: &[i32]) -> Result<Vec<String>> {
+/// Protein FASTA of the CDSes, optionally only of the biosynthetic core genes among them
+pub async fn ids_to_faa(pool: &PgPool, ids: &[i32], core_only: bool) -> Result<Vec<String>> {
     let mut fastas = Vec::with_capacity(ids.len());
     let rows = sqlx::query!(
         r#"
     SELECT cds_id, locus_tag, translation, accession, c.location FROM antismash.cdss AS c
     JOIN antismash.regions USING (region_id)
-    WHERE cds_id = ANY($1)
+    WHERE cds_id = ANY($1) AND (NOT $2 OR c.functional_class = $3)
         "#,
         ids,
+        core_only,
+        CORE_GENE_CLASS,
     )
     .fetch_all(pool)
     .await?;
@@ -0,0 +1,178 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::BTreeMap;
+
+use axum::{routing::get, Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::assembly::{type_counts, TypeCount};
+use super::extract;
+use super::go::{canonical_id, VersionParams};
+use super::normalize::Routes;
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new().route(
+        "/api/compare/assemblies/:id_a/:id_b",
+        get(compare_assemblies),
+    )
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TypeComparison {
+    pub term: String,
+    pub description: String,
+    pub category: String,
+    pub count_a: i64,
+    pub count_b: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MibigHit {
+    pub accession: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssemblyComparison {
+    pub assembly_a: String,
+    pub assembly_b: String,
+    pub types: Vec<TypeComparison>,
+    pub mibig_only_a: Vec<MibigHit>,
+    pub mibig_only_b: Vec<MibigHit>,
+}
+
+async fn compare_assemblies(
+    Extension(pool): Extension<PgPool>,
+    extract::Path((id_a, id_b)): extract::Path<(String, String)>,
+    extract::Query(params): extract::Query<VersionParams>,
+) -> Result<Json<Value>> {
+    let assembly_a = canonical_id(&pool, id_a, params.resolve_versions).await?;
+    let assembly_b = canonical_id(&pool, id_b, params.resolve_versions).await?;
+
+    let hits_a = best_mibig_hits(&pool, &assembly_a).await?;
+    let hits_b = best_mibig_hits(&pool, &assembly_b).await?;
+
+    let types = merge_type_counts(
+        type_counts(&pool, &assembly_a).await?,
+        type_counts(&pool, &assembly_b).await?,
+    );
+
+    Ok(Json(json!(AssemblyComparison {
+        assembly_a,
+        assembly_b,
+        types,
+        mibig_only_a: only_in(&hits_a, &hits_b),
+        mibig_only_b: only_in(&hits_b, &hits_a),
+    })))
+}
+
+/// Get the distinct best MIBiG hits of an assembly's regions
+async fn best_mibig_hits(pool: &PgPool, assembly_id: &str) -> Result<Vec<MibigHit>> {
+    let genome = sqlx::query!(
+        r#"
+        SELECT genome_id FROM antismash.genomes
+        WHERE assembly_id = $1 AND tombstoned IS FALSE"#,
+        assembly_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let hits = sqlx::query_as!(
+        MibigHit,
+        r#"
+        SELECT DISTINCT best_mibig_hit_acc AS "accession!",
+            COALESCE(best_mibig_hit_description, '') AS "description!"
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        WHERE genome_id = $1 AND best_mibig_hit_acc IS NOT NULL
+        ORDER BY 1"#,
+        genome.genome_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(hits)
+}
+
+/// Line up the BGC type counts of two assemblies, using 0 for types only one of them has
+fn merge_type_counts(a: Vec<TypeCount>, b: Vec<TypeCount>) -> Vec<TypeComparison> {
+    let mut merged: BTreeMap<String, TypeComparison> = BTreeMap::new();
+
+    for (counts, is_a) in [(a, true), (b, false)] {
+        for tc in counts {
+            let entry = merged
+                .entry(tc.term.clone())
+                .or_insert_with(|| TypeComparison {
+                    term: tc.term,
+                    description: tc.description,
+                    category: tc.category,
+                    count_a: 0,
+                    count_b: 0,
+                });
+            if is_a {
+                entry.count_a = tc.count;
+            } else {
+                entry.count_b = tc.count;
+            }
+        }
+    }
+
+    merged.into_values().collect()
+}
+
+fn only_in(hits: &[MibigHit], other: &[MibigHit]) -> Vec<MibigHit> {
+    hits.iter()
+        .filter(|hit| !other.iter().any(|o| o.accession == hit.accession))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tc(term: &str, count: i64) -> TypeCount {
+        TypeCount {
+            term: term.to_string(),
+            description: format!("{term} description"),
+            category: "cat".to_string(),
+            count,
+        }
+    }
+
+    fn hit(acc: &str) -> MibigHit {
+        MibigHit {
+            accession: acc.to_string(),
+            description: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_type_counts() {
+        let merged = merge_type_counts(
+            vec![tc("T1PKS", 2), tc("NRPS", 1)],
+            vec![tc("NRPS", 3), tc("lanthipeptide", 1)],
+        );
+        let counts: Vec<(&str, i64, i64)> = merged
+            .iter()
+            .map(|t| (t.term.as_str(), t.count_a, t.count_b))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("NRPS", 1, 3), ("T1PKS", 2, 0), ("lanthipeptide", 0, 1)]
+        );
+    }
+
+    #[test]
+    fn test_only_in() {
+        let a = vec![hit("BGC0000001"), hit("BGC0000002")];
+        let b = vec![hit("BGC0000002"), hit("BGC0000003")];
+        assert_eq!(only_in(&a, &b), vec![hit("BGC0000001")]);
+        assert_eq!(only_in(&b, &a), vec![hit("BGC0000003")]);
+        assert_eq!(only_in(&a, &a), vec![]);
+    }
+}
@@ -2,50 +2,66 @@
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
 use axum::{
-    extract,
     routing::{get, post},
-    Json, Router,
+    Extension, Json,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::query::{Query, ReturnType, SearchType, Term};
+use super::extract;
+use super::normalize::Routes;
+use super::ApiConfig;
+use crate::query::{ParseLimits, Query, ReturnType, SearchType, Term};
 use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/convert", post(convert_post))
         .route("/api/convert", get(convert_get))
+        .route("/api/convert/query", post(convert_query))
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct Payload {
+pub struct Payload {
     search_string: String,
     search_type: Option<SearchType>,
     return_type: Option<ReturnType>,
     verbose: Option<bool>,
+    include_tombstoned: Option<bool>,
+    resolve_versions: Option<bool>,
 }
 
-async fn convert_post(extract::Json(payload): extract::Json<Payload>) -> Result<Json<Value>> {
-    convert(payload)
+pub async fn convert_post(
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(payload): extract::Json<Payload>,
+) -> Result<Json<Value>> {
+    convert(payload, &config.parse_limits)
 }
 
-async fn convert_get(extract::Query(payload): extract::Query<Payload>) -> Result<Json<Value>> {
-    convert(payload)
+pub async fn convert_get(
+    Extension(config): Extension<ApiConfig>,
+    extract::Query(payload): extract::Query<Payload>,
+) -> Result<Json<Value>> {
+    convert(payload, &config.parse_limits)
 }
 
-fn convert(payload: Payload) -> Result<Json<Value>> {
+fn convert(payload: Payload, limits: &ParseLimits) -> Result<Json<Value>> {
     let search_type = payload.search_type.unwrap_or(SearchType::Region);
     let return_type = payload.return_type.unwrap_or(ReturnType::Json);
     let verbose = payload.verbose.unwrap_or(false);
+    let include_tombstoned = payload.include_tombstoned.unwrap_or(false);
+    let resolve_versions = payload.resolve_versions.unwrap_or(false);
 
-    let query = match Term::parse(&payload.search_string) {
+    let query = match Term::parse_with(&payload.search_string, limits) {
         Ok((_, term)) => Query {
             terms: term,
             search_type,
             return_type,
             verbose,
+            include_tombstoned,
+            resolve_versions,
         },
+        Err(nom::Err::Failure(e @ Error::InvalidRequest(_))) => return Err(e),
         Err(_) => {
             return Err(Error::InvalidRequest(
                 "failed to parse search string".to_string(),
@@ -55,3 +71,8 @@ fn convert(payload: Payload) -> Result<Json<Value>> {
 
     Ok(Json(json!(query)))
 }
+
+/// The canonical search string of a query built as JSON
+pub async fn convert_query(extract::Json(query): extract::Json<Query>) -> Result<Json<Value>> {
+    Ok(Json(json!({ "search_string": query.to_search_string() })))
+}
@@ -0,0 +1,66 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderValue, Method,
+};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::{Error, Result};
+
+/// Origins allowed if nothing else is configured, the official antiSMASH-DB front-end
+pub const DEFAULT_ALLOWED_ORIGINS: &str =
+    "https://antismash-db.secondarymetabolites.org,http://antismash-db.secondarymetabolites.org";
+
+/// Split a comma-separated list of origins, dropping empty entries and trailing slashes
+pub fn parse_origins(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|origin| origin.trim().trim_end_matches('/'))
+        .filter(|origin| !origin.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Build the CORS layer, a single `*` allows any origin
+pub fn cors_layer(origins: &[String]) -> Result<CorsLayer> {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        let values = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|_| Error::ConfigError(format!("invalid CORS origin {origin:?}")))
+            })
+            .collect::<Result<Vec<HeaderValue>>>()?;
+        AllowOrigin::list(values)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT])
+        .allow_headers([CONTENT_TYPE, AUTHORIZATION]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_origins() {
+        let tests = [
+            ("", vec![]),
+            ("*", vec!["*"]),
+            (
+                "https://example.org/, http://localhost:8080,,",
+                vec!["https://example.org", "http://localhost:8080"],
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_origins(input), expected, "{input}");
+        }
+        assert!(cors_layer(&parse_origins(DEFAULT_ALLOWED_ORIGINS)).is_ok());
+        assert!(cors_layer(&["bad\norigin".to_string()]).is_err());
+    }
+}
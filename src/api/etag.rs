@@ -0,0 +1,238 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    http::{
+        header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+        HeaderMap, HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+
+use super::ApiConfig;
+use crate::Result;
+
+/// How long the import record is cached before looking for a reimport
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The latest data import recorded in `antismash.db_metadata`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRecord {
+    pub fingerprint: String,
+    pub imported: SystemTime,
+}
+
+impl ImportRecord {
+    pub async fn fetch(pool: &PgPool) -> Result<Option<Self>> {
+        let record = sqlx::query!(
+            r#"
+            SELECT release, import_date
+                FROM antismash.db_metadata
+                ORDER BY import_date DESC
+                LIMIT 1"#
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|row| {
+            let imported = row.import_date.and_utc();
+            Self {
+                fingerprint: format!("{}-{}", row.release, imported.timestamp()),
+                imported: whole_seconds(imported.into()),
+            }
+        });
+        Ok(record)
+    }
+}
+
+/// The import record and when it was last looked up
+type CheckedRecord = (Option<ImportRecord>, Instant);
+
+/// Cached import record, so conditional requests don't hit the database every time
+#[derive(Debug, Clone, Default)]
+pub struct DbVersion(Arc<RwLock<Option<CheckedRecord>>>);
+
+impl DbVersion {
+    /// The latest import, or None if the import tooling didn't record one
+    pub async fn current(&self, pool: &PgPool) -> Result<Option<ImportRecord>> {
+        if let Some((record, checked)) = &*self.0.read().await {
+            if checked.elapsed() < RECHECK_INTERVAL {
+                return Ok(record.clone());
+            }
+        }
+
+        let record = ImportRecord::fetch(pool).await?;
+        *self.0.write().await = Some((record.clone(), Instant::now()));
+        Ok(record)
+    }
+}
+
+/// Summary of the database contents that changes on every reimport, empty if no import was recorded
+pub async fn fingerprint(pool: &PgPool) -> Result<String> {
+    Ok(ImportRecord::fetch(pool)
+        .await?
+        .map(|record| record.fingerprint)
+        .unwrap_or_default())
+}
+
+/// HTTP dates only have second precision
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn make_etag(fingerprint: &str, path_and_query: &str) -> String {
+    let digest = Sha256::digest(format!("{fingerprint}|{path_and_query}"));
+    format!("\"{}\"", &format!("{digest:x}")[..16])
+}
+
+/// Check the conditional request headers, If-None-Match takes precedence over If-Modified-Since
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| modified <= since)
+}
+
+/// Middleware adding ETag and Last-Modified headers to GET responses,
+/// answering matching conditional requests with 304 Not Modified
+pub async fn conditional<B>(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response> {
+    if req.method() != Method::GET {
+        return Ok(next.run(req).await);
+    }
+
+    let Some(ImportRecord {
+        fingerprint,
+        imported: modified,
+    }) = config.db_version.current(&pool).await?
+    else {
+        return Ok(next.run(req).await);
+    };
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+    let etag = make_etag(&fingerprint, path_and_query);
+    let last_modified = httpdate::fmt_http_date(modified);
+
+    let mut response = if is_not_modified(req.headers(), &etag, modified) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(req).await
+    };
+
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&etag) {
+            headers.insert(ETAG, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&last_modified) {
+            headers.insert(LAST_MODIFIED, value);
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_not_modified() {
+        let etag = make_etag("3-1-0", "/api/stats");
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let before = httpdate::fmt_http_date(modified - Duration::from_secs(1));
+        let after = httpdate::fmt_http_date(modified);
+        let other = make_etag("4-1-0", "/api/stats");
+
+        let tests = [
+            (vec![], false),
+            (vec![(IF_NONE_MATCH, etag.to_owned())], true),
+            (vec![(IF_NONE_MATCH, format!("W/{etag}"))], true),
+            (vec![(IF_NONE_MATCH, format!("{other}, {etag}"))], true),
+            (vec![(IF_NONE_MATCH, "*".to_string())], true),
+            (vec![(IF_NONE_MATCH, other.to_owned())], false),
+            (vec![(IF_MODIFIED_SINCE, after.to_owned())], true),
+            (vec![(IF_MODIFIED_SINCE, before)], false),
+            (vec![(IF_MODIFIED_SINCE, "yesterday".to_string())], false),
+            (
+                vec![(IF_NONE_MATCH, other), (IF_MODIFIED_SINCE, after)],
+                false,
+            ),
+        ];
+        for (input, expected) in tests {
+            let mut headers = HeaderMap::new();
+            for (name, value) in &input {
+                headers.insert(name, HeaderValue::from_str(value).unwrap());
+            }
+            assert_eq!(
+                is_not_modified(&headers, &etag, modified),
+                expected,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_db_version(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        let db_version = DbVersion::default();
+        assert_eq!(db_version.current(&pool).await.unwrap(), None);
+
+        sqlx::query(
+            "INSERT INTO antismash.db_metadata (release, antismash_version, import_date)
+                VALUES ('4.0', '7.1.0', '2026-03-01 12:00:00.5')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // The missing record is cached until the next recheck
+        assert_eq!(db_version.current(&pool).await.unwrap(), None);
+
+        let expected = ImportRecord {
+            fingerprint: "4.0-1772366400".to_string(),
+            imported: UNIX_EPOCH + Duration::from_secs(1_772_366_400),
+        };
+        let record = DbVersion::default().current(&pool).await.unwrap();
+        assert_eq!(record, Some(expected.to_owned()));
+        assert_eq!(fingerprint(&pool).await.unwrap(), expected.fingerprint);
+    }
+
+    #[test]
+    fn test_make_etag() {
+        let etag = make_etag("3-1-0", "/api/stats");
+        assert_eq!(etag.len(), 18);
+        assert_eq!(etag, make_etag("3-1-0", "/api/stats"));
+        assert_ne!(etag, make_etag("3-1-1", "/api/stats"));
+        assert_ne!(etag, make_etag("3-1-0", "/api/v2.0/stats"));
+    }
+}
@@ -0,0 +1,200 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::post,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+
+use super::extract;
+use super::normalize::Routes;
+use super::region::{query_ids, resolve_versions};
+use crate::query::{Query, SearchType};
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new().route("/api/export/taxonomy-tree", post(taxonomy_tree))
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TreeFormat {
+    #[default]
+    Json,
+    Newick,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaxonomyTreePayload {
+    pub query: Query,
+    #[serde(default)]
+    pub format: TreeFormat,
+}
+
+/// A node in the taxonomy tree of the matched regions
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TaxonNode {
+    pub name: String,
+    pub rank: &'static str,
+    pub regions: i64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TaxonNode>,
+}
+
+impl TaxonNode {
+    pub fn new(name: &str, rank: &'static str) -> Self {
+        Self {
+            name: name.to_owned(),
+            rank,
+            regions: 0,
+            children: Vec::new(),
+        }
+    }
+
+    /// Add the regions of one lineage, ordered from the highest rank to the leaf
+    pub fn insert(&mut self, lineage: &[(&'static str, String)], regions: i64) {
+        self.regions += regions;
+        let Some(((rank, name), rest)) = lineage.split_first() else {
+            return;
+        };
+        let child = match self.children.iter().position(|c| &c.name == name) {
+            Some(idx) => &mut self.children[idx],
+            None => {
+                self.children.push(TaxonNode::new(name, rank));
+                self.children.last_mut().unwrap()
+            }
+        };
+        child.insert(rest, regions);
+    }
+
+    pub fn to_newick(&self) -> String {
+        format!("{};", self.newick_node())
+    }
+
+    fn newick_node(&self) -> String {
+        let label = format!("{}[&regions={}]", newick_label(&self.name), self.regions);
+        if self.children.is_empty() {
+            return label;
+        }
+        let children: Vec<String> = self.children.iter().map(|c| c.newick_node()).collect();
+        format!("({}){label}", children.join(","))
+    }
+}
+
+/// Quote a Newick label if it contains any characters with a special meaning
+fn newick_label(name: &str) -> String {
+    if name
+        .chars()
+        .any(|c| c.is_whitespace() || "()[]':;,".contains(c))
+    {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_owned()
+    }
+}
+
+async fn taxonomy_tree(
+    Extension(pool): Extension<PgPool>,
+    extract::Json(mut req): extract::Json<TaxonomyTreePayload>,
+) -> Result<Response> {
+    if req.query.search_type != SearchType::Region {
+        return Err(Error::NotImplementedError(format!(
+            "{:?} taxonomy trees",
+            req.query.search_type
+        )));
+    }
+
+    resolve_versions(&pool, &mut req.query.terms, req.query.resolve_versions).await?;
+    let ids = query_ids(&pool, &req.query).await?;
+    let tree = build_tree(&pool, &ids).await?;
+
+    let response = match req.format {
+        TreeFormat::Json => Json(json!(tree)).into_response(),
+        TreeFormat::Newick => ([(CONTENT_TYPE, "text/x-nh")], tree.to_newick()).into_response(),
+    };
+    Ok(response)
+}
+
+async fn build_tree(pool: &PgPool, ids: &[i32]) -> Result<TaxonNode> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT superkingdom, phylum, class, taxonomic_order, family, genus, species,
+            assembly_id, COUNT(region_id) AS "regions!"
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        JOIN antismash.taxa USING (tax_id)
+        WHERE region_id = ANY($1)
+        GROUP BY superkingdom, phylum, class, taxonomic_order, family, genus, species, assembly_id
+        ORDER BY superkingdom, phylum, class, taxonomic_order, family, genus, species, assembly_id
+        "#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let unknown = || "Unknown".to_string();
+    let mut root = TaxonNode::new("root", "root");
+    for row in rows {
+        let lineage = [
+            ("superkingdom", row.superkingdom.unwrap_or_else(unknown)),
+            ("phylum", row.phylum.unwrap_or_else(unknown)),
+            ("class", row.class.unwrap_or_else(unknown)),
+            ("order", row.taxonomic_order.unwrap_or_else(unknown)),
+            ("family", row.family.unwrap_or_else(unknown)),
+            ("genus", row.genus.unwrap_or_else(unknown)),
+            ("species", row.species.unwrap_or_else(unknown)),
+            ("assembly", row.assembly_id),
+        ];
+        root.insert(&lineage, row.regions);
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lineage(genus: &str, species: &str, assembly: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("genus", genus.to_string()),
+            ("species", species.to_string()),
+            ("assembly", assembly.to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut root = TaxonNode::new("root", "root");
+        root.insert(&lineage("Streptomyces", "coelicolor", "GCF_1"), 2);
+        root.insert(&lineage("Streptomyces", "griseus", "GCF_2"), 3);
+        root.insert(&lineage("Amycolatopsis", "mediterranei", "GCF_3"), 1);
+
+        assert_eq!(root.regions, 6);
+        assert_eq!(root.children.len(), 2);
+        let streptomyces = &root.children[0];
+        assert_eq!(streptomyces.name, "Streptomyces");
+        assert_eq!(streptomyces.rank, "genus");
+        assert_eq!(streptomyces.regions, 5);
+        assert_eq!(streptomyces.children.len(), 2);
+        assert_eq!(streptomyces.children[1].children[0].name, "GCF_2");
+    }
+
+    #[test]
+    fn test_to_newick() {
+        let mut root = TaxonNode::new("root", "root");
+        root.insert(&lineage("Streptomyces", "coelicolor", "GCF_1"), 2);
+        root.insert(&lineage("Streptomyces", "sp. O'Hara", "GCF_2"), 1);
+
+        assert_eq!(
+            root.to_newick(),
+            "(((GCF_1[&regions=2])coelicolor[&regions=2],(GCF_2[&regions=1])'sp. O''Hara'[&regions=1])\
+             Streptomyces[&regions=3])root[&regions=3];"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+//! The axum extractors, with the JSON, query string and path extractors replaced by
+//! versions rejecting invalid requests with the usual error response
+
+use std::ops::Deref;
+
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+pub use axum::extract::*;
+
+use crate::Error;
+
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(Error))]
+pub struct Json<T>(pub T);
+
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(Error))]
+pub struct Query<T>(pub T);
+
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(Error))]
+pub struct Path<T>(pub T);
+
+// Like the axum extractors, so the fields of the extracted value can be used directly
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::InvalidRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Error::InvalidRequest(rejection.body_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{Body, HttpBody},
+        http::{header::CONTENT_TYPE, Request, StatusCode},
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn send(request: Request<Body>) -> (StatusCode, Value) {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/asdb").unwrap();
+        let (app, _) = crate::api::init_routes(pool, Default::default());
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().data().await.unwrap().unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_rejections() {
+        let tests = [
+            (
+                Request::post("/api/search")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"query": 1}"#))
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+                "INVALID_PARAMS",
+            ),
+            (
+                Request::post("/api/search?paginate=many")
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"query": {"terms": {}}}"#))
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+                "INVALID_PARAMS",
+            ),
+            (
+                Request::get("/api/job/not-a-job-id")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+                "INVALID_PARAMS",
+            ),
+            (
+                Request::get("/api/does/not/exist")
+                    .body(Body::empty())
+                    .unwrap(),
+                StatusCode::NOT_FOUND,
+                "NOT_FOUND",
+            ),
+        ];
+        for (request, status, code) in tests {
+            let uri = request.uri().to_string();
+            let (actual, body) = send(request).await;
+            assert_eq!(actual, status, "{uri}");
+            assert_eq!(body["error"]["code"], code, "{uri} {body}");
+        }
+    }
+}
@@ -1,21 +1,33 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{extract, response::Redirect, routing::get, Extension, Router};
+use axum::{response::Redirect, routing::get, Extension};
 use regex::Regex;
+use serde::Deserialize;
 use sqlx::PgPool;
 
-use crate::Result;
+use super::extract;
+use super::normalize::Routes;
+use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/goto/:identifier", get(goto))
         .route("/go/:identifier", get(goto))
         .route("/api/goto/:identifier/:region", get(goto_region))
         .route("/go/:identifier/:region", get(goto_region))
 }
 
-async fn canonical_id(pool: &PgPool, raw: String) -> Result<String> {
+#[derive(Debug, Default, Deserialize)]
+pub struct VersionParams {
+    /// Use the closest stored version of a sequence accession if the requested one isn't stored
+    #[serde(default)]
+    pub resolve_versions: bool,
+}
+
+/// Find the assembly ID for an assembly ID or sequence accession, with or without a version.
+/// Missing accession versions are only resolved to the closest stored one if `resolve` is set.
+pub async fn canonical_id(pool: &PgPool, raw: String, resolve: bool) -> Result<String> {
     let identifier = sanitise_id(&raw);
 
     // TODO: The old API had an "is it a v1 accession" check here
@@ -52,19 +64,34 @@ async fn canonical_id(pool: &PgPool, raw: String) -> Result<String> {
 
     // we store accessions and versions separately in dna_sequences
     if let Some((acc, ver)) = identifier.split_once('.') {
-        let version: i32 = ver.parse().unwrap_or(1);
-        if let Ok(res) = sqlx::query!(
-            r#"
-        SELECT assembly_id FROM antismash.genomes
-        JOIN antismash.dna_sequences USING (genome_id)
-        WHERE accession = $1 AND version = $2"#,
-            acc,
-            version,
-        )
-        .fetch_one(pool)
-        .await
-        {
-            return Ok(res.assembly_id);
+        let requested: i32 = ver.parse().unwrap_or(1);
+        let available = stored_versions(pool, acc).await?;
+        if !available.is_empty() && !available.contains(&requested) && !resolve {
+            return Err(Error::VersionNotFound(
+                identifier.to_owned(),
+                available.iter().map(|v| format!("{acc}.{v}")).collect(),
+            ));
+        }
+        if let Some(version) = closest_version(requested, &available) {
+            if version != requested {
+                eprintln!(
+                    "->> {:<12} - {identifier} not found, using {acc}.{version} instead",
+                    "RESOLVE"
+                );
+            }
+            if let Ok(res) = sqlx::query!(
+                r#"
+            SELECT assembly_id FROM antismash.genomes
+            JOIN antismash.dna_sequences USING (genome_id)
+            WHERE accession = $1 AND version = $2"#,
+                acc,
+                version,
+            )
+            .fetch_one(pool)
+            .await
+            {
+                return Ok(res.assembly_id);
+            }
         }
     }
 
@@ -81,22 +108,49 @@ async fn canonical_id(pool: &PgPool, raw: String) -> Result<String> {
         return Ok(res.assembly_id);
     }
 
-    Err(crate::Error::NotFound)
+    Err(Error::NotFound)
 }
 
-async fn goto(
+/// Get the versions stored for a sequence accession
+pub async fn stored_versions(pool: &PgPool, accession: &str) -> Result<Vec<i32>> {
+    let versions = sqlx::query!(
+        r#"
+    SELECT version FROM antismash.dna_sequences
+    WHERE accession = $1
+    ORDER BY version"#,
+        accession,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .filter_map(|row| row.version)
+    .collect();
+    Ok(versions)
+}
+
+/// Pick the stored version closest to the requested one, preferring the newer on a tie
+pub fn closest_version(requested: i32, available: &[i32]) -> Option<i32> {
+    available
+        .iter()
+        .copied()
+        .min_by_key(|v| ((v - requested).abs(), -v))
+}
+
+pub async fn goto(
     Extension(pool): Extension<PgPool>,
     extract::Path(identifier): extract::Path<String>,
+    extract::Query(params): extract::Query<VersionParams>,
 ) -> Result<Redirect> {
-    let id = canonical_id(&pool, identifier).await?;
+    let id = canonical_id(&pool, identifier, params.resolve_versions).await?;
     Ok(Redirect::to(&format!("/output/{id}/index.html")))
 }
 
-async fn goto_region(
+pub async fn goto_region(
     Extension(pool): Extension<PgPool>,
     extract::Path((identifier, region_raw)): extract::Path<(String, String)>,
+    extract::Query(params): extract::Query<VersionParams>,
 ) -> Result<Redirect> {
-    let id = canonical_id(&pool, identifier).await?;
+    let id = canonical_id(&pool, identifier, params.resolve_versions).await?;
     let region = sanitise_region(&region_raw);
     eprintln!("->> {region_raw} -> {region}");
     Ok(Redirect::to(&format!("/output/{id}/index.html#{region}")))
@@ -119,6 +173,52 @@ pub fn sanitise_id(raw: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_closest_version() {
+        let tests = [
+            (3, vec![3], Some(3)),
+            (2, vec![3], Some(3)),
+            (2, vec![1, 3], Some(3)),
+            (5, vec![1, 2, 3], Some(3)),
+            (1, vec![], None),
+        ];
+
+        for (requested, available, expected) in tests {
+            assert_eq!(closest_version(requested, &available), expected);
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_canonical_id(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        let tests = [
+            ("GCF_000203835.1", false, "GCF_000203835.1"),
+            ("GCF_000203835", false, "GCF_000203835.1"),
+            ("NC_003888.3", false, "GCF_000203835.1"),
+            ("NC_003888", false, "GCF_000203835.1"),
+            ("NC_003888.2", true, "GCF_000203835.1"),
+            ("NC_004129.5", true, "GCF_000012265.1"),
+        ];
+        for (identifier, resolve, expected) in tests {
+            let id = canonical_id(&pool, identifier.to_string(), resolve)
+                .await
+                .unwrap();
+            assert_eq!(id, expected, "{identifier}");
+        }
+
+        let error = canonical_id(&pool, "NC_003888.2".to_string(), false)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, Error::VersionNotFound(acc, versions) if acc == "NC_003888.2" && versions == &["NC_003888.3"]),
+            "{error:?}"
+        );
+        assert!(matches!(
+            canonical_id(&pool, "NC_000000.1".to_string(), false).await,
+            Err(Error::NotFound)
+        ));
+    }
+
     #[test]
     fn test_sanitise_region() {
         let tests = [
@@ -2,38 +2,117 @@
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
 use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 
 use axum::{
-    extract,
+    body::{boxed, Body},
+    http::{
+        header::{HeaderName, CONTENT_DISPOSITION},
+        HeaderMap, HeaderValue, Request,
+    },
+    response::{IntoResponse, Response},
     routing::{get, post},
-    Extension, Json, Router,
+    Extension, Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
-use uuid::Uuid;
+use tokio::fs;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 
+use super::admin::Admin;
+use super::extract::{self, OriginalUri};
+use super::job_events;
+use super::normalize::Routes;
+use super::pagination::Page;
+use super::region;
+use super::taxa;
+use super::ApiConfig;
+use crate::jobs;
+use crate::jobs::assembly_download::{
+    region_files, AssemblyDownload, DownloadEstimate, MAX_ASSEMBLIES,
+};
 use crate::jobs::blast::BlastInput;
 use crate::jobs::clusterblast::ClusterBlast;
-use crate::jobs::comparippson::CompaRiPPson;
+use crate::jobs::comparippson::{self, CompaRiPPson, CompaRiPPsonInput};
 use crate::jobs::ping::Ping;
-use crate::models::job::{JobEntry, JobStatus, JobType};
-use crate::Result;
+use crate::jobs::stored_query::StoredQuery;
+use crate::models::job::{JobEntry, JobFilter, JobId, JobStatus, JobType};
+use crate::query::{ReturnType, SearchType};
+use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/jobs/clusterblast", post(create_clusterblast))
         .route("/api/jobs/comparippson", post(create_comparippson))
         .route("/api/jobs/ping", post(create_ping))
+        .route("/api/jobs/storedquery", post(create_stored_query))
+        .route(
+            "/api/jobs/download/assemblies",
+            post(create_assembly_download),
+        )
+        .route(
+            "/api/jobs/download/assemblies/estimate",
+            post(estimate_assembly_download),
+        )
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/mine", get(list_session_jobs))
+        .route("/api/jobs/stats", get(job_stats))
+        .route("/api/jobs/stats/timeseries", get(job_timeseries))
         .route("/api/job/:job_id", get(get_job_info))
+        .route("/api/job/:job_id/download", get(download_job_file))
+        .route("/api/job/:job_id/events", get(job_events::job_events))
+}
+
+const MAX_SESSION_ID_LENGTH: usize = 64;
+const CHECKSUM_HEADER: HeaderName = HeaderName::from_static("x-checksum-sha256");
+
+/// Job payload with the optional front-end session it is submitted from
+#[derive(Debug, Deserialize)]
+pub struct Submission<T> {
+    #[serde(flatten)]
+    pub input: T,
+    pub session_id: Option<String>,
+}
+
+impl<T> Submission<T> {
+    /// Split into the job input and the validated session ID
+    pub fn into_parts(self) -> Result<(T, Option<String>)> {
+        if let Some(session_id) = &self.session_id {
+            validate_session_id(session_id)?;
+        }
+        Ok((self.input, self.session_id))
+    }
+}
+
+pub fn validate_session_id(session_id: &str) -> Result<()> {
+    if session_id.is_empty() || session_id.len() > MAX_SESSION_ID_LENGTH {
+        return Err(Error::InvalidRequest(format!(
+            "Session ID must be between 1 and {MAX_SESSION_ID_LENGTH} characters long"
+        )));
+    }
+    if !session_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::InvalidRequest(
+            "Session ID may only contain letters, digits, - and _".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 async fn create_clusterblast(
     Extension(pool): Extension<PgPool>,
-    extract::Json(input): extract::Json<BlastInput>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(submission): extract::Json<Submission<BlastInput>>,
 ) -> Result<Json<Value>> {
+    let (input, session_id) = submission.into_parts()?;
+    let input = input.validate(&config.sequence_limits)?;
     let mut job = JobEntry::new(JobType::ClusterBlast(ClusterBlast::from_blast(input)));
+    job.session_id = session_id;
     job.commit(&pool).await?;
 
     let info = JobInfo::try_from(job)?;
@@ -42,9 +121,14 @@ async fn create_clusterblast(
 
 async fn create_comparippson(
     Extension(pool): Extension<PgPool>,
-    extract::Json(input): extract::Json<BlastInput>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(submission): extract::Json<Submission<CompaRiPPsonInput>>,
 ) -> Result<Json<Value>> {
-    let mut job = JobEntry::new(JobType::CompaRiPPson(CompaRiPPson::from_blast(input)));
+    let (mut input, session_id) = submission.into_parts()?;
+    comparippson::validate_database(&input.database)?;
+    input.query = input.query.validate(&config.sequence_limits)?;
+    let mut job = JobEntry::new(JobType::CompaRiPPson(CompaRiPPson::from_input(input)));
+    job.session_id = session_id;
     job.commit(&pool).await?;
 
     let info = JobInfo::try_from(job)?;
@@ -58,35 +142,455 @@ struct PingRequest {
 
 async fn create_ping(
     Extension(pool): Extension<PgPool>,
-    extract::Json(req): extract::Json<PingRequest>,
+    extract::Json(submission): extract::Json<Submission<PingRequest>>,
 ) -> Result<Json<Value>> {
+    let (req, session_id) = submission.into_parts()?;
     let mut job = JobEntry::new(JobType::Ping(Ping::new(&req.greeting)));
+    job.session_id = session_id;
     job.commit(&pool).await?;
 
     let info = JobInfo::try_from(job)?;
     Ok(Json(json!(info)))
 }
 
-async fn get_job_info(
+/// Export of entries found earlier, by their IDs
+#[derive(Debug, Deserialize)]
+pub struct StoredQueryRequest {
+    pub ids: Vec<i32>,
+    pub search_type: SearchType,
+    pub return_type: ReturnType,
+    #[serde(default)]
+    pub csv_style: region::CsvStyle,
+    #[serde(default)]
+    pub core_only: bool,
+    #[serde(default)]
+    pub flank: u32,
+}
+
+async fn create_stored_query(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(submission): extract::Json<Submission<StoredQueryRequest>>,
+) -> Result<Json<Value>> {
+    let (req, session_id) = submission.into_parts()?;
+    let job_id = JobId::generate();
+    let mut query = StoredQuery::new(job_id, &req.ids, req.search_type, req.return_type);
+    query.input.csv_style = req.csv_style;
+    query.input.core_only = req.core_only;
+    query.input.flank = req.flank;
+    query.input.check_ids(&config.id_limits)?;
+    query.input.check_ids_exist(&pool).await?;
+
+    let mut job = JobEntry::new(JobType::StoredQuery(query));
+    job.id = job_id;
+    job.session_id = session_id;
+    job.commit(&pool).await?;
+
+    let info = JobInfo::try_from(job)?;
+    Ok(Json(json!(info)))
+}
+
+/// Assemblies to download, either listed explicitly or all assemblies below a taxonomy tree node
+#[derive(Debug, Deserialize)]
+pub struct AssemblyDownloadRequest {
+    #[serde(default)]
+    pub assemblies: Vec<String>,
+    pub taxon: Option<String>,
+}
+
+impl AssemblyDownloadRequest {
+    fn validate(&self) -> Result<()> {
+        if self.taxon.is_some() != self.assemblies.is_empty() {
+            return Err(Error::InvalidRequest(
+                "Request either a list of assemblies or a taxon".to_string(),
+            ));
+        }
+        check_assembly_count(self.assemblies.len())
+    }
+
+    /// The IDs of the requested assemblies, all of which exist and aren't tombstoned
+    async fn resolve(&self, pool: &PgPool) -> Result<Vec<String>> {
+        self.validate()?;
+        if let Some(taxon) = &self.taxon {
+            let assemblies = taxa::assemblies_below(pool, taxon).await?;
+            check_assembly_count(assemblies.len())?;
+            return Ok(assemblies);
+        }
+
+        let mut assemblies = self.assemblies.clone();
+        assemblies.sort();
+        assemblies.dedup();
+        let known = sqlx::query_scalar!(
+            r#"
+            SELECT assembly_id FROM antismash.genomes
+                WHERE assembly_id = ANY($1) AND tombstoned IS FALSE"#,
+            &assemblies,
+        )
+        .fetch_all(pool)
+        .await?;
+        let unknown: Vec<&str> = assemblies
+            .iter()
+            .filter(|a| !known.contains(a))
+            .map(|a| a.as_str())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(Error::InvalidRequest(format!(
+                "Unknown assemblies: {}",
+                unknown.join(", ")
+            )));
+        }
+        Ok(assemblies)
+    }
+
+    async fn estimate(
+        &self,
+        pool: &PgPool,
+        config: &ApiConfig,
+    ) -> Result<(Vec<String>, DownloadEstimate)> {
+        let Some(outdir) = &config.outdir else {
+            return Err(Error::NotImplementedError(
+                "Assembly downloads without an output directory".to_string(),
+            ));
+        };
+        let assemblies = self.resolve(pool).await?;
+        let files = region_files(pool, outdir, &assemblies).await?;
+        let estimate = DownloadEstimate::new(assemblies.len(), &files).await;
+        Ok((assemblies, estimate))
+    }
+}
+
+fn check_assembly_count(count: usize) -> Result<()> {
+    if count > MAX_ASSEMBLIES {
+        return Err(Error::InvalidRequest(format!(
+            "Cannot download more than {MAX_ASSEMBLIES} assemblies at once, {count} requested"
+        )));
+    }
+    Ok(())
+}
+
+async fn estimate_assembly_download(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(req): extract::Json<AssemblyDownloadRequest>,
+) -> Result<Json<Value>> {
+    let (_, estimate) = req.estimate(&pool, &config).await?;
+    Ok(Json(json!(estimate)))
+}
+
+async fn create_assembly_download(
     Extension(pool): Extension<PgPool>,
-    extract::Path(job_id): extract::Path<Uuid>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Json(submission): extract::Json<Submission<AssemblyDownloadRequest>>,
 ) -> Result<Json<Value>> {
-    let id = job_id.to_string();
-    let job = JobEntry::from_db(&pool, &id).await?;
+    let (req, session_id) = submission.into_parts()?;
+    let (assemblies, estimate) = req.estimate(&pool, &config).await?;
+    estimate.check()?;
+
+    let job_id = JobId::generate();
+    let mut job = JobEntry::new(JobType::AssemblyDownload(AssemblyDownload::new(
+        job_id, assemblies,
+    )));
+    job.id = job_id;
+    job.session_id = session_id;
+    job.commit(&pool).await?;
+
     let info = JobInfo::try_from(job)?;
     Ok(Json(json!(info)))
 }
 
+const DEFAULT_HITS_LIMIT: usize = 100;
+const MAX_HITS_LIMIT: usize = 1000;
+
+/// Paging of the hits in a job's results
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HitsPage {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl HitsPage {
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_HITS_LIMIT)
+            .clamp(1, MAX_HITS_LIMIT)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HitsPaging {
+    pub offset: usize,
+    pub limit: usize,
+    pub total: usize,
+}
+
+async fn get_job_info(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Path(job_id): extract::Path<JobId>,
+    extract::Query(page): extract::Query<HitsPage>,
+) -> Result<Json<Value>> {
+    let mut job = JobEntry::from_db(&pool, &job_id).await?;
+
+    let mut paging = None;
+    if let JobType::ClusterBlast(cb) = &mut job.jobtype {
+        let total = cb.results.page(page.offset(), page.limit());
+        paging = Some(HitsPaging {
+            offset: page.offset(),
+            limit: page.limit(),
+            total,
+        });
+    }
+
+    let file = match job.status {
+        JobStatus::Done => job.jobtype.download().map(|d| d.to_owned()),
+        _ => None,
+    };
+
+    let mut info = JobInfo::try_from(job)?;
+    if info.results.is_some() {
+        info.paging = paging;
+    }
+    if let Some(file) = file {
+        info.download = Some(DownloadInfo::new(&config.jobdir, &job_id, &file).await?);
+    }
+    Ok(Json(json!(info)))
+}
+
+/// Size and checksum of a job's result file, so interrupted downloads can be resumed and verified
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DownloadInfo {
+    /// Serves the file with support for range requests
+    pub url: String,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+}
+
+impl DownloadInfo {
+    async fn new(jobdir: &Path, job_id: &JobId, file: &str) -> Result<Self> {
+        let path = result_path(jobdir, job_id, file)?;
+        Ok(Self {
+            url: format!("/api/job/{job_id}/download"),
+            size: fs::metadata(&path).await.ok().map(|m| m.len()),
+            sha256: jobs::read_checksum(&path).await,
+        })
+    }
+}
+
+/// Location of a job's result file, which is stored as `<jobdir>/<job_id>/<filename>`
+fn result_path(jobdir: &Path, job_id: &JobId, file: &str) -> Result<PathBuf> {
+    let filename = Path::new(file).file_name().ok_or(Error::NotFound)?;
+    Ok(job_id.dir(jobdir).join(filename))
+}
+
+async fn download_job_file(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Path(job_id): extract::Path<JobId>,
+    request: Request<Body>,
+) -> Result<Response> {
+    let job = JobEntry::from_db(&pool, &job_id).await?;
+    let file = match job.status {
+        JobStatus::Done => job.jobtype.download().ok_or(Error::NotFound)?,
+        JobStatus::Error => return Err(Error::JobFailed(job.error.unwrap_or_default())),
+        _ => return Err(Error::NotFound),
+    };
+    let path = result_path(&config.jobdir, &job_id, file)?;
+    let sha256 = jobs::read_checksum(&path).await;
+
+    // ServeFile takes care of range requests, content length and conditional requests
+    let Ok(mut response) = ServeFile::new(&path).oneshot(request).await;
+    let headers = response.headers_mut();
+    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+        if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{name}\"")) {
+            headers.insert(CONTENT_DISPOSITION, value);
+        }
+    }
+    if let Some(value) = sha256.and_then(|s| HeaderValue::from_str(&s).ok()) {
+        headers.insert(CHECKSUM_HEADER, value);
+    }
+    Ok(response.map(boxed).into_response())
+}
+
+async fn job_stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
+    let stats = JobEntry::queue_stats(&pool).await?;
+    Ok(Json(json!(stats)))
+}
+
+const DEFAULT_TIMESERIES_DAYS: i32 = 30;
+const MAX_TIMESERIES_DAYS: i32 = 365;
+
+#[derive(Debug, Deserialize)]
+pub struct TimeseriesQuery {
+    pub days: Option<i32>,
+}
+
+async fn job_timeseries(
+    Extension(pool): Extension<PgPool>,
+    extract::Query(params): extract::Query<TimeseriesQuery>,
+) -> Result<Json<Value>> {
+    let days = params
+        .days
+        .unwrap_or(DEFAULT_TIMESERIES_DAYS)
+        .clamp(1, MAX_TIMESERIES_DAYS);
+    let series = JobEntry::timeseries(&pool, days).await?;
+    Ok(Json(json!({
+        "days": days,
+        "series": series,
+    })))
+}
+
+/// Operator-facing overview of a job, without its inputs and results
+#[derive(Debug, Serialize)]
+pub struct JobSummary {
+    pub id: JobId,
+    pub jobtype: String,
+    pub status: String,
+    pub runner: String,
+    pub submitted: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<JobEntry> for JobSummary {
+    fn from(value: JobEntry) -> Self {
+        Self {
+            id: value.id,
+            jobtype: value.jobtype.to_string(),
+            status: value.status.to_string(),
+            runner: value.runner,
+            submitted: value.submitted_date,
+            started: value.started_date,
+            finished: value.finished_date,
+            error: value.error,
+        }
+    }
+}
+
+async fn list_jobs(
+    _admin: Admin,
+    OriginalUri(uri): OriginalUri,
+    Extension(pool): Extension<PgPool>,
+    extract::Query(filter): extract::Query<JobFilter>,
+) -> Result<(HeaderMap, Json<Value>)> {
+    let jobs: Vec<JobSummary> = JobEntry::list(&pool, &filter)
+        .await?
+        .into_iter()
+        .map(JobSummary::from)
+        .collect();
+    let total = JobEntry::count(&pool, &filter).await?;
+
+    Ok((
+        job_page(&filter, total).headers(&uri, "limit"),
+        Json(json!({
+            "jobs": jobs,
+            "offset": filter.offset(),
+            "limit": filter.limit(),
+            "total": total,
+        })),
+    ))
+}
+
+fn job_page(filter: &JobFilter, total: i64) -> Page {
+    Page {
+        offset: filter.offset() as usize,
+        limit: filter.limit() as usize,
+        total: total as usize,
+    }
+}
+
+/// User-facing overview of a job submitted from a front-end session
+#[derive(Debug, Serialize)]
+pub struct SessionJob {
+    pub id: JobId,
+    pub jobtype: String,
+    pub status: String,
+    pub submitted: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished: Option<DateTime<Utc>>,
+    /// Where to get the job's status and results
+    pub link: String,
+    /// Result file of jobs producing downloads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<String>,
+}
+
+impl From<JobEntry> for SessionJob {
+    fn from(value: JobEntry) -> Self {
+        let download = match value.status {
+            JobStatus::Done => value.jobtype.download().map(|d| d.to_owned()),
+            _ => None,
+        };
+        Self {
+            link: format!("/api/job/{}", value.id),
+            id: value.id,
+            jobtype: value.jobtype.to_string(),
+            status: value.status.to_string(),
+            submitted: value.submitted_date,
+            finished: value.finished_date,
+            download,
+        }
+    }
+}
+
+async fn list_session_jobs(
+    OriginalUri(uri): OriginalUri,
+    Extension(pool): Extension<PgPool>,
+    extract::Query(filter): extract::Query<JobFilter>,
+) -> Result<(HeaderMap, Json<Value>)> {
+    let Some(session) = &filter.session else {
+        return Err(Error::InvalidRequest(
+            "Missing session parameter".to_string(),
+        ));
+    };
+    validate_session_id(session)?;
+
+    let jobs: Vec<SessionJob> = JobEntry::list(&pool, &filter)
+        .await?
+        .into_iter()
+        .map(SessionJob::from)
+        .collect();
+    let total = JobEntry::count(&pool, &filter).await?;
+
+    Ok((
+        job_page(&filter, total).headers(&uri, "limit"),
+        Json(json!({
+            "jobs": jobs,
+            "offset": filter.offset(),
+            "limit": filter.limit(),
+            "total": total,
+        })),
+    ))
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct JobInfo {
-    pub id: String,
+    pub id: JobId,
     pub jobtype: String,
     pub status: String,
     pub submitted: DateTime<Utc>,
+    /// Attempt the job is on, jobs failing with transient errors are retried
+    pub attempts: i32,
+    /// When a retried job will be picked up again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub next: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub results: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paging: Option<HitsPaging>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<DownloadInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 impl TryFrom<JobEntry> for JobInfo {
@@ -97,20 +601,29 @@ impl TryFrom<JobEntry> for JobInfo {
             jobtype: value.jobtype.to_string(),
             status: value.status.to_string(),
             submitted: value.submitted_date,
+            attempts: value.retries + 1,
+            retry_after: value
+                .retry_after
+                .filter(|_| matches!(value.status, JobStatus::Pending)),
             next: None,
             results: None,
+            paging: None,
+            download: None,
+            error: None,
         };
         match value.status {
-            JobStatus::Error | JobStatus::Delete => {} // do nothing
+            JobStatus::Error => info.error = value.error,
+            JobStatus::Delete => {} // do nothing
             JobStatus::Pending | JobStatus::Running => {
                 info.next = Some(format!("/api/job/{}", info.id))
             }
             JobStatus::Done => {
                 let val = match value.jobtype {
+                    JobType::AssemblyDownload(d) => serde_json::to_value(d.filename)?,
                     JobType::ClusterBlast(cb) => serde_json::to_value(cb.results)?,
                     JobType::CompaRiPPson(cr) => serde_json::to_value(cr.results)?,
                     JobType::Ping(ping) => serde_json::to_value(ping.reply)?,
-                    JobType::StoredQuery(q) => serde_json::to_value(q.filename)?,
+                    JobType::StoredQuery(q) => q.results(),
                 };
                 info.results = Some(val);
             }
@@ -118,3 +631,47 @@ impl TryFrom<JobEntry> for JobInfo {
         Ok(info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_session_id() {
+        let tests = [
+            ("abc-123_DEF", true),
+            ("", false),
+            ("a b", false),
+            ("../etc", false),
+            (&"a".repeat(MAX_SESSION_ID_LENGTH), true),
+            (&"a".repeat(MAX_SESSION_ID_LENGTH + 1), false),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(validate_session_id(input).is_ok(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_assembly_download_validate() {
+        let tests = [
+            (r#"{"assemblies": ["GCF_000203835.1"]}"#, true),
+            (r#"{"taxon": "genus_bacteria_a_b_c_d_streptomyces"}"#, true),
+            (r#"{}"#, false),
+            (r#"{"assemblies": []}"#, false),
+            (
+                r#"{"assemblies": ["GCF_000203835.1"], "taxon": "superkingdom_bacteria"}"#,
+                false,
+            ),
+        ];
+        for (input, expected) in tests {
+            let req: AssemblyDownloadRequest = serde_json::from_str(input).unwrap();
+            assert_eq!(req.validate().is_ok(), expected, "{input}");
+        }
+
+        let too_many = AssemblyDownloadRequest {
+            assemblies: vec!["GCF_000203835.1".to_string(); MAX_ASSEMBLIES + 1],
+            taxon: None,
+        };
+        assert!(too_many.validate().is_err());
+    }
+}
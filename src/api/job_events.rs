@@ -0,0 +1,191 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::{
+    response::sse::{Event, KeepAlive, Sse},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{sleep, timeout};
+
+use super::extract;
+use super::ApiConfig;
+use crate::models::job::JobId;
+use crate::{Error, Result};
+
+/// Channel the database announces committed job updates on, see the job update migration
+pub const JOB_UPDATE_CHANNEL: &str = "asdb_job_updates";
+
+/// Announcements buffered for slow subscribers before they have to re-read their job
+const JOB_EVENT_CAPACITY: usize = 1024;
+
+/// How often a watched job is re-read if no announcements arrive, in case they are lost
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long to wait before listening again after losing the database connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// IDs of updated jobs, shared by all clients watching jobs so a single database
+/// connection listens for the announcements
+#[derive(Debug, Clone)]
+pub struct JobEvents(broadcast::Sender<String>);
+
+impl Default for JobEvents {
+    fn default() -> Self {
+        Self(broadcast::channel(JOB_EVENT_CAPACITY).0)
+    }
+}
+
+impl JobEvents {
+    /// Forward the database announcements to the subscribers until the server stops
+    pub fn listen(&self, pool: &PgPool) {
+        let pool = pool.clone();
+        let sender = self.0.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = forward(&pool, &sender).await {
+                    eprintln!("->> Listening for job updates failed: {e}");
+                }
+                sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.0.subscribe()
+    }
+}
+
+async fn forward(pool: &PgPool, sender: &broadcast::Sender<String>) -> Result<()> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(JOB_UPDATE_CHANNEL).await?;
+    loop {
+        let notification = listener.recv().await?;
+        // Sending only fails if nobody is watching a job right now
+        let _ = sender.send(notification.payload().to_owned());
+    }
+}
+
+/// State of a job as pushed to the clients, a new version is committed for every update
+#[derive(Debug, PartialEq, Serialize)]
+pub struct JobState {
+    pub id: String,
+    pub status: String,
+    pub version: i32,
+    pub started: Option<DateTime<Utc>>,
+    pub finished: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JobState {
+    async fn fetch(pool: &PgPool, id: &str) -> Result<Option<Self>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT status, version, error, started_date, finished_date
+                FROM asdb_jobs.jobs
+                WHERE id = $1"#,
+            id,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| Self {
+            id: id.to_owned(),
+            status: row.status,
+            version: row.version,
+            started: row.started_date.map(|d| d.and_utc()),
+            finished: row.finished_date.map(|d| d.and_utc()),
+            error: row.error,
+        }))
+    }
+
+    /// No further updates are expected for done, failed or deleted jobs
+    pub fn is_final(&self) -> bool {
+        matches!(self.status.as_str(), "done" | "error" | "delete")
+    }
+
+    fn to_event(&self) -> Event {
+        Event::default()
+            .event("status")
+            .json_data(self)
+            .unwrap_or_else(|_| Event::default().event("status"))
+    }
+}
+
+struct Watch {
+    pool: PgPool,
+    id: String,
+    updates: broadcast::Receiver<String>,
+    last: Option<JobState>,
+    finished: bool,
+}
+
+impl Watch {
+    /// Wait for the next update of the job, the stream ends once the job is finished
+    /// or gone
+    async fn next(mut self) -> Option<(std::result::Result<Event, Infallible>, Self)> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            if self.last.is_some() {
+                match timeout(FALLBACK_POLL_INTERVAL, self.updates.recv()).await {
+                    Ok(Ok(id)) if id != self.id => continue,
+                    Ok(Err(RecvError::Closed)) => sleep(FALLBACK_POLL_INTERVAL).await,
+                    // Announced, lagged behind or timed out, re-read the job in all cases
+                    _ => {}
+                }
+            }
+
+            let Ok(current) = JobState::fetch(&self.pool, &self.id).await else {
+                sleep(FALLBACK_POLL_INTERVAL).await;
+                continue;
+            };
+            let Some(current) = current else {
+                self.finished = true;
+                let event = Event::default().event("deleted").data(&self.id);
+                return Some((Ok(event), self));
+            };
+            if self.last.as_ref() == Some(&current) {
+                continue;
+            }
+            self.finished = current.is_final();
+            let event = current.to_event();
+            self.last = Some(current);
+            return Some((Ok(event), self));
+        }
+    }
+}
+
+/// Server-sent events with the status of a job, sent on connecting and whenever the runner
+/// commits an update. The stream ends when the job is done, failed or deleted.
+pub async fn job_events(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Path(job_id): extract::Path<JobId>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let id = job_id.to_string();
+    // Subscribe before the first read, so no update can slip in between
+    let updates = config.job_events.subscribe();
+    if JobState::fetch(&pool, &id).await?.is_none() {
+        return Err(Error::NotFound);
+    }
+
+    let watch = Watch {
+        pool,
+        id,
+        updates,
+        last: None,
+        finished: false,
+    };
+    let events = stream::unfold(watch, Watch::next);
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,140 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    http::HeaderMap,
+    middleware,
+    routing::{get, post},
+    Extension, Json,
+};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use super::extract;
+use super::normalize::Routes;
+use super::{
+    available, convert, etag, go, region, search, search_stats, secmet, stats, taxa, version,
+    ApiConfig,
+};
+use crate::Result;
+
+/// Versioned paths of the old Python asdb API, so existing antiSMASH-DB front-end deployments
+/// can switch backends. Endpoints that didn't change shape reuse the current handlers.
+pub fn routes() -> Routes {
+    // Only depend on the database contents, so clients can cache them
+    let v1_cached = Routes::new()
+        .route("/stats", get(stats_v1))
+        .route(
+            "/available/term/:category/:term",
+            get(available::terms::available_terms_by_category),
+        )
+        .route(
+            "/available/categories",
+            get(available::available_categories),
+        )
+        .route(
+            "/available/filters/:category",
+            get(available::available_filters_by_category),
+        )
+        .route(
+            "/available/filter_values/:category/:filter_name",
+            get(available::available_filter_values_by_category),
+        )
+        .route_layer(middleware::from_fn(etag::conditional));
+
+    let v1 = Routes::new()
+        .route("/version", get(version::version))
+        .route("/search", post(search_v1))
+        .route("/tree/secmet", get(secmet::secmet_tree))
+        .route("/tree/taxa", get(taxa::tax_tree))
+        .route("/assembly/:identifier", get(region::show_assembly))
+        .route("/genome/:identifier", get(region::show_acc))
+        .route("/area/:record/:location", get(region::area))
+        .route("/goto/:identifier", get(go::goto))
+        .route("/goto/:identifier/:region", get(go::goto_region))
+        .route(
+            "/convert",
+            get(convert::convert_get).post(convert::convert_post),
+        )
+        .merge(v1_cached);
+
+    let v2 = Routes::new()
+        .route("/stats", get(stats::stats))
+        .route_layer(middleware::from_fn(etag::conditional))
+        .route("/search", post(search::search));
+
+    Routes::new().nest("/api/v1.0", v1).nest("/api/v2.0", v2)
+}
+
+async fn stats_v1(pool: Extension<PgPool>, config: Extension<ApiConfig>) -> Result<Json<Value>> {
+    let Json(stats) = stats::stats(pool, config).await?;
+    Ok(Json(to_v1_stats(stats)))
+}
+
+async fn search_v1(
+    extract::Query(mut params): extract::Query<search::SearchParams>,
+    uri: extract::OriginalUri,
+    pool: Extension<PgPool>,
+    req: extract::Json<search::SearchPayload>,
+) -> Result<(HeaderMap, Json<Value>)> {
+    params.explain = false;
+    search_stats::count_search(&pool, &req.query);
+    let (headers, Json(reply)) =
+        search::search_json(None, extract::Query(params), uri, pool, req).await?;
+    Ok((headers, Json(to_v1_search(reply))))
+}
+
+/// The v1.0 stats had neither the assembly of the top secmet taxon nor the cluster categories
+fn to_v1_stats(mut stats: Value) -> Value {
+    if let Some(obj) = stats.as_object_mut() {
+        obj.remove("top_secmet_assembly_id");
+    }
+    if let Some(clusters) = stats["clusters"].as_array_mut() {
+        for cluster in clusters {
+            if let Some(obj) = cluster.as_object_mut() {
+                obj.remove("category");
+            }
+        }
+    }
+    stats
+}
+
+/// The v1.0 search returned its results as `clusters`
+fn to_v1_search(mut reply: Value) -> Value {
+    if let Some(obj) = reply.as_object_mut() {
+        if let Some(regions) = obj.remove("regions") {
+            obj.insert("clusters".to_string(), regions);
+        }
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_to_v1_stats() {
+        let stats = json!({
+            "num_clusters": 3,
+            "top_secmet_taxon": 1,
+            "top_secmet_assembly_id": "GCF_000203835.1",
+            "clusters": [{"name": "NRPS", "description": "NRPS", "count": 2, "category": "NRPS"}],
+        });
+        let expected = json!({
+            "num_clusters": 3,
+            "top_secmet_taxon": 1,
+            "clusters": [{"name": "NRPS", "description": "NRPS", "count": 2}],
+        });
+        assert_eq!(to_v1_stats(stats), expected);
+    }
+
+    #[test]
+    fn test_to_v1_search() {
+        let reply = json!({"regions": [{"bgc_id": 1}], "offset": 0, "paginate": 1, "total": 1});
+        let expected = json!({"clusters": [{"bgc_id": 1}], "offset": 0, "paginate": 1, "total": 1});
+        assert_eq!(to_v1_search(reply), expected);
+    }
+}
@@ -1,31 +1,89 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+pub mod admin;
+pub mod analyze;
+pub mod assembly;
 pub mod available;
 pub mod cds;
+pub mod compare;
 pub mod convert;
+pub mod cors;
 pub mod domains;
+pub mod etag;
+pub mod export;
+pub mod extract;
 pub mod go;
 pub mod job;
+pub mod job_events;
+pub mod legacy;
+pub mod normalize;
+pub mod pagination;
 pub mod region;
+pub mod request_id;
+pub mod saved_search;
 pub mod search;
+pub mod search_stats;
+pub mod secmet;
+pub mod sequence;
 pub mod stats;
+pub mod stream;
+pub mod subscription;
 pub mod taxa;
 pub mod version;
 
+use std::path::PathBuf;
+
 use axum::{Extension, Router};
 use sqlx::PgPool;
 
-pub fn init_routes(pool: PgPool) -> Router {
-    Router::new()
+#[derive(Debug, Clone, Default)]
+pub struct ApiConfig {
+    pub admin_token: Option<String>,
+    pub stats_cache: stats::StatsCache,
+    pub db_version: etag::DbVersion,
+    pub sequence_limits: crate::jobs::blast::SequenceLimits,
+    pub parse_limits: crate::query::ParseLimits,
+    pub id_limits: crate::jobs::stored_query::IdLimits,
+    /// Directory containing the antiSMASH outputs, if served
+    pub outdir: Option<PathBuf>,
+    /// Base directory of the job result files
+    pub jobdir: PathBuf,
+    pub job_events: job_events::JobEvents,
+}
+
+/// All API routes, and the fixed segments of their paths for [`normalize::RouteSegments`]
+pub fn init_routes(pool: PgPool, config: ApiConfig) -> (Router, normalize::RouteSegments) {
+    let (router, segments) = normalize::Routes::new()
+        .merge(admin::routes())
+        .merge(analyze::routes())
+        .merge(assembly::routes())
         .merge(available::routes())
+        .merge(compare::routes())
         .merge(convert::routes())
+        .merge(export::routes())
         .merge(go::routes())
         .merge(job::routes())
+        .merge(legacy::routes())
         .merge(region::routes())
+        .merge(saved_search::routes())
         .merge(search::routes())
+        .merge(search_stats::routes())
+        .merge(secmet::routes())
+        .merge(sequence::routes())
         .merge(stats::routes())
+        .merge(subscription::routes())
         .merge(taxa::routes())
         .merge(version::routes())
+        .into_parts();
+    let router = router
+        .fallback(not_found)
         .layer(Extension(pool))
+        .layer(Extension(config));
+    (router, segments)
+}
+
+/// Unknown routes get the usual error response rather than an empty body
+async fn not_found() -> crate::Error {
+    crate::Error::NotFound
 }
@@ -0,0 +1,275 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    http::{uri::PathAndQuery, Request, Uri},
+    response::IntoResponse,
+    routing::{MethodRouter, Route},
+    Router,
+};
+use tower::{Layer, Service};
+
+/// A router that keeps track of the paths of its routes, so the path normalization knows
+/// which segments are fixed without a separately maintained list
+#[derive(Debug, Default)]
+pub struct Routes {
+    router: Router,
+    paths: Vec<String>,
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, path: &str, method_router: MethodRouter) -> Self {
+        self.router = self.router.route(path, method_router);
+        self.paths.push(path.to_string());
+        self
+    }
+
+    pub fn merge(mut self, other: Routes) -> Self {
+        self.router = self.router.merge(other.router);
+        self.paths.extend(other.paths);
+        self
+    }
+
+    pub fn nest(mut self, path: &str, other: Routes) -> Self {
+        self.router = self.router.nest(path, other.router);
+        self.paths
+            .extend(other.paths.iter().map(|p| format!("{path}{p}")));
+        self
+    }
+
+    /// Like [`Router::route_layer`], the layer only wraps the routes added so far
+    pub fn route_layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + 'static,
+        L::Service: Service<Request<Body>> + Clone + Send + 'static,
+        <L::Service as Service<Request<Body>>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request<Body>>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+    {
+        self.router = self.router.route_layer(layer);
+        self
+    }
+
+    /// The router and the segment patterns of its routes
+    pub fn into_parts(self) -> (Router, RouteSegments) {
+        let segments = RouteSegments::from_paths(&self.paths);
+        (self.router, segments)
+    }
+}
+
+/// One segment of a route pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Fixed segment, stored lowercased
+    Literal(String),
+    /// `:param`, matches any non-empty segment
+    Param,
+    /// `*rest`, matches the remainder of the path
+    Rest,
+}
+
+/// The segment patterns of the API routes. Only the segments in literal positions of the
+/// matching route are lowercased, identifiers in parameter positions are left alone.
+#[derive(Debug, Clone, Default)]
+pub struct RouteSegments {
+    patterns: Vec<Vec<Segment>>,
+}
+
+impl RouteSegments {
+    pub fn from_paths<S: AsRef<str>>(paths: &[S]) -> Self {
+        let patterns = paths
+            .iter()
+            .map(|path| {
+                path.as_ref()
+                    .split('/')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| {
+                        if s.starts_with(':') {
+                            Segment::Param
+                        } else if s.starts_with('*') {
+                            Segment::Rest
+                        } else {
+                            Segment::Literal(s.to_ascii_lowercase())
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    /// Rewrite the request URI so that e.g. `/API/Search/` is routed like `/api/search`.
+    /// This needs to run before the router, so it can't be added as a regular router layer.
+    pub fn normalize_request<B>(&self, mut req: Request<B>) -> Request<B> {
+        let path = req.uri().path();
+        let normalized = self.normalize_path(path);
+        if normalized == path {
+            return req;
+        }
+
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{normalized}?{query}"),
+            None => normalized,
+        };
+
+        let mut parts = req.uri().clone().into_parts();
+        let Ok(path_and_query) = PathAndQuery::try_from(path_and_query) else {
+            return req;
+        };
+        parts.path_and_query = Some(path_and_query);
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+        }
+        req
+    }
+
+    fn normalize_path(&self, path: &str) -> String {
+        let segments: Vec<&str> = path.trim_end_matches('/').split('/').skip(1).collect();
+        // Like the router, prefer the route with the most fixed segments.
+        // Paths not matching any route, like the served output files, are left untouched.
+        let Some(pattern) = self
+            .patterns
+            .iter()
+            .filter_map(|pattern| Some((pattern, literal_matches(pattern, &segments)?)))
+            .max_by_key(|(_, literals)| *literals)
+            .map(|(pattern, _)| pattern)
+        else {
+            return path.to_string();
+        };
+
+        let normalized: Vec<&str> = segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| match pattern.get(i) {
+                Some(Segment::Literal(literal)) => literal.as_str(),
+                _ => segment,
+            })
+            .collect();
+        format!("/{}", normalized.join("/"))
+    }
+}
+
+/// Number of literal segments if the path segments match the pattern, ignoring case
+fn literal_matches(pattern: &[Segment], segments: &[&str]) -> Option<usize> {
+    let mut literals = 0;
+    for (i, part) in pattern.iter().enumerate() {
+        match part {
+            Segment::Rest => return Some(literals),
+            Segment::Param if segments.get(i)?.is_empty() => return None,
+            Segment::Param => (),
+            Segment::Literal(literal) if segments.get(i)?.eq_ignore_ascii_case(literal) => {
+                literals += 1
+            }
+            Segment::Literal(_) => return None,
+        }
+    }
+    (pattern.len() == segments.len()).then_some(literals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segments() -> RouteSegments {
+        RouteSegments::from_paths(&[
+            "/api/search",
+            "/api/assembly/:identifier",
+            "/go/:identifier",
+            "/api/v2.0/stats",
+            "/api/tree/taxa",
+            "/api/searches/:token",
+            "/api/searches/mine",
+        ])
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let tests = [
+            ("/api/search", "/api/search"),
+            ("/api/search/", "/api/search"),
+            ("/API/Search/", "/api/search"),
+            (
+                "/api/assembly/GCF_000203835.1/",
+                "/api/assembly/GCF_000203835.1",
+            ),
+            ("/Go/NC_003888.3", "/go/NC_003888.3"),
+            ("/API/V2.0/Stats", "/api/v2.0/stats"),
+            // Identifiers that happen to look like fixed segments keep their case
+            ("/api/assembly/Search", "/api/assembly/Search"),
+            ("/Go/Stats", "/go/Stats"),
+            ("/API/Searches/AbCd", "/api/searches/AbCd"),
+            ("/API/Searches/Mine", "/api/searches/mine"),
+            ("/API/Unknown/", "/API/Unknown/"),
+            ("/api/assembly//", "/api/assembly//"),
+            ("/output/GCF_000203835.1/", "/output/GCF_000203835.1/"),
+            ("/", "/"),
+        ];
+        let segments = segments();
+        for (input, expected) in tests {
+            assert_eq!(segments.normalize_path(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_request() {
+        let req = Request::builder()
+            .uri("/API/Tree/Taxa/?id=1")
+            .body(())
+            .unwrap();
+        let req = segments().normalize_request(req);
+        assert_eq!(req.uri().path(), "/api/tree/taxa");
+        assert_eq!(req.uri().query(), Some("id=1"));
+    }
+
+    #[test]
+    fn test_routes() {
+        let v1 = Routes::new().route("/Search", axum::routing::post(|| async {}));
+        let routes = Routes::new()
+            .route("/api/job/:job_id", axum::routing::get(|| async {}))
+            .route("/files/*path", axum::routing::get(|| async {}))
+            .nest("/api/v1.0", v1);
+        let (_, segments) = routes.into_parts();
+        let literal = |s: &str| Segment::Literal(s.to_string());
+        assert_eq!(
+            segments.patterns,
+            [
+                vec![literal("api"), literal("job"), Segment::Param],
+                vec![literal("files"), Segment::Rest],
+                vec![literal("api"), literal("v1.0"), literal("search")],
+            ]
+        );
+        assert_eq!(segments.normalize_path("/Files/A/B"), "/files/A/B");
+    }
+
+    #[tokio::test]
+    async fn test_api_route_segments() {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost/asdb").unwrap();
+        let (_, segments) = crate::api::init_routes(pool, Default::default());
+        let tests = [
+            ("/API/Jobs/StoredQuery/", "/api/jobs/storedquery"),
+            ("/api/job/AbC/Download", "/api/job/AbC/download"),
+            (
+                "/API/Region/12/AntiSMASH.json",
+                "/api/region/12/antismash.json",
+            ),
+            ("/API/Jobs/Mine", "/api/jobs/mine"),
+            ("/API/Searches/Mine", "/api/searches/Mine"),
+            ("/Go/Search", "/go/Search"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(segments.normalize_path(input), expected, "{input}");
+        }
+        assert!(!segments
+            .patterns
+            .iter()
+            .flatten()
+            .any(|s| matches!(s, Segment::Literal(l) if l.starts_with(':'))));
+    }
+}
@@ -0,0 +1,136 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::http::{
+    header::{HeaderName, LINK},
+    HeaderMap, HeaderValue, Uri,
+};
+
+pub const TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+/// Position of a page of results within all `total` results
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub offset: usize,
+    /// Number of results per page, 0 if all results are returned at once
+    pub limit: usize,
+    pub total: usize,
+}
+
+impl Page {
+    /// The `X-Total-Count` header and RFC 5988 `Link` headers to the previous and next pages.
+    /// The links repeat the request URI with the offset and the limit, sent as `limit_param`,
+    /// replaced.
+    pub fn headers(&self, uri: &Uri, limit_param: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(self.total));
+
+        let links: Vec<String> = self
+            .neighbours()
+            .into_iter()
+            .map(|(rel, offset)| {
+                let target = page_uri(uri, offset, self.limit, limit_param);
+                format!("<{target}>; rel=\"{rel}\"")
+            })
+            .collect();
+        if let Ok(value) = HeaderValue::from_str(&links.join(", ")) {
+            if !links.is_empty() {
+                headers.insert(LINK, value);
+            }
+        }
+        headers
+    }
+
+    fn neighbours(&self) -> Vec<(&'static str, usize)> {
+        let mut pages = Vec::new();
+        if self.limit == 0 {
+            return pages;
+        }
+        if self.offset > 0 {
+            pages.push(("prev", self.offset.saturating_sub(self.limit)));
+        }
+        if self.offset + self.limit < self.total {
+            pages.push(("next", self.offset + self.limit));
+        }
+        pages
+    }
+}
+
+fn page_uri(uri: &Uri, offset: usize, limit: usize, limit_param: &str) -> String {
+    let mut params: Vec<String> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| {
+            let key = param.split('=').next().unwrap_or_default();
+            !key.is_empty() && key != "offset" && key != limit_param
+        })
+        .map(|param| param.to_string())
+        .collect();
+    params.push(format!("offset={offset}"));
+    params.push(format!("{limit_param}={limit}"));
+    format!("{}?{}", uri.path(), params.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() {
+        let uri: Uri = "/api/jobs?status=done&offset=20&limit=10".parse().unwrap();
+        let tests = [
+            (
+                Page {
+                    offset: 20,
+                    limit: 10,
+                    total: 50,
+                },
+                Some(
+                    r#"</api/jobs?status=done&offset=10&limit=10>; rel="prev", </api/jobs?status=done&offset=30&limit=10>; rel="next""#,
+                ),
+            ),
+            (
+                Page {
+                    offset: 0,
+                    limit: 10,
+                    total: 50,
+                },
+                Some(r#"</api/jobs?status=done&offset=10&limit=10>; rel="next""#),
+            ),
+            (
+                Page {
+                    offset: 5,
+                    limit: 10,
+                    total: 12,
+                },
+                Some(r#"</api/jobs?status=done&offset=0&limit=10>; rel="prev""#),
+            ),
+            (
+                Page {
+                    offset: 0,
+                    limit: 10,
+                    total: 10,
+                },
+                None,
+            ),
+            (
+                Page {
+                    offset: 0,
+                    limit: 0,
+                    total: 50,
+                },
+                None,
+            ),
+        ];
+        for (page, expected) in tests {
+            let headers = page.headers(&uri, "limit");
+            assert_eq!(
+                headers[TOTAL_COUNT_HEADER].to_str().unwrap(),
+                page.total.to_string()
+            );
+            let link = headers.get(LINK).map(|l| l.to_str().unwrap());
+            assert_eq!(link, expected, "{page:?}");
+        }
+    }
+}
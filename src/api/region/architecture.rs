@@ -0,0 +1,116 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use crate::{Error, Result};
+
+/// Short names used in domain architecture drawings, and the aSDomain profiles they stand for
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("C", "Condensation(_[A-Za-z]+)?|Cglyc"),
+    ("Cy", "Heterocyclization"),
+    ("E", "Epimerization"),
+    ("A", "AMP-binding|A-OX"),
+    ("PCP", "PCP"),
+    ("TE", "Thioesterase"),
+    ("TD", "TD"),
+    ("KS", "PKS_KS"),
+    ("AT", "PKS_AT"),
+    ("KR", "PKS_KR"),
+    ("DH", "PKS_DH[A-Za-z0-9]*"),
+    ("ER", "PKS_ER"),
+    ("ACP", "ACP(_beta)?|PP-binding"),
+    ("MT", "[cno]MT"),
+];
+
+/// Translate a domain architecture pattern like `C-A-*-TE` into a POSIX regular expression
+/// matching architecture strings of the form `<Condensation_LCL><AMP-binding><PCP>`.
+/// Elements are abbreviations like `A` or full aSDomain profile names, `?` stands for any
+/// single domain and `*` for any number of domains. The pattern matches a run of domains
+/// anywhere in the architecture unless anchored with `^` or `$`.
+pub fn architecture_to_regex(pattern: &str) -> Result<String> {
+    let invalid =
+        |reason: &str| Error::InvalidRequest(format!("invalid architecture {pattern}: {reason}"));
+
+    let mut architecture = pattern.trim();
+    let anchor_start = architecture.starts_with('^');
+    let anchor_end = architecture.ends_with('$');
+    architecture = architecture.trim_start_matches('^').trim_end_matches('$');
+    if architecture.is_empty() {
+        return Err(invalid("architecture is empty"));
+    }
+
+    let mut regex = String::new();
+    if anchor_start {
+        regex.push('^');
+    }
+    for element in architecture.split('-').map(str::trim) {
+        let pattern = match element {
+            "?" => "<[^>]+>".to_string(),
+            "*" => "(<[^>]+>)*".to_string(),
+            "" => return Err(invalid("empty domain name")),
+            name => match ABBREVIATIONS.iter().find(|(abbr, _)| *abbr == name) {
+                Some((_, names)) => format!("<({names})>"),
+                None => {
+                    if !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+                    {
+                        return Err(invalid("bad character in domain name"));
+                    }
+                    format!("<{}>", name.replace('.', r"\."))
+                }
+            },
+        };
+        regex.push_str(&pattern);
+    }
+    if anchor_end {
+        regex.push('$');
+    }
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_architecture_to_regex() {
+        let tests = [
+            ("PKS_KS-PKS_AT", "<PKS_KS><PKS_AT>"),
+            ("KS-?-ACP", "<(PKS_KS)><[^>]+><(ACP(_beta)?|PP-binding)>"),
+            (
+                "^A-*-TE$",
+                "^<(AMP-binding|A-OX)>(<[^>]+>)*<(Thioesterase)>$",
+            ),
+            ("Foo.1", r"<Foo\.1>"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(architecture_to_regex(input).unwrap(), expected, "{input}");
+        }
+
+        let invalid = ["", "^$", "C--A", "C-A-", "C-[A]", "C-A>"];
+        for input in invalid {
+            assert!(architecture_to_regex(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_architecture_matches() {
+        let architecture = "<Condensation_Starter><AMP-binding><PCP><Epimerization><Thioesterase>";
+        let tests = [
+            ("C-A-PCP", true),
+            ("A-PCP-TE", false),
+            ("A-PCP-*-TE", true),
+            ("A-PCP-?-TE", true),
+            ("A-PCP-?-?-TE", false),
+            ("^A", false),
+            ("^C-A", true),
+            ("E-TE$", true),
+            ("PCP-E-TE-*$", true),
+        ];
+        for (input, expected) in tests {
+            let regex = Regex::new(&architecture_to_regex(input).unwrap()).unwrap();
+            assert_eq!(regex.is_match(architecture), expected, "{input}");
+        }
+    }
+}
@@ -1,12 +1,13 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{extract, Extension, Json};
+use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
 
 use super::{ids_to_regions, sanitise_id, Region, RegionId};
+use crate::api::extract;
 use crate::{Error, Result};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -0,0 +1,187 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use async_recursion::async_recursion;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::time::Instant;
+
+use super::expression::{expression_query, handle_expression, SqlParam};
+use super::{all_region_ids, combine_ids};
+use crate::query::{Expression, Operator, Query, Term};
+use crate::search::Category;
+use crate::Result;
+
+/// Instrumentation results for a single expression of a query
+#[derive(Debug, Serialize)]
+pub struct ExpressionAudit {
+    pub category: Category,
+    pub value: String,
+    pub count: i64,
+    pub sql: String,
+    pub params: Vec<SqlParam>,
+    pub rows: usize,
+    pub elapsed_ms: f64,
+    pub plan: Option<PlanSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueryAudit {
+    pub total: usize,
+    pub elapsed_ms: f64,
+    pub expressions: Vec<ExpressionAudit>,
+}
+
+/// The interesting bits of an `EXPLAIN (ANALYZE, FORMAT JSON)` run
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PlanSummary {
+    pub planning_ms: f64,
+    pub execution_ms: f64,
+    pub node_type: String,
+    pub total_cost: f64,
+    pub actual_rows: f64,
+    pub seq_scans: Vec<String>,
+}
+
+impl PlanSummary {
+    pub fn from_explain(explain: &Value) -> Option<Self> {
+        let entry = explain.get(0)?;
+        let plan = entry.get("Plan")?;
+
+        let mut seq_scans = Vec::new();
+        collect_seq_scans(plan, &mut seq_scans);
+
+        Some(Self {
+            planning_ms: entry.get("Planning Time")?.as_f64()?,
+            execution_ms: entry.get("Execution Time")?.as_f64()?,
+            node_type: plan.get("Node Type")?.as_str()?.to_string(),
+            total_cost: plan.get("Total Cost")?.as_f64()?,
+            actual_rows: plan.get("Actual Rows")?.as_f64()?,
+            seq_scans,
+        })
+    }
+}
+
+fn collect_seq_scans(plan: &Value, seq_scans: &mut Vec<String>) {
+    if plan.get("Node Type").and_then(|n| n.as_str()) == Some("Seq Scan") {
+        if let Some(relation) = plan.get("Relation Name").and_then(|r| r.as_str()) {
+            seq_scans.push(relation.to_string());
+        }
+    }
+    if let Some(children) = plan.get("Plans").and_then(|p| p.as_array()) {
+        for child in children {
+            collect_seq_scans(child, seq_scans);
+        }
+    }
+}
+
+/// Run a query with per-expression timings, row counts and query plans
+pub async fn audit(pool: &PgPool, query: &Query) -> Result<QueryAudit> {
+    let mut expressions = Vec::new();
+    let start = Instant::now();
+    let ids = audit_term(pool, &query.terms, &mut expressions).await?;
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(QueryAudit {
+        total: ids.len(),
+        elapsed_ms,
+        expressions,
+    })
+}
+
+#[async_recursion]
+async fn audit_term(
+    pool: &PgPool,
+    term: &Term,
+    expressions: &mut Vec<ExpressionAudit>,
+) -> Result<Vec<i32>> {
+    match term {
+        Term::Expr(e) => {
+            let (ids, audit) = audit_expression(pool, e).await?;
+            expressions.push(audit);
+            Ok(ids)
+        }
+        Term::Op(o) => {
+            let left = audit_term(pool, &o.left, expressions).await?;
+            let right = audit_term(pool, &o.right, expressions).await?;
+            Ok(combine_ids(&o.operator, left, right))
+        }
+        Term::Not(n) => {
+            let excluded = audit_term(pool, &n.term, expressions).await?;
+            Ok(combine_ids(
+                &Operator::Except,
+                all_region_ids(pool).await?,
+                excluded,
+            ))
+        }
+    }
+}
+
+async fn audit_expression(pool: &PgPool, expr: &Expression) -> Result<(Vec<i32>, ExpressionAudit)> {
+    let query = expression_query(expr)?;
+
+    let start = Instant::now();
+    let ids = handle_expression(pool, expr).await?;
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let explain: Value = sqlx::query_scalar_with(
+        &format!("EXPLAIN (ANALYZE, FORMAT JSON) {}", query.sql),
+        query.arguments(),
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let audit = ExpressionAudit {
+        category: expr.category.clone(),
+        value: expr.value.to_owned(),
+        count: expr.count,
+        sql: query.sql,
+        params: query.params,
+        rows: ids.len(),
+        elapsed_ms,
+        plan: PlanSummary::from_explain(&explain),
+    };
+    Ok((ids, audit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_plan_summary() {
+        let explain = json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Total Cost": 42.5,
+                "Actual Rows": 3,
+                "Plans": [
+                    {"Node Type": "Seq Scan", "Relation Name": "regions", "Actual Rows": 10},
+                    {"Node Type": "Hash", "Plans": [
+                        {"Node Type": "Index Scan", "Relation Name": "bgc_types"},
+                        {"Node Type": "Seq Scan", "Relation Name": "rel_regions_types"}
+                    ]}
+                ]
+            },
+            "Planning Time": 0.25,
+            "Triggers": [],
+            "Execution Time": 1.5
+        }]);
+        let expected = PlanSummary {
+            planning_ms: 0.25,
+            execution_ms: 1.5,
+            node_type: "Hash Join".to_string(),
+            total_cost: 42.5,
+            actual_rows: 3.0,
+            seq_scans: vec!["regions".to_string(), "rel_regions_types".to_string()],
+        };
+        assert_eq!(PlanSummary::from_explain(&explain), Some(expected));
+    }
+
+    #[test]
+    fn test_plan_summary_invalid() {
+        assert_eq!(PlanSummary::from_explain(&json!({})), None);
+    }
+}
@@ -0,0 +1,69 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::api::extract;
+use crate::{Error, Result};
+
+#[derive(Debug, Serialize)]
+pub struct KnownCompound {
+    /// Where the compound entry comes from, e.g. npatlas
+    pub source: String,
+    pub accession: String,
+    pub name: String,
+    pub smiles: Option<String>,
+    /// MIBiG entry of the compound's gene cluster the region has a KnownClusterBlast hit to
+    pub mibig_accession: String,
+    pub similarity: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegionCompounds {
+    pub region_id: i32,
+    pub compounds: Vec<KnownCompound>,
+}
+
+/// Known compounds whose gene clusters are KnownClusterBlast hits of a region,
+/// most similar hits first
+pub async fn region_compounds(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT region_id
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let compounds = sqlx::query_as!(
+        KnownCompound,
+        r#"
+        SELECT c.source, c.accession, c.name, c.smiles, c.mibig_accession,
+            MAX(h.similarity) AS similarity
+        FROM antismash.clusterblast_hits AS h
+        JOIN antismash.clusterblast_algorithms AS a USING (algorithm_id)
+        JOIN antismash.compound_references AS c ON (c.mibig_accession = h.acc)
+        WHERE h.region_id = $1 AND a.name = 'knownclusterblast'
+        GROUP BY c.compound_reference_id
+        ORDER BY MAX(h.similarity) DESC NULLS LAST, c.name, c.source"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(json!(RegionCompounds {
+        region_id,
+        compounds,
+    })))
+}
@@ -4,6 +4,19 @@ use std::convert::From;
 
 use serde::{Deserialize, Serialize};
 
+use crate::models::location::{SimpleLocation, Strand};
+
+/// How to write regions of hybrid BGC types in CSV exports
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvStyle {
+    /// One row per region, with a combined "hybrid" type
+    #[default]
+    Flat,
+    /// One row per region and BGC type
+    Expanded,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Region {
     #[serde(rename = "bgc_id")]
@@ -14,11 +27,19 @@ pub struct Region {
     pub start_pos: i32,
     pub end_pos: i32,
     pub contig_edge: bool,
+    /// Regions usually span both strands, so this is mostly unstranded
+    #[serde(default)]
+    pub strand: Strand,
 
     #[serde(rename = "acc")]
     pub accession: Option<String>,
     pub assembly_id: Option<String>,
     pub version: Option<i32>,
+    /// Length of the parent record, if its sequence is stored
+    #[serde(default)]
+    pub record_length: Option<i32>,
+    #[serde(default)]
+    pub record_definition: Option<String>,
 
     pub genus: Option<String>,
     pub species: Option<String>,
@@ -31,6 +52,15 @@ pub struct Region {
     pub best_mibig_hit_similarity: Option<i32>,
     pub best_mibig_hit_description: Option<String>,
     pub best_mibig_hit_acc: Option<String>,
+
+    /// The individual BGC types making up `term`
+    #[serde(rename = "terms", default)]
+    pub types: Vec<String>,
+    /// Categories and descriptions of the BGC types, in the same order as `types`
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub descriptions: Vec<String>,
 }
 
 impl Region {
@@ -38,24 +68,39 @@ impl Region {
         "#Genus\tSpecies\tStrain\tNCBI accession\tFrom\tTo\tBGC type\tOn contig edge\tMost similar known cluster\tSimilarity in %\tMIBiG BGC-ID\tResults URL"
     }
 
-    pub fn to_csv(self) -> String {
+    pub fn to_csv(self, style: CsvStyle) -> String {
+        match style {
+            CsvStyle::Flat => self.csv_row(&self.term),
+            CsvStyle::Expanded if self.types.is_empty() => self.csv_row(&self.term),
+            CsvStyle::Expanded => self
+                .types
+                .iter()
+                .map(|t| self.csv_row(t))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        }
+    }
+
+    fn csv_row(&self, term: &str) -> String {
         let acc_with_version = format!(
             "{}.{}",
-            self.accession.unwrap_or_default(),
+            self.accession.as_deref().unwrap_or_default(),
             self.version.unwrap_or_default()
         );
         let parts = [
-            self.genus.unwrap_or_default(),
-            self.species.unwrap_or_default(),
-            self.strain.unwrap_or_default(),
+            self.genus.to_owned().unwrap_or_default(),
+            self.species.to_owned().unwrap_or_default(),
+            self.strain.to_owned().unwrap_or_default(),
             acc_with_version.clone(),
             format!("{}", self.start_pos),
             format!("{}", self.end_pos),
-            self.term,
+            term.to_owned(),
             format!("{}", self.contig_edge),
-            self.best_mibig_hit_description.unwrap_or_default(),
+            self.best_mibig_hit_description
+                .to_owned()
+                .unwrap_or_default(),
             format!("{}", self.best_mibig_hit_similarity.unwrap_or_default()),
-            self.best_mibig_hit_acc.unwrap_or_default(),
+            self.best_mibig_hit_acc.to_owned().unwrap_or_default(),
             format!(
                 "https://antismash-db.secondarymetabolites.org/area?record={}&start={}&end={}",
                 acc_with_version, self.start_pos, self.end_pos
@@ -75,10 +120,13 @@ pub struct DbRegion {
     pub start_pos: i32,
     pub end_pos: i32,
     pub contig_edge: bool,
+    pub location: String,
 
     pub accession: Option<String>,
     pub assembly_id: Option<String>,
     pub version: Option<i32>,
+    pub record_length: Option<i32>,
+    pub record_definition: Option<String>,
 
     pub genus: Option<String>,
     pub species: Option<String>,
@@ -95,30 +143,26 @@ pub struct DbRegion {
 
 impl From<DbRegion> for Region {
     fn from(value: DbRegion) -> Self {
-        let term = if let Some(terms) = value.terms {
-            if terms.len() == 1 {
-                terms[0].to_owned()
-            } else {
-                format!("{} hybrid", terms.join(" "))
-            }
-        } else {
-            "".to_string()
+        let types = value.terms.unwrap_or_default();
+        let term = match types.as_slice() {
+            [] => String::new(),
+            [single] => single.to_owned(),
+            _ => format!("{} hybrid", types.join(" ")),
         };
-        let description: String = if let Some(descs) = value.descriptions {
-            if descs.len() == 1 {
-                descs[0].to_string()
-            } else {
-                format!("Hybrid region: {}", descs.join(", "))
-            }
-        } else {
-            "".to_string()
+        let descriptions = value.descriptions.unwrap_or_default();
+        let description = match descriptions.as_slice() {
+            [] => String::new(),
+            [single] => single.to_owned(),
+            _ => format!("Hybrid region: {}", descriptions.join(", ")),
         };
-        let categories = value.categories.unwrap_or(Vec::new());
-        let category = if categories.len() == 1 {
-            categories[0].to_owned()
-        } else {
-            "hybrid".to_string()
+        let categories = value.categories.unwrap_or_default();
+        let category = match categories.as_slice() {
+            [single] => single.to_owned(),
+            _ => "hybrid".to_string(),
         };
+        let strand = SimpleLocation::parse(&value.location)
+            .map(|(_, location)| location.strand)
+            .unwrap_or_default();
         Self {
             region_id: value.region_id,
             record_number: value.record_number,
@@ -126,9 +170,12 @@ impl From<DbRegion> for Region {
             start_pos: value.start_pos,
             end_pos: value.end_pos,
             contig_edge: value.contig_edge,
+            strand,
             accession: value.accession,
             assembly_id: value.assembly_id,
             version: value.version,
+            record_length: value.record_length,
+            record_definition: value.record_definition,
             genus: value.genus,
             species: value.species,
             strain: value.strain,
@@ -138,6 +185,9 @@ impl From<DbRegion> for Region {
             best_mibig_hit_similarity: value.best_mibig_hit_similarity,
             best_mibig_hit_description: value.best_mibig_hit_description,
             best_mibig_hit_acc: value.best_mibig_hit_acc,
+            types,
+            categories,
+            descriptions,
         }
     }
 }
@@ -172,4 +222,91 @@ mod tests {
             assert_eq!(result, expected);
         }
     }
+
+    fn db_region(location: &str) -> DbRegion {
+        DbRegion {
+            region_id: 1,
+            record_number: 1,
+            region_number: 1,
+            start_pos: 10,
+            end_pos: 20,
+            contig_edge: false,
+            location: location.to_string(),
+            accession: Some("NC_003888".to_string()),
+            assembly_id: Some("GCF_000203835.1".to_string()),
+            version: Some(3),
+            record_length: Some(8_667_507),
+            record_definition: Some("Streptomyces coelicolor A3(2) complete genome".to_string()),
+            genus: Some("Streptomyces".to_string()),
+            species: Some("coelicolor".to_string()),
+            strain: Some("A3(2)".to_string()),
+            terms: Some(vec!["NRPS".to_string(), "T1PKS".to_string()]),
+            descriptions: Some(vec!["NRPS".to_string(), "Type I PKS".to_string()]),
+            categories: Some(vec!["NRPS".to_string(), "PKS".to_string()]),
+            best_mibig_hit_similarity: None,
+            best_mibig_hit_description: None,
+            best_mibig_hit_acc: None,
+        }
+    }
+
+    #[test]
+    fn test_region_json() {
+        let tests = [
+            ("[10:20]", "Unstranded"),
+            ("[10:20](+)", "Forward"),
+            ("[10:20](-)", "Reverse"),
+            ("{[10:15], [17:20]}", "Unstranded"),
+        ];
+        for (location, strand) in tests {
+            let region: Region = db_region(location).into();
+            let json = serde_json::to_value(&region).unwrap();
+            assert_eq!(json["strand"], strand, "{location}");
+            assert_eq!(json["record_length"], 8_667_507);
+            assert_eq!(
+                json["record_definition"],
+                "Streptomyces coelicolor A3(2) complete genome"
+            );
+            assert!(json.get("location").is_none());
+            assert_eq!(json["term"], "NRPS T1PKS hybrid");
+            assert_eq!(json["terms"], serde_json::json!(["NRPS", "T1PKS"]));
+            assert_eq!(json["categories"], serde_json::json!(["NRPS", "PKS"]));
+            assert_eq!(
+                json["descriptions"],
+                serde_json::json!(["NRPS", "Type I PKS"])
+            );
+        }
+
+        // Region JSON stored before the record fields were added still loads
+        let mut json = serde_json::to_value(Region::from(db_region("[10:20](-)"))).unwrap();
+        for field in [
+            "strand",
+            "record_length",
+            "record_definition",
+            "terms",
+            "categories",
+            "descriptions",
+        ] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let region: Region = serde_json::from_value(json).unwrap();
+        assert_eq!(region.strand, Strand::Unstranded);
+        assert_eq!(region.record_length, None);
+        assert!(region.types.is_empty());
+    }
+
+    #[test]
+    fn test_to_csv_style() {
+        let region: Region = db_region("[10:20]").into();
+
+        let flat = region.clone().to_csv(CsvStyle::Flat);
+        assert_eq!(flat.lines().count(), 1);
+        assert!(flat.contains("\tNRPS T1PKS hybrid\t"));
+
+        let expanded: Vec<String> = region
+            .to_csv(CsvStyle::Expanded)
+            .lines()
+            .map(|l| l.split('\t').nth(6).unwrap().to_string())
+            .collect();
+        assert_eq!(expanded, vec!["NRPS", "T1PKS"]);
+    }
 }
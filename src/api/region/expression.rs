@@ -1,24 +1,365 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use sqlx::PgPool;
+use std::time::Duration;
+
+use async_recursion::async_recursion;
+use futures::future::BoxFuture;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use sqlx::{postgres::PgArguments, Arguments, PgConnection, PgPool, Postgres, Transaction};
 use strum;
 
-use crate::query::Expression;
+use crate::api::go::{closest_version, stored_versions};
+use crate::api::taxa::{lineage_params, parse_node_id};
+use crate::query::{Expression, Operator, Term};
 use crate::search::category::Category;
 use crate::{Error, Result};
 
-use crate::query::filters::tfbs;
+use crate::query::filters::{candidates, clusterblast, domains, tfbs};
+use crate::search::filters::get_filters_by_category;
 
+use super::architecture::architecture_to_regex;
+use super::motif::prosite_to_regex;
 use super::RegionId;
 
+/// Longest regular expression accepted for compound sequence searches
+pub const REGEX_MAX_LENGTH: usize = 100;
+/// Time after which a regular expression search is cancelled
+pub const REGEX_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time after which any other expression search is cancelled
+pub const SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A bind parameter for an expression query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SqlParam {
+    Text(String),
+    OptionalText(Option<String>),
+    Int(i32),
+    BigInt(i64),
+}
+
+impl From<String> for SqlParam {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<Option<String>> for SqlParam {
+    fn from(value: Option<String>) -> Self {
+        Self::OptionalText(value)
+    }
+}
+
+impl From<i32> for SqlParam {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i64> for SqlParam {
+    fn from(value: i64) -> Self {
+        Self::BigInt(value)
+    }
+}
+
+type RunQuery = Box<
+    dyn for<'c> FnOnce(&'c mut PgConnection) -> BoxFuture<'c, sqlx::Result<Vec<RegionId>>> + Send,
+>;
+
+/// A category query checked against the database at compile time, along with its SQL text
+/// for combining it with other queries and explaining it
+pub struct CheckedQuery {
+    pub query: ExpressionQuery,
+    run: RunQuery,
+}
+
+impl CheckedQuery {
+    /// Run the query, cancelling it if it runs for longer than `timeout`
+    pub async fn fetch_with_timeout(
+        self,
+        pool: &PgPool,
+        timeout: Duration,
+    ) -> Result<Vec<RegionId>> {
+        let mut tx = begin_with_timeout(pool, timeout).await?;
+        let ids = (self.run)(&mut tx)
+            .await
+            .map_err(|e| timeout_error(e, timeout))?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+}
+
+/// Build a [`CheckedQuery`] from a single SQL literal, so the SQL that runs is the SQL
+/// `query_as!` checked. The parameters need to be owned variables.
+macro_rules! checked_query {
+    ($sql:literal $(, $param:ident)* $(,)?) => {{
+        let run = sqlx::query_as!(RegionId, $sql $(, $param)*);
+        CheckedQuery {
+            query: ExpressionQuery::new($sql, vec![$(SqlParam::from($param)),*]),
+            run: Box::new(move |conn: &mut PgConnection| Box::pin(run.fetch_all(conn))),
+        }
+    }};
+}
+
+/// The SQL and bind parameters used to look up the region IDs matching an expression,
+/// or a combination of expressions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpressionQuery {
+    pub sql: String,
+    pub params: Vec<SqlParam>,
+}
+
+impl ExpressionQuery {
+    pub fn new(sql: &str, params: Vec<SqlParam>) -> Self {
+        Self {
+            sql: sql.trim().to_owned(),
+            params,
+        }
+    }
+
+    /// Combine two queries with the SQL set operation matching `operator`,
+    /// so the database does the set work instead of sending both ID lists over
+    pub fn combine(self, operator: &Operator, other: ExpressionQuery) -> Self {
+        let keyword = match operator {
+            Operator::And => "INTERSECT",
+            Operator::Or => "UNION",
+            Operator::Except => "EXCEPT",
+        };
+        let sql = format!(
+            "({})\n{keyword}\n({})",
+            self.sql,
+            shift_placeholders(&other.sql, self.params.len())
+        );
+        let mut params = self.params;
+        params.extend(other.params);
+        Self { sql, params }
+    }
+
+    /// All regions not matched by this query
+    pub fn negate(self) -> Self {
+        Self {
+            sql: format!(
+                "SELECT region_id FROM antismash.regions\nEXCEPT\n({})",
+                self.sql
+            ),
+            params: self.params,
+        }
+    }
+
+    pub fn arguments(&self) -> PgArguments {
+        let mut args = PgArguments::default();
+        for param in &self.params {
+            match param {
+                SqlParam::Text(t) => args.add(t.to_owned()),
+                SqlParam::OptionalText(t) => args.add(t.to_owned()),
+                SqlParam::Int(i) => args.add(*i),
+                SqlParam::BigInt(i) => args.add(*i),
+            }
+        }
+        args
+    }
+
+    pub async fn fetch(&self, pool: &PgPool) -> Result<Vec<RegionId>> {
+        let ids = sqlx::query_as_with::<_, RegionId, _>(&self.sql, self.arguments())
+            .fetch_all(pool)
+            .await?;
+        Ok(ids)
+    }
+
+    /// The planner's plan for the query, in PostgreSQL's JSON format. Doesn't run the query.
+    pub async fn explain(&self, pool: &PgPool) -> Result<serde_json::Value> {
+        let plan = sqlx::query_scalar_with(
+            &format!("EXPLAIN (FORMAT JSON) {}", self.sql),
+            self.arguments(),
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(plan)
+    }
+
+    /// Like fetch, but cancel the query if it runs for longer than `timeout`
+    pub async fn fetch_with_timeout(
+        &self,
+        pool: &PgPool,
+        timeout: Duration,
+    ) -> Result<Vec<RegionId>> {
+        let mut tx = begin_with_timeout(pool, timeout).await?;
+        let ids = sqlx::query_as_with::<_, RegionId, _>(&self.sql, self.arguments())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| timeout_error(e, timeout))?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+}
+
+/// Start a transaction in which statements are cancelled after `timeout`
+async fn begin_with_timeout(
+    pool: &PgPool,
+    timeout: Duration,
+) -> Result<Transaction<'static, Postgres>> {
+    let mut tx = pool.begin().await?;
+    // SET doesn't take bind parameters, but this is just a number
+    sqlx::query(&format!(
+        "SET LOCAL statement_timeout = {}",
+        timeout.as_millis()
+    ))
+    .execute(&mut *tx)
+    .await?;
+    Ok(tx)
+}
+
+fn timeout_error(error: sqlx::Error, timeout: Duration) -> Error {
+    match Error::from(error) {
+        e if e.is_query_canceled() => Error::QueryTimeout(format!(
+            "search took longer than {} seconds, try a more specific query",
+            timeout.as_secs()
+        )),
+        e => e,
+    }
+}
+
+/// Renumber the `$n` bind parameters of `sql` to follow `offset` earlier parameters
+fn shift_placeholders(sql: &str, offset: usize) -> String {
+    let placeholder = Regex::new(r"\$(\d+)").unwrap();
+    placeholder
+        .replace_all(sql, |caps: &regex::Captures| {
+            let index: usize = caps[1].parse().unwrap_or_default();
+            format!("${}", index + offset)
+        })
+        .into_owned()
+}
+
 pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i32>> {
-    let region_ids = match expr.category {
+    validate_filters(expr)?;
+    let query = checked_expression_query(expr)?;
+    let mut region_ids = query
+        .fetch_with_timeout(pool, expression_timeout(expr))
+        .await?;
+
+    for filter in &expr.filters {
+        region_ids = match expr.category {
+            Category::Tfbs => tfbs::apply_filter(pool, &region_ids, filter).await?,
+            Category::Pfam | Category::Tigrfam => {
+                domains::apply_filter(pool, &region_ids, expr, filter).await?
+            }
+            Category::ClusterBlast | Category::KnownCluster | Category::SubCluster => {
+                clusterblast::apply_filter(pool, &region_ids, expr, filter).await?
+            }
+            Category::CandidateKind => {
+                candidates::apply_filter(pool, &region_ids, expr, filter).await?
+            }
+            // Flags changing the expression query itself, like the compound sequence regex
+            _ => region_ids,
+        };
+    }
+
+    let results: Vec<i32> = region_ids.into_iter().map(|r| r.region_id).collect();
+    Ok(results)
+}
+
+/// Check that all filters of an expression are declared for its category
+pub fn validate_filters(expr: &Expression) -> Result<()> {
+    let available = get_filters_by_category(&expr.category);
+    for filter in &expr.filters {
+        if available.iter().any(|a| a.value == filter.name()) {
+            continue;
+        }
+        let message = if available.is_empty() {
+            format!("{} queries don't support filters", expr.category)
+        } else {
+            let valid: Vec<&str> = available.iter().map(|a| a.value.as_str()).collect();
+            format!(
+                "Unknown {} filter {}, valid filters are: {}",
+                expr.category,
+                filter.name(),
+                valid.join(", ")
+            )
+        };
+        return Err(Error::InvalidRequest(message));
+    }
+    Ok(())
+}
+
+/// Check that the accession versions requested in a term are stored in the database,
+/// optionally replacing missing versions by the closest stored one
+#[async_recursion]
+pub async fn resolve_versions(
+    pool: &PgPool,
+    term: &mut Term,
+    resolve: bool,
+) -> Result<Vec<String>> {
+    match term {
+        Term::Expr(expr) => Ok(resolve_acc_version(pool, expr, resolve)
+            .await?
+            .into_iter()
+            .collect()),
+        Term::Op(op) => {
+            let mut notices = resolve_versions(pool, &mut op.left, resolve).await?;
+            notices.extend(resolve_versions(pool, &mut op.right, resolve).await?);
+            Ok(notices)
+        }
+        Term::Not(not) => resolve_versions(pool, &mut not.term, resolve).await,
+    }
+}
+
+async fn resolve_acc_version(
+    pool: &PgPool,
+    expr: &mut Expression,
+    resolve: bool,
+) -> Result<Option<String>> {
+    if expr.category != Category::Acc {
+        return Ok(None);
+    }
+    let Some((acc, ver)) = expr.value.split_once('.') else {
+        return Ok(None);
+    };
+    // Leave invalid versions for the expression handler to complain about
+    let Ok(requested) = ver.parse::<i32>() else {
+        return Ok(None);
+    };
+
+    let available = stored_versions(pool, acc).await?;
+    if available.is_empty() || available.contains(&requested) {
+        return Ok(None);
+    }
+
+    if !resolve {
+        return Err(Error::VersionNotFound(
+            expr.value.to_owned(),
+            available.iter().map(|v| format!("{acc}.{v}")).collect(),
+        ));
+    }
+
+    // available isn't empty, so there always is a closest version
+    let version = closest_version(requested, &available).unwrap();
+    let resolved = format!("{acc}.{version}");
+    let notice = format!("{} not found, using {resolved} instead", expr.value);
+    expr.value = resolved;
+    Ok(Some(notice))
+}
+
+/// The SQL and bind parameters of an expression, for combining and explaining queries
+pub fn expression_query(expr: &Expression) -> Result<ExpressionQuery> {
+    Ok(checked_expression_query(expr)?.query)
+}
+
+/// The compile-time checked query looking up the region IDs matching an expression
+fn checked_expression_query(expr: &Expression) -> Result<CheckedQuery> {
+    // JSON queries don't go through the parser, so check the count here as well
+    expr.check_count()?;
+    let value = expr.value.to_owned();
+    let fuzzy_value = format!("%{}%", expr.value);
+    let count = expr.count;
+
+    let query = match expr.category {
         Category::Acc => {
             if let Some((acc, ver)) = expr.value.split_once(".") {
                 let version: i32 = ver.parse()?;
-                sqlx::query_as!(
-                    RegionId,
+                let acc = acc.to_owned();
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
@@ -27,87 +368,60 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
                     acc,
                     version,
                 )
-                .fetch_all(pool)
-                .await?
             } else {
-                sqlx::query_as!(
-                    RegionId,
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             WHERE accession = $1
             "#,
-                    expr.value,
+                    value,
                 )
-                .fetch_all(pool)
-                .await?
             }
         }
-        Category::Assembly => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::Assembly => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             WHERE assembly_id = $1
                 "#,
-                expr.value
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Type => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Type => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.rel_regions_types USING (region_id)
             JOIN antismash.bgc_types USING (bgc_type_id)
             WHERE term = $1
             GROUP BY region_id HAVING COUNT(*) >= $2
             "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::TypeCategory => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::TypeCategory => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.rel_regions_types USING (region_id)
             JOIN antismash.bgc_types USING (bgc_type_id)
             WHERE category = $1
             GROUP BY region_id HAVING COUNT(*) >= $2
             "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::CandidateKind => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::CandidateKind => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.candidates USING (region_id)
             JOIN antismash.candidate_types USING (candidate_type_id)
             WHERE description ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
             "#,
-                format!("%{}%", expr.value),
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Substrate => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            fuzzy_value,
+            count,
+        ),
+        Category::Substrate => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.modules USING (region_id)
             JOIN antismash.rel_modules_monomers AS r_m_m USING (module_id)
@@ -115,16 +429,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE substrates.name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
             "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Monomer => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::Monomer => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.modules USING (region_id)
             JOIN antismash.rel_modules_monomers AS r_m_m USING (module_id)
@@ -132,32 +441,22 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE monomers.name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
             "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Profile => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::Profile => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss AS cds USING (region_id)
             JOIN antismash.profile_hits AS ph USING (cds_id)
             WHERE ph.name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Resfam => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::Resfam => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
             JOIN antismash.resfam_domains USING (cds_id)
@@ -169,16 +468,12 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             )
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
+            value,
+            count,
+        ),
         Category::Pfam => {
             if expr.value.to_lowercase().starts_with("pfam") {
-                sqlx::query_as!(
-                    RegionId,
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
@@ -186,14 +481,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE pfam_id ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                     "#,
-                    expr.value,
-                    expr.count,
+                    value,
+                    count,
                 )
-                .fetch_all(pool)
-                .await?
             } else {
-                sqlx::query_as!(
-                    RegionId,
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
@@ -206,17 +498,14 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             )
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                    format!("%{}%", expr.value),
-                    expr.count,
+                    fuzzy_value,
+                    count,
                 )
-                .fetch_all(pool)
-                .await?
             }
         }
         Category::Tigrfam => {
             if expr.value.to_lowercase().starts_with("tigrfam") {
-                sqlx::query_as!(
-                    RegionId,
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
@@ -224,14 +513,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE tigrfam_id ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                     "#,
-                    expr.value,
-                    expr.count,
+                    value,
+                    count,
                 )
-                .fetch_all(pool)
-                .await?
             } else {
-                sqlx::query_as!(
-                    RegionId,
+                checked_query!(
                     r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
@@ -244,17 +530,13 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             )
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                    format!("%{}%", expr.value),
-                    expr.count,
+                    fuzzy_value,
+                    count,
                 )
-                .fetch_all(pool)
-                .await?
             }
         }
-        Category::GOTerm => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::GOTerm => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
             JOIN antismash.pfam_domains USING (cds_id)
@@ -263,16 +545,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE identifier ILIKE $1 OR description ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                format!("%{}%", expr.value),
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::AsDomain => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            fuzzy_value,
+            count,
+        ),
+        Category::AsDomain => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
             JOIN antismash.as_domains USING (cds_id)
@@ -280,16 +557,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE profiles.name ILIKE $1 OR description ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                format!("%{}%", expr.value),
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::AsDomainSubtype => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            fuzzy_value,
+            count,
+        ),
+        Category::AsDomainSubtype => checked_query!(
+            r#"
             WITH subtype_cte AS
             (
                 SELECT subtype
@@ -303,41 +575,49 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             JOIN subtype_cte USING (subtype)
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
+            value,
+            count,
+        ),
+        // Domains are ordered from N to C terminus, so reverse strand CDSes are flipped
+        Category::DomainArchitecture => {
+            let architecture = architecture_to_regex(&expr.value)?;
+            checked_query!(
+                r#"
+            SELECT region_id FROM antismash.cdss
+            JOIN (
+                SELECT c.cds_id, string_agg('<' || p.name || '>', '' ORDER BY
+                    CASE WHEN c.location LIKE '%(-)' THEN -d.start_pos ELSE d.start_pos END,
+                    d.as_domain_id) AS architecture
+                FROM antismash.as_domains AS d
+                JOIN antismash.as_domain_profiles AS p USING (as_domain_profile_id)
+                JOIN antismash.cdss AS c USING (cds_id)
+                GROUP BY c.cds_id, c.location
+            ) AS architectures USING (cds_id)
+            WHERE architecture ~ $1
+            GROUP BY region_id HAVING COUNT(*) >= $2
+                "#,
+                architecture,
+                count,
             )
-            .fetch_all(pool)
-            .await?
         }
-        Category::ModuleQuery => handle_modulequery(pool, &expr.value).await?,
-        Category::CrossCdsModule => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::ModuleQuery => handle_modulequery(&expr.value)?,
+        Category::CrossCdsModule => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.modules USING (region_id)
             WHERE multi_gene IS TRUE
                 "#
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::ContigEdge => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        ),
+        Category::ContigEdge => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             WHERE contig_edge IS TRUE
                 "#
-            )
-            .fetch_all(pool)
-            .await?
-        }
+        ),
         Category::T2pksElongation => {
             // This is a numeric search type
             let elongations: i32 = expr.value.parse()?;
-            sqlx::query_as!(
-                RegionId,
+            checked_query!(
                 r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
@@ -348,15 +628,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
                 elongations,
-                expr.count,
+                count,
             )
-            .fetch_all(pool)
-            .await?
         }
-        Category::T2pksProductClass => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::T2pksProductClass => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.t2pks USING (protocluster_id)
@@ -364,16 +640,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE product_class ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::T2pksStarter => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::T2pksStarter => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.t2pks USING (protocluster_id)
@@ -381,16 +652,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::T2pksProfile => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::T2pksProfile => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.t2pks USING (protocluster_id)
@@ -399,16 +665,11 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::SmCoG => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::SmCoG => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.cdss USING (region_id)
             JOIN antismash.smcog_hits USING (cds_id)
@@ -416,188 +677,198 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             WHERE smcog.name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Tfbs => {
-            let mut ids: Vec<RegionId> = sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+            count,
+        ),
+        Category::Tfbs => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.binding_sites USING (region_id)
             JOIN antismash.regulators USING (regulator_id)
             WHERE name ILIKE $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
+            value,
+            count,
+        ),
+        Category::ProteinMotif => {
+            let motif = prosite_to_regex(&expr.value)?;
+            checked_query!(
+                r#"
+            SELECT region_id FROM antismash.regions
+            JOIN antismash.cdss USING (region_id)
+            WHERE translation ~ $1
+            GROUP BY region_id HAVING COUNT(*) >= $2
+                "#,
+                motif,
+                count,
             )
-            .fetch_all(pool)
-            .await?;
-
-            for filter in &expr.filters {
-                ids = tfbs::tfbs_quality(pool, &ids, filter).await?;
-            }
-
-            ids
         }
-        Category::CompoundSeq => {
-            sqlx::query_as!(
-                RegionId,
+        Category::CompoundSeq if is_regex_search(expr) => {
+            validate_regex(&expr.value)?;
+            checked_query!(
                 r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.ripps USING (protocluster_id)
-            WHERE peptide_sequence ILIKE $1
+            WHERE peptide_sequence ~* $1
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                format!("%{}%", expr.value),
-                expr.count,
+                value,
+                count,
             )
-            .fetch_all(pool)
-            .await?
         }
-        Category::CompoundClass => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::CompoundSeq => checked_query!(
+            r#"
+            SELECT region_id FROM antismash.regions
+            JOIN antismash.protoclusters USING (region_id)
+            JOIN antismash.ripps USING (protocluster_id)
+            WHERE peptide_sequence ILIKE $1
+            GROUP BY region_id HAVING COUNT(*) >= $2
+                "#,
+            fuzzy_value,
+            count,
+        ),
+        // SMILES are case sensitive, lower case atoms are aromatic
+        Category::Smiles => checked_query!(
+            r#"
+            SELECT region_id FROM antismash.regions
+            JOIN antismash.candidates USING (region_id)
+            WHERE strpos(smiles, $1) > 0
+            GROUP BY region_id HAVING COUNT(*) >= $2
+                "#,
+            value,
+            count,
+        ),
+        Category::Compound => checked_query!(
+            r#"
+            SELECT DISTINCT region_id FROM antismash.clusterblast_hits AS h
+            JOIN antismash.clusterblast_algorithms AS a USING (algorithm_id)
+            JOIN antismash.compound_references AS c ON (c.mibig_accession = h.acc)
+            WHERE a.name = 'knownclusterblast' AND (c.name ILIKE $1 OR c.accession ILIKE $2)
+                "#,
+            fuzzy_value,
+            value,
+        ),
+        Category::CompoundClass => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.ripps USING (protocluster_id)
             WHERE subclass ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Strain => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Strain => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE strain ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Species => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Species => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE species ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Genus => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Genus => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE genus ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Family => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Family => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE family ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Order => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Order => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE taxonomic_order ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Class => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Class => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE class ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Phylum => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Phylum => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE phylum ILIKE $1
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::Superkingdom => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::Superkingdom => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.dna_sequences USING (accession)
             JOIN antismash.genomes USING (genome_id)
             JOIN antismash.taxa USING (tax_id)
             WHERE superkingdom ILIKE $1
                 "#,
-                expr.value,
+            value,
+        ),
+        Category::TaxNode => {
+            let lineage = parse_node_id(&expr.value)?;
+            // Ranks below the node's level are bound as NULL and don't restrict the search
+            let [superkingdom, phylum, class, order, family, genus, species] =
+                lineage_params(&lineage);
+            checked_query!(
+                r#"
+            SELECT region_id FROM antismash.regions
+            JOIN antismash.dna_sequences USING (accession)
+            JOIN antismash.genomes USING (genome_id)
+            JOIN antismash.taxa USING (tax_id)
+            WHERE COALESCE(superkingdom, '') ILIKE $1
+                AND ($2::text IS NULL OR COALESCE(phylum, '') ILIKE $2)
+                AND ($3::text IS NULL OR COALESCE(class, '') ILIKE $3)
+                AND ($4::text IS NULL OR COALESCE(taxonomic_order, '') ILIKE $4)
+                AND ($5::text IS NULL OR COALESCE(family, '') ILIKE $5)
+                AND ($6::text IS NULL OR COALESCE(genus, '') ILIKE $6)
+                AND ($7::text IS NULL OR COALESCE(species, '') ILIKE $7)
+                "#,
+                superkingdom,
+                phylum,
+                class,
+                order,
+                family,
+                genus,
+                species,
             )
-            .fetch_all(pool)
-            .await?
         }
-        Category::CompaRiPPsonMibig => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+        Category::CompaRiPPsonMibig => checked_query!(
+            r#"
             SELECT region_id FROM antismash.regions
             JOIN antismash.comparippson_hits USING (region_id)
             JOIN antismash.comparippson_mibig_references AS mibig USING (comparippson_mibig_id)
@@ -608,85 +879,329 @@ pub async fn handle_expression(pool: &PgPool, expr: &Expression) -> Result<Vec<i
             )
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                format!("%{}%", expr.value),
-                expr.count
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::ClusterCompareRegion => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            fuzzy_value,
+            count,
+        ),
+        Category::ClusterCompareRegion => checked_query!(
+            r#"
             SELECT r.region_id FROM antismash.regions AS r
             JOIN antismash.protoclusters USING (region_id)
             JOIN antismash.cluster_compare_hits USING (protocluster_id)
             WHERE reference_accession ILIKE $1 AND protocluster_id != NULL
                 "#,
-                expr.value,
-            )
-            .fetch_all(pool)
-            .await?
-        }
-        Category::ClusterCompareProtocluster => {
-            sqlx::query_as!(
-                RegionId,
-                r#"
+            value,
+        ),
+        Category::ClusterCompareProtocluster => checked_query!(
+            r#"
             SELECT r.region_id FROM antismash.regions AS r
             JOIN antismash.cluster_compare_hits USING (region_id)
             WHERE reference_accession ILIKE $1 AND region_id != NULL
             GROUP BY region_id HAVING COUNT(*) >= $2
                 "#,
-                expr.value,
-                expr.count,
-            )
-            .fetch_all(pool)
-            .await?
-        }
+            value,
+            count,
+        ),
         Category::ClusterBlast => {
-            handle_clusterblast(pool, &expr.value, ClusterBlastAlgorithm::ClusterBlast).await?
-        }
-        Category::KnownCluster => {
-            handle_clusterblast(pool, &expr.value, ClusterBlastAlgorithm::KnownClusterBlast).await?
-        }
-        Category::SubCluster => {
-            handle_clusterblast(pool, &expr.value, ClusterBlastAlgorithm::SubClusterBlast).await?
+            clusterblast_query(&expr.value, ClusterBlastAlgorithm::ClusterBlast, expr.count)
         }
+        Category::KnownCluster => clusterblast_query(
+            &expr.value,
+            ClusterBlastAlgorithm::KnownClusterBlast,
+            expr.count,
+        ),
+        Category::SubCluster => clusterblast_query(
+            &expr.value,
+            ClusterBlastAlgorithm::SubClusterBlast,
+            expr.count,
+        ),
     };
-    let results: Vec<i32> = region_ids.into_iter().map(|r| r.region_id).collect();
-    Ok(results)
+    Ok(query)
+}
+
+/// Time after which the search for a single expression is cancelled
+pub fn expression_timeout(expr: &Expression) -> Duration {
+    if is_regex_search(expr) {
+        REGEX_TIMEOUT
+    } else {
+        SEARCH_TIMEOUT
+    }
+}
+
+fn is_regex_search(expr: &Expression) -> bool {
+    match expr.category {
+        Category::ProteinMotif => true,
+        Category::CompoundSeq => expr.has_flag("regex"),
+        _ => false,
+    }
+}
+
+/// Reject regular expressions that are invalid or too expensive before handing them to the database
+fn validate_regex(pattern: &str) -> Result<()> {
+    if pattern.is_empty() {
+        return Err(Error::InvalidRequest(
+            "empty regular expression".to_string(),
+        ));
+    }
+    if pattern.len() > REGEX_MAX_LENGTH {
+        return Err(Error::InvalidRequest(format!(
+            "regular expression longer than {REGEX_MAX_LENGTH} characters"
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .size_limit(1 << 16)
+        .build()
+        .map_err(|e| Error::InvalidRequest(format!("invalid regular expression: {e}")))?;
+    Ok(())
 }
 
 #[derive(Debug, PartialEq, Eq, strum::AsRefStr)]
 #[strum(serialize_all = "lowercase")]
-enum ClusterBlastAlgorithm {
+pub enum ClusterBlastAlgorithm {
     ClusterBlast,
     KnownClusterBlast,
     SubClusterBlast,
 }
 
-async fn handle_clusterblast(
-    pool: &PgPool,
-    term: &str,
-    algorithm: ClusterBlastAlgorithm,
-) -> Result<Vec<RegionId>> {
-    Ok(sqlx::query_as!(
-        RegionId,
+impl ClusterBlastAlgorithm {
+    pub fn for_category(category: &Category) -> Option<Self> {
+        match category {
+            Category::ClusterBlast => Some(Self::ClusterBlast),
+            Category::KnownCluster => Some(Self::KnownClusterBlast),
+            Category::SubCluster => Some(Self::SubClusterBlast),
+            _ => None,
+        }
+    }
+}
+
+fn clusterblast_query(term: &str, algorithm: ClusterBlastAlgorithm, count: i64) -> CheckedQuery {
+    let term = term.to_owned();
+    let algorithm = algorithm.as_ref().to_owned();
+    checked_query!(
         r#"
     SELECT r.region_id FROM antismash.regions AS r
     JOIN antismash.clusterblast_hits USING (region_id)
     JOIN antismash.clusterblast_algorithms USING (algorithm_id)
     WHERE acc ILIKE $1 AND name = $2
+    GROUP BY r.region_id HAVING COUNT(*) >= $3
         "#,
         term,
-        algorithm.as_ref(),
+        algorithm,
+        count,
     )
-    .fetch_all(pool)
-    .await?)
 }
 
-async fn handle_modulequery(_pool: &PgPool, _term: &str) -> Result<Vec<RegionId>> {
+fn handle_modulequery(_term: &str) -> Result<CheckedQuery> {
     Err(Error::NotImplementedError(
         "module query not implemented yet".to_string(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine() {
+        let left = ExpressionQuery::new(
+            "SELECT region_id FROM a WHERE x = $1 AND y = $2",
+            vec![SqlParam::Text("x".to_string()), SqlParam::Int(2)],
+        );
+        let right = ExpressionQuery::new(
+            "SELECT region_id FROM b WHERE z ILIKE $1 OR w ILIKE $1",
+            vec![SqlParam::Text("z".to_string())],
+        );
+        let tests = [
+            (Operator::And, "INTERSECT"),
+            (Operator::Or, "UNION"),
+            (Operator::Except, "EXCEPT"),
+        ];
+        for (operator, keyword) in tests {
+            let combined = left.clone().combine(&operator, right.clone());
+            assert_eq!(
+                combined.sql,
+                format!(
+                    "(SELECT region_id FROM a WHERE x = $1 AND y = $2)\n{keyword}\n(SELECT region_id FROM b WHERE z ILIKE $3 OR w ILIKE $3)"
+                )
+            );
+            assert_eq!(
+                combined.params,
+                vec![
+                    SqlParam::Text("x".to_string()),
+                    SqlParam::Int(2),
+                    SqlParam::Text("z".to_string())
+                ]
+            );
+        }
+
+        let negated = right.negate();
+        assert_eq!(
+            negated.sql,
+            "SELECT region_id FROM antismash.regions\nEXCEPT\n(SELECT region_id FROM b WHERE z ILIKE $1 OR w ILIKE $1)"
+        );
+        assert_eq!(shift_placeholders("$1, $10, $2", 10), "$11, $20, $12");
+    }
+
+    #[test]
+    fn test_validate_regex() {
+        let valid = ["C.{2,4}C.{2,4}C", "^MS[TA]", "GG(I|L)GD"];
+        for pattern in valid {
+            assert!(validate_regex(pattern).is_ok(), "{pattern}");
+        }
+
+        let long = "A".repeat(REGEX_MAX_LENGTH + 1);
+        let invalid = ["", "C.{2,4", "[AC", long.as_str(), "(A{1000}){1000}"];
+        for pattern in invalid {
+            assert!(validate_regex(pattern).is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_validate_filters() {
+        use crate::query::filters::{BooleanFilter, Filter, TextFilter};
+
+        let expr = |category, filters: &[Filter]| Expression::new(category, None, filters, 1);
+        let valid = [
+            expr(Category::Acc, &[]),
+            expr(
+                Category::CandidateKind,
+                &[Filter::Text(TextFilter::new("bgctype", "NRPS"))],
+            ),
+            expr(
+                Category::CompoundSeq,
+                &[Filter::Boolean(BooleanFilter::new("regex"))],
+            ),
+        ];
+        for e in valid {
+            assert!(validate_filters(&e).is_ok(), "{e:?}");
+        }
+
+        let tests = [
+            (
+                expr(
+                    Category::Pfam,
+                    &[Filter::Boolean(BooleanFilter::new("best"))],
+                ),
+                "Unknown pfam filter best, valid filters are: score, evalue",
+            ),
+            (
+                expr(
+                    Category::Acc,
+                    &[Filter::Boolean(BooleanFilter::new("regex"))],
+                ),
+                "acc queries don't support filters",
+            ),
+        ];
+        for (e, expected) in tests {
+            let error = validate_filters(&e).unwrap_err().to_string();
+            assert!(error.contains(expected), "{error}");
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_handle_expression(pool: PgPool) {
+        use crate::query::filters::{BooleanFilter, Filter};
+
+        crate::testutils::seed(&pool).await.unwrap();
+        let tests = [
+            (Category::Acc, "NC_003888", vec![1, 2]),
+            (Category::Acc, "NC_003888.3", vec![1, 2]),
+            (Category::Acc, "NC_003888.2", vec![]),
+            (Category::Assembly, "GCF_000012265.1", vec![3]),
+            (Category::Type, "NRPS", vec![1, 3]),
+            (Category::TypeCategory, "PKS", vec![3]),
+            (Category::CandidateKind, "hybrid", vec![3]),
+            (Category::Substrate, "ala", vec![1]),
+            (Category::Monomer, "ohmal", vec![3]),
+            (Category::Profile, "AMP-binding", vec![1, 3]),
+            (Category::Resfam, "abc_efflux", vec![3]),
+            (Category::Pfam, "PF00109", vec![3]),
+            (Category::Pfam, "AMP-binding", vec![1, 3]),
+            (Category::Tigrfam, "TIGR01720", vec![1]),
+            (Category::GOTerm, "catalytic", vec![3]),
+            (Category::AsDomain, "Adenylation", vec![1, 3]),
+            (Category::AsDomainSubtype, "trans-at-ks", vec![3]),
+            // Reverse strand domains are flipped, so CDS 4 matches as well
+            (Category::DomainArchitecture, "A-PCP", vec![1, 3]),
+            (Category::DomainArchitecture, "PCP-A", vec![]),
+            (Category::CrossCdsModule, "", vec![3]),
+            (Category::ContigEdge, "", vec![2]),
+            (Category::T2pksElongation, "7", vec![3]),
+            (Category::T2pksElongation, "8", vec![]),
+            (Category::T2pksProductClass, "angucycline", vec![3]),
+            (Category::T2pksStarter, "acetyl-CoA", vec![3]),
+            (Category::T2pksProfile, "KSIII", vec![3]),
+            (Category::SmCoG, "SMCOG1002", vec![1]),
+            (Category::Tfbs, "argr", vec![2]),
+            (Category::ProteinMotif, "C-x(2)-C", vec![2]),
+            (Category::CompoundSeq, "GAIG", vec![2]),
+            (Category::Smiles, "C(=O)O", vec![1, 3]),
+            (Category::Smiles, "C1CCCCC1", vec![]),
+            (Category::Compound, "streptomycin", vec![1]),
+            (Category::Compound, "NPA000001", vec![1]),
+            (Category::CompoundClass, "class i", vec![2]),
+            (Category::Strain, "A3(2)", vec![1, 2]),
+            (Category::Species, "fluorescens", vec![3]),
+            (Category::Genus, "streptomyces", vec![1, 2]),
+            (Category::Family, "Pseudomonadaceae", vec![3]),
+            (Category::Order, "Kitasatosporales", vec![1, 2]),
+            (Category::Class, "Gammaproteobacteria", vec![3]),
+            (Category::Phylum, "actinomycetota", vec![1, 2]),
+            (Category::Superkingdom, "Bacteria", vec![1, 2, 3]),
+            (
+                Category::TaxNode,
+                "genus_bacteria_pseudomonadota_gammaproteobacteria_pseudomonadales_pseudomonadaceae_pseudomonas",
+                vec![3],
+            ),
+            (Category::CompaRiPPsonMibig, "nisin", vec![2]),
+            (Category::ClusterBlast, "NC_003888_c1", vec![3]),
+            (Category::KnownCluster, "BGC0000315", vec![1]),
+            (Category::SubCluster, "AJ007731_c1", vec![3]),
+        ];
+        for (category, value, expected) in tests {
+            let expr = Expression::new(category.clone(), Some(value), &[], 1);
+            let mut ids = handle_expression(&pool, &expr).await.unwrap();
+            ids.sort_unstable();
+            assert_eq!(ids, expected, "{category} {value}");
+        }
+
+        let counted = [
+            (Category::Type, "NRPS", 2, vec![]),
+            (Category::TypeCategory, "PKS", 2, vec![3]),
+            (Category::Profile, "AMP-binding", 2, vec![]),
+        ];
+        for (category, value, count, expected) in counted {
+            let expr = Expression::new(category.clone(), Some(value), &[], count);
+            let mut ids = handle_expression(&pool, &expr).await.unwrap();
+            ids.sort_unstable();
+            assert_eq!(ids, expected, "{category} {value} {count}");
+        }
+
+        let regex = Expression::new(
+            Category::CompoundSeq,
+            Some("C.{2}C"),
+            &[Filter::Boolean(BooleanFilter::new("regex"))],
+            1,
+        );
+        assert_eq!(handle_expression(&pool, &regex).await.unwrap(), vec![2]);
+
+        // Valid for the regex crate, but not for PostgreSQL
+        let regex = Expression::new(
+            Category::CompoundSeq,
+            Some("C(?i)C"),
+            &[Filter::Boolean(BooleanFilter::new("regex"))],
+            1,
+        );
+        let (status, body) = handle_expression(&pool, &regex)
+            .await
+            .unwrap_err()
+            .client_error();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body.message,
+            "invalid regular expression: quantifier operand invalid"
+        );
+
+        let module = Expression::new(Category::ModuleQuery, Some("A"), &[], 1);
+        assert!(handle_expression(&pool, &module).await.is_err());
+    }
+}
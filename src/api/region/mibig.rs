@@ -0,0 +1,150 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::expression::ClusterBlastAlgorithm;
+use crate::api::extract;
+use crate::{Error, Result};
+
+#[derive(Debug, Serialize)]
+pub struct KnownClusterHit {
+    pub accession: String,
+    pub description: String,
+    pub rank: i32,
+    pub similarity: Option<i32>,
+    pub url: String,
+    pub best: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterCompareHit {
+    pub accession: String,
+    pub description: String,
+    /// Set if the hit is for a single protocluster instead of the whole region
+    pub protocluster_number: Option<i32>,
+    pub score: f64,
+    pub identity_metric: Option<f64>,
+    pub order_metric: Option<f64>,
+    pub components_metric: Option<f64>,
+    pub url: String,
+    pub best: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MibigCrosslinks {
+    pub region_id: i32,
+    pub best_mibig_hit_acc: Option<String>,
+    pub knownclusterblast: Vec<KnownClusterHit>,
+    pub clustercompare: Vec<ClusterCompareHit>,
+}
+
+/// All MIBiG entries similar to a region according to KnownClusterBlast and ClusterCompare
+pub async fn region_mibig_hits(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    let region = sqlx::query!(
+        r#"
+        SELECT region_id, best_mibig_hit_acc
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let knownclusterblast = sqlx::query!(
+        r#"
+        SELECT acc, description, rank, similarity
+        FROM antismash.clusterblast_hits
+        JOIN antismash.clusterblast_algorithms USING (algorithm_id)
+        WHERE region_id = $1 AND name = $2
+        ORDER BY rank"#,
+        region_id,
+        ClusterBlastAlgorithm::KnownClusterBlast.as_ref(),
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| KnownClusterHit {
+        url: mibig_url(&row.acc),
+        best: region.best_mibig_hit_acc.as_deref() == Some(row.acc.as_str()),
+        accession: row.acc,
+        description: row.description,
+        rank: row.rank,
+        similarity: row.similarity,
+    })
+    .collect();
+
+    let mut clustercompare: Vec<ClusterCompareHit> = sqlx::query!(
+        r#"
+        SELECT reference_accession, description, protocluster_number AS "protocluster_number?",
+            score, identity_metric, order_metric, components_metric
+        FROM antismash.cluster_compare_hits AS h
+        LEFT JOIN antismash.protoclusters AS p USING (protocluster_id)
+        WHERE h.region_id = $1 OR p.region_id = $1
+        ORDER BY score DESC, reference_accession"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| ClusterCompareHit {
+        url: mibig_url(&row.reference_accession),
+        accession: row.reference_accession,
+        description: row.description,
+        protocluster_number: row.protocluster_number,
+        score: row.score,
+        identity_metric: row.identity_metric,
+        order_metric: row.order_metric,
+        components_metric: row.components_metric,
+        best: false,
+    })
+    .collect();
+    // sorted by score, so the first one is the best
+    if let Some(best) = clustercompare.first_mut() {
+        best.best = true;
+    }
+
+    Ok(Json(json!(MibigCrosslinks {
+        region_id: region.region_id,
+        best_mibig_hit_acc: region.best_mibig_hit_acc,
+        knownclusterblast,
+        clustercompare,
+    })))
+}
+
+/// Link to the MIBiG repository page of an entry, ignoring any entry version
+fn mibig_url(accession: &str) -> String {
+    let base = accession.split('.').next().unwrap_or(accession);
+    format!("https://mibig.secondarymetabolites.org/repository/{base}/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mibig_url() {
+        let tests = [
+            (
+                "BGC0000194",
+                "https://mibig.secondarymetabolites.org/repository/BGC0000194/",
+            ),
+            (
+                "BGC0000194.5",
+                "https://mibig.secondarymetabolites.org/repository/BGC0000194/",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(mibig_url(input), expected, "{input}");
+        }
+    }
+}
@@ -1,113 +1,195 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use async_recursion::async_recursion;
-use axum::{extract, routing::get, Extension, Json, Router};
+use axum::{routing::get, Extension, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
 
+use crate::api::cds;
+use crate::api::extract;
 use crate::api::go::sanitise_id;
-use crate::query::{Operation, Operator, Query, ReturnType, Term};
-use crate::Result;
+use crate::api::normalize::Routes;
+use crate::query::{Operation, Operator, Query, ReturnType, Sort, SortBy, SortOrder, Term};
+use crate::{Error, Result};
 
+pub mod architecture;
 pub mod area;
+pub mod audit;
+pub mod compounds;
 pub mod data;
 pub mod expression;
+pub mod mibig;
 pub mod modules;
+pub mod motif;
+pub mod results;
+pub mod shape;
+pub mod smiles;
+pub mod tfbs;
 
 pub use area::area;
-pub use data::{DbRegion, Region};
-pub use expression::handle_expression;
+pub use data::{CsvStyle, DbRegion, Region};
+pub use expression::{handle_expression, resolve_versions};
+pub use shape::{shape_regions, RegionShape};
 
-pub fn routes() -> Router {
-    Router::new()
+use expression::{expression_query, expression_timeout, ExpressionQuery, SEARCH_TIMEOUT};
+
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/assembly/:identifier", get(show_assembly))
         .route("/api/genome/:identifier", get(show_acc))
         .route("/api/area/:record/:location", get(area))
+        .route(
+            "/api/region/:region_id/mibig",
+            get(mibig::region_mibig_hits),
+        )
+        .route(
+            "/api/region/:region_id/antismash.json",
+            get(results::region_antismash_json),
+        )
+        .route(
+            "/api/region/:region_id/modules",
+            get(modules::region_modules),
+        )
+        .route(
+            "/api/region/:region_id/compounds",
+            get(compounds::region_compounds),
+        )
+        .route("/api/region/:region_id/smiles", get(smiles::region_smiles))
+        .route("/api/region/:region_id/tfbs", get(tfbs::region_tfbs))
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Reply {
-    pub regions: Vec<Region>,
+    pub regions: Vec<Value>,
     pub offset: usize,
     pub paginate: usize,
     pub total: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notices: Vec<String>,
 }
 
 pub async fn search(
     pool: &PgPool,
-    query: &Query,
+    query: &mut Query,
     paginate: usize,
     offset: usize,
+    sort: &Sort,
+    shape: RegionShape,
 ) -> Result<Json<Value>> {
     let value = match &query.return_type {
         ReturnType::Json => {
-            let (total, all_regions) = core_search(pool, query).await?;
-
-            let regions: Vec<Region>;
-            if paginate > 0 {
-                regions = Vec::from(&all_regions[offset..offset + paginate]);
-            } else {
-                regions = Vec::from(&all_regions[offset..]);
-            }
+            let limit = (paginate > 0).then_some(paginate);
+            let (total, regions, notices) = core_search(pool, query, sort, offset, limit).await?;
+            let regions = shape_regions(pool, &regions, shape).await?;
 
             json!(Reply {
                 regions,
-                offset: 0,
-                paginate: total,
-                total
+                offset: offset.min(total),
+                paginate,
+                total,
+                notices,
             })
         }
-        _other => {
-            let _ids = handle_term(pool, &query.terms).await?;
-            todo!()
+        other => {
+            return Err(Error::NotImplementedError(format!(
+                "{} replies to region searches",
+                other.as_ref()
+            )))
         }
     };
     Ok(Json(value))
 }
 
-async fn show_assembly(
+pub async fn show_assembly(
     Extension(pool): Extension<PgPool>,
     extract::Path(identifier): extract::Path<String>,
 ) -> Result<Json<Value>> {
     let id = sanitise_id(&identifier);
-    let query = Query::from_str(&format!("{{[assembly|{id}]}}"))?;
-    let (_, regions) = core_search(&pool, &query).await?;
+    let mut query = Query::from_str(&format!("{{[assembly|{id}]}}"))?;
+    let (_, regions, _) = core_search(&pool, &mut query, &Sort::default(), 0, None).await?;
 
     Ok(Json(json!(regions)))
 }
 
-async fn show_acc(
+pub async fn show_acc(
     Extension(pool): Extension<PgPool>,
     extract::Path(identifier): extract::Path<String>,
 ) -> Result<Json<Value>> {
     let id = sanitise_id(&identifier);
-    let query = Query::from_str(&format!("{{[acc|{id}]}}"))?;
-    let (_, regions) = core_search(&pool, &query).await?;
+    let mut query = Query::from_str(&format!("{{[acc|{id}]}}"))?;
+    let (_, regions, _) = core_search(&pool, &mut query, &Sort::default(), 0, None).await?;
 
     Ok(Json(json!(regions)))
 }
 
+#[derive(Debug, sqlx::FromRow)]
 pub struct RegionId {
     pub region_id: i32,
 }
 
-pub async fn core_search(pool: &PgPool, query: &Query) -> Result<(usize, Vec<Region>)> {
-    let ids: Vec<i32> = handle_term(&pool, &query.terms).await?;
+/// The total number of matching regions and the requested page of them
+pub async fn core_search(
+    pool: &PgPool,
+    query: &mut Query,
+    sort: &Sort,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<(usize, Vec<Region>, Vec<String>)> {
+    let notices = resolve_versions(pool, &mut query.terms, query.resolve_versions).await?;
+    let ids = query_ids(pool, query).await?;
     let total = ids.len();
-    let regions = ids_to_regions(pool, &ids).await?;
-    Ok((total, regions))
+    let regions = ids_to_sorted_regions(pool, &ids, sort, offset, limit).await?;
+    Ok((total, regions, notices))
 }
 
+pub async fn query_ids(pool: &PgPool, query: &Query) -> Result<Vec<i32>> {
+    let ids = handle_term(pool, &query.terms).await?;
+    if query.include_tombstoned {
+        return Ok(ids);
+    }
+    drop_tombstoned(pool, &ids).await
+}
+
+/// Remove regions belonging to tombstoned assemblies from a list of region IDs
+pub async fn drop_tombstoned(pool: &PgPool, ids: &[i32]) -> Result<Vec<i32>> {
+    let ids = sorted_unique(ids);
+    let mut kept = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(ID_CHUNK_SIZE) {
+        let rows = sqlx::query_as!(
+            RegionId,
+            r#"
+        SELECT region_id
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = ANY($1) AND tombstoned IS FALSE
+        "#,
+            chunk,
+        )
+        .fetch_all(pool)
+        .await?;
+        kept.extend(rows.into_iter().map(|r| r.region_id));
+    }
+    Ok(kept)
+}
+
+/// Regions ordered by region ID, fetched in chunks of at most [`ID_CHUNK_SIZE`] IDs
 pub async fn ids_to_regions(pool: &PgPool, ids: &[i32]) -> Result<Vec<Region>> {
-    let regions = sqlx::query_as!(
+    let ids = sorted_unique(ids);
+    let mut regions: Vec<Region> = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(ID_CHUNK_SIZE) {
+        let rows = sqlx::query_as!(
             DbRegion,
             r#"
-        SELECT region_id, region_number, record_number, start_pos, end_pos,
+        SELECT region_id, region_number, record_number, start_pos, end_pos, location,
             accession, assembly_id, version, contig_edge, genus, species, strain,
+            octet_length(dna) AS record_length, definition AS record_definition,
             best_mibig_hit_similarity, best_mibig_hit_description, best_mibig_hit_acc,
             array_agg(t.term) AS terms, array_agg(t.description) AS descriptions, array_agg(t.category) AS categories
         FROM antismash.regions
@@ -117,74 +199,340 @@ pub async fn ids_to_regions(pool: &PgPool, ids: &[i32]) -> Result<Vec<Region>> {
         JOIN antismash.rel_regions_types USING (region_id)
         JOIN antismash.bgc_types AS t USING (bgc_type_id)
         WHERE region_id = ANY($1)
-        GROUP BY region_id, region_number, record_number, start_pos, end_pos,
+        GROUP BY region_id, region_number, record_number, start_pos, end_pos, location,
             accession, assembly_id, version, genus, species, strain,
+            octet_length(dna), definition,
             best_mibig_hit_similarity, best_mibig_hit_description, best_mibig_hit_acc
         ORDER BY region_id
         "#,
-            ids,
+            chunk,
         )
         .fetch_all(pool)
-        .await?
-        .into_iter()
-        .map(|r| r.into())
-        .collect();
+        .await?;
+        regions.extend(rows.into_iter().map(Region::from));
+    }
+    Ok(regions)
+}
+
+/// A page of the regions in the requested order. Only the page is hydrated, the regions are
+/// ordered by their sort keys alone. A `limit` of None returns all regions after `offset`.
+pub async fn ids_to_sorted_regions(
+    pool: &PgPool,
+    ids: &[i32],
+    sort: &Sort,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<Vec<Region>> {
+    let page = sorted_region_ids(pool, ids, sort, offset, limit).await?;
+    let mut regions = ids_to_regions(pool, &page).await?;
+    // Hydration returns the regions by ID, restore the order of the page
+    let positions: HashMap<i32, usize> = page.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    regions.sort_by_key(|r| positions[&r.region_id]);
     Ok(regions)
 }
 
-pub async fn ids_to_fasta(pool: &PgPool, ids: &[i32]) -> Result<Vec<String>> {
+/// IDs of a page of the regions in the requested order, with missing values last in either
+/// direction and ties broken by ascending region ID
+pub async fn sorted_region_ids(
+    pool: &PgPool,
+    ids: &[i32],
+    sort: &Sort,
+    offset: usize,
+    limit: Option<usize>,
+) -> Result<Vec<i32>> {
+    sorted_region_ids_in_chunks(pool, ids, sort, offset, limit, ID_CHUNK_SIZE).await
+}
+
+/// Sort keys of a region, compared like the ORDER BY of [`sorted_region_ids_in_chunks`]
+#[derive(Debug)]
+struct SortKey {
+    region_id: i32,
+    taxonomy: String,
+    value: Option<i32>,
+}
+
+/// Every chunk is sorted and cut to the end of the page in the database,
+/// the chunk results are merged here before the page is taken
+async fn sorted_region_ids_in_chunks(
+    pool: &PgPool,
+    ids: &[i32],
+    sort: &Sort,
+    offset: usize,
+    limit: Option<usize>,
+    chunk_size: usize,
+) -> Result<Vec<i32>> {
+    let ids = sorted_unique(ids);
+    let page_end = limit.map(|l| (offset + l) as i64);
+    let mut keys = Vec::new();
+    for chunk in ids.chunks(chunk_size) {
+        // The C collation sorts taxonomy strings by bytes, like the merge below
+        let rows = sqlx::query_as!(
+            SortKey,
+            r#"
+        SELECT region_id,
+            concat_ws(' ', genus, species, strain) COLLATE "C" AS "taxonomy!",
+            (CASE $2
+                WHEN 'start_pos' THEN start_pos
+                WHEN 'similarity' THEN best_mibig_hit_similarity
+                WHEN 'length' THEN end_pos - start_pos
+            END) AS value
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        JOIN antismash.taxa USING (tax_id)
+        WHERE region_id = ANY($1)
+        ORDER BY
+            CASE WHEN $2 = 'taxonomy' AND NOT $3 THEN concat_ws(' ', genus, species, strain) COLLATE "C" END ASC,
+            CASE WHEN $2 = 'taxonomy' AND $3 THEN concat_ws(' ', genus, species, strain) COLLATE "C" END DESC,
+            CASE WHEN NOT $3 THEN (CASE $2
+                WHEN 'start_pos' THEN start_pos
+                WHEN 'similarity' THEN best_mibig_hit_similarity
+                WHEN 'length' THEN end_pos - start_pos
+            END) END ASC NULLS LAST,
+            CASE WHEN $3 THEN (CASE $2
+                WHEN 'start_pos' THEN start_pos
+                WHEN 'similarity' THEN best_mibig_hit_similarity
+                WHEN 'length' THEN end_pos - start_pos
+            END) END DESC NULLS LAST,
+            CASE WHEN $2 = 'region_id' AND $3 THEN region_id END DESC,
+            region_id
+        LIMIT $4
+        "#,
+            chunk,
+            sort.sort_by.as_ref(),
+            sort.sort_order == SortOrder::Desc,
+            page_end,
+        )
+        .fetch_all(pool)
+        .await?;
+        keys.extend(rows);
+    }
+
+    keys.sort_by(|a, b| compare_sort_keys(a, b, sort));
+    Ok(keys
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|key| key.region_id)
+        .collect())
+}
+
+fn compare_sort_keys(a: &SortKey, b: &SortKey, sort: &Sort) -> Ordering {
+    let desc = sort.sort_order == SortOrder::Desc;
+    let directed = |ordering: Ordering| if desc { ordering.reverse() } else { ordering };
+    let by_key = match sort.sort_by {
+        SortBy::RegionId => Ordering::Equal,
+        SortBy::Taxonomy => directed(a.taxonomy.cmp(&b.taxonomy)),
+        _ => match (a.value, b.value) {
+            (Some(x), Some(y)) => directed(x.cmp(&y)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    };
+    let by_id = if sort.sort_by == SortBy::RegionId {
+        directed(a.region_id.cmp(&b.region_id))
+    } else {
+        a.region_id.cmp(&b.region_id)
+    };
+    by_key.then(by_id)
+}
+
+/// Most IDs sent to the database as a single array parameter, very broad searches
+/// can match hundreds of thousands of regions
+pub const ID_CHUNK_SIZE: usize = 10_000;
+
+/// Sorted IDs without duplicates, so chunks neither overlap nor need reordering
+pub fn sorted_unique(ids: &[i32]) -> Vec<i32> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// DNA FASTA of the regions, extended by `flank` bp on each side up to the ends of the record
+pub async fn ids_to_fasta(pool: &PgPool, ids: &[i32], flank: u32) -> Result<Vec<String>> {
+    let ids = sorted_unique(ids);
+    let flank = i64::from(flank);
     let mut fastas = Vec::with_capacity(ids.len());
-    let rows = sqlx::query!(
-        r#"
-    SELECT accession, version, start_pos, end_pos, genus, species, strain,
-        SUBSTRING(dna FROM start_pos FOR end_pos - start_pos) AS sequence,
-        array_agg(term) AS terms
-    FROM antismash.regions
-    JOIN antismash.dna_sequences USING (accession)
-    JOIN antismash.genomes USING (genome_id)
-    JOIN antismash.taxa USING (tax_id)
-    JOIN antismash.rel_regions_types USING (region_id)
-    JOIN antismash.bgc_types AS t USING (bgc_type_id)
-    WHERE region_id = ANY($1)
-    GROUP BY region_id, accession, version, start_pos, end_pos, genus, species, strain, sequence
+    for chunk in ids.chunks(ID_CHUNK_SIZE) {
+        let rows = sqlx::query!(
+            r#"
+    SELECT accession, version, start_pos AS "start_pos!", end_pos AS "end_pos!", genus, species, strain,
+        SUBSTRING(dna FROM start_pos FOR end_pos - start_pos) AS sequence
+    FROM (
+        SELECT region_id, accession, version, genus, species, strain, dna,
+            GREATEST(r.start_pos - $2::bigint, 0)::int AS start_pos,
+            LEAST(r.end_pos + $2::bigint, length(dna))::int AS end_pos
+        FROM antismash.regions AS r
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        JOIN antismash.taxa USING (tax_id)
+        WHERE region_id = ANY($1)
+    ) AS flanked
     ORDER BY region_id
     "#,
-        ids
-    )
-    .fetch_all(pool)
-    .await?;
-
-    for row in rows {
-        fastas.push(format!(
-            ">{}.{}|{}-{}|{} {} {}\n{}",
-            row.accession,
-            row.version.unwrap_or_default(),
-            row.start_pos,
-            row.end_pos,
-            row.genus.unwrap_or_default(),
-            row.species.unwrap_or_default(),
-            row.strain.unwrap_or_default(),
-            data::break_lines(&row.sequence.unwrap_or_default(), 80)
-        ))
+            chunk,
+            flank,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for row in rows {
+            fastas.push(format!(
+                ">{}.{}|{}-{}|{} {} {}\n{}",
+                row.accession,
+                row.version.unwrap_or_default(),
+                row.start_pos,
+                row.end_pos,
+                row.genus.unwrap_or_default(),
+                row.species.unwrap_or_default(),
+                row.strain.unwrap_or_default(),
+                data::break_lines(&row.sequence.unwrap_or_default(), 80)
+            ))
+        }
     }
     Ok(fastas)
 }
 
+/// Amino acid FASTA of every CDS in the regions, ordered by region and position
+pub async fn ids_to_protein_fasta(
+    pool: &PgPool,
+    ids: &[i32],
+    core_only: bool,
+) -> Result<Vec<String>> {
+    let ids = sorted_unique(ids);
+    let mut fastas = Vec::new();
+    for chunk in ids.chunks(ID_CHUNK_SIZE) {
+        let rows = sqlx::query!(
+            r#"
+    SELECT locus_tag, protein_id, translation, c.location, accession, version, region_number
+    FROM antismash.cdss AS c
+    JOIN antismash.regions USING (region_id)
+    JOIN antismash.dna_sequences USING (accession)
+    WHERE region_id = ANY($1) AND translation IS NOT NULL
+        AND (NOT $2 OR c.functional_class = $3)
+    ORDER BY region_id, cds_id
+    "#,
+            chunk,
+            core_only,
+            cds::CORE_GENE_CLASS,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for row in rows {
+            let name = row
+                .locus_tag
+                .or(row.protein_id)
+                .unwrap_or("unknown_id".to_string());
+            fastas.push(format!(
+                ">{name}|{}.{}|{}|region{:03}\n{}",
+                row.accession,
+                row.version.unwrap_or_default(),
+                row.location,
+                row.region_number,
+                data::break_lines(&row.translation.unwrap_or_default(), 80)
+            ))
+        }
+    }
+    Ok(fastas)
+}
+
+#[async_recursion]
 async fn handle_term(pool: &PgPool, term: &Term) -> Result<Vec<i32>> {
+    if !matches!(term, Term::Expr(_)) {
+        if let Some(query) = term_query(term)? {
+            let ids = query
+                .fetch_with_timeout(pool, term_timeout(term))
+                .await?
+                .into_iter()
+                .map(|r| r.region_id)
+                .collect();
+            return Ok(ids);
+        }
+    }
+
     let ids = match term {
         Term::Expr(e) => handle_expression(pool, &e).await?,
         Term::Op(o) => handle_op(pool, &o).await?,
+        Term::Not(n) => {
+            let excluded = handle_term(pool, &n.term).await?;
+            combine_ids(&Operator::Except, all_region_ids(pool).await?, excluded)
+        }
     };
     Ok(ids)
 }
 
 #[async_recursion]
 async fn handle_op(pool: &PgPool, op: &Operation) -> Result<Vec<i32>> {
-    let left_ids: HashSet<i32> = HashSet::from_iter(handle_term(pool, &op.left).await?.into_iter());
-    let right_ids: HashSet<i32> =
-        HashSet::from_iter(handle_term(pool, &op.right).await?.into_iter());
+    // "a AND NOT b" is "a EXCEPT b", no need to fetch all regions for the negation
+    if op.operator == Operator::And {
+        if let Term::Not(n) = op.right.as_ref() {
+            let left_ids = handle_term(pool, &op.left).await?;
+            let right_ids = handle_term(pool, &n.term).await?;
+            return Ok(combine_ids(&Operator::Except, left_ids, right_ids));
+        }
+        if let Term::Not(n) = op.left.as_ref() {
+            let left_ids = handle_term(pool, &op.right).await?;
+            let right_ids = handle_term(pool, &n.term).await?;
+            return Ok(combine_ids(&Operator::Except, left_ids, right_ids));
+        }
+    }
+    let left_ids = handle_term(pool, &op.left).await?;
+    let right_ids = handle_term(pool, &op.right).await?;
+    Ok(combine_ids(&op.operator, left_ids, right_ids))
+}
+
+/// The term as a single query combining its expressions with SQL set operations.
+/// Filters are applied in Rust, so terms containing filtered expressions are None
+/// and have their parts combined in memory instead.
+pub fn term_query(term: &Term) -> Result<Option<ExpressionQuery>> {
+    let query = match term {
+        Term::Expr(e) if e.filters.is_empty() => Some(expression_query(e)?),
+        Term::Expr(_) => None,
+        Term::Op(o) => {
+            // "a AND NOT b" is "a EXCEPT b", as in handle_op
+            let (operator, left, right) = match (&o.operator, o.left.as_ref(), o.right.as_ref()) {
+                (Operator::And, left, Term::Not(n)) => (&Operator::Except, left, n.term.as_ref()),
+                (Operator::And, Term::Not(n), right) => (&Operator::Except, right, n.term.as_ref()),
+                (operator, left, right) => (operator, left, right),
+            };
+            match (term_query(left)?, term_query(right)?) {
+                (Some(left), Some(right)) => Some(left.combine(operator, right)),
+                _ => None,
+            }
+        }
+        Term::Not(n) => term_query(&n.term)?.map(ExpressionQuery::negate),
+    };
+    Ok(query)
+}
+
+/// The strictest timeout of all expressions in the term, so combining a regular expression
+/// search with other terms doesn't extend its time limit
+fn term_timeout(term: &Term) -> Duration {
+    term.expressions()
+        .into_iter()
+        .map(expression_timeout)
+        .min()
+        .unwrap_or(SEARCH_TIMEOUT)
+}
 
-    let res = match op.operator {
+pub async fn all_region_ids(pool: &PgPool) -> Result<Vec<i32>> {
+    let ids = sqlx::query_as!(RegionId, "SELECT region_id FROM antismash.regions")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| r.region_id)
+        .collect();
+    Ok(ids)
+}
+
+fn combine_ids(operator: &Operator, left: Vec<i32>, right: Vec<i32>) -> Vec<i32> {
+    let left_ids: HashSet<i32> = HashSet::from_iter(left);
+    let right_ids: HashSet<i32> = HashSet::from_iter(right);
+
+    match operator {
         Operator::Except => left_ids
             .difference(&right_ids)
             .map(|i| *i)
@@ -194,6 +542,143 @@ async fn handle_op(pool: &PgPool, op: &Operation) -> Result<Vec<i32>> {
             .intersection(&right_ids)
             .map(|i| *i)
             .collect::<Vec<i32>>(),
-    };
-    Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_ids_to_sorted_regions(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        sqlx::query(
+            "UPDATE antismash.regions SET best_mibig_hit_similarity = 80 WHERE region_id = 2",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "UPDATE antismash.regions SET best_mibig_hit_similarity = 20 WHERE region_id = 3",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let sort = |sort_by, sort_order| Sort {
+            sort_by,
+            sort_order,
+        };
+        let tests = [
+            (sort(SortBy::RegionId, SortOrder::Asc), [1, 2, 3]),
+            (sort(SortBy::RegionId, SortOrder::Desc), [3, 2, 1]),
+            (sort(SortBy::Taxonomy, SortOrder::Asc), [3, 1, 2]),
+            (sort(SortBy::Taxonomy, SortOrder::Desc), [1, 2, 3]),
+            (sort(SortBy::StartPos, SortOrder::Asc), [1, 3, 2]),
+            (sort(SortBy::StartPos, SortOrder::Desc), [2, 1, 3]),
+            (sort(SortBy::Similarity, SortOrder::Asc), [3, 2, 1]),
+            (sort(SortBy::Similarity, SortOrder::Desc), [2, 3, 1]),
+            (sort(SortBy::Length, SortOrder::Desc), [3, 1, 2]),
+        ];
+        for (sort, expected) in tests {
+            let regions = ids_to_sorted_regions(&pool, &[3, 1, 2, 1], &sort, 0, None)
+                .await
+                .unwrap();
+            let ids: Vec<i32> = regions.iter().map(|r| r.region_id).collect();
+            assert_eq!(ids, expected, "{sort:?}");
+
+            // Merging chunk results gives the same order as sorting everything at once
+            for chunk_size in [1, 2] {
+                let ids =
+                    sorted_region_ids_in_chunks(&pool, &[3, 1, 2], &sort, 0, None, chunk_size)
+                        .await
+                        .unwrap();
+                assert_eq!(ids, expected, "{sort:?} {chunk_size}");
+            }
+        }
+
+        let taxonomy = sort(SortBy::Taxonomy, SortOrder::Asc);
+        let pages = [
+            (0, Some(2), vec![3, 1]),
+            (1, Some(1), vec![1]),
+            (2, None, vec![2]),
+            (5, Some(2), vec![]),
+        ];
+        for (offset, limit, expected) in pages {
+            let regions = ids_to_sorted_regions(&pool, &[1, 2, 3], &taxonomy, offset, limit)
+                .await
+                .unwrap();
+            let ids: Vec<i32> = regions.iter().map(|r| r.region_id).collect();
+            assert_eq!(ids, expected, "{offset} {limit:?}");
+
+            let ids = sorted_region_ids_in_chunks(&pool, &[1, 2, 3], &taxonomy, offset, limit, 1)
+                .await
+                .unwrap();
+            assert_eq!(ids, expected, "{offset} {limit:?}");
+        }
+    }
+
+    #[test]
+    fn test_term_query() {
+        let tests = [
+            ("{[type|NRPS]}", Some(0)),
+            ("{[type|NRPS]} OR {[type|T1PKS]}", Some(1)),
+            ("{[type|NRPS]} AND NOT {[acc|NC_003888]}", Some(1)),
+            ("NOT {[type|NRPS]}", Some(1)),
+            (
+                "({[type|NRPS]} OR {[type|T1PKS]}) AND {[acc|NC_003888]}",
+                Some(2),
+            ),
+            ("{[type|NRPS]} AND {[tfbs|ZuR] WITH [quality|==:30]}", None),
+            ("NOT {[tfbs|ZuR] WITH [quality|==:30]}", None),
+        ];
+        for (input, expected) in tests {
+            let (_, term) = Term::parse(input).unwrap();
+            let query = term_query(&term).unwrap();
+            let operations = query.as_ref().map(|q| {
+                ["INTERSECT", "UNION", "EXCEPT"]
+                    .iter()
+                    .map(|keyword| q.sql.matches(keyword).count())
+                    .sum::<usize>()
+            });
+            assert_eq!(operations, expected, "{input}");
+        }
+
+        let (_, term) = Term::parse("{[type|NRPS]} AND NOT {[acc|NC_003888]}").unwrap();
+        let query = term_query(&term).unwrap().unwrap();
+        assert!(query.sql.contains("\nEXCEPT\n"), "{}", query.sql);
+        assert!(!query.sql.contains("INTERSECT"), "{}", query.sql);
+    }
+
+    #[test]
+    fn test_sorted_unique() {
+        let ids: Vec<i32> = (0..250_000).rev().chain(0..10).collect();
+        let unique = sorted_unique(&ids);
+        assert_eq!(unique.len(), 250_000);
+        assert!(unique.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(unique.chunks(ID_CHUNK_SIZE).count(), 25);
+    }
+
+    /// Hydration time of a very broad search, run with `cargo test -- --ignored`
+    /// against the database in `DATABASE_URL`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_ids_to_regions() {
+        let pool = PgPool::connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let ids: Vec<i32> = (1..=500_000).rev().collect();
+
+        let start = std::time::Instant::now();
+        let regions = ids_to_regions(&pool, &ids).await.unwrap();
+        eprintln!(
+            "->> Hydrated {} regions from {} IDs in {:?}",
+            regions.len(),
+            ids.len(),
+            start.elapsed()
+        );
+        assert!(regions
+            .windows(2)
+            .all(|pair| pair[0].region_id < pair[1].region_id));
+    }
 }
@@ -1,2 +1,211 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::HashMap;
+
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::expression::{ExpressionQuery, SqlParam};
+use crate::api::extract;
+use crate::{Error, Result};
+
+/// Most monomers accepted in an ordered monomer search
+pub const MAX_MONOMERS: usize = 20;
+
+/// Regions with consecutive modules incorporating the monomers in the given order.
+/// Modules are numbered by their position in the region, and assembly lines on the
+/// reverse strand run backwards, so the monomers match in either direction.
+pub fn monomer_sequence_query(monomers: &[String]) -> Result<ExpressionQuery> {
+    if monomers.is_empty() {
+        return Err(Error::InvalidRequest("No monomers given".to_string()));
+    }
+    if monomers.len() > MAX_MONOMERS {
+        return Err(Error::InvalidRequest(format!(
+            "Cannot search for more than {MAX_MONOMERS} monomers at once"
+        )));
+    }
+    if monomers.iter().any(|m| m.trim().is_empty()) {
+        return Err(Error::InvalidRequest("Empty monomer name".to_string()));
+    }
+
+    let mut joins = Vec::new();
+    let mut conditions = vec!["m0.name ILIKE $1".to_string()];
+    for i in 1..monomers.len() {
+        joins.push(format!(
+            "JOIN names AS m{i} ON (m{i}.region_id = m0.region_id AND m{i}.position = m0.position + {i} * direction.step)"
+        ));
+        conditions.push(format!("m{i}.name ILIKE ${}", i + 1));
+    }
+
+    let sql = format!(
+        r#"
+            WITH ordered AS (
+                SELECT region_id, module_id,
+                    ROW_NUMBER() OVER (PARTITION BY region_id ORDER BY start_pos, module_id) AS position
+                FROM antismash.modules
+            ), names AS (
+                SELECT region_id, position, monomers.name FROM ordered
+                JOIN antismash.rel_modules_monomers AS r_m_m USING (module_id)
+                JOIN antismash.monomers AS monomers ON (r_m_m.monomer = monomers.monomer_id)
+            )
+            SELECT DISTINCT m0.region_id FROM names AS m0
+            CROSS JOIN (VALUES (1), (-1)) AS direction(step)
+            {}
+            WHERE {}
+            "#,
+        joins.join("\n            "),
+        conditions.join(" AND ")
+    );
+    let params = monomers
+        .iter()
+        .map(|m| SqlParam::Text(m.trim().to_owned()))
+        .collect();
+    Ok(ExpressionQuery::new(&sql, params))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleDomain {
+    pub name: String,
+    pub description: Option<String>,
+    pub subtypes: Vec<String>,
+    pub locus_tag: Option<String>,
+    pub location: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Module {
+    pub location: String,
+    pub start: i32,
+    pub end: i32,
+    #[serde(rename = "type")]
+    pub module_type: String,
+    pub complete: bool,
+    pub iterative: bool,
+    /// Set if the module's domains are spread over more than one CDS
+    pub cross_cds: bool,
+    pub substrates: Vec<String>,
+    pub monomers: Vec<String>,
+    pub domains: Vec<ModuleDomain>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegionModules {
+    pub region_id: i32,
+    pub modules: Vec<Module>,
+}
+
+/// The NRPS/PKS modules of a region in genomic order, with their domains, for drawing
+/// the domain architecture
+pub async fn region_modules(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT region_id
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let mut domains: HashMap<i32, Vec<ModuleDomain>> = HashMap::new();
+    let rows = sqlx::query!(
+        r#"
+        SELECT module_id AS "module_id!", p.name, p.description, locus_tag, d.location,
+            array_remove(array_agg(subtype ORDER BY subtype), NULL) AS "subtypes!"
+        FROM antismash.as_domains AS d
+        JOIN antismash.as_domain_profiles AS p USING (as_domain_profile_id)
+        JOIN antismash.cdss USING (cds_id)
+        LEFT JOIN antismash.rel_as_domain_to_subtype USING (as_domain_id)
+        WHERE region_id = $1 AND module_id IS NOT NULL
+        GROUP BY as_domain_id, p.name, p.description, locus_tag
+        ORDER BY d.start_pos, as_domain_id"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?;
+    for row in rows {
+        domains
+            .entry(row.module_id)
+            .or_default()
+            .push(ModuleDomain {
+                name: row.name,
+                description: row.description,
+                subtypes: row.subtypes,
+                locus_tag: row.locus_tag,
+                location: row.location,
+            });
+    }
+
+    let modules = sqlx::query!(
+        r#"
+        SELECT module_id, location, start_pos, end_pos, type AS module_type,
+            complete, iterative, multi_gene,
+            array_remove(array_agg(DISTINCT s.name), NULL) AS "substrates!",
+            array_remove(array_agg(DISTINCT m.name), NULL) AS "monomers!"
+        FROM antismash.modules
+        LEFT JOIN antismash.rel_modules_monomers AS r_m_m USING (module_id)
+        LEFT JOIN antismash.substrates AS s ON (r_m_m.substrate = s.substrate_id)
+        LEFT JOIN antismash.monomers AS m ON (r_m_m.monomer = m.monomer_id)
+        WHERE region_id = $1
+        GROUP BY module_id
+        ORDER BY start_pos, module_id"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| Module {
+        location: row.location,
+        start: row.start_pos,
+        end: row.end_pos,
+        module_type: row.module_type,
+        complete: row.complete,
+        iterative: row.iterative,
+        cross_cds: row.multi_gene,
+        substrates: row.substrates,
+        monomers: row.monomers,
+        domains: domains.remove(&row.module_id).unwrap_or_default(),
+    })
+    .collect();
+
+    Ok(Json(json!(RegionModules { region_id, modules })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monomer_sequence_query() {
+        let query = monomer_sequence_query(&["ala".to_string(), " val ".to_string()]).unwrap();
+        assert!(query.sql.starts_with("WITH ordered AS"));
+        assert!(query.sql.contains(
+            "JOIN names AS m1 ON (m1.region_id = m0.region_id AND m1.position = m0.position + 1 * direction.step)"
+        ));
+        assert!(query
+            .sql
+            .contains("WHERE m0.name ILIKE $1 AND m1.name ILIKE $2"));
+        assert!(!query.sql.contains("m2"));
+        assert_eq!(
+            query.params,
+            vec![
+                SqlParam::Text("ala".to_string()),
+                SqlParam::Text("val".to_string())
+            ]
+        );
+
+        let too_many = vec!["ala".to_string(); MAX_MONOMERS + 1];
+        for invalid in [vec![], vec!["ala".to_string(), " ".to_string()], too_many] {
+            assert!(monomer_sequence_query(&invalid).is_err(), "{invalid:?}");
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use crate::{Error, Result};
+
+/// Largest repetition count PostgreSQL regular expressions accept
+const MAX_REPEAT: u16 = 255;
+
+/// Translate a PROSITE-style motif like `C-x(2,4)-[LIVM]-{P}-H` into a POSIX regular expression.
+/// For convenience, plain runs of residues like `GHSLG` are accepted as elements as well.
+pub fn prosite_to_regex(motif: &str) -> Result<String> {
+    let invalid = |reason: &str| Error::InvalidRequest(format!("invalid motif {motif}: {reason}"));
+
+    let motif = motif.trim().trim_end_matches('.');
+    if motif.is_empty() {
+        return Err(invalid("motif is empty"));
+    }
+
+    let elements: Vec<&str> = motif.split('-').collect();
+    let last = elements.len() - 1;
+    let mut regex = String::new();
+
+    for (i, raw) in elements.iter().enumerate() {
+        let mut element = raw.trim();
+
+        if let Some(rest) = element.strip_prefix('<') {
+            if i != 0 {
+                return Err(invalid("'<' is only allowed at the start"));
+            }
+            regex.push('^');
+            element = rest;
+        }
+        let anchor_end = match element.strip_suffix('>') {
+            Some(rest) if i == last => {
+                element = rest;
+                true
+            }
+            Some(_) => return Err(invalid("'>' is only allowed at the end")),
+            None => false,
+        };
+
+        let (residues, repeat) = match element.split_once('(') {
+            Some((residues, repeat)) => {
+                let Some(repeat) = repeat.strip_suffix(')') else {
+                    return Err(invalid("unclosed repetition"));
+                };
+                (residues, Some(parse_repeat(repeat).map_err(&invalid)?))
+            }
+            None => (element, None),
+        };
+
+        let pattern = if residues == "x" || residues == "X" {
+            ".".to_string()
+        } else if let Some(allowed) = residues.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            check_residues(allowed).ok_or_else(|| invalid("bad residue in []"))?;
+            format!("[{allowed}]")
+        } else if let Some(excluded) = residues.strip_prefix('{').and_then(|r| r.strip_suffix('}'))
+        {
+            check_residues(excluded).ok_or_else(|| invalid("bad residue in {}"))?;
+            format!("[^{excluded}]")
+        } else {
+            check_residues(residues).ok_or_else(|| invalid("bad residue"))?;
+            if repeat.is_some() && residues.len() > 1 {
+                return Err(invalid("repetitions need a single residue"));
+            }
+            residues.to_string()
+        };
+
+        regex.push_str(&pattern);
+        if let Some(repeat) = repeat {
+            regex.push_str(&repeat);
+        }
+        if anchor_end {
+            regex.push('$');
+        }
+    }
+
+    Ok(regex)
+}
+
+fn parse_repeat(raw: &str) -> std::result::Result<String, &'static str> {
+    let count = |raw: &str| -> std::result::Result<u16, &'static str> {
+        let count: u16 = raw.trim().parse().map_err(|_| "bad repetition")?;
+        if count > MAX_REPEAT {
+            return Err("repetitions can be at most 255");
+        }
+        Ok(count)
+    };
+    match raw.split_once(',') {
+        Some((min, max)) => {
+            let (min, max) = (count(min)?, count(max)?);
+            if min > max {
+                return Err("bad repetition");
+            }
+            Ok(format!("{{{min},{max}}}"))
+        }
+        None => Ok(format!("{{{}}}", count(raw)?)),
+    }
+}
+
+fn check_residues(residues: &str) -> Option<()> {
+    if residues.is_empty() || !residues.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prosite_to_regex() {
+        let tests = [
+            ("C-x(2,4)-C", "C.{2,4}C"),
+            ("C-x(255)-C", "C.{255}C"),
+            ("[LIVM]-{P}-H-x(3)-H.", "[LIVM][^P]H.{3}H"),
+            ("<M-x-K", "^M.K"),
+            ("G-x-S-x-G>", "G.S.G$"),
+            ("GHSLG", "GHSLG"),
+            ("G-H-S-L-G", "GHSLG"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(prosite_to_regex(input).unwrap(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_prosite_to_regex_invalid() {
+        let tests = [
+            "",
+            "C-x(4,2)-C",
+            "C-x(300)-C",
+            "C-x(2,256)-C",
+            "C-x(2-C",
+            "c-x-c",
+            "C-<x",
+            "C>-x",
+            "GH(2)",
+            "[L1]",
+        ];
+        for input in tests {
+            assert!(prosite_to_regex(input).is_err(), "{input}");
+        }
+    }
+}
@@ -0,0 +1,354 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::path::{Path, PathBuf};
+
+use axum::{Extension, Json};
+use regex::{Captures, Regex};
+use serde_json::{json, Map, Value};
+use sqlx::PgPool;
+use tokio::fs;
+
+use crate::api::extract;
+use crate::api::ApiConfig;
+use crate::{Error, Result};
+
+/// Where a region sits in its record and in the antiSMASH output directory
+#[derive(Debug)]
+struct RegionLocation {
+    region_id: i32,
+    assembly_id: String,
+    accession: String,
+    version: Option<i32>,
+    region_number: i32,
+    location: String,
+    start_pos: i32,
+    end_pos: i32,
+    contig_edge: bool,
+}
+
+impl RegionLocation {
+    fn record_id(&self) -> String {
+        format!("{}.{}", self.accession, self.version.unwrap_or_default())
+    }
+
+    fn info(&self, source: &str) -> Value {
+        json!({
+            "region_id": self.region_id,
+            "record_id": self.record_id(),
+            "region_number": self.region_number,
+            "start": self.start_pos,
+            "end": self.end_pos,
+            "source": source,
+        })
+    }
+}
+
+/// The region's part of the stored antiSMASH result JSON, for embedding the antiSMASH
+/// visualisations. Coordinates are relative to the region start. Without a stored result,
+/// a minimal record is built from the database instead.
+pub async fn region_antismash_json(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    let region = sqlx::query_as!(
+        RegionLocation,
+        r#"
+        SELECT region_id, assembly_id, accession, version, region_number, location,
+            start_pos, end_pos, contig_edge
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    if let Some(outdir) = &config.outdir {
+        if let Ok(content) = fs::read(result_json_path(outdir, &region.assembly_id)).await {
+            let results: Value = serde_json::from_slice(&content)?;
+            if let Some(sliced) = slice_results(&results, &region) {
+                return Ok(Json(sliced));
+            }
+        }
+    }
+    Ok(Json(minimal_results(&pool, &region).await?))
+}
+
+/// Location of an assembly's antiSMASH result JSON in the antiSMASH output directory
+pub fn result_json_path(outdir: &Path, assembly_id: &str) -> PathBuf {
+    outdir.join(assembly_id).join(format!("{assembly_id}.json"))
+}
+
+/// The full result with only the region's record, `None` if the record is not in the result.
+/// Module results are dropped, as they are not split up by region.
+fn slice_results(results: &Value, region: &RegionLocation) -> Option<Value> {
+    let record_id = region.record_id();
+    let record = results
+        .get("records")?
+        .as_array()?
+        .iter()
+        .find(|r| r.get("id").and_then(Value::as_str) == Some(record_id.as_str()))?;
+
+    let mut sliced = results.as_object()?.clone();
+    sliced.remove("timings");
+    sliced.insert(
+        "records".to_string(),
+        json!([slice_record(record, region.start_pos, region.end_pos)]),
+    );
+    sliced.insert("region".to_string(), region.info("antismash"));
+    Some(Value::Object(sliced))
+}
+
+/// The part of a record between `start` and `end`, keeping only features and areas
+/// entirely within it
+fn slice_record(record: &Value, start: i32, end: i32) -> Value {
+    let mut sliced = record.as_object().cloned().unwrap_or_default();
+    sliced.insert("modules".to_string(), json!({}));
+
+    if let Some(data) = sliced.get_mut("seq").and_then(|seq| seq.get_mut("data")) {
+        let part = data
+            .as_str()
+            .and_then(|seq| seq.get(start as usize..end as usize))
+            .unwrap_or_default()
+            .to_string();
+        *data = Value::String(part);
+    }
+
+    if let Some(Value::Array(features)) = sliced.get_mut("features") {
+        features.retain_mut(|feature| {
+            let Some(location) = feature.get("location").and_then(Value::as_str) else {
+                return false;
+            };
+            let Some(shifted) = shift_location(location, start, end) else {
+                return false;
+            };
+            feature["location"] = Value::String(shifted);
+            true
+        });
+    }
+
+    if let Some(Value::Array(areas)) = sliced.get_mut("areas") {
+        areas.retain_mut(|area| {
+            let (Some(area_start), Some(area_end)) = (
+                area.get("start").and_then(Value::as_i64),
+                area.get("end").and_then(Value::as_i64),
+            ) else {
+                return false;
+            };
+            if area_start < start as i64 || area_end > end as i64 {
+                return false;
+            }
+            area["start"] = json!(area_start - start as i64);
+            area["end"] = json!(area_end - start as i64);
+            true
+        });
+    }
+
+    Value::Object(sliced)
+}
+
+/// Move a location like `[100:250](+)` or `join{[1:5](+), [7:9](+)}` to coordinates relative
+/// to `start`, `None` if any part of it lies outside `start..end`
+fn shift_location(location: &str, start: i32, end: i32) -> Option<String> {
+    let part = Regex::new(r"\[(<?)(\d+):(>?)(\d+)\]").unwrap();
+    let mut inside = part.is_match(location);
+    let shifted = part.replace_all(location, |caps: &Captures| {
+        let part_start: i32 = caps[2].parse().unwrap_or(-1);
+        let part_end: i32 = caps[4].parse().unwrap_or(i32::MAX);
+        if part_start < start || part_end > end {
+            inside = false;
+        }
+        format!(
+            "[{}{}:{}{}]",
+            &caps[1],
+            part_start - start,
+            &caps[3],
+            part_end - start
+        )
+    });
+    inside.then(|| shifted.into_owned())
+}
+
+/// A record with the region and its CDSes, for regions without a stored antiSMASH result
+async fn minimal_results(pool: &PgPool, region: &RegionLocation) -> Result<Value> {
+    let sequence = sqlx::query_scalar!(
+        r#"
+        SELECT SUBSTRING(dna FROM $2 + 1 FOR $3 - $2) AS sequence
+        FROM antismash.dna_sequences
+        WHERE accession = $1"#,
+        region.accession,
+        region.start_pos,
+        region.end_pos,
+    )
+    .fetch_one(pool)
+    .await?
+    .unwrap_or_default();
+
+    let products = sqlx::query_scalar!(
+        r#"
+        SELECT term FROM antismash.rel_regions_types
+        JOIN antismash.bgc_types USING (bgc_type_id)
+        WHERE region_id = $1
+        ORDER BY term"#,
+        region.region_id,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut features = vec![json!({
+        "type": "region",
+        "location": shift_location(&region.location, region.start_pos, region.end_pos)
+            .unwrap_or_default(),
+        "qualifiers": {
+            "region_number": [region.region_number.to_string()],
+            "contig_edge": [region.contig_edge.to_string().to_uppercase()],
+            "product": products,
+        },
+    })];
+
+    let cdss = sqlx::query!(
+        r#"
+        SELECT locus_tag, protein_id, gene_id, product, functional_class, translation, location
+        FROM antismash.cdss
+        WHERE region_id = $1
+        ORDER BY cds_id"#,
+        region.region_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    for cds in cdss {
+        let Some(location) = shift_location(&cds.location, region.start_pos, region.end_pos) else {
+            continue;
+        };
+        features.push(json!({
+            "type": "CDS",
+            "location": location,
+            "qualifiers": qualifiers(&[
+                ("locus_tag", cds.locus_tag),
+                ("protein_id", cds.protein_id),
+                ("gene", cds.gene_id),
+                ("product", cds.product),
+                ("gene_kind", cds.functional_class),
+                ("translation", cds.translation),
+            ]),
+        }));
+    }
+
+    Ok(json!({
+        "records": [{
+            "id": region.record_id(),
+            "name": region.accession,
+            "seq": {"data": sequence},
+            "features": features,
+            "areas": [],
+            "modules": {},
+        }],
+        "region": region.info("database"),
+    }))
+}
+
+/// GenBank-style qualifiers, every value is a list, missing values are left out
+fn qualifiers(values: &[(&str, Option<String>)]) -> Value {
+    let map: Map<String, Value> = values
+        .iter()
+        .filter_map(|(key, value)| {
+            value
+                .as_ref()
+                .map(|value| (key.to_string(), json!([value])))
+        })
+        .collect();
+    Value::Object(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_location() {
+        let tests = [
+            ("[100:250](+)", Some("[0:150](+)")),
+            ("[<120:>300](-)", Some("[<20:>200](-)")),
+            (
+                "join{[100:150](+), [200:300](+)}",
+                Some("join{[0:50](+), [100:200](+)}"),
+            ),
+            ("join{[50:150](+), [200:300](+)}", None),
+            ("[250:301](+)", None),
+            ("garbage", None),
+        ];
+        for (location, expected) in tests {
+            assert_eq!(
+                shift_location(location, 100, 300).as_deref(),
+                expected,
+                "{location}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_slice_results() {
+        let region = RegionLocation {
+            region_id: 3,
+            assembly_id: "GCF_000010605.1".to_string(),
+            accession: "NC_010572".to_string(),
+            version: Some(1),
+            region_number: 1,
+            location: "[2:6](+)".to_string(),
+            start_pos: 2,
+            end_pos: 6,
+            contig_edge: false,
+        };
+        let results = json!({
+            "version": "7.1.0",
+            "timings": {"NC_010572.1": {}},
+            "records": [
+                {"id": "NC_000001.1", "seq": {"data": "AAAA"}},
+                {
+                    "id": "NC_010572.1",
+                    "seq": {"data": "ACGTACGTAC"},
+                    "features": [
+                        {"type": "region", "location": "[2:6](+)"},
+                        {"type": "CDS", "location": "[3:5](-)"},
+                        {"type": "CDS", "location": "[5:9](+)"},
+                    ],
+                    "areas": [{"start": 2, "end": 6}, {"start": 7, "end": 9}],
+                    "modules": {"antismash.detection.hmm_detection": {}},
+                },
+            ],
+        });
+
+        let sliced = slice_results(&results, &region).unwrap();
+        assert_eq!(
+            sliced,
+            json!({
+                "version": "7.1.0",
+                "records": [{
+                    "id": "NC_010572.1",
+                    "seq": {"data": "GTAC"},
+                    "features": [
+                        {"type": "region", "location": "[0:4](+)"},
+                        {"type": "CDS", "location": "[1:3](-)"},
+                    ],
+                    "areas": [{"start": 0, "end": 4}],
+                    "modules": {},
+                }],
+                "region": {
+                    "region_id": 3,
+                    "record_id": "NC_010572.1",
+                    "region_number": 1,
+                    "start": 2,
+                    "end": 6,
+                    "source": "antismash",
+                },
+            })
+        );
+
+        let missing = json!({"records": [{"id": "NC_000001.1"}]});
+        assert_eq!(slice_results(&missing, &region), None);
+    }
+}
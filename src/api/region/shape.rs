@@ -0,0 +1,218 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use super::Region;
+use crate::Result;
+
+/// How much of each region to include in search replies
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionShape {
+    /// Only what list views show, without the description strings
+    Minimal,
+    /// All region fields
+    #[default]
+    Standard,
+    /// All region fields, plus the region's protoclusters and candidate clusters
+    Full,
+}
+
+/// The fields of a region shown in result tables
+#[derive(Debug, Serialize)]
+pub struct MinimalRegion<'a> {
+    pub bgc_id: i32,
+    pub record_number: i32,
+    pub region_number: i32,
+    pub start_pos: i32,
+    pub end_pos: i32,
+    pub contig_edge: bool,
+    pub acc: Option<&'a str>,
+    pub assembly_id: Option<&'a str>,
+    pub version: Option<i32>,
+    pub genus: Option<&'a str>,
+    pub species: Option<&'a str>,
+    pub strain: Option<&'a str>,
+    pub term: &'a str,
+    pub terms: &'a [String],
+    pub category: &'a str,
+    pub best_mibig_hit_similarity: Option<i32>,
+    pub best_mibig_hit_acc: Option<&'a str>,
+}
+
+impl<'a> From<&'a Region> for MinimalRegion<'a> {
+    fn from(region: &'a Region) -> Self {
+        Self {
+            bgc_id: region.region_id,
+            record_number: region.record_number,
+            region_number: region.region_number,
+            start_pos: region.start_pos,
+            end_pos: region.end_pos,
+            contig_edge: region.contig_edge,
+            acc: region.accession.as_deref(),
+            assembly_id: region.assembly_id.as_deref(),
+            version: region.version,
+            genus: region.genus.as_deref(),
+            species: region.species.as_deref(),
+            strain: region.strain.as_deref(),
+            term: &region.term,
+            terms: &region.types,
+            category: &region.category,
+            best_mibig_hit_similarity: region.best_mibig_hit_similarity,
+            best_mibig_hit_acc: region.best_mibig_hit_acc.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Protocluster {
+    #[serde(skip)]
+    pub region_id: i32,
+    pub protocluster_number: i32,
+    pub term: String,
+    pub start_pos: i32,
+    pub end_pos: i32,
+    pub core_start: i32,
+    pub core_end: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Candidate {
+    #[serde(skip)]
+    pub region_id: i32,
+    pub candidate_number: i32,
+    /// Kind of candidate cluster, like "single" or "chemical hybrid"
+    pub kind: String,
+    pub start_pos: i32,
+    pub end_pos: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FullRegion<'a> {
+    #[serde(flatten)]
+    pub region: &'a Region,
+    pub protoclusters: Vec<Protocluster>,
+    pub candidates: Vec<Candidate>,
+}
+
+/// Serialise the regions of a reply page in the requested shape, the full shape costs
+/// two more queries for the whole page
+pub async fn shape_regions(
+    pool: &PgPool,
+    regions: &[Region],
+    shape: RegionShape,
+) -> Result<Vec<Value>> {
+    let values = match shape {
+        RegionShape::Minimal => regions
+            .iter()
+            .map(|region| serde_json::to_value(MinimalRegion::from(region)))
+            .collect::<std::result::Result<_, _>>()?,
+        RegionShape::Standard => regions
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<std::result::Result<_, _>>()?,
+        RegionShape::Full => {
+            let ids: Vec<i32> = regions.iter().map(|r| r.region_id).collect();
+            let mut protoclusters =
+                by_region(fetch_protoclusters(pool, &ids).await?, |p| p.region_id);
+            let mut candidates = by_region(fetch_candidates(pool, &ids).await?, |c| c.region_id);
+            regions
+                .iter()
+                .map(|region| {
+                    serde_json::to_value(FullRegion {
+                        region,
+                        protoclusters: protoclusters.remove(&region.region_id).unwrap_or_default(),
+                        candidates: candidates.remove(&region.region_id).unwrap_or_default(),
+                    })
+                })
+                .collect::<std::result::Result<_, _>>()?
+        }
+    };
+    Ok(values)
+}
+
+fn by_region<T>(rows: Vec<T>, region_id: impl Fn(&T) -> i32) -> HashMap<i32, Vec<T>> {
+    let mut grouped: HashMap<i32, Vec<T>> = HashMap::new();
+    for row in rows {
+        grouped.entry(region_id(&row)).or_default().push(row);
+    }
+    grouped
+}
+
+async fn fetch_protoclusters(pool: &PgPool, ids: &[i32]) -> Result<Vec<Protocluster>> {
+    let protoclusters = sqlx::query_as!(
+        Protocluster,
+        r#"
+        SELECT region_id, protocluster_number, term, start_pos, end_pos, core_start, core_end
+        FROM antismash.protoclusters
+        JOIN antismash.bgc_types USING (bgc_type_id)
+        WHERE region_id = ANY($1)
+        ORDER BY region_id, protocluster_number"#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(protoclusters)
+}
+
+async fn fetch_candidates(pool: &PgPool, ids: &[i32]) -> Result<Vec<Candidate>> {
+    let candidates = sqlx::query_as!(
+        Candidate,
+        r#"
+        SELECT region_id, candidate_number, description AS kind, start_pos, end_pos
+        FROM antismash.candidates
+        JOIN antismash.candidate_types USING (candidate_type_id)
+        WHERE region_id = ANY($1)
+        ORDER BY region_id, candidate_number"#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_shape_regions(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        let regions = super::super::ids_to_regions(&pool, &[1, 3]).await.unwrap();
+
+        let standard = shape_regions(&pool, &regions, RegionShape::Standard)
+            .await
+            .unwrap();
+        for field in ["description", "best_mibig_hit_description"] {
+            assert!(standard[0].get(field).is_some(), "{field}");
+        }
+        assert!(standard[0].get("protoclusters").is_none());
+        assert_eq!(standard[0]["record_length"], 60);
+        assert_eq!(standard[1]["record_length"], 54);
+
+        let minimal = shape_regions(&pool, &regions, RegionShape::Minimal)
+            .await
+            .unwrap();
+        assert_eq!(minimal[0]["bgc_id"], 1);
+        assert_eq!(minimal[1]["term"], standard[1]["term"]);
+        assert_eq!(minimal[1]["terms"].as_array().unwrap().len(), 3);
+        for field in ["description", "best_mibig_hit_description"] {
+            assert!(minimal[0].get(field).is_none(), "{field}");
+        }
+
+        let full = shape_regions(&pool, &regions, RegionShape::Full)
+            .await
+            .unwrap();
+        assert_eq!(full[0]["bgc_id"], 1);
+        assert_eq!(full[0]["protoclusters"].as_array().unwrap().len(), 1);
+        assert_eq!(full[1]["protoclusters"][1]["term"], "T2PKS");
+        assert_eq!(full[1]["protoclusters"][1]["core_start"], 20);
+        assert_eq!(full[1]["candidates"][0]["kind"], "chemical hybrid");
+        assert_eq!(full[1]["candidates"][1]["candidate_number"], 2);
+    }
+}
@@ -0,0 +1,63 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::api::extract;
+use crate::{Error, Result};
+
+#[derive(Debug, Serialize)]
+pub struct PredictedStructure {
+    pub candidate_number: i32,
+    /// Kind of candidate cluster the structure was predicted for
+    pub kind: String,
+    pub location: String,
+    pub smiles: String,
+    pub polymer: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegionStructures {
+    pub region_id: i32,
+    pub structures: Vec<PredictedStructure>,
+}
+
+/// The core structures antiSMASH predicted for a region's candidate clusters
+pub async fn region_smiles(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT region_id
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let structures = sqlx::query_as!(
+        PredictedStructure,
+        r#"
+        SELECT candidate_number, description AS kind, location, smiles AS "smiles!", polymer
+        FROM antismash.candidates
+        JOIN antismash.candidate_types USING (candidate_type_id)
+        WHERE region_id = $1 AND smiles IS NOT NULL
+        ORDER BY candidate_number"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(json!(RegionStructures {
+        region_id,
+        structures,
+    })))
+}
@@ -0,0 +1,105 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use crate::api::extract;
+use crate::{Error, Result};
+
+#[derive(Debug, Serialize)]
+pub struct BindingSite {
+    pub regulator: String,
+    pub description: Option<String>,
+    pub score: f64,
+    /// Confidence level, as used by the quality filter of the Tfbs category
+    pub strength: i16,
+    pub confidence: &'static str,
+    pub accession: String,
+    pub start: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegionBindingSites {
+    pub region_id: i32,
+    pub binding_sites: Vec<BindingSite>,
+}
+
+/// Name of a confidence level, matching the choices of the Tfbs quality filter
+pub fn confidence_name(strength: i16) -> &'static str {
+    match strength {
+        s if s >= 30 => "strong",
+        s if s >= 20 => "medium",
+        _ => "weak",
+    }
+}
+
+/// All transcription factor binding sites predicted in a region, in genomic order
+pub async fn region_tfbs(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(region_id): extract::Path<i32>,
+) -> Result<Json<Value>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT region_id
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE region_id = $1 AND tombstoned IS FALSE"#,
+        region_id,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let binding_sites = sqlx::query!(
+        r#"
+        SELECT name, description, score, strength, accession, b.start_pos
+        FROM antismash.binding_sites AS b
+        JOIN antismash.regulators USING (regulator_id)
+        JOIN antismash.regulator_confidence USING (confidence_id)
+        JOIN antismash.regions USING (region_id)
+        WHERE region_id = $1
+        ORDER BY b.start_pos, binding_site_id"#,
+        region_id,
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| BindingSite {
+        regulator: row.name,
+        description: row.description,
+        score: row.score,
+        strength: row.strength,
+        confidence: confidence_name(row.strength),
+        accession: row.accession,
+        start: row.start_pos,
+    })
+    .collect();
+
+    Ok(Json(json!(RegionBindingSites {
+        region_id,
+        binding_sites,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence_name() {
+        let tests = [
+            (30, "strong"),
+            (25, "medium"),
+            (20, "medium"),
+            (10, "weak"),
+            (0, "weak"),
+        ];
+        for (strength, expected) in tests {
+            assert_eq!(confidence_name(strength), expected, "{strength}");
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    http::{header::HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: Uuid;
+}
+
+/// ID of the request currently being handled, if any
+pub fn current() -> Option<Uuid> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Assign every request an ID, reusing a UUID sent by a proxy in front of us.
+/// The ID is logged, returned in the `X-Request-Id` header and added to error payloads.
+pub async fn assign<B>(request: Request<B>, next: Next<B>) -> Response {
+    let id = incoming_id(&request).unwrap_or_else(Uuid::new_v4);
+    eprintln!(
+        "->> {:<12} - {id} {} {}",
+        "REQUEST",
+        request.method(),
+        request.uri()
+    );
+
+    let mut response = REQUEST_ID.scope(id, next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn incoming_id<B>(request: &Request<B>) -> Option<Uuid> {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value.trim()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{
+        body::{Body, HttpBody},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::Error;
+
+    async fn fail() -> crate::Result<()> {
+        Err(Error::NotFound)
+    }
+
+    #[tokio::test]
+    async fn test_assign() {
+        let app = Router::new()
+            .route("/fail", get(fail))
+            .layer(middleware::from_fn(assign));
+
+        let known = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let tests = [
+            (None, None),
+            (Some("not-a-uuid"), None),
+            (Some(known), Some(known)),
+        ];
+        for (incoming, expected) in tests {
+            let mut request = Request::builder().uri("/fail");
+            if let Some(incoming) = incoming {
+                request = request.header(REQUEST_ID_HEADER, incoming);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            let id = response.headers()[REQUEST_ID_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string();
+            assert!(Uuid::parse_str(&id).is_ok(), "{incoming:?}");
+            if let Some(expected) = expected {
+                assert_eq!(id, expected);
+            }
+
+            let body = response.into_body().data().await.unwrap().unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"]["request_id"], id.as_str(), "{incoming:?}");
+        }
+        assert_eq!(current(), None);
+    }
+}
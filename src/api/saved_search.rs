@@ -0,0 +1,98 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    routing::{get, post},
+    Extension, Json,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use super::extract;
+use super::normalize::Routes;
+use crate::query::Query;
+use crate::{Error, Result};
+
+const TOKEN_LENGTH: usize = 12;
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/searches", post(save_search))
+        .route("/api/searches/:token", get(load_search))
+}
+
+/// Tokens are derived from the query, so saving the same query twice gives the same link
+fn make_token(query: &Value) -> String {
+    let digest = Sha256::digest(query.to_string());
+    format!("{digest:x}")[..TOKEN_LENGTH].to_string()
+}
+
+async fn save_search(
+    Extension(pool): Extension<PgPool>,
+    extract::Json(query): extract::Json<Query>,
+) -> Result<Json<Value>> {
+    let query = serde_json::to_value(&query)?;
+    let token = make_token(&query);
+
+    let stored = sqlx::query_scalar!(
+        r#"
+        WITH inserted AS (
+            INSERT INTO asdb_jobs.saved_searches (token, query) VALUES ($1, $2)
+            ON CONFLICT (token) DO NOTHING
+            RETURNING query
+        )
+        SELECT query AS "query!" FROM inserted
+        UNION ALL
+        SELECT query FROM asdb_jobs.saved_searches WHERE token = $1"#,
+        token,
+        query,
+    )
+    .fetch_one(&pool)
+    .await?;
+    if stored != query {
+        return Err(Error::InvalidRequest(
+            "Failed to save search, please try again".to_string(),
+        ));
+    }
+
+    Ok(Json(json!({
+        "token": token,
+        "url": format!("/api/searches/{token}"),
+    })))
+}
+
+async fn load_search(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(token): extract::Path<String>,
+) -> Result<Json<Value>> {
+    if token.len() != TOKEN_LENGTH || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::NotFound);
+    }
+    let query = sqlx::query_scalar!(
+        "SELECT query FROM asdb_jobs.saved_searches WHERE token = $1",
+        token,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(Json(query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make_token() {
+        let query = Query::from_str("{[type|NRPS]}").unwrap();
+        let value = serde_json::to_value(&query).unwrap();
+        let token = make_token(&value);
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        assert_eq!(token, make_token(&serde_json::to_value(&query).unwrap()));
+
+        let other = Query::from_str("{[type|T1PKS]}").unwrap();
+        assert_ne!(token, make_token(&serde_json::to_value(&other).unwrap()));
+    }
+}
@@ -1,39 +1,192 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{extract, routing::post, Extension, Json, Router};
+use axum::{
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    routing::post,
+    Extension, Json,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use sqlx::PgPool;
 
+use super::admin::Admin;
+use super::extract::{self, OriginalUri};
+use super::normalize::Routes;
+use super::pagination::Page;
+use super::region::expression::{expression_query, SEARCH_TIMEOUT};
+use super::region::modules::monomer_sequence_query;
 use super::region::search as region_search;
-use crate::query::{Query, ReturnType, SearchType};
+use super::region::{
+    drop_tombstoned, ids_to_sorted_regions, query_ids, resolve_versions, shape_regions, term_query,
+    CsvStyle, RegionShape,
+};
+use super::search_stats;
+use super::stream;
+use super::taxa::parse_node_id;
+use crate::query::{
+    Category, Expression, Operation, Operator, Query, ReturnType, SearchType, Sort, Term,
+};
 use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new().route("/api/search", post(search))
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/search", post(search))
+        .route("/api/search/monomers", post(search_monomers))
 }
 
 #[derive(Debug, Deserialize, Serialize)]
-struct SearchPayload {
+pub struct SearchPayload {
     pub query: Query,
     pub offset: Option<usize>,
     pub paginate: Option<usize>,
+    #[serde(flatten)]
+    pub sort: Sort,
+    /// Row layout of CSV downloads
+    #[serde(default)]
+    pub csv_style: CsvStyle,
+    /// Taxonomy tree node ID all results need to be under, as used by the taxnode category
+    pub taxon_scope: Option<String>,
 }
 
-async fn search(
+impl SearchPayload {
+    /// Restrict the query to the taxon scope, if one is set. The scope is added as a
+    /// taxnode term around the whole query rather than to every expression, so regions
+    /// matched by negated terms are kept inside the scope too.
+    pub fn scoped(mut self) -> Result<Self> {
+        let Some(scope) = self.taxon_scope.take() else {
+            return Ok(self);
+        };
+        parse_node_id(&scope)?;
+        let scope = Expression::new(Category::TaxNode, Some(&scope), &[], 1);
+        self.query.terms = Term::Op(Operation::new(
+            Operator::And,
+            self.query.terms,
+            Term::Expr(scope),
+        ));
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParams {
+    /// Include the query plans of the category SQL in the reply, admin only
+    #[serde(default)]
+    pub explain: bool,
+    /// Override the paging of the payload, so the pagination links work with the same payload
+    pub offset: Option<usize>,
+    pub paginate: Option<usize>,
+    /// Only export the biosynthetic core genes in protein FASTA downloads
+    #[serde(default)]
+    pub core_only: bool,
+    /// Extend the regions by this many bp on each side in DNA FASTA downloads
+    #[serde(default)]
+    pub flank: u32,
+    /// Fields included for each region in JSON replies
+    #[serde(default)]
+    pub shape: RegionShape,
+}
+
+pub async fn search(
+    admin: Option<Admin>,
+    query_params: extract::Query<SearchParams>,
+    uri: OriginalUri,
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<SearchPayload>,
+) -> Result<Response> {
+    let req = req.scoped()?;
+    search_stats::count_search(&pool, &req.query);
+    if matches!(
+        req.query.return_type,
+        ReturnType::Xlsx | ReturnType::Sqlite | ReturnType::Sideload
+    ) {
+        return Err(Error::InvalidRequest(format!(
+            "{} files are built by stored query jobs, submit the search to /api/jobs/storedquery",
+            req.query.return_type.as_ref()
+        )));
+    }
+    if req.query.search_type == SearchType::Region
+        && matches!(
+            req.query.return_type,
+            ReturnType::Csv | ReturnType::Fasta | ReturnType::Fastaa
+        )
+    {
+        return download(&pool, req, &query_params).await;
+    }
+    let reply = search_json(
+        admin,
+        query_params,
+        uri,
+        Extension(pool),
+        extract::Json(req),
+    )
+    .await?;
+    Ok(reply.into_response())
+}
+
+/// Send region CSV and FASTA exports, including the proteins of the regions, as they are generated, without a stored query job
+async fn download(
+    pool: &PgPool,
+    mut req: SearchPayload,
+    params: &SearchParams,
+) -> Result<Response> {
+    resolve_versions(pool, &mut req.query.terms, req.query.resolve_versions).await?;
+    let ids = query_ids(pool, &req.query).await?;
+    let response = match req.query.return_type {
+        ReturnType::Csv => stream::download(
+            stream::region_csv(pool, &ids, req.csv_style).await?,
+            "text/csv",
+            "regions.csv",
+        ),
+        ReturnType::Fastaa => stream::download(
+            stream::region_faa(pool, &ids, params.core_only),
+            "text/x-fasta",
+            "region_proteins.fa",
+        ),
+        _ => stream::download(
+            stream::region_fasta(pool, &ids, params.flank),
+            "text/x-fasta",
+            "regions.fa",
+        ),
+    };
+    Ok(response)
+}
+
+pub async fn search_json(
+    admin: Option<Admin>,
+    extract::Query(params): extract::Query<SearchParams>,
+    OriginalUri(uri): OriginalUri,
     Extension(pool): Extension<PgPool>,
     extract::Json(req): extract::Json<SearchPayload>,
-) -> Result<Json<Value>> {
-    let offset = req.offset.unwrap_or(0);
+) -> Result<(HeaderMap, Json<Value>)> {
+    let mut req = req.scoped()?;
+    if params.explain && admin.is_none() {
+        return Err(Error::Unauthorized);
+    }
 
-    let paginate = req.paginate.unwrap_or(match &req.query.return_type {
-        ReturnType::Json => 100,
-        _ => 0,
-    });
+    let offset = params.offset.or(req.offset).unwrap_or(0);
+
+    let paginate = params
+        .paginate
+        .or(req.paginate)
+        .unwrap_or(match &req.query.return_type {
+            ReturnType::Json => 100,
+            _ => 0,
+        });
 
     let res = match req.query.search_type {
-        SearchType::Region => region_search(&pool, &req.query, paginate, offset).await?,
+        SearchType::Region => {
+            region_search(
+                &pool,
+                &mut req.query,
+                paginate,
+                offset,
+                &req.sort,
+                params.shape,
+            )
+            .await?
+        }
         _ => {
             return Err(Error::NotImplementedError(format!(
                 "{:?} searches",
@@ -41,5 +194,139 @@ async fn search(
             )))
         }
     };
-    Ok(res)
+
+    let Json(mut res) = res;
+    let headers = match res.get("total").and_then(Value::as_u64) {
+        Some(total) => Page {
+            offset,
+            limit: paginate,
+            total: total as usize,
+        }
+        .headers(&uri, "paginate"),
+        None => HeaderMap::new(),
+    };
+
+    if params.explain {
+        // Versions were resolved during the search, so this explains the queries that actually ran
+        let plans = explain(&pool, &req.query).await?;
+        if let Some(obj) = res.as_object_mut() {
+            obj.insert("explain".to_string(), plans);
+        }
+    }
+    Ok((headers, Json(res)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MonomerSearchPayload {
+    /// Monomers incorporated by consecutive modules, in assembly line order
+    pub monomers: Vec<String>,
+    pub offset: Option<usize>,
+    pub paginate: Option<usize>,
+    #[serde(flatten)]
+    pub sort: Sort,
+    #[serde(default)]
+    pub include_tombstoned: bool,
+    #[serde(default)]
+    pub shape: RegionShape,
+}
+
+/// Regions whose modules incorporate the monomers in order, which the AND syntax can't express
+pub async fn search_monomers(
+    OriginalUri(uri): OriginalUri,
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<MonomerSearchPayload>,
+) -> Result<(HeaderMap, Json<Value>)> {
+    let query = monomer_sequence_query(&req.monomers)?;
+    let mut ids: Vec<i32> = query
+        .fetch_with_timeout(&pool, SEARCH_TIMEOUT)
+        .await?
+        .into_iter()
+        .map(|r| r.region_id)
+        .collect();
+    if !req.include_tombstoned {
+        ids = drop_tombstoned(&pool, &ids).await?;
+    }
+
+    let total = ids.len();
+    let offset = req.offset.unwrap_or(0).min(total);
+    let paginate = req.paginate.unwrap_or(100);
+    let limit = (paginate > 0).then_some(paginate);
+    let regions = ids_to_sorted_regions(&pool, &ids, &req.sort, offset, limit).await?;
+    let regions = shape_regions(&pool, &regions, req.shape).await?;
+
+    let headers = Page {
+        offset,
+        limit: paginate,
+        total,
+    }
+    .headers(&uri, "paginate");
+    Ok((
+        headers,
+        Json(json!({
+            "regions": regions,
+            "offset": offset,
+            "paginate": paginate,
+            "total": total,
+        })),
+    ))
+}
+
+/// The SQL and query plan of the combined query, or of every expression in the query
+/// if they are combined in memory
+async fn explain(pool: &PgPool, query: &Query) -> Result<Value> {
+    if !matches!(query.terms, Term::Expr(_)) {
+        if let Some(combined) = term_query(&query.terms)? {
+            let plan = combined.explain(pool).await?;
+            return Ok(json!([{
+                "sql": combined.sql,
+                "params": combined.params,
+                "plan": plan,
+            }]));
+        }
+    }
+
+    let mut plans = Vec::new();
+    for expr in query.terms.expressions() {
+        let expr_query = expression_query(expr)?;
+        let plan = expr_query.explain(pool).await?;
+        plans.push(json!({
+            "category": expr.category,
+            "value": expr.value,
+            "sql": expr_query.sql,
+            "params": expr_query.params,
+            "plan": plan,
+        }));
+    }
+    Ok(json!(plans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoped() {
+        let payload = |scope: Option<&str>| SearchPayload {
+            query: Query::from_str("NOT {[type|NRPS]}").unwrap(),
+            offset: None,
+            paginate: None,
+            sort: Sort::default(),
+            csv_style: CsvStyle::default(),
+            taxon_scope: scope.map(str::to_string),
+        };
+
+        let unscoped = payload(None).scoped().unwrap();
+        assert_eq!(unscoped.query.to_search_string(), "NOT {[type|NRPS]}");
+
+        let scoped = payload(Some("phylum_bacteria_actinomycetota"))
+            .scoped()
+            .unwrap();
+        assert_eq!(
+            scoped.query.to_search_string(),
+            "NOT {[type|NRPS]} AND {[taxnode|phylum_bacteria_actinomycetota]}"
+        );
+        assert!(scoped.taxon_scope.is_none());
+
+        assert!(payload(Some("genus_bacteria")).scoped().is_err());
+    }
 }
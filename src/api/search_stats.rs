@@ -0,0 +1,140 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::BTreeMap;
+
+use axum::{routing::get, Extension, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::admin::Admin;
+use super::extract;
+use super::normalize::Routes;
+use crate::query::Query;
+use crate::Result;
+
+pub fn routes() -> Routes {
+    Routes::new().route("/api/admin/search-stats", get(search_stats))
+}
+
+const DEFAULT_STATS_DAYS: i32 = 30;
+const MAX_STATS_DAYS: i32 = 365;
+
+/// Query categories of a search, each listed once
+pub fn query_categories(query: &Query) -> Vec<&'static str> {
+    let mut categories: Vec<&'static str> = query
+        .terms
+        .expressions()
+        .into_iter()
+        .map(|expr| (&expr.category).into())
+        .collect();
+    categories.sort_unstable();
+    categories.dedup();
+    categories
+}
+
+/// Add a search to today's counters
+pub async fn record(
+    pool: &PgPool,
+    search_type: &str,
+    return_type: &str,
+    categories: &[&str],
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO asdb_jobs.search_counters (day, search_type, category, return_type, count)
+            SELECT CURRENT_DATE, $1, category, $2, 1 FROM unnest($3::text[]) AS category
+        ON CONFLICT (day, search_type, category, return_type)
+            DO UPDATE SET count = search_counters.count + 1
+        "#,
+        search_type,
+        return_type,
+        categories as &[&str],
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Count a search in the background, failing to count it never fails the search
+pub fn count_search(pool: &PgPool, query: &Query) {
+    let pool = pool.clone();
+    let search_type = query.search_type.as_ref().to_owned();
+    let return_type = query.return_type.as_ref().to_owned();
+    let categories = query_categories(query);
+    tokio::spawn(async move {
+        if let Err(e) = record(&pool, &search_type, &return_type, &categories).await {
+            eprintln!("->> Failed to count search: {e}");
+        }
+    });
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchStatsQuery {
+    pub days: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchCount {
+    pub search_type: String,
+    pub category: String,
+    pub return_type: String,
+    pub count: i64,
+}
+
+/// Searches of the last `days` days by category and return type, most used first
+async fn search_stats(
+    _admin: Admin,
+    Extension(pool): Extension<PgPool>,
+    extract::Query(params): extract::Query<SearchStatsQuery>,
+) -> Result<Json<Value>> {
+    let days = params
+        .days
+        .unwrap_or(DEFAULT_STATS_DAYS)
+        .clamp(1, MAX_STATS_DAYS);
+    let counts = sqlx::query_as!(
+        SearchCount,
+        r#"
+        SELECT search_type, category, return_type, SUM(count)::bigint AS "count!"
+            FROM asdb_jobs.search_counters
+            WHERE day > CURRENT_DATE - $1::int
+            GROUP BY search_type, category, return_type
+            ORDER BY 4 DESC, search_type, category, return_type"#,
+        days,
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut by_category: BTreeMap<&str, i64> = BTreeMap::new();
+    for count in &counts {
+        *by_category.entry(&count.category).or_default() += count.count;
+    }
+
+    Ok(Json(json!({
+        "days": days,
+        "by_category": by_category,
+        "counts": counts,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_categories() {
+        let tests = [
+            ("{[type|NRPS]}", vec!["type"]),
+            (
+                "{[type|NRPS]} AND NOT ({[genus|Streptomyces]} OR {[type|T1PKS]})",
+                vec!["genus", "type"],
+            ),
+            ("{[pfam|PF00001]} OR {[acc|NC_003888]}", vec!["acc", "pfam"]),
+        ];
+        for (input, expected) in tests {
+            let query = Query::from_str(input).unwrap();
+            assert_eq!(query_categories(&query), expected, "{input}");
+        }
+    }
+}
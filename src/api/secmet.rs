@@ -0,0 +1,179 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::HashMap;
+
+use axum::{routing::get, Extension, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::normalize::Routes;
+use crate::Result;
+
+pub fn routes() -> Routes {
+    Routes::new().route("/api/tree/secmet", get(secmet_tree))
+}
+
+#[derive(Debug, Serialize)]
+struct SecmetNode {
+    id: String,
+    parent: String,
+    text: String,
+    state: Option<NodeState>,
+    #[serde(rename = "type")]
+    node_type: Option<String>,
+    li_attr: Option<LiAttr>,
+    children: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct NodeState {
+    disabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct LiAttr {
+    #[serde(rename = "data-query")]
+    data_query: String,
+}
+
+struct Category {
+    name: String,
+    description: String,
+    parent: Option<String>,
+}
+
+pub async fn secmet_tree(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
+    let categories: Vec<Category> = sqlx::query!(
+        r#"
+        SELECT category, description, parent_category
+        FROM antismash.bgc_categories
+        ORDER BY category"#
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| Category {
+        name: row.category,
+        description: row.description,
+        parent: row.parent_category,
+    })
+    .collect();
+
+    let types = sqlx::query!(
+        r#"
+        SELECT term, description, category, COUNT(region_id) AS "count!"
+        FROM antismash.bgc_types
+        JOIN antismash.rel_regions_types USING (bgc_type_id)
+        JOIN antismash.regions USING (region_id)
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE tombstoned IS FALSE
+        GROUP BY term, description, category
+        ORDER BY term"#
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let type_counts: Vec<(&str, i64)> = types
+        .iter()
+        .map(|t| (t.category.as_str(), t.count))
+        .collect();
+    let totals = category_totals(&categories, &type_counts);
+
+    let mut nodes: Vec<SecmetNode> = Vec::with_capacity(categories.len() + types.len());
+    for category in &categories {
+        let Some(count) = totals.get(category.name.as_str()) else {
+            continue;
+        };
+        nodes.push(SecmetNode {
+            id: format!("category_{}", category.name),
+            parent: match &category.parent {
+                Some(p) => format!("category_{p}"),
+                None => "#".to_string(),
+            },
+            text: format!("{} ({count})", category.description),
+            state: Some(NodeState { disabled: true }),
+            node_type: Some("category".to_string()),
+            li_attr: Some(LiAttr {
+                data_query: format!("{{[typecategory|{}]}}", category.name),
+            }),
+            children: true,
+        });
+    }
+
+    for t in &types {
+        nodes.push(SecmetNode {
+            id: format!("type_{}", t.term),
+            parent: format!("category_{}", t.category),
+            text: format!("{} ({})", t.description, t.count),
+            state: None,
+            node_type: Some("cluster".to_string()),
+            li_attr: Some(LiAttr {
+                data_query: format!("{{[type|{}]}}", t.term),
+            }),
+            children: false,
+        });
+    }
+
+    Ok(Json(json!(nodes)))
+}
+
+/// Sum up the region counts of the types in each category, including those of subcategories.
+/// Categories without any regions are left out.
+fn category_totals<'a>(
+    categories: &'a [Category],
+    type_counts: &[(&str, i64)],
+) -> HashMap<&'a str, i64> {
+    let parents: HashMap<&'a str, Option<&'a str>> = categories
+        .iter()
+        .map(|c| (c.name.as_str(), c.parent.as_deref()))
+        .collect();
+
+    let mut totals: HashMap<&'a str, i64> = HashMap::new();
+    for (category, count) in type_counts {
+        let mut current = parents.get_key_value(*category).map(|(name, _)| *name);
+        let mut depth = 0;
+        while let Some(name) = current {
+            // Guard against cycles in the category table
+            if depth > categories.len() {
+                break;
+            }
+            *totals.entry(name).or_default() += count;
+            current = parents.get(name).copied().flatten();
+            depth += 1;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(name: &str, parent: Option<&str>) -> Category {
+        Category {
+            name: name.to_string(),
+            description: name.to_string(),
+            parent: parent.map(|p| p.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_category_totals() {
+        let categories = [
+            category("PKS", None),
+            category("T1PKS", Some("PKS")),
+            category("NRPS", None),
+            category("terpene", None),
+        ];
+        let type_counts = [("T1PKS", 3), ("PKS", 2), ("NRPS", 4)];
+        let totals = category_totals(&categories, &type_counts);
+
+        assert_eq!(totals.get("PKS"), Some(&5));
+        assert_eq!(totals.get("T1PKS"), Some(&3));
+        assert_eq!(totals.get("NRPS"), Some(&4));
+        assert_eq!(totals.get("terpene"), None);
+    }
+}
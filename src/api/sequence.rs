@@ -0,0 +1,149 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use super::extract;
+use super::normalize::Routes;
+use super::region::data::break_lines;
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new().route("/api/sequence/:accession", get(sequence_slice))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SliceParams {
+    /// Zero-based start of the slice, defaults to the start of the record
+    pub start: Option<i32>,
+    /// Exclusive end of the slice, defaults to the end of the record
+    pub end: Option<i32>,
+    /// Return the reverse complement of the slice
+    #[serde(default)]
+    pub rc: bool,
+}
+
+/// FASTA of a slice of a DNA record, e.g. to get the flanking regions of a BGC.
+/// The accession may carry a version, which then needs to match the stored one.
+async fn sequence_slice(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(accession): extract::Path<String>,
+    extract::Query(params): extract::Query<SliceParams>,
+) -> Result<Response> {
+    let (acc, requested_version) = match accession.split_once('.') {
+        Some((acc, ver)) => {
+            let Ok(version) = ver.parse::<i32>() else {
+                return Err(Error::InvalidRequest(format!(
+                    "Invalid accession version {accession}"
+                )));
+            };
+            (acc, Some(version))
+        }
+        None => (accession.as_str(), None),
+    };
+
+    let start = params.start.unwrap_or(0);
+    if start < 0 || params.end.is_some_and(|end| end <= start) {
+        return Err(Error::InvalidRequest(format!(
+            "Invalid slice {start}..{}",
+            params.end.unwrap_or_default()
+        )));
+    }
+
+    let record = sqlx::query!(
+        r#"
+        SELECT version, length(dna) AS "length!",
+            SUBSTRING(dna FROM $2 + 1 FOR COALESCE($3, length(dna)) - $2) AS "slice!"
+        FROM antismash.dna_sequences
+        JOIN antismash.genomes USING (genome_id)
+        WHERE accession = $1 AND tombstoned IS FALSE"#,
+        acc,
+        start,
+        params.end,
+    )
+    .fetch_optional(&pool)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let version = record.version.unwrap_or_default();
+    if requested_version.is_some_and(|requested| requested != version) {
+        return Err(Error::VersionNotFound(
+            accession.to_owned(),
+            vec![format!("{acc}.{version}")],
+        ));
+    }
+
+    let end = params.end.unwrap_or(record.length);
+    if end > record.length || start >= record.length {
+        return Err(Error::InvalidRequest(format!(
+            "Slice {start}..{end} is outside of {acc}.{version}, which is {} bp long",
+            record.length
+        )));
+    }
+
+    let (sequence, strand) = if params.rc {
+        (reverse_complement(&record.slice), " reverse complement")
+    } else {
+        (record.slice, "")
+    };
+    let fasta = format!(
+        ">{acc}.{version}|{start}-{end}{strand}\n{}\n",
+        break_lines(&sequence, 80)
+    );
+    Ok(([(CONTENT_TYPE, "text/x-fasta")], fasta).into_response())
+}
+
+/// Reverse complement of a DNA sequence, keeping the case and IUPAC ambiguity codes
+pub fn reverse_complement(sequence: &str) -> String {
+    sequence
+        .chars()
+        .rev()
+        .map(|c| {
+            let complement = match c.to_ascii_uppercase() {
+                'A' => 'T',
+                'T' | 'U' => 'A',
+                'C' => 'G',
+                'G' => 'C',
+                'R' => 'Y',
+                'Y' => 'R',
+                'K' => 'M',
+                'M' => 'K',
+                'B' => 'V',
+                'V' => 'B',
+                'D' => 'H',
+                'H' => 'D',
+                other => other,
+            };
+            if c.is_ascii_lowercase() {
+                complement.to_ascii_lowercase()
+            } else {
+                complement
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        let tests = [
+            ("ATGC", "GCAT"),
+            ("aacgN", "Ncgtt"),
+            ("RYKMBVDHSW", "WSDHBVKMRY"),
+            ("", ""),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(reverse_complement(input), expected, "{input}");
+        }
+    }
+}
@@ -1,17 +1,43 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{routing::get, Extension, Json, Router};
-use serde::Serialize;
+use std::sync::Arc;
+
+use axum::{middleware, routing::get, Extension, Json};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
+use tokio::sync::RwLock;
 
-use crate::Result;
+use super::extract;
+use super::normalize::Routes;
+use super::region;
+use super::{etag, ApiConfig};
+use crate::query::{ParseLimits, Query};
+use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/stats", get(stats))
-        .route("/api/v2.0/stats", get(stats))
+        .route("/api/stats/types", get(type_stats))
+        .route("/api/stats/taxa", get(taxa_stats))
+        .route_layer(middleware::from_fn(etag::conditional))
+}
+
+/// Materialized statistics, kept up to date by the scheduler if enabled
+#[derive(Debug, Clone, Default)]
+pub struct StatsCache(Arc<RwLock<Option<Value>>>);
+
+impl StatsCache {
+    pub async fn get(&self) -> Option<Value> {
+        self.0.read().await.clone()
+    }
+
+    pub async fn refresh(&self, pool: &PgPool) -> Result<()> {
+        let stats = compute_stats(pool).await?;
+        *self.0.write().await = Some(stats);
+        Ok(())
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -37,25 +63,48 @@ struct StatCluster {
     category: String,
 }
 
-async fn stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
-    let num_clusters =
-        sqlx::query!("SELECT COUNT(*) FROM antismash.regions WHERE contig_edge IS FALSE;")
-            .fetch_one(&pool)
+pub async fn stats(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+) -> Result<Json<Value>> {
+    if let Some(stats) = config.stats_cache.get().await {
+        return Ok(Json(stats));
+    }
+    Ok(Json(compute_stats(&pool).await?))
+}
+
+pub async fn compute_stats(pool: &PgPool) -> Result<Value> {
+    let num_clusters = sqlx::query!(
+        r#"
+        SELECT COUNT(*)
+        FROM antismash.regions
+        JOIN antismash.dna_sequences USING (accession)
+        JOIN antismash.genomes USING (genome_id)
+        WHERE contig_edge IS FALSE AND tombstoned IS FALSE;"#
+    )
+    .fetch_one(pool)
+    .await?
+    .count
+    .unwrap_or(0);
+
+    let num_genomes =
+        sqlx::query!("SELECT COUNT(*) FROM antismash.genomes WHERE tombstoned IS FALSE;")
+            .fetch_one(pool)
             .await?
             .count
             .unwrap_or(0);
 
-    let num_genomes = sqlx::query!("SELECT COUNT(*) FROM antismash.genomes;")
-        .fetch_one(&pool)
-        .await?
-        .count
-        .unwrap_or(0);
-
-    let num_sequences = sqlx::query!("SELECT COUNT(*) FROM antismash.dna_sequences")
-        .fetch_one(&pool)
-        .await?
-        .count
-        .unwrap_or(0);
+    let num_sequences = sqlx::query!(
+        r#"
+        SELECT COUNT(*)
+        FROM antismash.dna_sequences
+        JOIN antismash.genomes USING (genome_id)
+        WHERE tombstoned IS FALSE;"#
+    )
+    .fetch_one(pool)
+    .await?
+    .count
+    .unwrap_or(0);
 
     let top_seq_info = sqlx::query!(
         r#"
@@ -63,12 +112,13 @@ async fn stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
         FROM antismash.taxa
         JOIN antismash.genomes USING (tax_id)
         JOIN antismash.dna_sequences USING (genome_id)
+        WHERE tombstoned IS FALSE
         GROUP BY tax_id
         ORDER BY tax_count DESC
         LIMIT 1;
     "#
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await?;
 
     let top_seq_taxon = top_seq_info.tax_id;
@@ -88,12 +138,13 @@ async fn stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
         JOIN antismash.genomes USING (tax_id)
         JOIN antismash.dna_sequences USING (genome_id)
         JOIN antismash.regions USING (accession)
+        WHERE tombstoned IS FALSE
         GROUP BY tax_id, assembly_id
         ORDER BY clusters_per_seq DESC
         LIMIT 1;
     "#
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await?;
 
     let top_secmet_taxon = secmet_info.tax_id;
@@ -112,12 +163,17 @@ async fn stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
             FROM antismash.bgc_types
             JOIN (
                 SELECT bgc_type_id, COUNT(1) AS count
-                FROM antismash.rel_regions_types GROUP BY bgc_type_id
+                FROM antismash.rel_regions_types
+                JOIN antismash.regions USING (region_id)
+                JOIN antismash.dna_sequences USING (accession)
+                JOIN antismash.genomes USING (genome_id)
+                WHERE tombstoned IS FALSE
+                GROUP BY bgc_type_id
             ) AS sub
             USING (bgc_type_id)
             ORDER BY sub.count DESC, term, category;"#
     )
-    .fetch_all(&pool)
+    .fetch_all(pool)
     .await?
     .iter()
     .map(|row| StatCluster {
@@ -142,6 +198,144 @@ async fn stats(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
         clusters,
     };
 
-    let body = Json(json!(stats));
-    Ok(body)
+    Ok(json!(stats))
+}
+
+/// Ranks the taxa statistics can be grouped by, with their column in antismash.taxa
+const STATS_RANKS: [(&str, &str); 8] = [
+    ("superkingdom", "superkingdom"),
+    ("kingdom", "kingdom"),
+    ("phylum", "phylum"),
+    ("class", "class"),
+    ("order", "taxonomic_order"),
+    ("family", "family"),
+    ("genus", "genus"),
+    ("species", "species"),
+];
+
+fn rank_column(rank: &str) -> Result<&'static str> {
+    STATS_RANKS
+        .iter()
+        .find(|(name, _)| *name == rank)
+        .map(|(_, column)| *column)
+        .ok_or_else(|| {
+            let ranks: Vec<&str> = STATS_RANKS.iter().map(|(name, _)| *name).collect();
+            Error::InvalidRequest(format!(
+                "Invalid rank {rank:?}, valid choices are: {}",
+                ranks.join(", ")
+            ))
+        })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DistributionQuery {
+    /// Only count regions matching this search string
+    pub query: Option<String>,
+    pub rank: Option<String>,
+}
+
+impl DistributionQuery {
+    /// Region IDs matching the query, or None to count all regions
+    async fn region_ids(&self, pool: &PgPool, limits: &ParseLimits) -> Result<Option<Vec<i32>>> {
+        let Some(search) = &self.query else {
+            return Ok(None);
+        };
+        let query = Query::parse(search, limits).map_err(|e| match e {
+            Error::InvalidRequest(_) => e,
+            _ => Error::InvalidRequest(format!("Invalid query {search:?}")),
+        })?;
+        Ok(Some(region::query_ids(pool, &query).await?))
+    }
+}
+
+/// Number of regions of every BGC type
+pub async fn type_stats(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Query(params): extract::Query<DistributionQuery>,
+) -> Result<Json<Value>> {
+    let ids = params.region_ids(&pool, &config.parse_limits).await?;
+
+    let types: Vec<StatCluster> = sqlx::query!(
+        r#"
+        SELECT term, description, category, COUNT(region_id) AS "count!"
+            FROM antismash.bgc_types
+            JOIN antismash.rel_regions_types USING (bgc_type_id)
+            JOIN antismash.regions USING (region_id)
+            JOIN antismash.dna_sequences USING (accession)
+            JOIN antismash.genomes USING (genome_id)
+            WHERE tombstoned IS FALSE AND ($1::int[] IS NULL OR region_id = ANY($1))
+            GROUP BY term, description, category
+            ORDER BY 4 DESC, term"#,
+        ids.as_deref(),
+    )
+    .fetch_all(&pool)
+    .await?
+    .into_iter()
+    .map(|row| StatCluster {
+        name: row.term,
+        description: row.description,
+        count: row.count,
+        category: row.category,
+    })
+    .collect();
+
+    Ok(Json(json!({ "types": types })))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct TaxonCount {
+    name: String,
+    regions: i64,
+    genomes: i64,
+}
+
+/// Number of regions and genomes per taxon of the requested rank, genus by default
+pub async fn taxa_stats(
+    Extension(pool): Extension<PgPool>,
+    Extension(config): Extension<ApiConfig>,
+    extract::Query(params): extract::Query<DistributionQuery>,
+) -> Result<Json<Value>> {
+    let rank = params.rank.as_deref().unwrap_or("genus");
+    let column = rank_column(rank)?;
+    let ids = params.region_ids(&pool, &config.parse_limits).await?;
+
+    // The column name comes from the fixed list of ranks, so it is safe to format in
+    let taxa: Vec<TaxonCount> = sqlx::query_as(&format!(
+        r#"
+        SELECT COALESCE({column}, 'Unclassified') AS name,
+                COUNT(DISTINCT region_id) AS regions,
+                COUNT(DISTINCT genome_id) AS genomes
+            FROM antismash.regions
+            JOIN antismash.dna_sequences USING (accession)
+            JOIN antismash.genomes USING (genome_id)
+            JOIN antismash.taxa USING (tax_id)
+            WHERE tombstoned IS FALSE AND ($1::int[] IS NULL OR region_id = ANY($1))
+            GROUP BY 1
+            ORDER BY regions DESC, name"#
+    ))
+    .bind(ids)
+    .fetch_all(&pool)
+    .await?;
+
+    Ok(Json(json!({ "rank": rank, "taxa": taxa })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_column() {
+        let tests = [
+            ("genus", Some("genus")),
+            ("order", Some("taxonomic_order")),
+            ("taxonomic_order", None),
+            ("strain", None),
+            ("genus; DROP TABLE", None),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(rank_column(input).ok(), expected, "{input}");
+        }
+    }
 }
@@ -0,0 +1,192 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::future::Future;
+
+use axum::{
+    body::StreamBody,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use sqlx::PgPool;
+
+use super::cds;
+use super::domains;
+use super::region::{self, sorted_unique, CsvStyle, Region, ID_CHUNK_SIZE};
+use super::version::DatabaseVersion;
+use crate::Result;
+
+/// Lines of a text export, each ending in a line break, generated as they are consumed
+pub type Lines = BoxStream<'static, Result<String>>;
+
+/// Most entries fetched with their sequences at once, region sequences can be large
+pub const FASTA_CHUNK_SIZE: usize = 100;
+
+/// Lines produced by `fetch` for chunks of `chunk_size` IDs, fetching the next chunk only
+/// once the lines of the previous one have been consumed
+pub fn chunked<F, Fut>(ids: &[i32], chunk_size: usize, fetch: F) -> Lines
+where
+    F: FnMut(Vec<i32>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Vec<String>>> + Send + 'static,
+{
+    let chunks: Vec<Vec<i32>> = sorted_unique(ids)
+        .chunks(chunk_size)
+        .map(<[i32]>::to_vec)
+        .collect();
+    stream::iter(chunks)
+        .then(fetch)
+        .map_ok(|lines| stream::iter(lines.into_iter().map(|line| Ok(format!("{line}\n")))))
+        .try_flatten()
+        .boxed()
+}
+
+/// Prepend fixed lines, like a CSV header, to streamed lines
+pub fn with_header(header: Vec<String>, lines: Lines) -> Lines {
+    stream::iter(header.into_iter().map(|line| Ok(format!("{line}\n"))))
+        .chain(lines)
+        .boxed()
+}
+
+/// DNA sequences of the regions, with `flank` bp of context on each side
+pub fn region_fasta(pool: &PgPool, ids: &[i32], flank: u32) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, FASTA_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { region::ids_to_fasta(&pool, &chunk, flank).await }
+    })
+}
+
+/// Protein sequences of all CDSes in the regions, or only of their biosynthetic core genes
+pub fn region_faa(pool: &PgPool, ids: &[i32], core_only: bool) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, FASTA_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { region::ids_to_protein_fasta(&pool, &chunk, core_only).await }
+    })
+}
+
+pub async fn region_csv(pool: &PgPool, ids: &[i32], style: CsvStyle) -> Result<Lines> {
+    let comment = DatabaseVersion::fetch(pool).await?.csv_comment();
+    let pool = pool.clone();
+    let rows = chunked(ids, ID_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move {
+            let regions = region::ids_to_regions(&pool, &chunk).await?;
+            Ok(regions.into_iter().map(|r| r.to_csv(style)).collect())
+        }
+    });
+    Ok(with_header(
+        vec![comment, Region::csv_header().to_string()],
+        rows,
+    ))
+}
+
+pub async fn gene_csv(pool: &PgPool, ids: &[i32]) -> Result<Lines> {
+    let comment = DatabaseVersion::fetch(pool).await?.csv_comment();
+    let pool = pool.clone();
+    let rows = chunked(ids, ID_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move {
+            let genes = cds::ids_to_genes(&pool, &chunk).await?;
+            Ok(genes.into_iter().map(|c| c.to_csv()).collect())
+        }
+    });
+    Ok(with_header(
+        vec![comment, cds::Cds::csv_header().to_string()],
+        rows,
+    ))
+}
+
+pub fn gene_fna(pool: &PgPool, ids: &[i32]) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, FASTA_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { cds::ids_to_fna(&pool, &chunk).await }
+    })
+}
+
+pub fn gene_faa(pool: &PgPool, ids: &[i32], core_only: bool) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, ID_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { cds::ids_to_faa(&pool, &chunk, core_only).await }
+    })
+}
+
+pub async fn domain_csv(pool: &PgPool, ids: &[i32]) -> Result<Lines> {
+    let comment = DatabaseVersion::fetch(pool).await?.csv_comment();
+    let pool = pool.clone();
+    let rows = chunked(ids, ID_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move {
+            let domains = domains::ids_to_domains(&pool, &chunk).await?;
+            Ok(domains.into_iter().map(|d| d.to_csv()).collect())
+        }
+    });
+    Ok(with_header(vec![comment], rows))
+}
+
+pub fn domain_fna(pool: &PgPool, ids: &[i32]) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, FASTA_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { domains::ids_to_fna(&pool, &chunk).await }
+    })
+}
+
+pub fn domain_faa(pool: &PgPool, ids: &[i32]) -> Lines {
+    let pool = pool.clone();
+    chunked(ids, ID_CHUNK_SIZE, move |chunk| {
+        let pool = pool.clone();
+        async move { domains::ids_to_faa(&pool, &chunk).await }
+    })
+}
+
+/// A download sending the lines as they are generated. Errors after the first chunk can
+/// only abort the transfer, as the status has been sent already.
+pub fn download(lines: Lines, content_type: &'static str, filename: &str) -> Response {
+    (
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        StreamBody::new(lines),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[tokio::test]
+    async fn test_chunked() {
+        let ids: Vec<i32> = (1..=7).rev().chain([3, 5]).collect();
+        let lines = chunked(
+            &ids,
+            3,
+            |chunk| async move { Ok(vec![format!("{chunk:?}")]) },
+        );
+        let lines: Vec<String> = with_header(vec!["#header".to_string()], lines)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(lines, ["#header\n", "[1, 2, 3]\n", "[4, 5, 6]\n", "[7]\n"]);
+
+        let failing = chunked(&[1, 100], 1, |chunk| async move {
+            if chunk.contains(&100) {
+                return Err(Error::NotFound);
+            }
+            Ok(vec![format!("{chunk:?}")])
+        });
+        let result: Vec<Result<String>> = failing.collect().await;
+        assert_eq!(result.len(), 2);
+        assert!(result[0].is_ok());
+        assert!(result[1].is_err());
+    }
+}
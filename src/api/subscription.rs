@@ -0,0 +1,155 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use axum::{
+    routing::{get, post},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::extract;
+use super::job::validate_session_id;
+use super::normalize::Routes;
+use crate::jobs::subscription::{resolve_webhook, MAX_SUBSCRIPTIONS_PER_SESSION};
+use crate::models::subscription::Subscription;
+use crate::query::{Query, SearchType};
+use crate::{Error, Result};
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/subscriptions", post(create_subscription))
+        .route(
+            "/api/subscription/:subscription_id",
+            get(get_subscription).delete(delete_subscription),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionRequest {
+    pub query: Query,
+    pub interval_hours: Option<i32>,
+    pub webhook_url: Option<String>,
+    pub session_id: Option<String>,
+}
+
+impl SubscriptionRequest {
+    fn validate(&self) -> Result<()> {
+        if self.query.search_type != SearchType::Region {
+            return Err(Error::InvalidRequest(
+                "Only region searches can be subscribed to".to_string(),
+            ));
+        }
+        if self.interval_hours.is_some_and(|hours| hours < 1) {
+            return Err(Error::InvalidRequest(
+                "Subscription interval must be at least one hour".to_string(),
+            ));
+        }
+        if let Some(url) = &self.webhook_url {
+            let valid = reqwest::Url::parse(url)
+                .is_ok_and(|parsed| parsed.scheme() == "https" || parsed.scheme() == "http");
+            if !valid {
+                return Err(Error::InvalidRequest(format!(
+                    "Invalid webhook URL {url:?}"
+                )));
+            }
+        }
+        if let Some(session_id) = &self.session_id {
+            validate_session_id(session_id)?;
+        }
+        Ok(())
+    }
+}
+
+async fn create_subscription(
+    Extension(pool): Extension<PgPool>,
+    extract::Json(req): extract::Json<SubscriptionRequest>,
+) -> Result<Json<Value>> {
+    req.validate()?;
+    if let Some(url) = &req.webhook_url {
+        resolve_webhook(url).await?;
+    }
+    if let Some(session_id) = &req.session_id {
+        if Subscription::count_for_session(&pool, session_id).await?
+            >= MAX_SUBSCRIPTIONS_PER_SESSION
+        {
+            return Err(Error::InvalidRequest(format!(
+                "A session can have at most {MAX_SUBSCRIPTIONS_PER_SESSION} subscriptions"
+            )));
+        }
+    }
+    let subscription = Subscription::new(
+        &req.query,
+        req.interval_hours,
+        req.webhook_url,
+        req.session_id,
+    )?;
+    subscription.insert(&pool).await?;
+    Ok(Json(json!(subscription)))
+}
+
+async fn get_subscription(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(subscription_id): extract::Path<Uuid>,
+) -> Result<Json<Value>> {
+    let subscription = Subscription::from_db(&pool, &subscription_id.to_string()).await?;
+    let last_job = subscription
+        .last_job_id
+        .as_ref()
+        .map(|job_id| format!("/api/job/{job_id}"));
+    Ok(Json(json!({
+        "subscription": subscription,
+        "last_job": last_job,
+    })))
+}
+
+async fn delete_subscription(
+    Extension(pool): Extension<PgPool>,
+    extract::Path(subscription_id): extract::Path<Uuid>,
+) -> Result<Json<Value>> {
+    let subscription = Subscription::from_db(&pool, &subscription_id.to_string()).await?;
+    subscription.delete(&pool).await?;
+    Ok(Json(json!({ "deleted": subscription.id })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let base = r#""query": {"search": "region", "return_type": "csv",
+            "terms": {"termType": "expr", "category": "type", "value": "lassopeptide",
+                "filters": [], "count": 1}}"#;
+        let tests = [
+            (format!("{{{base}}}"), true),
+            (
+                format!(
+                    r#"{{{base}, "interval_hours": 24, "webhook_url": "https://example.org/hook"}}"#
+                ),
+                true,
+            ),
+            (format!(r#"{{{base}, "interval_hours": 0}}"#), false),
+            (
+                format!(r#"{{{base}, "webhook_url": "ftp://example.org"}}"#),
+                false,
+            ),
+            (format!(r#"{{{base}, "webhook_url": "not a url"}}"#), false),
+            (format!(r#"{{{base}, "session_id": "a b"}}"#), false),
+            (
+                format!("{{{}}}", base.replace(r#""region""#, r#""gene""#)),
+                false,
+            ),
+            (
+                format!("{{{}}}", base.replace(r#""csv""#, r#""fastaa""#)),
+                true,
+            ),
+        ];
+        for (input, expected) in tests {
+            let req: SubscriptionRequest = serde_json::from_str(&input).unwrap();
+            assert_eq!(req.validate().is_ok(), expected, "{input}");
+        }
+    }
+}
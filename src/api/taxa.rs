@@ -1,22 +1,98 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{extract::Query, routing::get, Extension, Json, Router};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use async_recursion::async_recursion;
+use axum::{routing::get, Extension, Json};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
 
+use super::extract::Query;
+use super::normalize::Routes;
 use crate::{Error, Result};
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes() -> Routes {
+    Routes::new()
         .route("/api/tree/taxa", get(tax_tree))
-        .route("/api/v1.0/tree/taxa", get(tax_tree))
+        .route("/api/tree/taxa/search", get(tax_tree_search))
+}
+
+/// Taxonomic ranks encoded in the tree node IDs, from the top down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::AsRefStr, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TaxRank {
+    Superkingdom,
+    Phylum,
+    Class,
+    Order,
+    Family,
+    Genus,
+    Species,
+}
+
+impl TaxRank {
+    pub const ALL: [TaxRank; 7] = [
+        TaxRank::Superkingdom,
+        TaxRank::Phylum,
+        TaxRank::Class,
+        TaxRank::Order,
+        TaxRank::Family,
+        TaxRank::Genus,
+        TaxRank::Species,
+    ];
+
+    /// Column holding the rank in antismash.taxa
+    pub fn column(&self) -> &'static str {
+        match self {
+            TaxRank::Superkingdom => "superkingdom",
+            TaxRank::Phylum => "phylum",
+            TaxRank::Class => "class",
+            TaxRank::Order => "taxonomic_order",
+            TaxRank::Family => "family",
+            TaxRank::Genus => "genus",
+            TaxRank::Species => "species",
+        }
+    }
+
+    /// Number of ranks above this one
+    pub fn depth(&self) -> usize {
+        Self::ALL.iter().position(|r| r == self).unwrap_or_default()
+    }
+}
+
+/// Translate a tree node ID like `phylum_bacteria_actinomycetota` back into the lineage it encodes.
+/// Empty ranks are empty entries in the lineage, like in `class_bacteria_actinomycetota_`.
+pub fn parse_node_id(id: &str) -> Result<Vec<&str>> {
+    let mut parts = id.split('_');
+    let level = parts.next().unwrap_or_default();
+    let lineage: Vec<&str> = parts.collect();
+
+    let Ok(rank) = TaxRank::from_str(level) else {
+        return Err(Error::InvalidRequest(format!("Invalid tree node id {id}")));
+    };
+    if lineage.len() != rank.depth() + 1 {
+        return Err(Error::InvalidRequest(format!(
+            "Tree node id {id} doesn't match its {level} level"
+        )));
+    }
+    Ok(lineage)
+}
+
+/// The lineage bound as the rank parameters of the taxonomy queries. Ranks below the
+/// lineage are bound as NULL and don't restrict the query.
+pub fn lineage_params<S: AsRef<str>>(lineage: &[S]) -> [Option<String>; 7] {
+    std::array::from_fn(|i| lineage.get(i).map(|l| l.as_ref().to_string()))
 }
 
 #[derive(Debug, Deserialize)]
-struct TaxTreeQuery {
+pub struct TaxTreeQuery {
     id: String,
+    /// Skip over empty ranks instead of showing an "Unclassified" node for them
+    #[serde(default)]
+    collapse_unclassified: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -73,390 +149,387 @@ struct LiAttr {
     data_assembly: String,
 }
 
-async fn tax_tree(
+/// IDs of all assemblies below a tree node
+pub async fn assemblies_below(pool: &PgPool, node_id: &str) -> Result<Vec<String>> {
+    let [superkingdom, phylum, class, order, family, genus, species] =
+        lineage_params(&parse_node_id(node_id)?);
+    let assemblies = sqlx::query_scalar!(
+        r#"
+        SELECT assembly_id
+        FROM antismash.taxa
+        JOIN antismash.genomes USING (tax_id)
+        WHERE tombstoned IS FALSE
+            AND ($1::text IS NULL OR COALESCE(superkingdom, '') ILIKE $1)
+            AND ($2::text IS NULL OR COALESCE(phylum, '') ILIKE $2)
+            AND ($3::text IS NULL OR COALESCE(class, '') ILIKE $3)
+            AND ($4::text IS NULL OR COALESCE(taxonomic_order, '') ILIKE $4)
+            AND ($5::text IS NULL OR COALESCE(family, '') ILIKE $5)
+            AND ($6::text IS NULL OR COALESCE(genus, '') ILIKE $6)
+            AND ($7::text IS NULL OR COALESCE(species, '') ILIKE $7)
+        ORDER BY assembly_id"#,
+        superkingdom,
+        phylum,
+        class,
+        order,
+        family,
+        genus,
+        species,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(assemblies)
+}
+
+#[derive(Debug)]
+struct TaxonRow {
+    name: String,
+    count: i64,
+}
+
+#[derive(Debug)]
+struct StrainRow {
+    genus: Option<String>,
+    species: Option<String>,
+    strain: Option<String>,
+    assembly_id: String,
+}
+
+pub async fn tax_tree(
     Extension(pool): Extension<PgPool>,
     Query(params): Query<TaxTreeQuery>,
 ) -> Result<Json<Value>> {
-    let id = params.id;
-    let body = Json(json!(get_taxon_tree_nodes(pool, id).await?));
+    let body = Json(json!(
+        get_taxon_tree_nodes(&pool, &params.id, params.collapse_unclassified).await?
+    ));
     Ok(body)
 }
 
-async fn get_taxon_tree_nodes(pool: PgPool, tree_id: String) -> Result<Vec<TreeNode>> {
-    let mut nodes: Vec<TreeNode> = Vec::new();
+async fn get_taxon_tree_nodes(
+    pool: &PgPool,
+    tree_id: &str,
+    collapse: bool,
+) -> Result<Vec<TreeNode>> {
     if tree_id == "1" {
-        nodes.extend(get_superkingdom(pool).await?)
-    } else {
-        let params: Vec<&str> = tree_id.split("_").collect();
-        if params.len() < 1 {
-            return Err(Error::InvalidRequest("Invalid tree id".to_string()));
-        }
-        let tax_level = params[0];
-        match tax_level {
-            "superkingdom" => nodes.extend(get_phylum(pool, &params[1..]).await?),
-            "phylum" => nodes.extend(get_class(pool, &params[1..]).await?),
-            "class" => nodes.extend(get_order(pool, &params[1..]).await?),
-            "order" => nodes.extend(get_family(pool, &params[1..]).await?),
-            "family" => nodes.extend(get_genus(pool, &params[1..]).await?),
-            "genus" => nodes.extend(get_species(pool, &params[1..]).await?),
-            "species" => nodes.extend(get_strain(pool, &params[1..]).await?),
-            _ => {
-                return Err(Error::InvalidRequest(format!(
-                    "Invalid tax_level {tax_level}"
-                )))
-            }
-        }
+        return get_child_nodes(pool, &[], "#", collapse).await;
     }
-
-    Ok(nodes)
+    let lineage: Vec<String> = parse_node_id(tree_id)?
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+    get_child_nodes(pool, &lineage, tree_id, collapse).await
 }
 
-async fn get_superkingdom(pool: PgPool) -> Result<Vec<TreeNode>> {
-    let nodes = sqlx::query!(
+/// Nodes one rank below `lineage`, attached to `parent`.
+/// With `collapse` set, the children of empty ranks are attached directly instead.
+#[async_recursion]
+async fn get_child_nodes(
+    pool: &PgPool,
+    lineage: &[String],
+    parent: &str,
+    collapse: bool,
+) -> Result<Vec<TreeNode>> {
+    let Some(rank) = TaxRank::ALL.get(lineage.len()).copied() else {
+        return get_strain_nodes(pool, lineage, parent).await;
+    };
+
+    // Missing and empty ranks both show up as one unclassified node
+    let [superkingdom, phylum, class, order, family, genus, species] = lineage_params(lineage);
+    let rows = sqlx::query_as!(
+        TaxonRow,
         r#"
-        SELECT superkingdom, COUNT(assembly_id)
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        GROUP BY superkingdom
-        ORDER BY superkingdom;"#
+        SELECT name AS "name!", COUNT(assembly_id) AS "count!"
+        FROM (
+            SELECT COALESCE(CASE $1::int
+                WHEN 0 THEN superkingdom
+                WHEN 1 THEN phylum
+                WHEN 2 THEN class
+                WHEN 3 THEN taxonomic_order
+                WHEN 4 THEN family
+                WHEN 5 THEN genus
+                ELSE species
+            END, '') AS name, assembly_id
+            FROM antismash.taxa
+            JOIN antismash.genomes USING (tax_id)
+            WHERE tombstoned IS FALSE
+                AND ($2::text IS NULL OR COALESCE(superkingdom, '') ILIKE $2)
+                AND ($3::text IS NULL OR COALESCE(phylum, '') ILIKE $3)
+                AND ($4::text IS NULL OR COALESCE(class, '') ILIKE $4)
+                AND ($5::text IS NULL OR COALESCE(taxonomic_order, '') ILIKE $5)
+                AND ($6::text IS NULL OR COALESCE(family, '') ILIKE $6)
+                AND ($7::text IS NULL OR COALESCE(genus, '') ILIKE $7)
+                AND ($8::text IS NULL OR COALESCE(species, '') ILIKE $8)
+        ) AS ranked
+        GROUP BY name
+        ORDER BY name"#,
+        rank.depth() as i32,
+        superkingdom,
+        phylum,
+        class,
+        order,
+        family,
+        genus,
+        species,
     )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "superkingdom_{}",
-                node.superkingdom.clone().unwrap_or_default().to_lowercase()
-            ),
-            "#".to_string(),
-            format!(
-                "{} ({})",
-                node.superkingdom.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
-            ),
-        )
-    })
-    .collect();
+    .fetch_all(pool)
+    .await?;
 
-    Ok(nodes)
-}
+    let mut nodes = Vec::new();
+    for row in rows {
+        let name = row.name;
+        let mut child_lineage = lineage.to_vec();
+        child_lineage.push(name.to_lowercase());
+        if name.is_empty() && collapse {
+            nodes.extend(get_child_nodes(pool, &child_lineage, parent, collapse).await?);
+            continue;
+        }
 
-async fn get_phylum(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    if params.len() < 1 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
+        let text = if name.is_empty() {
+            "Unclassified"
+        } else {
+            &name
+        };
+        nodes.push(TreeNode::new(
+            format!("{}_{}", rank.as_ref(), child_lineage.join("_")),
+            parent.to_string(),
+            format!("{text} ({})", row.count),
         ));
     }
-    let nodes = sqlx::query!(
-        r#"
-        SELECT phylum, COUNT(assembly_id)
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        GROUP BY phylum
-        ORDER BY phylum;"#,
-        params[0]
-    )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "phylum_{}_{}",
-                params.join("_"),
-                node.phylum.clone().unwrap_or_default().to_lowercase()
-            ),
-            format!("superkingdom_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.phylum.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
-            ),
-        )
-    })
-    .collect();
 
     Ok(nodes)
 }
 
-async fn get_class(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    eprintln!("{params:?}");
-    if params.len() < 2 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
-    }
-    let nodes = sqlx::query!(
+async fn get_strain_nodes(
+    pool: &PgPool,
+    lineage: &[String],
+    parent: &str,
+) -> Result<Vec<TreeNode>> {
+    let [superkingdom, phylum, class, order, family, genus, species] = lineage_params(lineage);
+    let nodes = sqlx::query_as!(
+        StrainRow,
         r#"
-        SELECT class, COUNT(assembly_id)
+        SELECT genus, species, strain, assembly_id
         FROM antismash.taxa
         JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        GROUP BY class
-        ORDER BY class;"#,
-        params[0],
-        params[1],
+        WHERE tombstoned IS FALSE
+            AND COALESCE(superkingdom, '') ILIKE $1
+            AND COALESCE(phylum, '') ILIKE $2
+            AND COALESCE(class, '') ILIKE $3
+            AND COALESCE(taxonomic_order, '') ILIKE $4
+            AND COALESCE(family, '') ILIKE $5
+            AND COALESCE(genus, '') ILIKE $6
+            AND COALESCE(species, '') ILIKE $7
+        ORDER BY strain"#,
+        superkingdom,
+        phylum,
+        class,
+        order,
+        family,
+        genus,
+        species,
     )
-    .fetch_all(&pool)
+    .fetch_all(pool)
     .await?
-    .iter()
+    .into_iter()
     .map(|node| {
-        TreeNode::new(
-            format!(
-                "class_{}_{}",
-                params.join("_"),
-                node.class.clone().unwrap_or_default().to_lowercase()
-            ),
-            format!("phylum_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.class.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
-            ),
-        )
+        let text = format!(
+            "{} {} {} {}",
+            node.genus.unwrap_or_default(),
+            node.species.unwrap_or_default(),
+            node.strain.unwrap_or_default(),
+            node.assembly_id
+        );
+        TreeNode::new(node.assembly_id.to_owned(), parent.to_string(), text)
+            .set_leaf(node.assembly_id)
     })
     .collect();
 
     Ok(nodes)
 }
 
-async fn get_order(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    eprintln!("{params:?}");
-    if params.len() < 3 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
-    }
-    let nodes = sqlx::query!(
-        r#"
-        SELECT taxonomic_order, COUNT(assembly_id)
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        AND class ILIKE $3
-        GROUP BY taxonomic_order
-        ORDER BY taxonomic_order;"#,
-        params[0],
-        params[1],
-        params[2],
-    )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "order_{}_{}",
-                params.join("_"),
-                node.taxonomic_order
-                    .clone()
-                    .unwrap_or_default()
-                    .to_lowercase()
-            ),
-            format!("class_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.taxonomic_order.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
-            ),
-        )
-    })
-    .collect();
+/// Shortest search string accepted, shorter ones match most of the tree
+const TREE_SEARCH_MIN_LENGTH: usize = 3;
 
-    Ok(nodes)
+#[derive(Debug, Deserialize)]
+pub struct TaxTreeSearch {
+    q: String,
+    /// Leave out the empty ranks the tree skips with `collapse_unclassified`
+    #[serde(default)]
+    collapse_unclassified: bool,
 }
 
-async fn get_family(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    eprintln!("{params:?}");
-    if params.len() < 4 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
+/// IDs of all tree nodes on the path to assemblies whose taxonomy, strain or assembly ID
+/// contains the search string, so the tree can expand to them
+pub async fn tax_tree_search(
+    Extension(pool): Extension<PgPool>,
+    Query(params): Query<TaxTreeSearch>,
+) -> Result<Json<Value>> {
+    let search = params.q.trim();
+    if search.len() < TREE_SEARCH_MIN_LENGTH {
+        return Err(Error::InvalidRequest(format!(
+            "Search string must be at least {TREE_SEARCH_MIN_LENGTH} characters"
+        )));
     }
-    let nodes = sqlx::query!(
+
+    let rows = sqlx::query!(
         r#"
-        SELECT family, COUNT(assembly_id)
+        SELECT superkingdom, phylum, class, taxonomic_order, family, genus, species, strain,
+            assembly_id
         FROM antismash.taxa
         JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        AND class ILIKE $3
-        AND taxonomic_order ILIKE $4
-        GROUP BY family
-        ORDER BY family;"#,
-        params[0],
-        params[1],
-        params[2],
-        params[3],
+        WHERE tombstoned IS FALSE
+        AND concat_ws(' ', superkingdom, phylum, class, taxonomic_order, family, genus,
+            species, strain, assembly_id) ILIKE $1
+        ORDER BY assembly_id"#,
+        format!("%{search}%"),
     )
     .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "family_{}_{}",
-                params.join("_"),
-                node.family.clone().unwrap_or_default().to_lowercase()
-            ),
-            format!("order_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.family.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
-            ),
-        )
-    })
-    .collect();
+    .await?;
 
-    Ok(nodes)
+    let mut seen = HashSet::new();
+    let mut ids = Vec::new();
+    for row in rows {
+        let lineage = [
+            row.superkingdom,
+            row.phylum,
+            row.class,
+            row.taxonomic_order,
+            row.family,
+            row.genus,
+            row.species,
+        ]
+        .map(Option::unwrap_or_default);
+        for id in node_path(&lineage, &row.assembly_id, params.collapse_unclassified) {
+            if seen.insert(id.to_owned()) {
+                ids.push(id);
+            }
+        }
+    }
+
+    Ok(Json(json!(ids)))
 }
 
-async fn get_genus(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    eprintln!("{params:?}");
-    if params.len() < 5 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
-    }
-    let nodes = sqlx::query!(
-        r#"
-        SELECT genus, COUNT(assembly_id)
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        AND class ILIKE $3
-        AND taxonomic_order ILIKE $4
-        AND family ILIKE $5
-        GROUP BY genus
-        ORDER BY genus;"#,
-        params[0],
-        params[1],
-        params[2],
-        params[3],
-        params[4],
-    )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "genus_{}_{}",
-                params.join("_"),
-                node.genus.clone().unwrap_or_default().to_lowercase()
+/// Node IDs from the top of the tree down to an assembly's leaf node
+fn node_path(lineage: &[String], assembly_id: &str, collapse: bool) -> Vec<String> {
+    let entries: Vec<String> = lineage.iter().map(|name| name.to_lowercase()).collect();
+    let mut path: Vec<String> = TaxRank::ALL
+        .iter()
+        .zip(lineage)
+        .enumerate()
+        .filter(|(_, (_, name))| !(collapse && name.is_empty()))
+        .map(|(depth, (rank, _))| format!("{}_{}", rank.as_ref(), entries[..=depth].join("_")))
+        .collect();
+    path.push(assembly_id.to_string());
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_node_id() {
+        let tests = [
+            ("superkingdom_bacteria", vec!["bacteria"]),
+            (
+                "phylum_bacteria_actinomycetota",
+                vec!["bacteria", "actinomycetota"],
             ),
-            format!("family_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.genus.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
+            ("class_bacteria__bacilli", vec!["bacteria", "", "bacilli"]),
+            (
+                "class_bacteria_actinomycetota_",
+                vec!["bacteria", "actinomycetota", ""],
             ),
-        )
-    })
-    .collect();
-
-    Ok(nodes)
-}
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_node_id(input).unwrap(), expected);
+        }
 
-async fn get_species(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    eprintln!("{params:?}");
-    if params.len() < 6 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
+        for invalid in [
+            "1",
+            "strain_bacteria",
+            "phylum_bacteria",
+            "class_a_b_c_d",
+            "phylum_b_c_",
+        ] {
+            assert!(parse_node_id(invalid).is_err());
+        }
     }
-    let nodes = sqlx::query!(
-        r#"
-        SELECT species, COUNT(assembly_id)
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        AND class ILIKE $3
-        AND taxonomic_order ILIKE $4
-        AND family ILIKE $5
-        AND genus ILIKE $6
-        GROUP BY species
-        ORDER BY species;"#,
-        params[0],
-        params[1],
-        params[2],
-        params[3],
-        params[4],
-        params[5],
-    )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        TreeNode::new(
-            format!(
-                "species_{}_{}",
-                params.join("_"),
-                node.species.clone().unwrap_or_default().to_lowercase()
+
+    #[sqlx::test(migrations = false)]
+    async fn test_get_taxon_tree_nodes(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        sqlx::query("UPDATE antismash.taxa SET class = NULL WHERE ncbi_taxid = 220664")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let ids =
+            |nodes: Vec<TreeNode>| -> Vec<String> { nodes.into_iter().map(|n| n.id).collect() };
+        let tests = [
+            ("1", false, vec!["superkingdom_bacteria"]),
+            (
+                "phylum_bacteria_pseudomonadota",
+                false,
+                vec!["class_bacteria_pseudomonadota_"],
             ),
-            format!("genus_{}", params.join("_")),
-            format!(
-                "{} ({})",
-                node.species.clone().unwrap_or_default(),
-                node.count.unwrap_or_default()
+            (
+                "class_bacteria_pseudomonadota_",
+                false,
+                vec!["order_bacteria_pseudomonadota__pseudomonadales"],
             ),
-        )
-    })
-    .collect();
-
-    Ok(nodes)
-}
+            (
+                "phylum_bacteria_pseudomonadota",
+                true,
+                vec!["order_bacteria_pseudomonadota__pseudomonadales"],
+            ),
+            (
+                "species_bacteria_actinomycetota_actinomycetes_kitasatosporales_streptomycetaceae_streptomyces_coelicolor",
+                false,
+                vec!["GCF_000203835.1"],
+            ),
+        ];
+        for (id, collapse, expected) in tests {
+            let nodes = get_taxon_tree_nodes(&pool, id, collapse).await.unwrap();
+            assert_eq!(ids(nodes), expected, "{id}");
+        }
 
-async fn get_strain(pool: PgPool, params: &[&str]) -> Result<Vec<TreeNode>> {
-    if params.len() < 7 {
-        return Err(Error::InvalidRequest(
-            "Not enough taxon parameters".to_string(),
-        ));
-    }
-    let nodes = sqlx::query!(
-        r#"
-        SELECT genus, species, strain, assembly_id
-        FROM antismash.taxa
-        JOIN antismash.genomes USING (tax_id)
-        WHERE superkingdom ILIKE $1
-        AND phylum ILIKE $2
-        AND class ILIKE $3
-        AND taxonomic_order ILIKE $4
-        AND family ILIKE $5
-        AND genus ILIKE $6
-        AND species ILIKE $7
-        ORDER BY strain;"#,
-        params[0],
-        params[1],
-        params[2],
-        params[3],
-        params[4],
-        params[5],
-        params[6],
-    )
-    .fetch_all(&pool)
-    .await?
-    .iter()
-    .map(|node| {
-        let parent = format!("species_{}", params.join("_"));
-        let text = format!(
-            "{} {} {} {}",
-            node.genus.clone().unwrap_or_default(),
-            node.species.clone().unwrap_or_default(),
-            node.strain.clone().unwrap_or_default(),
-            node.assembly_id.clone()
+        assert_eq!(
+            assemblies_below(&pool, "class_bacteria_pseudomonadota_")
+                .await
+                .unwrap(),
+            ["GCF_000012265.1"]
         );
-        TreeNode::new(node.assembly_id.to_owned(), parent, text)
-            .set_leaf(node.assembly_id.to_owned())
-    })
-    .collect();
+    }
 
-    Ok(nodes)
+    #[test]
+    fn test_node_path() {
+        let lineage = [
+            "Bacteria",
+            "Actinomycetota",
+            "",
+            "",
+            "Streptomycetaceae",
+            "Streptomyces",
+            "griseus",
+        ]
+        .map(String::from);
+        let expected = [
+            "superkingdom_bacteria",
+            "phylum_bacteria_actinomycetota",
+            "class_bacteria_actinomycetota_",
+            "order_bacteria_actinomycetota__",
+            "family_bacteria_actinomycetota___streptomycetaceae",
+            "genus_bacteria_actinomycetota___streptomycetaceae_streptomyces",
+            "species_bacteria_actinomycetota___streptomycetaceae_streptomyces_griseus",
+            "GCF_000010605.1",
+        ];
+        assert_eq!(node_path(&lineage, "GCF_000010605.1", false), expected);
+
+        let collapsed: Vec<&str> = expected
+            .iter()
+            .filter(|id| !id.ends_with('_'))
+            .copied()
+            .collect();
+        assert_eq!(node_path(&lineage, "GCF_000010605.1", true), collapsed);
+    }
 }
@@ -1,17 +1,120 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use axum::{routing::get, Json, Router};
+use axum::{routing::get, Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use serde_json::{json, Value};
+use sqlx::PgPool;
 
+use super::normalize::Routes;
 use crate::Result;
 
-pub fn routes() -> Router {
-    Router::new().route("/api/version", get(version))
+pub fn routes() -> Routes {
+    Routes::new()
+        .route("/api/version", get(version))
+        .route("/api/version/database", get(database_version))
 }
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
-async fn version() -> Result<Json<Value>> {
+pub async fn version() -> Result<Json<Value>> {
     Ok(Json(json!({"api": VERSION})))
 }
+
+/// The data release a query runs against, for citing exactly which data was used
+#[derive(Debug, Default, Serialize)]
+pub struct DatabaseVersion {
+    /// Unset if the import tooling didn't record any release information
+    pub release: Option<String>,
+    pub antismash_version: Option<String>,
+    pub import_date: Option<DateTime<Utc>>,
+    pub genomes: i64,
+    pub records: i64,
+    pub regions: i64,
+}
+
+impl DatabaseVersion {
+    pub async fn fetch(pool: &PgPool) -> Result<Self> {
+        let mut version = sqlx::query!(
+            r#"
+            SELECT release, antismash_version, import_date
+                FROM antismash.db_metadata
+                ORDER BY import_date DESC
+                LIMIT 1"#
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|row| Self {
+            release: Some(row.release),
+            antismash_version: Some(row.antismash_version),
+            import_date: Some(row.import_date.and_utc()),
+            ..Default::default()
+        })
+        .unwrap_or_default();
+
+        let counts = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM antismash.genomes WHERE tombstoned IS FALSE) AS "genomes!",
+                (SELECT COUNT(*) FROM antismash.dna_sequences
+                    JOIN antismash.genomes USING (genome_id)
+                    WHERE tombstoned IS FALSE) AS "records!",
+                (SELECT COUNT(*) FROM antismash.regions
+                    JOIN antismash.dna_sequences USING (accession)
+                    JOIN antismash.genomes USING (genome_id)
+                    WHERE tombstoned IS FALSE) AS "regions!""#
+        )
+        .fetch_one(pool)
+        .await?;
+        version.genomes = counts.genomes;
+        version.records = counts.records;
+        version.regions = counts.regions;
+
+        Ok(version)
+    }
+
+    /// Comment line identifying the data release, for the top of exported files
+    pub fn csv_comment(&self) -> String {
+        let release = self.release.as_deref().unwrap_or("unknown release");
+        let mut comment = format!("# antiSMASH-DB {release}");
+        if let Some(antismash_version) = &self.antismash_version {
+            comment.push_str(&format!(", antiSMASH {antismash_version}"));
+        }
+        if let Some(import_date) = &self.import_date {
+            comment.push_str(&format!(", imported {}", import_date.format("%Y-%m-%d")));
+        }
+        comment
+    }
+}
+
+pub async fn database_version(Extension(pool): Extension<PgPool>) -> Result<Json<Value>> {
+    let version = DatabaseVersion::fetch(&pool).await?;
+    Ok(Json(json!(version)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_csv_comment() {
+        let tests = [
+            (DatabaseVersion::default(), "# antiSMASH-DB unknown release"),
+            (
+                DatabaseVersion {
+                    release: Some("4.0".to_string()),
+                    antismash_version: Some("7.1.0".to_string()),
+                    import_date: Some(Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()),
+                    ..Default::default()
+                },
+                "# antiSMASH-DB 4.0, antiSMASH 7.1.0, imported 2026-03-01",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(input.csv_comment(), expected, "{input:?}");
+        }
+    }
+}
@@ -1,22 +1,32 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use std::fs::remove_dir_all;
-use std::path::PathBuf;
+use std::fs::{read_dir, remove_dir_all};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use sqlx::PgPool;
 
-use crate::models::job::JobEntry;
+use crate::models::job::{JobEntry, JobId, JobStatus};
 use crate::Result;
 
-pub async fn run(pool: &PgPool, job_base_dir: &PathBuf, days: f64) -> Result<()> {
+pub async fn run(
+    pool: &PgPool,
+    job_base_dir: &Path,
+    days: f64,
+    statuses: &[JobStatus],
+    dry_run: bool,
+) -> Result<()> {
+    if dry_run {
+        return report(pool, job_base_dir, days, statuses).await;
+    }
+
     loop {
-        let Some(job) = JobEntry::next_to_clean(pool, days).await? else {
+        let Some(job) = JobEntry::next_to_clean(pool, days, statuses).await? else {
             break;
         };
 
-        let mut jobdir = job_base_dir.clone();
-        jobdir.push(&job.id);
+        let jobdir = job.id.dir(job_base_dir);
 
         if jobdir.exists() {
             eprintln!("Removing {jobdir:?}");
@@ -27,6 +37,8 @@ pub async fn run(pool: &PgPool, job_base_dir: &PathBuf, days: f64) -> Result<()>
         job.delete(pool).await?;
     }
 
+    sweep_orphans(pool, job_base_dir, days, statuses, false).await?;
+
     eprintln!("Vacuuming the jobs table");
     sqlx::query!("VACUUM asdb_jobs.jobs").execute(pool).await?;
     eprintln!("Vacuuming the controls table");
@@ -36,3 +48,181 @@ pub async fn run(pool: &PgPool, job_base_dir: &PathBuf, days: f64) -> Result<()>
 
     Ok(())
 }
+
+/// Print what a cleanup run would remove, without touching anything
+async fn report(
+    pool: &PgPool,
+    job_base_dir: &Path,
+    days: f64,
+    statuses: &[JobStatus],
+) -> Result<()> {
+    let jobs = JobEntry::to_clean(pool, days, statuses, i64::MAX).await?;
+
+    let mut total = 0;
+    for job in &jobs {
+        let jobdir = job.id.dir(job_base_dir);
+        if jobdir.exists() {
+            let size = dir_size(&jobdir)?;
+            total += size;
+            eprintln!("Would remove {jobdir:?} ({})", format_size(size));
+        }
+        eprintln!(
+            "Would delete job {} ({}, {}, submitted {})",
+            job.id, job.jobtype, job.status, job.submitted_date
+        );
+    }
+
+    total += sweep_orphans(pool, job_base_dir, days, statuses, true).await?;
+
+    eprintln!(
+        "Would delete {} jobs and reclaim {}",
+        jobs.len(),
+        format_size(total)
+    );
+    Ok(())
+}
+
+/// Remove job directories older than `days` that have no job in the database anymore,
+/// e.g. left behind by crashed runs. Returns the bytes reclaimed (or reclaimable on dry runs).
+async fn sweep_orphans(
+    pool: &PgPool,
+    job_base_dir: &Path,
+    days: f64,
+    statuses: &[JobStatus],
+    dry_run: bool,
+) -> Result<u64> {
+    if !statuses.is_empty() {
+        eprintln!("Skipping orphaned job directories when filtering by status");
+        return Ok(0);
+    }
+    if !job_base_dir.is_dir() {
+        return Ok(0);
+    }
+
+    let max_age = Duration::from_secs_f64(days * 24.0 * 3600.0);
+    let candidates = old_job_dirs(job_base_dir, max_age)?;
+    let ids: Vec<JobId> = candidates.iter().map(|(id, _)| *id).collect();
+    let existing = JobEntry::existing_ids(pool, &ids).await?;
+
+    let mut reclaimed = 0;
+    for (id, path) in candidates {
+        if existing.contains(&id) {
+            continue;
+        }
+        let size = dir_size(&path)?;
+        reclaimed += size;
+        if dry_run {
+            eprintln!("Would remove orphaned {path:?} ({})", format_size(size));
+        } else {
+            eprintln!("Removing orphaned {path:?} ({})", format_size(size));
+            remove_dir_all(&path)?;
+        }
+    }
+    Ok(reclaimed)
+}
+
+/// Job directories directly below `base` that haven't been modified for `max_age`.
+/// Directories not named after a job ID are left alone.
+fn old_job_dirs(base: &Path, max_age: Duration) -> Result<Vec<(JobId, PathBuf)>> {
+    let cutoff = SystemTime::now()
+        .checked_sub(max_age)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut dirs = Vec::new();
+    for entry in read_dir(base)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_dir() || metadata.modified()? > cutoff {
+            continue;
+        }
+        let Some(id) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        dirs.push((id, entry.path()));
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Total size in bytes of all files below `path`
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_size() {
+        let dir = std::env::temp_dir().join(format!("asdb-cleanup-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("results.json"), b"12345").unwrap();
+        std::fs::write(dir.join("nested").join("input.fa"), b">a\nMAGIC").unwrap();
+
+        let size = dir_size(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(size, 13);
+    }
+
+    #[test]
+    fn test_old_job_dirs() {
+        let dir = std::env::temp_dir().join(format!("asdb-orphans-{}", std::process::id()));
+        let job_a = "00000000-0000-4000-8000-00000000000a";
+        let job_b = "00000000-0000-4000-8000-00000000000b";
+        std::fs::create_dir_all(dir.join(job_a)).unwrap();
+        std::fs::create_dir_all(dir.join(job_b)).unwrap();
+        std::fs::create_dir_all(dir.join("not-a-job")).unwrap();
+        std::fs::write(dir.join("00000000-0000-4000-8000-00000000000c"), b"").unwrap();
+
+        let old = old_job_dirs(&dir, Duration::ZERO).unwrap();
+        let recent = old_job_dirs(&dir, Duration::from_secs(3600)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = old.iter().map(|(id, _)| id.to_string()).collect();
+        assert_eq!(names, vec![job_a, job_b]);
+        assert!(recent.is_empty());
+    }
+
+    #[test]
+    fn test_format_size() {
+        let tests = [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (5 * 1024 * 1024, "5.0 MiB"),
+            (3 * 1024 * 1024 * 1024, "3.0 GiB"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(format_size(input), expected, "{input}");
+        }
+    }
+}
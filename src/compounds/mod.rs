@@ -0,0 +1,175 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use sqlx::PgPool;
+
+use crate::{Error, Result};
+
+/// Where a compound table comes from, the data of each source is replaced as a whole
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, strum::AsRefStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum CompoundSource {
+    /// Natural Products Atlas export
+    #[default]
+    Npatlas,
+    /// MIBiG compound list
+    Mibig,
+}
+
+/// A known compound and one of the MIBiG entries of its gene cluster
+#[derive(Debug, PartialEq)]
+pub struct CompoundReference {
+    pub accession: String,
+    pub name: String,
+    pub smiles: Option<String>,
+    pub mibig_accession: String,
+}
+
+/// Header names of the columns, NPAtlas export names first
+const ACCESSION_COLUMNS: &[&str] = &["npaid", "accession"];
+const NAME_COLUMNS: &[&str] = &["compound_names", "compound_name", "name"];
+const SMILES_COLUMNS: &[&str] = &["compound_smiles", "smiles"];
+const MIBIG_COLUMNS: &[&str] = &["mibig_ids", "mibig_accession", "mibig"];
+
+fn find_column(header: &[&str], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|h| names.contains(&h.trim().to_lowercase().as_str()))
+}
+
+/// Parse a tab-separated compound table with a header line. Compounds can list several
+/// MIBiG entries separated by commas, semicolons or pipes, versions are dropped so they
+/// match KnownClusterBlast hits. Compounds without MIBiG entries are skipped.
+pub fn parse_compound_table(content: &str) -> Result<Vec<CompoundReference>> {
+    let mut lines = content.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split('\t').collect();
+    let missing =
+        |column: &str| Error::InvalidRequest(format!("compound table has no {column} column"));
+    let accession_col =
+        find_column(&header, ACCESSION_COLUMNS).ok_or_else(|| missing("accession"))?;
+    let name_col = find_column(&header, NAME_COLUMNS).ok_or_else(|| missing("name"))?;
+    let mibig_col = find_column(&header, MIBIG_COLUMNS).ok_or_else(|| missing("MIBiG"))?;
+    let smiles_col = find_column(&header, SMILES_COLUMNS);
+
+    let mut references = Vec::new();
+    for (number, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let field = |col: usize| fields.get(col).copied().unwrap_or_default();
+        let (accession, name) = (field(accession_col), field(name_col));
+        if accession.is_empty() || name.is_empty() {
+            return Err(Error::InvalidRequest(format!(
+                "compound table line {} is missing the accession or name",
+                number + 2
+            )));
+        }
+        let smiles = smiles_col
+            .map(field)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+
+        for mibig in field(mibig_col).split([',', ';', '|']) {
+            let mibig = mibig.trim();
+            let mibig = mibig.split_once('.').map_or(mibig, |(acc, _)| acc);
+            if mibig.is_empty() {
+                continue;
+            }
+            references.push(CompoundReference {
+                accession: accession.to_string(),
+                name: name.to_string(),
+                smiles: smiles.clone(),
+                mibig_accession: mibig.to_string(),
+            });
+        }
+    }
+    Ok(references)
+}
+
+/// Replace the compounds of a source by the ones in a table file
+pub async fn import(pool: &PgPool, file: &Path, source: CompoundSource) -> Result<usize> {
+    let content = tokio::fs::read_to_string(file).await?;
+    let mut references = parse_compound_table(&content)?;
+    references.sort_by(|a, b| {
+        (&a.accession, &a.mibig_accession).cmp(&(&b.accession, &b.mibig_accession))
+    });
+    references
+        .dedup_by(|a, b| a.accession == b.accession && a.mibig_accession == b.mibig_accession);
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        "DELETE FROM antismash.compound_references WHERE source = $1",
+        source.as_ref(),
+    )
+    .execute(&mut *tx)
+    .await?;
+    for reference in &references {
+        sqlx::query!(
+            r#"
+        INSERT INTO antismash.compound_references (source, accession, name, smiles, mibig_accession)
+        VALUES ($1, $2, $3, $4, $5)"#,
+            source.as_ref(),
+            reference.accession,
+            reference.name,
+            reference.smiles,
+            reference.mibig_accession,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(references.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compound_table() {
+        let table = "npaid\tcompound_names\tcompound_smiles\tmibig_ids\n\
+            NPA000001\tErythromycin A\tCCC1OC(=O)\tBGC0000055.5\n\
+            NPA000002\tActinorhodin\t\tBGC0000194, BGC0000195\n\
+            NPA000003\tUnlinked\tCC\t\n\
+            \n";
+        let expected = vec![
+            CompoundReference {
+                accession: "NPA000001".to_string(),
+                name: "Erythromycin A".to_string(),
+                smiles: Some("CCC1OC(=O)".to_string()),
+                mibig_accession: "BGC0000055".to_string(),
+            },
+            CompoundReference {
+                accession: "NPA000002".to_string(),
+                name: "Actinorhodin".to_string(),
+                smiles: None,
+                mibig_accession: "BGC0000194".to_string(),
+            },
+            CompoundReference {
+                accession: "NPA000002".to_string(),
+                name: "Actinorhodin".to_string(),
+                smiles: None,
+                mibig_accession: "BGC0000195".to_string(),
+            },
+        ];
+        assert_eq!(parse_compound_table(table).unwrap(), expected);
+
+        let minimal = "Accession\tName\tMIBiG\nBGC0000315.c1\tstreptomycin\tBGC0000315\n";
+        let parsed = parse_compound_table(minimal).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].smiles, None);
+
+        let invalid = [
+            "",
+            "npaid\tmibig_ids\nNPA1\tBGC1\n",
+            "npaid\tname\tmibig\n\tx\tBGC1\n",
+        ];
+        for table in invalid {
+            assert!(parse_compound_table(table).is_err(), "{table}");
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use sqlx::{ConnectOptions, PgPool};
+use tokio::fs;
+
+use crate::jobs::{self, comparippson::CompaRiPPsonConfig, Executor};
+use crate::{Error, Result};
+
+/// Schemas the API and the job runners work on
+const SCHEMAS: [&str; 2] = ["antismash", "asdb_jobs"];
+
+/// What to validate, as the serve and run commands would use it
+#[derive(Debug, Clone)]
+pub struct CheckConfig {
+    pub jobdir: PathBuf,
+    pub outdir: Option<PathBuf>,
+    pub dbdir: PathBuf,
+    pub executor: Executor,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    /// What was found if the check passed, the problem otherwise
+    pub outcome: std::result::Result<String, String>,
+}
+
+/// Outcome of all checks, failing checks don't stop the later ones
+#[derive(Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(&mut self, name: &str, outcome: Result<String>) {
+        self.checks.push(Check {
+            name: name.to_string(),
+            outcome: outcome.map_err(|e| describe(&e)),
+        });
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| c.outcome.is_err()).count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            let (label, detail) = match &check.outcome {
+                Ok(detail) => ("ok", detail),
+                Err(problem) => ("FAILED", problem),
+            };
+            writeln!(f, "{label:<6}  {:<width$}  {detail}", check.name)?;
+        }
+        let failures = self.failures();
+        match failures {
+            0 => write!(f, "All {} checks passed", self.checks.len()),
+            _ => write!(f, "{failures} of {} checks failed", self.checks.len()),
+        }
+    }
+}
+
+/// Error message including its causes, the top level messages are rather terse
+fn describe(error: &Error) -> String {
+    if let Error::ConfigError(problem) = error {
+        return problem.to_owned();
+    }
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        // Some errors already include their cause in their message
+        let cause_message = cause.to_string();
+        if !message.contains(&cause_message) {
+            message.push_str(&format!(": {cause_message}"));
+        }
+        source = cause.source();
+    }
+    message
+}
+
+/// Validate the whole configuration, reporting every check instead of stopping at the
+/// first failure
+pub async fn run(pool: &PgPool, config: &CheckConfig) -> Report {
+    let mut report = Report::default();
+
+    let connected = check_database(pool).await;
+    let connected_ok = connected.is_ok();
+    report.add("database", connected);
+    if connected_ok {
+        report.add("schema", check_schema(pool).await);
+        report.add("migrations", check_migrations(pool).await);
+    } else {
+        let skipped = || Err(Error::ConfigError("skipped, no database connection".into()));
+        report.add("schema", skipped());
+        report.add("migrations", skipped());
+    }
+
+    report.add("jobdir", check_dir(&config.jobdir, true).await);
+    if let Some(outdir) = &config.outdir {
+        report.add("outdir", check_dir(outdir, true).await);
+    }
+    // The runners only read the databases, which are often mounted read-only
+    report.add("dbdir", check_dir(&config.dbdir, false).await);
+    report.add("comparippson", check_comparippson(&config.dbdir).await);
+    report.add(
+        "executor",
+        jobs::check_executor(config.executor)
+            .await
+            .map(|found| found.join(", ")),
+    );
+
+    report
+}
+
+async fn check_database(pool: &PgPool) -> Result<String> {
+    // A connection of its own reports why connecting failed, the pool only times out
+    let mut connection = pool.connect_options().connect().await?;
+    let version: String = sqlx::query_scalar("SELECT version()")
+        .fetch_one(&mut connection)
+        .await?;
+    Ok(format!("connected to {version}"))
+}
+
+async fn check_schema(pool: &PgPool) -> Result<String> {
+    let present: Vec<String> =
+        sqlx::query_scalar("SELECT nspname::text FROM pg_namespace WHERE nspname = ANY($1)")
+            .bind(&SCHEMAS[..])
+            .fetch_all(pool)
+            .await?;
+    let missing: Vec<&str> = SCHEMAS
+        .into_iter()
+        .filter(|schema| !present.iter().any(|p| p == schema))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::ConfigError(format!(
+            "missing schemas {}",
+            missing.join(", ")
+        )));
+    }
+    Ok(format!("found schemas {}", SCHEMAS.join(", ")))
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<String> {
+    let migrator = sqlx::migrate!();
+    // The migrations table only exists once the first migration ran
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await
+            .unwrap_or_default();
+    let pending: Vec<i64> = migrator
+        .iter()
+        .map(|m| m.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+    if let Some(first) = pending.first() {
+        return Err(Error::ConfigError(format!(
+            "{} pending migrations starting at {first}, run the migrate command",
+            pending.len()
+        )));
+    }
+    Ok(format!(
+        "all {} migrations applied",
+        migrator.iter().count()
+    ))
+}
+
+/// Check that a directory exists, and that files can be created in it if needed
+async fn check_dir(dir: &Path, writable: bool) -> Result<String> {
+    if !fs::metadata(dir).await.is_ok_and(|m| m.is_dir()) {
+        return Err(Error::ConfigError(format!("{dir:?} is not a directory")));
+    }
+    if !writable {
+        let mut entries = fs::read_dir(dir)
+            .await
+            .map_err(|e| Error::ConfigError(format!("can't read {dir:?}: {e}")))?;
+        entries.next_entry().await?;
+        return Ok(format!("{dir:?} is readable"));
+    }
+
+    let probe = dir.join(format!(".asdb-check-{}", std::process::id()));
+    fs::write(&probe, b"")
+        .await
+        .map_err(|e| Error::ConfigError(format!("can't write to {dir:?}: {e}")))?;
+    fs::remove_file(&probe).await?;
+    Ok(format!("{dir:?} is writable"))
+}
+
+async fn check_comparippson(dbdir: &Path) -> Result<String> {
+    let config = CompaRiPPsonConfig::load(dbdir).await?;
+    let mut databases = Vec::new();
+    for (name, database) in &config.databases {
+        let metadata = database.metadata().await?;
+        databases.push(format!(
+            "{name} {} {} ({} entries)",
+            metadata.name,
+            metadata.version,
+            metadata.entries.len()
+        ));
+    }
+    Ok(format!("loaded metadata of {}", databases.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = Report::default();
+        report.add("database", Ok("connected".to_string()));
+        report.add(
+            "jobdir",
+            Err(Error::ConfigError("\"/nope\" is not a directory".into())),
+        );
+        assert_eq!(report.failures(), 1);
+        assert_eq!(
+            report.to_string(),
+            "ok      database  connected\n\
+             FAILED  jobdir    \"/nope\" is not a directory\n\
+             1 of 2 checks failed"
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let error = Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no such file",
+        ));
+        assert_eq!(describe(&error), "IO error: no such file");
+    }
+}
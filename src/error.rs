@@ -3,13 +3,17 @@
 
 use std::ffi::OsString;
 use std::io;
+use std::time::Duration;
 use std::{env::VarError, num::ParseIntError};
 
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
 use nom::error::{ErrorKind, ParseError};
+use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error as ThisError;
 use zip::result::ZipError;
 
@@ -29,6 +33,10 @@ pub enum Error {
     InvalidRequest(String),
     #[error("Not found")]
     NotFound,
+    #[error("Accession {} not found, stored versions: {}", .0, .1.join(", "))]
+    VersionNotFound(String, Vec<String>),
+    #[error("Unauthorized")]
+    Unauthorized,
     #[error("Parser error")]
     ParserError,
     #[error("Json Parser error")]
@@ -43,25 +51,168 @@ pub enum Error {
     CompaRiPPsonError(String),
     #[error("Error compressing file")]
     CompressionError(#[from] ZipError),
+    #[error("Error writing spreadsheet")]
+    SpreadsheetError(#[from] rust_xlsxwriter::XlsxError),
+    #[error("Invalid configuration: {}", .0)]
+    ConfigError(String),
+    #[error("Timed out after {} seconds", .0.as_secs())]
+    TimeoutError(Duration),
+    #[error("Query timed out: {}", .0)]
+    QueryTimeout(String),
+    #[error("HTTP request failed")]
+    HttpError(#[from] reqwest::Error),
+    #[error("Job failed: {}", .0)]
+    JobFailed(String),
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> Response {
-        println!("->> {:<12} - {self:?}", "INTO_RES");
+impl Error {
+    /// Whether the database cancelled the statement, e.g. because of the statement timeout
+    pub fn is_query_canceled(&self) -> bool {
+        let Self::SqlError(e) = self else {
+            return false;
+        };
+        e.as_database_error()
+            .and_then(|d| d.code())
+            .is_some_and(|code| code == "57014")
+    }
+
+    /// The database's complaint about a regular expression it couldn't compile. The regex
+    /// crate accepts some patterns PostgreSQL doesn't, so not all of them are caught up front.
+    fn invalid_regex(&self) -> Option<String> {
+        let Self::SqlError(e) = self else {
+            return None;
+        };
+        e.as_database_error()
+            .filter(|d| d.code().is_some_and(|code| code == "2201B"))
+            .map(|d| d.message().to_string())
+    }
 
+    /// Whether a job failing with this error might succeed when tried again later,
+    /// like after losing the database connection. Errors in the job itself, like invalid
+    /// SQL or missing files, fail the job right away.
+    pub fn is_transient(&self) -> bool {
         match self {
-            Self::InvalidRequest(msg) => (StatusCode::BAD_REQUEST, msg.to_owned()),
-            Self::NotFound => (
+            Self::SqlError(e) => match e {
+                sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => true,
+                sqlx::Error::Database(d) => d.code().is_some_and(|c| is_transient_sqlstate(&c)),
+                _ => false,
+            },
+            Self::IoError(e) => is_network_error(e.kind()),
+            Self::HttpError(e) => e.is_connect() || e.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+/// Connection exceptions, the server shutting down, and serialization failures or deadlocks
+fn is_transient_sqlstate(code: &str) -> bool {
+    code.starts_with("08") || matches!(code, "57P01" | "40001" | "40P01")
+}
+
+fn is_network_error(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+    )
+}
+
+impl Error {
+    /// What the client gets to see of the error
+    pub fn client_error(&self) -> (StatusCode, ErrorBody) {
+        let (status, code, message, detail) = match self {
+            Self::InvalidRequest(msg) => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS,
+                msg.to_owned(),
+                None,
+            ),
+            Self::ParserError => (
+                StatusCode::BAD_REQUEST,
+                ClientError::PARSER_ERROR,
+                "Failed to parse the search query".to_string(),
+                None,
+            ),
+            Self::NotFound | Self::SqlError(sqlx::Error::RowNotFound) => (
                 StatusCode::NOT_FOUND,
-                ClientError::NOT_FOUND.as_ref().to_string(),
+                ClientError::NOT_FOUND,
+                "Not found".to_string(),
+                None,
+            ),
+            Self::VersionNotFound(accession, versions) => (
+                StatusCode::NOT_FOUND,
+                ClientError::VERSION_NOT_FOUND,
+                self.to_string(),
+                Some(json!({"accession": accession, "stored_versions": versions})),
+            ),
+            Self::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ClientError::UNAUTHORIZED,
+                "Unauthorized".to_string(),
+                None,
+            ),
+            Self::NotImplementedError(msg) => (
+                StatusCode::NOT_IMPLEMENTED,
+                ClientError::NOT_IMPLEMENTED,
+                msg.to_owned(),
+                None,
+            ),
+            Self::QueryTimeout(msg) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ClientError::QUERY_TIMEOUT,
+                msg.to_owned(),
+                None,
+            ),
+            Self::SqlError(_) if self.invalid_regex().is_some() => (
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS,
+                self.invalid_regex().unwrap_or_default(),
+                None,
+            ),
+            _ if self.is_query_canceled() => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ClientError::QUERY_TIMEOUT,
+                "query took too long, try a more specific search".to_string(),
+                None,
+            ),
+            Self::JobFailed(msg) => (
+                StatusCode::CONFLICT,
+                ClientError::JOB_FAILED,
+                "The job failed".to_string(),
+                Some(json!({"job_error": msg})),
             ),
-            Self::NotImplementedError(msg) => (StatusCode::NOT_IMPLEMENTED, msg.to_owned()),
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                ClientError::UNHANDLED_SERVER_ERROR.as_ref().to_string(),
+                ClientError::UNHANDLED_SERVER_ERROR,
+                "Internal server error".to_string(),
+                None,
             ),
+        };
+        let body = ErrorBody {
+            code,
+            message,
+            detail,
+            request_id: None,
+        };
+        (status, body)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let request_id = crate::api::request_id::current();
+        match request_id {
+            Some(id) => println!("->> {:<12} - {id} {self:?}", "INTO_RES"),
+            None => println!("->> {:<12} - {self:?}", "INTO_RES"),
         }
-        .into_response()
+
+        let (status, mut body) = self.client_error();
+        body.request_id = request_id;
+        (status, Json(json!({ "error": body }))).into_response()
     }
 }
 
@@ -74,10 +225,145 @@ impl<I> ParseError<I> for Error {
     }
 }
 
-#[derive(Debug, strum::AsRefStr)]
+/// Machine-readable error codes, these are part of the API and must not change
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, strum::AsRefStr)]
 #[allow(non_camel_case_types)]
 pub enum ClientError {
     INVALID_PARAMS,
+    PARSER_ERROR,
     NOT_FOUND,
+    VERSION_NOT_FOUND,
+    UNAUTHORIZED,
+    NOT_IMPLEMENTED,
+    QUERY_TIMEOUT,
+    JOB_FAILED,
     UNHANDLED_SERVER_ERROR,
 }
+
+/// Sent as `{"error": <ErrorBody>}` for every error response
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub code: ClientError,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Value>,
+    /// Set while handling an API request, so users can quote it in bug reports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<uuid::Uuid>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        let tests = [
+            (Error::SqlError(sqlx::Error::PoolTimedOut), true),
+            (Error::SqlError(sqlx::Error::PoolClosed), true),
+            (
+                Error::SqlError(sqlx::Error::Io(io::ErrorKind::ConnectionReset.into())),
+                true,
+            ),
+            (Error::SqlError(sqlx::Error::RowNotFound), false),
+            (
+                Error::SqlError(sqlx::Error::ColumnNotFound("region_id".to_string())),
+                false,
+            ),
+            (
+                Error::IoError(io::ErrorKind::ConnectionRefused.into()),
+                true,
+            ),
+            (Error::IoError(io::ErrorKind::TimedOut.into()), true),
+            (Error::IoError(io::ErrorKind::NotFound.into()), false),
+            (
+                Error::IoError(io::Error::other("podman pull failed")),
+                false,
+            ),
+            (Error::TimeoutError(Duration::from_secs(10)), false),
+            (Error::InvalidRequest("bad".to_string()), false),
+            (Error::JobFailed("blastp crashed".to_string()), false),
+        ];
+        for (error, expected) in tests {
+            assert_eq!(error.is_transient(), expected, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn test_is_transient_sqlstate() {
+        let tests = [
+            ("08006", true),
+            ("57P01", true),
+            ("40001", true),
+            ("40P01", true),
+            ("23505", false),
+            ("42P01", false),
+            ("57014", false),
+        ];
+        for (code, expected) in tests {
+            assert_eq!(is_transient_sqlstate(code), expected, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_client_error() {
+        let tests = [
+            (
+                Error::InvalidRequest("bad".to_string()),
+                StatusCode::BAD_REQUEST,
+                ClientError::INVALID_PARAMS,
+            ),
+            (
+                Error::ParserError,
+                StatusCode::BAD_REQUEST,
+                ClientError::PARSER_ERROR,
+            ),
+            (
+                Error::NotFound,
+                StatusCode::NOT_FOUND,
+                ClientError::NOT_FOUND,
+            ),
+            (
+                Error::SqlError(sqlx::Error::RowNotFound),
+                StatusCode::NOT_FOUND,
+                ClientError::NOT_FOUND,
+            ),
+            (
+                Error::VersionNotFound("NC_003888".to_string(), vec!["3".to_string()]),
+                StatusCode::NOT_FOUND,
+                ClientError::VERSION_NOT_FOUND,
+            ),
+            (
+                Error::QueryTimeout("slow".to_string()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ClientError::QUERY_TIMEOUT,
+            ),
+            (
+                Error::JobFailed("blastp crashed".to_string()),
+                StatusCode::CONFLICT,
+                ClientError::JOB_FAILED,
+            ),
+            (
+                Error::TimeoutError(Duration::from_secs(10)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ClientError::UNHANDLED_SERVER_ERROR,
+            ),
+        ];
+        for (error, status, code) in tests {
+            let (actual_status, body) = error.client_error();
+            assert_eq!(actual_status, status, "{error:?}");
+            assert_eq!(body.code, code, "{error:?}");
+        }
+
+        let (_, body) =
+            Error::VersionNotFound("NC_003888".to_string(), vec!["3".to_string()]).client_error();
+        assert_eq!(
+            serde_json::to_value(body).unwrap(),
+            json!({
+                "code": "VERSION_NOT_FOUND",
+                "message": "Accession NC_003888 not found, stored versions: 3",
+                "detail": {"accession": "NC_003888", "stored_versions": ["3"]},
+            })
+        );
+    }
+}
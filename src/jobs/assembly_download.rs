@@ -0,0 +1,193 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::fs;
+
+use crate::api::version::DatabaseVersion;
+use crate::models::job::JobId;
+use crate::query::{ReturnType, SearchType};
+use crate::{Error, Result};
+
+use super::paths;
+use super::stored_query::{region_gbk_path, zip_files, Manifest};
+use super::RunConfig;
+
+/// Largest total size of GenBank files a single download job may pack
+pub const MAX_DOWNLOAD_SIZE: u64 = 500 * 1024 * 1024;
+/// Most assemblies a single download job may cover
+pub const MAX_ASSEMBLIES: usize = 1000;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AssemblyDownloadInput {
+    pub job_id: JobId,
+    pub assemblies: Vec<String>,
+}
+
+/// All region GenBank files of a list of assemblies, packed into a single zip file
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AssemblyDownload {
+    pub input: AssemblyDownloadInput,
+    pub filename: Option<String>,
+}
+
+impl AssemblyDownload {
+    pub fn new(job_id: JobId, assemblies: Vec<String>) -> Self {
+        Self {
+            input: AssemblyDownloadInput { job_id, assemblies },
+            filename: None,
+        }
+    }
+}
+
+/// What a download of a set of assemblies would contain
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct DownloadEstimate {
+    pub assemblies: usize,
+    pub regions: usize,
+    /// Total size of the GenBank files in bytes, before compression
+    pub size: u64,
+    /// Regions without a GenBank file in the output directory
+    pub missing: usize,
+    pub max_size: u64,
+}
+
+impl DownloadEstimate {
+    pub async fn new(assemblies: usize, files: &[(PathBuf, i32)]) -> Self {
+        let mut estimate = Self {
+            assemblies,
+            regions: files.len(),
+            max_size: MAX_DOWNLOAD_SIZE,
+            ..Default::default()
+        };
+        for (path, _) in files {
+            match fs::metadata(path).await {
+                Ok(metadata) => estimate.size += metadata.len(),
+                Err(_) => estimate.missing += 1,
+            }
+        }
+        estimate
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.regions == 0 {
+            return Err(Error::InvalidRequest(
+                "No regions found for the requested assemblies".to_string(),
+            ));
+        }
+        if self.size > self.max_size {
+            return Err(Error::InvalidRequest(format!(
+                "Download of {} MiB exceeds the limit of {} MiB, please request fewer assemblies",
+                self.size / (1024 * 1024),
+                self.max_size / (1024 * 1024)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// GenBank files of all regions of the given assemblies, with their region IDs
+pub async fn region_files(
+    pool: &PgPool,
+    outdir: &Path,
+    assemblies: &[String],
+) -> Result<Vec<(PathBuf, i32)>> {
+    let files = sqlx::query!(
+        r#"
+        SELECT region_id, assembly_id, accession, version, region_number
+            FROM antismash.regions
+            JOIN antismash.dna_sequences USING (accession)
+            JOIN antismash.genomes USING (genome_id)
+            WHERE assembly_id = ANY($1) AND tombstoned IS FALSE
+            ORDER BY assembly_id, accession, region_number"#,
+        assemblies,
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let path = region_gbk_path(
+            outdir,
+            &row.assembly_id,
+            &row.accession,
+            row.version.unwrap_or_default(),
+            row.region_number,
+        )?;
+        Ok((path, row.region_id))
+    })
+    .collect::<Result<_>>()?;
+    Ok(files)
+}
+
+pub async fn run(
+    mut download: AssemblyDownload,
+    pool: &PgPool,
+    config: &RunConfig,
+) -> Result<AssemblyDownload> {
+    let Some(outdir) = &config.outdir else {
+        return Err(Error::InvalidRequest(
+            "Genbank format requested, but no output directory specified".to_string(),
+        ));
+    };
+    let job_id = download.input.job_id;
+    let jobdir = job_id.dir(&config.jobdir);
+    fs::create_dir_all(&jobdir).await?;
+    let jobdir = paths::ensure_within(&config.jobdir, &jobdir).await?;
+
+    // The database or the files might have changed since the job was submitted
+    let files = region_files(pool, outdir, &download.input.assemblies).await?;
+    DownloadEstimate::new(download.input.assemblies.len(), &files)
+        .await
+        .check()?;
+
+    let manifest = Manifest {
+        job_id,
+        search_type: SearchType::Region,
+        return_type: ReturnType::Genbank,
+        created: Utc::now(),
+        runner: config.name.to_owned(),
+        version: super::VERSION,
+        query: Some(json!({ "assemblies": download.input.assemblies })),
+        database: Some(DatabaseVersion::fetch(pool).await?),
+        files: Vec::new(),
+        missing: Vec::new(),
+    };
+    // Files missing from the output directory are listed in the archive itself
+    let (data, _missing) = zip_files(outdir, &files, manifest).await?;
+
+    let filename = format!("{job_id}.zip");
+    super::write_result(&jobdir, &filename, &data).await?;
+
+    download.filename = Some(format!("/{}/{job_id}/{filename}", config.urlroot));
+    Ok(download)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_check() {
+        let estimate = |regions, size| DownloadEstimate {
+            assemblies: 1,
+            regions,
+            size,
+            missing: 0,
+            max_size: MAX_DOWNLOAD_SIZE,
+        };
+        let tests = [
+            (estimate(3, 1024), true),
+            (estimate(3, MAX_DOWNLOAD_SIZE), true),
+            (estimate(3, MAX_DOWNLOAD_SIZE + 1), false),
+            (estimate(0, 0), false),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(input.check().is_ok(), expected, "{input:?}");
+        }
+    }
+}
@@ -11,10 +11,103 @@ pub struct BlastInput {
     pub sequence: String,
 }
 
+pub const DEFAULT_MIN_SEQUENCE_LENGTH: usize = 10;
+pub const DEFAULT_MAX_SEQUENCE_LENGTH: usize = 10_000;
+const MAX_NAME_LENGTH: usize = 100;
+/// The 20 standard amino acids, the ambiguity codes B, Z, J and X, selenocysteine,
+/// pyrrolysine and stop codons
+const PROTEIN_ALPHABET: &str = "ACDEFGHIKLMNPQRSTVWYBZJXUO*";
+const NUCLEOTIDE_ALPHABET: &str = "ACGTUN";
+
+/// Length limits for submitted query sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceLimits {
+    pub min_length: usize,
+    pub max_length: usize,
+}
+
+impl Default for SequenceLimits {
+    fn default() -> Self {
+        Self {
+            min_length: DEFAULT_MIN_SEQUENCE_LENGTH,
+            max_length: DEFAULT_MAX_SEQUENCE_LENGTH,
+        }
+    }
+}
+
 impl BlastInput {
     pub fn to_fasta(&self) -> String {
         format!(">{}\n{}", self.name, self.sequence)
     }
+
+    /// Check the input is a usable protein query, returning a copy with the sequence
+    /// stripped of whitespace and upper-cased
+    pub fn validate(&self, limits: &SequenceLimits) -> Result<Self> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(Error::InvalidRequest("Sequence name is empty".to_string()));
+        }
+        if name.len() > MAX_NAME_LENGTH {
+            return Err(Error::InvalidRequest(format!(
+                "Sequence name is longer than {MAX_NAME_LENGTH} characters"
+            )));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || "._-|:".contains(*c)))
+        {
+            return Err(Error::InvalidRequest(format!(
+                "Invalid character {c:?} in sequence name, only letters, digits and ._-|: are allowed"
+            )));
+        }
+
+        if self.sequence.contains('>') {
+            return Err(Error::InvalidRequest(
+                "Sequence contains a FASTA header, submit the bare sequence".to_string(),
+            ));
+        }
+        let sequence: String = self
+            .sequence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+
+        if let Some((pos, c)) = sequence
+            .chars()
+            .enumerate()
+            .find(|(_, c)| !PROTEIN_ALPHABET.contains(*c))
+        {
+            return Err(Error::InvalidRequest(format!(
+                "Invalid character {c:?} at position {} of the sequence",
+                pos + 1
+            )));
+        }
+        if sequence.len() < limits.min_length {
+            return Err(Error::InvalidRequest(format!(
+                "Sequence is {} residues long, the minimum is {}",
+                sequence.len(),
+                limits.min_length
+            )));
+        }
+        if sequence.len() > limits.max_length {
+            return Err(Error::InvalidRequest(format!(
+                "Sequence is {} residues long, the maximum is {}",
+                sequence.len(),
+                limits.max_length
+            )));
+        }
+        if sequence.chars().all(|c| NUCLEOTIDE_ALPHABET.contains(c)) {
+            return Err(Error::InvalidRequest(
+                "Sequence looks like a nucleotide sequence, submit a protein sequence".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            sequence,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -30,12 +123,26 @@ pub struct BlastResult {
     pub s_start: u64,
     pub s_end: u64,
     pub s_len: u64,
+    pub evalue: f64,
+    pub bitscore: f64,
+    /// Percentage of the query covered by the alignment
+    pub q_coverage: f64,
+    /// Percentage of the subject covered by the alignment
+    pub s_coverage: f64,
+}
+
+/// Percentage of a sequence of length `len` covered by an alignment from `start` to `end`
+fn coverage(start: u64, end: u64, len: u64) -> f64 {
+    if len == 0 {
+        return 0.0;
+    }
+    (start.abs_diff(end) + 1) as f64 / len as f64 * 100.0
 }
 
 impl BlastResult {
     pub fn from_str(line: &str) -> Result<Self> {
         let parts: Vec<&str> = line.trim().split('\t').collect();
-        if parts.len() != 11 {
+        if parts.len() != 13 {
             return Err(Error::ParserError);
         }
 
@@ -50,6 +157,8 @@ impl BlastResult {
         let s_start = parts[8].parse()?;
         let s_end = parts[9].parse()?;
         let s_len = parts[10].parse()?;
+        let evalue = parts[11].parse().or(Err(Error::ParserError))?;
+        let bitscore = parts[12].parse().or(Err(Error::ParserError))?;
 
         let identity = (nident as f64 / f64::max(q_len as f64, s_len as f64)) * 100.0;
 
@@ -65,6 +174,10 @@ impl BlastResult {
             s_start,
             s_end,
             s_len,
+            evalue,
+            bitscore,
+            q_coverage: coverage(q_start, q_end, q_len),
+            s_coverage: coverage(s_start, s_end, s_len),
         })
     }
 }
@@ -75,7 +188,7 @@ mod tests {
 
     #[test]
     fn test_from_str() {
-        let line = "ABCD\tDEFG\t7\tMAGICHAT\t1\t8\t8\tMAGICCAT\t1\t8\t8";
+        let line = "ABCD\tDEFG\t7\tMAGICHAT\t1\t8\t8\tMAGICCAT\t1\t4\t8\t1.5e-5\t42.7";
         let expected = BlastResult {
             q_acc: "ABCD".to_owned(),
             s_acc: "DEFG".to_owned(),
@@ -86,11 +199,63 @@ mod tests {
             q_len: 8,
             s_seq: "MAGICCAT".to_owned(),
             s_start: 1,
-            s_end: 8,
+            s_end: 4,
             s_len: 8,
+            evalue: 1.5e-5,
+            bitscore: 42.7,
+            q_coverage: 100.0,
+            s_coverage: 50.0,
         };
 
         let res = BlastResult::from_str(line).unwrap();
         assert_eq!(res, expected);
+
+        let short = "ABCD\tDEFG\t7\tMAGICHAT\t1\t8\t8\tMAGICCAT\t1\t8\t8";
+        assert!(BlastResult::from_str(short).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let limits = SequenceLimits {
+            min_length: 5,
+            max_length: 20,
+        };
+        let tests = [
+            ("query", "MAGICHAT", Ok("MAGICHAT")),
+            (" query ", "magic\nhat ", Ok("MAGICHAT")),
+            ("sp|P12345|x", "MAGICHAT*", Ok("MAGICHAT*")),
+            ("", "MAGICHAT", Err("Sequence name is empty")),
+            (
+                "my query",
+                "MAGICHAT",
+                Err("Invalid character ' ' in sequence name"),
+            ),
+            ("query", ">other\nMAGICHAT", Err("FASTA header")),
+            (
+                "query",
+                "MAGIC1HAT",
+                Err("Invalid character '1' at position 6"),
+            ),
+            ("query", "MAG", Err("3 residues long, the minimum is 5")),
+            (
+                "query",
+                &"A".repeat(21),
+                Err("21 residues long, the maximum is 20"),
+            ),
+            ("query", "GATTACAGATTACA", Err("nucleotide")),
+        ];
+        for (name, sequence, expected) in tests {
+            let input = BlastInput {
+                name: name.to_string(),
+                sequence: sequence.to_string(),
+            };
+            match (input.validate(&limits), expected) {
+                (Ok(res), Ok(expected)) => assert_eq!(res.sequence, expected, "{sequence}"),
+                (Err(Error::InvalidRequest(msg)), Err(expected)) => {
+                    assert!(msg.contains(expected), "{msg} does not contain {expected}")
+                }
+                (res, expected) => panic!("{name} {sequence}: {res:?} vs {expected:?}"),
+            }
+        }
     }
 }
@@ -0,0 +1,498 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::path::Path;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{Connection, Executor, PgPool};
+use tokio::fs;
+
+use crate::query::SearchType;
+use crate::Result;
+
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// Layout of the SQLite results bundle.
+/// The comments are kept by SQLite, so `.schema` in the sqlite3 shell doubles as documentation.
+pub const SCHEMA: &str = r#"
+-- Key/value metadata about the bundle: schema_version, job_id, search_type and version
+CREATE TABLE bundle_info (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+-- Taxonomy of the genomes the regions were found in
+CREATE TABLE taxa (
+    tax_id INTEGER PRIMARY KEY,
+    ncbi_taxid INTEGER,     -- NCBI taxonomy ID, if known
+    superkingdom TEXT,
+    phylum TEXT,
+    class TEXT,
+    taxonomic_order TEXT,
+    family TEXT,
+    genus TEXT,
+    species TEXT,
+    strain TEXT
+);
+
+-- BGC regions, coordinates are 0-based, end exclusive
+CREATE TABLE regions (
+    region_id INTEGER PRIMARY KEY,
+    tax_id INTEGER NOT NULL REFERENCES taxa,
+    assembly_id TEXT NOT NULL,
+    accession TEXT NOT NULL,    -- nucleotide record accession, without version
+    version INTEGER,
+    region_number INTEGER NOT NULL,
+    start_pos INTEGER NOT NULL,
+    end_pos INTEGER NOT NULL,
+    contig_edge INTEGER NOT NULL,   -- 1 if the region is on a contig edge
+    best_mibig_hit_acc TEXT,
+    best_mibig_hit_description TEXT,
+    best_mibig_hit_similarity INTEGER   -- in percent
+);
+
+-- BGC types of each region, one row per type
+CREATE TABLE region_types (
+    region_id INTEGER NOT NULL REFERENCES regions,
+    bgc_type TEXT NOT NULL
+);
+
+-- Genes, only the matched ones for gene and domain searches
+CREATE TABLE cdss (
+    cds_id INTEGER PRIMARY KEY,
+    region_id INTEGER NOT NULL REFERENCES regions,
+    locus_tag TEXT,
+    protein_id TEXT,
+    product TEXT,
+    location TEXT NOT NULL,
+    translation TEXT
+);
+
+-- antiSMASH domains, only the matched ones for domain searches
+CREATE TABLE domains (
+    as_domain_id INTEGER PRIMARY KEY,
+    cds_id INTEGER NOT NULL REFERENCES cdss,
+    name TEXT NOT NULL,
+    location TEXT NOT NULL,
+    score REAL,
+    evalue REAL,
+    translation TEXT
+);
+
+CREATE INDEX region_types_region_id ON region_types (region_id);
+CREATE INDEX cdss_region_id ON cdss (region_id);
+CREATE INDEX domains_cds_id ON domains (cds_id);
+"#;
+
+#[derive(Debug, Default)]
+pub struct BundleTaxon {
+    pub tax_id: i32,
+    pub ncbi_taxid: Option<i32>,
+    pub superkingdom: Option<String>,
+    pub phylum: Option<String>,
+    pub class: Option<String>,
+    pub taxonomic_order: Option<String>,
+    pub family: Option<String>,
+    pub genus: Option<String>,
+    pub species: Option<String>,
+    pub strain: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BundleRegion {
+    pub region_id: i32,
+    pub tax_id: i32,
+    pub assembly_id: String,
+    pub accession: String,
+    pub version: Option<i32>,
+    pub region_number: i32,
+    pub start_pos: i32,
+    pub end_pos: i32,
+    pub contig_edge: bool,
+    pub best_mibig_hit_acc: Option<String>,
+    pub best_mibig_hit_description: Option<String>,
+    pub best_mibig_hit_similarity: Option<i32>,
+    pub types: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BundleCds {
+    pub cds_id: i32,
+    pub region_id: i32,
+    pub locus_tag: Option<String>,
+    pub protein_id: Option<String>,
+    pub product: Option<String>,
+    pub location: String,
+    pub translation: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BundleDomain {
+    pub as_domain_id: i32,
+    pub cds_id: i32,
+    pub name: String,
+    pub location: String,
+    pub score: Option<f64>,
+    pub evalue: Option<f64>,
+    pub translation: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Bundle {
+    pub taxa: Vec<BundleTaxon>,
+    pub regions: Vec<BundleRegion>,
+    pub cdss: Vec<BundleCds>,
+    pub domains: Vec<BundleDomain>,
+}
+
+impl Bundle {
+    /// Collect everything belonging to the matched ids.
+    /// Region searches get all genes and domains of the regions, gene and domain searches
+    /// only the matched entries plus the regions they are in.
+    pub async fn fetch(pool: &PgPool, search_type: &SearchType, ids: &[i32]) -> Result<Self> {
+        let (region_ids, cds_ids, domain_ids) = match search_type {
+            SearchType::Region => {
+                let cds_ids = sqlx::query_scalar!(
+                    "SELECT cds_id FROM antismash.cdss WHERE region_id = ANY($1) ORDER BY cds_id",
+                    ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                let domain_ids = sqlx::query_scalar!(
+                    r#"SELECT as_domain_id FROM antismash.as_domains
+                    WHERE cds_id = ANY($1) ORDER BY as_domain_id"#,
+                    &cds_ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                (ids.to_vec(), cds_ids, domain_ids)
+            }
+            SearchType::Gene => {
+                let region_ids = sqlx::query_scalar!(
+                    r#"SELECT DISTINCT region_id FROM antismash.cdss
+                    WHERE cds_id = ANY($1) ORDER BY region_id"#,
+                    ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                let domain_ids = sqlx::query_scalar!(
+                    r#"SELECT as_domain_id FROM antismash.as_domains
+                    WHERE cds_id = ANY($1) ORDER BY as_domain_id"#,
+                    ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                (region_ids, ids.to_vec(), domain_ids)
+            }
+            SearchType::Domain => {
+                let cds_ids = sqlx::query_scalar!(
+                    r#"SELECT DISTINCT cds_id FROM antismash.as_domains
+                    WHERE as_domain_id = ANY($1) ORDER BY cds_id"#,
+                    ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                let region_ids = sqlx::query_scalar!(
+                    r#"SELECT DISTINCT region_id FROM antismash.cdss
+                    WHERE cds_id = ANY($1) ORDER BY region_id"#,
+                    &cds_ids,
+                )
+                .fetch_all(pool)
+                .await?;
+                (region_ids, cds_ids, ids.to_vec())
+            }
+        };
+
+        let regions = sqlx::query_as!(
+            BundleRegion,
+            r#"
+            SELECT region_id, tax_id, assembly_id, accession, version, region_number,
+                start_pos, end_pos, contig_edge, best_mibig_hit_acc,
+                best_mibig_hit_description, best_mibig_hit_similarity,
+                array_agg(term ORDER BY term) AS "types!"
+            FROM antismash.regions
+            JOIN antismash.dna_sequences USING (accession)
+            JOIN antismash.genomes USING (genome_id)
+            JOIN antismash.rel_regions_types USING (region_id)
+            JOIN antismash.bgc_types USING (bgc_type_id)
+            WHERE region_id = ANY($1)
+            GROUP BY region_id, tax_id, assembly_id, version
+            ORDER BY region_id"#,
+            &region_ids,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut tax_ids: Vec<i32> = regions.iter().map(|r| r.tax_id).collect();
+        tax_ids.sort_unstable();
+        tax_ids.dedup();
+
+        let taxa = sqlx::query_as!(
+            BundleTaxon,
+            r#"
+            SELECT tax_id, ncbi_taxid, superkingdom, phylum, class, taxonomic_order,
+                family, genus, species, strain
+            FROM antismash.taxa
+            WHERE tax_id = ANY($1)
+            ORDER BY tax_id"#,
+            &tax_ids,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let cdss = sqlx::query_as!(
+            BundleCds,
+            r#"
+            SELECT cds_id, region_id, locus_tag, protein_id, product, location, translation
+            FROM antismash.cdss
+            WHERE cds_id = ANY($1)
+            ORDER BY cds_id"#,
+            &cds_ids,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let domains = sqlx::query_as!(
+            BundleDomain,
+            r#"
+            SELECT as_domain_id, cds_id, p.name, location, score, evalue, translation
+            FROM antismash.as_domains
+            JOIN antismash.as_domain_profiles AS p USING (as_domain_profile_id)
+            WHERE as_domain_id = ANY($1)
+            ORDER BY as_domain_id"#,
+            &domain_ids,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self {
+            taxa,
+            regions,
+            cdss,
+            domains,
+        })
+    }
+}
+
+/// Write the bundle into a fresh SQLite database at `path`
+pub async fn write_bundle(path: &Path, bundle: &Bundle, info: &[(&str, String)]) -> Result<()> {
+    if fs::try_exists(path).await? {
+        fs::remove_file(path).await?;
+    }
+
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    conn.execute(SCHEMA).await?;
+
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("INSERT INTO bundle_info (key, value) VALUES ('schema_version', ?)")
+        .bind(SCHEMA_VERSION.to_string())
+        .execute(&mut *tx)
+        .await?;
+    for (key, value) in info {
+        sqlx::query("INSERT INTO bundle_info (key, value) VALUES (?, ?)")
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for taxon in &bundle.taxa {
+        sqlx::query(
+            r#"INSERT INTO taxa (tax_id, ncbi_taxid, superkingdom, phylum, class, taxonomic_order,
+                family, genus, species, strain) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(taxon.tax_id)
+        .bind(taxon.ncbi_taxid)
+        .bind(&taxon.superkingdom)
+        .bind(&taxon.phylum)
+        .bind(&taxon.class)
+        .bind(&taxon.taxonomic_order)
+        .bind(&taxon.family)
+        .bind(&taxon.genus)
+        .bind(&taxon.species)
+        .bind(&taxon.strain)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for region in &bundle.regions {
+        sqlx::query(
+            r#"INSERT INTO regions (region_id, tax_id, assembly_id, accession, version,
+                region_number, start_pos, end_pos, contig_edge, best_mibig_hit_acc,
+                best_mibig_hit_description, best_mibig_hit_similarity)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(region.region_id)
+        .bind(region.tax_id)
+        .bind(&region.assembly_id)
+        .bind(&region.accession)
+        .bind(region.version)
+        .bind(region.region_number)
+        .bind(region.start_pos)
+        .bind(region.end_pos)
+        .bind(region.contig_edge)
+        .bind(&region.best_mibig_hit_acc)
+        .bind(&region.best_mibig_hit_description)
+        .bind(region.best_mibig_hit_similarity)
+        .execute(&mut *tx)
+        .await?;
+
+        for bgc_type in &region.types {
+            sqlx::query("INSERT INTO region_types (region_id, bgc_type) VALUES (?, ?)")
+                .bind(region.region_id)
+                .bind(bgc_type)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+
+    for cds in &bundle.cdss {
+        sqlx::query(
+            r#"INSERT INTO cdss (cds_id, region_id, locus_tag, protein_id, product, location,
+                translation) VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(cds.cds_id)
+        .bind(cds.region_id)
+        .bind(&cds.locus_tag)
+        .bind(&cds.protein_id)
+        .bind(&cds.product)
+        .bind(&cds.location)
+        .bind(&cds.translation)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for domain in &bundle.domains {
+        sqlx::query(
+            r#"INSERT INTO domains (as_domain_id, cds_id, name, location, score, evalue,
+                translation) VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(domain.as_domain_id)
+        .bind(domain.cds_id)
+        .bind(&domain.name)
+        .bind(&domain.location)
+        .bind(domain.score)
+        .bind(domain.evalue)
+        .bind(&domain.translation)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// Build the bundle for a stored query in a scratch file and return its contents
+pub async fn to_sqlite(
+    pool: &PgPool,
+    scratch: &Path,
+    search_type: &SearchType,
+    ids: &[i32],
+    info: &[(&str, String)],
+) -> Result<Vec<u8>> {
+    let bundle = Bundle::fetch(pool, search_type, ids).await?;
+    write_bundle(scratch, &bundle, info).await?;
+    let data = fs::read(scratch).await?;
+    fs::remove_file(scratch).await?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Row;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_bundle() {
+        let dir = std::env::temp_dir().join(format!("asdb-bundle-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("bob.sqlite");
+
+        let bundle = Bundle {
+            taxa: vec![BundleTaxon {
+                tax_id: 1,
+                genus: Some("Streptomyces".to_string()),
+                species: Some("coelicolor".to_string()),
+                ..Default::default()
+            }],
+            regions: vec![BundleRegion {
+                region_id: 7,
+                tax_id: 1,
+                assembly_id: "GCF_000203835.1".to_string(),
+                accession: "NC_003888".to_string(),
+                version: Some(3),
+                region_number: 1,
+                end_pos: 1000,
+                contig_edge: true,
+                types: vec!["NRPS".to_string(), "T1PKS".to_string()],
+                ..Default::default()
+            }],
+            cdss: vec![BundleCds {
+                cds_id: 3,
+                region_id: 7,
+                locus_tag: Some("SCO1234".to_string()),
+                location: "[10:20](+)".to_string(),
+                ..Default::default()
+            }],
+            domains: vec![BundleDomain {
+                as_domain_id: 5,
+                cds_id: 3,
+                name: "PKS_KS".to_string(),
+                location: "[10:20](+)".to_string(),
+                score: Some(123.4),
+                ..Default::default()
+            }],
+        };
+        write_bundle(&path, &bundle, &[("job_id", "bob".to_string())])
+            .await
+            .unwrap();
+
+        let mut conn = SqliteConnection::connect_with(&SqliteConnectOptions::new().filename(&path))
+            .await
+            .unwrap();
+
+        let row = sqlx::query(
+            r#"SELECT r.contig_edge, t.genus, COUNT(*) AS types
+            FROM regions r JOIN taxa t USING (tax_id) JOIN region_types USING (region_id)
+            GROUP BY r.region_id"#,
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert!(row.get::<bool, _>("contig_edge"));
+        assert_eq!(row.get::<String, _>("genus"), "Streptomyces");
+        assert_eq!(row.get::<i64, _>("types"), 2);
+
+        let row = sqlx::query(
+            r#"SELECT c.locus_tag, d.name, d.score FROM domains d JOIN cdss c USING (cds_id)"#,
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(row.get::<String, _>("locus_tag"), "SCO1234");
+        assert_eq!(row.get::<String, _>("name"), "PKS_KS");
+        assert_eq!(row.get::<f64, _>("score"), 123.4);
+
+        let info: Vec<(String, String)> =
+            sqlx::query_as("SELECT key, value FROM bundle_info ORDER BY key")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            info,
+            vec![
+                ("job_id".to_string(), "bob".to_string()),
+                ("schema_version".to_string(), SCHEMA_VERSION.to_string()),
+            ]
+        );
+
+        conn.close().await.unwrap();
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -5,14 +5,39 @@ use std::convert::TryFrom;
 use std::process::Stdio;
 
 use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::AsyncWriteExt;
 
 use super::blast::{BlastInput, BlastResult};
 use crate::{Error, Result};
 
+pub const CLUSTERBLAST_DB: &str = "clusterblast/proteins.dmnd";
+/// Number of hits stored per job, promiscuous queries can find tens of thousands
+pub const CLUSTERBLAST_MAX_HITS: usize = 1000;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ClusterBlastResults {
     pub hits: Vec<ClusterBlastResult>,
+    /// Set if more hits were found than are stored
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl ClusterBlastResults {
+    /// Sort the hits by identity, best first, and only keep the `max_hits` best ones
+    pub fn sort_and_cap(&mut self, max_hits: usize) {
+        self.hits.sort_by(|a, b| b.identity.total_cmp(&a.identity));
+        if self.hits.len() > max_hits {
+            self.hits.truncate(max_hits);
+            self.truncated = true;
+        }
+    }
+
+    /// Only keep a page of the hits, returning the number of hits before paging
+    pub fn page(&mut self, offset: usize, limit: usize) -> usize {
+        let total = self.hits.len();
+        self.hits = self.hits.drain(..).skip(offset).take(limit).collect();
+        total
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -25,14 +50,20 @@ impl ClusterBlast {
     pub fn new(name: String, sequence: String) -> Self {
         Self {
             input: BlastInput { name, sequence },
-            results: ClusterBlastResults { hits: Vec::new() },
+            results: ClusterBlastResults {
+                hits: Vec::new(),
+                truncated: false,
+            },
         }
     }
 
     pub fn from_blast(input: BlastInput) -> Self {
         Self {
             input,
-            results: ClusterBlastResults { hits: Vec::new() },
+            results: ClusterBlastResults {
+                hits: Vec::new(),
+                truncated: false,
+            },
         }
     }
 }
@@ -54,6 +85,11 @@ pub struct ClusterBlastResult {
     pub s_start: u64,
     pub s_end: u64,
     pub s_len: u64,
+    /// Not available for jobs run before these were recorded
+    pub evalue: Option<f64>,
+    pub bitscore: Option<f64>,
+    pub q_coverage: Option<f64>,
+    pub s_coverage: Option<f64>,
 }
 
 impl TryFrom<BlastResult> for ClusterBlastResult {
@@ -91,32 +127,31 @@ impl TryFrom<BlastResult> for ClusterBlastResult {
             s_start: value.s_start,
             s_end: value.s_end,
             s_len: value.s_len,
+            evalue: Some(value.evalue),
+            bitscore: Some(value.bitscore),
+            q_coverage: Some(value.q_coverage),
+            s_coverage: Some(value.s_coverage),
         })
     }
 }
 
 pub async fn run(mut data: ClusterBlast, config: &super::RunConfig) -> Result<ClusterBlast> {
+    let db = config.tool_dbdir().join("clusterblast/proteins");
     // The dbdir should always convert to a str
-    let dbdir = config.dbdir.to_str().unwrap();
-    let dbdir_mapping = format!("{}:/databases:ro", dbdir);
+    let db = db.to_str().unwrap();
 
     #[rustfmt::skip]
     let args = &[
-        "run", "--detach=false", "--rm", "--interactive",
-        "--volume", dbdir_mapping.as_str(), 
-        "--name", config.name.as_str(),
-        "docker.io/antismash/asdb-jobs:latest",
-        "diamond", "blastp",
+        "blastp",
         "--threads", "4",
-        "--db", "/databases/clusterblast/proteins",
+        "--db", db,
         "--compress", "0",
         "--max-target-seqs", "50",
         "--evalue", "1e-05",
-        "--outfmt", "6", "qseqid", "sseqid", "nident", "qseq", "qstart", "qend", "qlen", "sseq", "sstart", "send", "slen",
+        "--outfmt", "6", "qseqid", "sseqid", "nident", "qseq", "qstart", "qend", "qlen", "sseq", "sstart", "send", "slen", "evalue", "bitscore",
         ];
 
-    let mut command = tokio::process::Command::new("podman");
-    command.args(args);
+    let mut command = config.tool_command("diamond", args);
     command.stdin(Stdio::piped());
     command.stdout(Stdio::piped());
     command.stderr(Stdio::null());
@@ -126,14 +161,83 @@ pub async fn run(mut data: ClusterBlast, config: &super::RunConfig) -> Result<Cl
     stdin.write(data.input.to_fasta().as_bytes()).await?;
     drop(stdin);
 
-    let res = child.wait_with_output().await?;
+    let hits = &mut data.results.hits;
+    config
+        .stream_tool(child, |line| {
+            let hit: ClusterBlastResult = BlastResult::from_str(line)?.try_into()?;
+            hits.push(hit);
+            Ok(hits.len() < super::MAX_HITS)
+        })
+        .await?;
+    data.results.sort_and_cap(CLUSTERBLAST_MAX_HITS);
 
-    let mut reader = BufReader::new(res.stdout.as_ref()).lines();
+    Ok(data)
+}
 
-    while let Some(line) = reader.next_line().await? {
-        let hit: ClusterBlastResult = BlastResult::from_str(&line)?.try_into()?;
-        data.results.hits.push(hit);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(identity: f64) -> ClusterBlastResult {
+        ClusterBlastResult {
+            q_acc: "query".to_string(),
+            s_locus: format!("locus_{identity}"),
+            s_description: "".to_string(),
+            s_acc: "NC_003888".to_string(),
+            s_rec_start: "1".to_string(),
+            s_rec_end: "100".to_string(),
+            identity,
+            q_seq: "".to_string(),
+            q_start: 1,
+            q_end: 100,
+            q_len: 100,
+            s_seq: "".to_string(),
+            s_start: 1,
+            s_end: 100,
+            s_len: 100,
+            evalue: Some(1e-10),
+            bitscore: Some(100.0),
+            q_coverage: Some(100.0),
+            s_coverage: Some(100.0),
+        }
     }
 
-    Ok(data)
+    fn identities(results: &ClusterBlastResults) -> Vec<f64> {
+        results.hits.iter().map(|h| h.identity).collect()
+    }
+
+    #[test]
+    fn test_sort_and_cap() {
+        let tests = [
+            (vec![50.0, 90.0, 70.0], 5, vec![90.0, 70.0, 50.0], false),
+            (vec![50.0, 90.0, 70.0], 3, vec![90.0, 70.0, 50.0], false),
+            (vec![50.0, 90.0, 70.0, 80.0], 2, vec![90.0, 80.0], true),
+        ];
+        for (input, max_hits, expected, truncated) in tests {
+            let mut results = ClusterBlastResults {
+                hits: input.iter().copied().map(hit).collect(),
+                truncated: false,
+            };
+            results.sort_and_cap(max_hits);
+            assert_eq!(identities(&results), expected, "{input:?}");
+            assert_eq!(results.truncated, truncated, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_page() {
+        let tests = [
+            (0, 2, vec![1.0, 2.0]),
+            (3, 10, vec![4.0, 5.0]),
+            (10, 10, vec![]),
+        ];
+        for (offset, limit, expected) in tests {
+            let mut results = ClusterBlastResults {
+                hits: [1.0, 2.0, 3.0, 4.0, 5.0].into_iter().map(hit).collect(),
+                truncated: false,
+            };
+            assert_eq!(results.page(offset, limit), 5);
+            assert_eq!(identities(&results), expected, "{offset} {limit}");
+        }
+    }
 }
@@ -1,20 +1,56 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::RwLock;
 
 use super::blast::{BlastInput, BlastResult};
 use crate::{Error, Result};
 
-pub const COMPARIPPSON_DB_BASE: &'static str = "/databases/comparippson/asdb/3.9/cores.fa";
-pub const COMPARIPPSON_METADATA: &'static str = "comparippson/asdb/3.9/metadata.json";
+/// Name and location below the database directory of all known CompaRiPPson databases
+pub const COMPARIPPSON_DATABASES: [(&str, &str); 2] = [
+    ("asdb", "comparippson/asdb/3.9"),
+    ("mibig", "comparippson/mibig/4.0"),
+];
+pub const COMPARIPPSON_DB_FILE: &str = "cores.fa";
+pub const COMPARIPPSON_METADATA_FILE: &str = "metadata.json";
+pub const DEFAULT_DATABASE: &str = "asdb";
+
+fn default_database() -> String {
+    DEFAULT_DATABASE.to_string()
+}
+
+/// Check a requested database name against the list of known databases
+pub fn validate_database(name: &str) -> Result<()> {
+    if COMPARIPPSON_DATABASES
+        .iter()
+        .any(|(known, _)| *known == name)
+    {
+        return Ok(());
+    }
+    let known: Vec<&str> = COMPARIPPSON_DATABASES.iter().map(|(n, _)| *n).collect();
+    Err(Error::InvalidRequest(format!(
+        "Invalid CompaRiPPson database {name:?}, valid choices are: {}",
+        known.join(", ")
+    )))
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CompaRiPPsonInput {
+    #[serde(flatten)]
+    pub query: BlastInput,
+    /// Jobs submitted before database selection existed ran against the asdb database
+    #[serde(default = "default_database")]
+    pub database: String,
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CompaRiPPsonResults {
@@ -23,19 +59,19 @@ pub struct CompaRiPPsonResults {
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CompaRiPPson {
-    pub input: BlastInput,
+    pub input: CompaRiPPsonInput,
     pub results: CompaRiPPsonResults,
 }
 
 impl CompaRiPPson {
     pub fn new(name: String, sequence: String) -> Self {
-        Self {
-            input: BlastInput { name, sequence },
-            results: CompaRiPPsonResults { hits: Vec::new() },
-        }
+        Self::from_input(CompaRiPPsonInput {
+            query: BlastInput { name, sequence },
+            database: default_database(),
+        })
     }
 
-    pub fn from_blast(input: BlastInput) -> Self {
+    pub fn from_input(input: CompaRiPPsonInput) -> Self {
         Self {
             input,
             results: CompaRiPPsonResults { hits: Vec::new() },
@@ -60,6 +96,11 @@ pub struct CompaRiPPsonResult {
     pub s_start: u64,
     pub s_end: u64,
     pub s_len: u64,
+    /// Not available for jobs run before these were recorded
+    pub evalue: Option<f64>,
+    pub bitscore: Option<f64>,
+    pub q_coverage: Option<f64>,
+    pub s_coverage: Option<f64>,
 }
 
 impl CompaRiPPsonResult {
@@ -98,48 +139,47 @@ impl CompaRiPPsonResult {
             s_start: value.s_start,
             s_end: value.s_end,
             s_len: value.s_len,
+            evalue: Some(value.evalue),
+            bitscore: Some(value.bitscore),
+            q_coverage: Some(value.q_coverage),
+            s_coverage: Some(value.s_coverage),
         })
     }
 }
 
 pub async fn run(mut data: CompaRiPPson, config: &super::RunConfig) -> Result<CompaRiPPson> {
+    let database = config.comparippson_config.get(&data.input.database)?;
+    let metadata = database.metadata().await?;
+    let db = config.tool_dbdir().join(&database.db);
     // The dbdir should always convert to a str
-    let dbdir = config.dbdir.to_str().unwrap();
-    let dbdir_mapping = format!("{}:/databases:ro", dbdir);
+    let db = db.to_str().unwrap();
 
     #[rustfmt::skip]
     let args = &[
-        "run", "--detach=false", "--rm", "--interactive",
-        "--volume", dbdir_mapping.as_str(),
-        "--name", config.name.as_str(),
-        "docker.io/antismash/asdb-jobs:latest",
-        "blastp",
         "-num_threads", "4",
-        "-db", COMPARIPPSON_DB_BASE,
-        "-outfmt", "6 qacc sacc nident qseq qstart qend qlen sseq sstart send slen",
+        "-db", db,
+        "-outfmt", "6 qacc sacc nident qseq qstart qend qlen sseq sstart send slen evalue bitscore",
     ];
 
-    let mut command = tokio::process::Command::new("podman");
-    command.args(args);
+    let mut command = config.tool_command("blastp", args);
     command.stdin(Stdio::piped());
     command.stdout(Stdio::piped());
 
     let mut child = command.spawn()?;
     let mut stdin = child.stdin.take().unwrap();
-    stdin.write(data.input.to_fasta().as_bytes()).await?;
+    stdin
+        .write_all(data.input.query.to_fasta().as_bytes())
+        .await?;
     drop(stdin);
 
-    let res = child.wait_with_output().await?;
-
-    let mut reader = BufReader::new(res.stdout.as_ref()).lines();
-
-    while let Some(line) = reader.next_line().await? {
-        let blast = BlastResult::from_str(&line)?;
-        data.results.hits.push(CompaRiPPsonResult::from_blast(
-            blast,
-            &config.comparippson_config.metadata,
-        )?);
-    }
+    let hits = &mut data.results.hits;
+    config
+        .stream_tool(child, |line| {
+            let blast = BlastResult::from_str(line)?;
+            hits.push(CompaRiPPsonResult::from_blast(blast, &metadata)?);
+            Ok(hits.len() < super::MAX_HITS)
+        })
+        .await?;
 
     Ok(data)
 }
@@ -172,12 +212,126 @@ pub struct Entry {
     pub end: Coordinate,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+/// Metadata as last read from disk, with the modification time of the file it was read from
+#[derive(Debug)]
+struct LoadedMetadata {
+    metadata: Arc<Metadata>,
+    modified: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompaRiPPsonDatabase {
+    /// Path of the blast database, relative to the database directory
+    pub db: PathBuf,
+    pub metadata_file: PathBuf,
+    loaded: Arc<RwLock<LoadedMetadata>>,
+}
+
+impl CompaRiPPsonDatabase {
+    async fn load(db: PathBuf, metadata_file: PathBuf) -> Result<Self> {
+        let modified = tokio::fs::metadata(&metadata_file).await?.modified()?;
+        let metadata = Metadata::from_json(&tokio::fs::read_to_string(&metadata_file).await?)?;
+        Ok(Self {
+            db,
+            metadata_file,
+            loaded: Arc::new(RwLock::new(LoadedMetadata {
+                metadata: Arc::new(metadata),
+                modified,
+            })),
+        })
+    }
+
+    /// Current metadata, re-read from disk if the file changed since it was last loaded.
+    /// If reloading fails, e.g. because the file is still being written, the previous metadata
+    /// is kept and the reload is retried on the next call.
+    pub async fn metadata(&self) -> Result<Arc<Metadata>> {
+        let modified = tokio::fs::metadata(&self.metadata_file)
+            .await
+            .and_then(|m| m.modified());
+        {
+            let loaded = self.loaded.read().await;
+            match &modified {
+                Ok(modified) if *modified != loaded.modified => {}
+                _ => return Ok(loaded.metadata.clone()),
+            }
+        }
+
+        let mut loaded = self.loaded.write().await;
+        let Ok(modified) = modified else {
+            return Ok(loaded.metadata.clone());
+        };
+        // Another job might have reloaded the metadata while we were waiting for the lock
+        if modified == loaded.modified {
+            return Ok(loaded.metadata.clone());
+        }
+
+        let metadata = match tokio::fs::read_to_string(&self.metadata_file)
+            .await
+            .map_err(Error::from)
+            .and_then(|data| Metadata::from_json(&data))
+        {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                eprintln!(
+                    "->> Failed to reload CompaRiPPson metadata {:?}: {e}",
+                    self.metadata_file
+                );
+                return Ok(loaded.metadata.clone());
+            }
+        };
+        eprintln!(
+            "->> Reloaded CompaRiPPson metadata for {} {} from {:?}",
+            metadata.name, metadata.version, self.metadata_file
+        );
+        *loaded = LoadedMetadata {
+            metadata: Arc::new(metadata),
+            modified,
+        };
+        Ok(loaded.metadata.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct CompaRiPPsonConfig {
-    pub metadata: Metadata,
+    pub databases: BTreeMap<String, CompaRiPPsonDatabase>,
     pub dbdir: PathBuf,
 }
 
+impl CompaRiPPsonConfig {
+    /// Load the metadata of all databases present in `dbdir`, the asdb database is required
+    pub async fn load(dbdir: &Path) -> Result<Self> {
+        let mut databases = BTreeMap::new();
+        for (name, subdir) in COMPARIPPSON_DATABASES {
+            let metadata_file = dbdir.join(subdir).join(COMPARIPPSON_METADATA_FILE);
+            if !metadata_file.is_file() {
+                if name == DEFAULT_DATABASE {
+                    return Err(Error::ConfigError(format!(
+                        "CompaRiPPson metadata {metadata_file:?} does not exist"
+                    )));
+                }
+                eprintln!("->> Skipping CompaRiPPson database {name}, {metadata_file:?} not found");
+                continue;
+            }
+            let db = Path::new(subdir).join(COMPARIPPSON_DB_FILE);
+            databases.insert(
+                name.to_string(),
+                CompaRiPPsonDatabase::load(db, metadata_file).await?,
+            );
+        }
+        Ok(Self {
+            databases,
+            dbdir: dbdir.to_owned(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Result<&CompaRiPPsonDatabase> {
+        validate_database(name)?;
+        self.databases.get(name).ok_or_else(|| {
+            Error::CompaRiPPsonError(format!("CompaRiPPson database {name} is not configured"))
+        })
+    }
+}
+
 /// Biopython coordinates can be fuzzy locations that start with < or >
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(transparent)]
@@ -258,4 +412,61 @@ mod tests {
             "EDF57_RS23885".to_string()
         );
     }
+
+    #[tokio::test]
+    async fn test_metadata_reload() {
+        let dir = std::env::temp_dir().join(format!("asdb-comparippson-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let metadata_file = dir.join(COMPARIPPSON_METADATA_FILE);
+        let write = |version: &str| {
+            let data = format!(
+                r#"{{"description_format": "", "fields": [], "id_format": "", "name": "test",
+                    "url": "", "version": "{version}", "entries": {{}}}}"#
+            );
+            std::fs::write(&metadata_file, data).unwrap();
+        };
+
+        write("1.0");
+        let database = CompaRiPPsonDatabase::load(dir.join("cores.fa"), metadata_file.clone())
+            .await
+            .unwrap();
+        assert_eq!(database.metadata().await.unwrap().version, "1.0");
+
+        // make sure the mtime changes even on file systems with coarse timestamps
+        let later = SystemTime::now() + std::time::Duration::from_secs(10);
+        write("2.0");
+        std::fs::File::options()
+            .write(true)
+            .open(&metadata_file)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(database.metadata().await.unwrap().version, "2.0");
+
+        // broken files keep the previous metadata around
+        std::fs::write(&metadata_file, "{").unwrap();
+        let version = database.metadata().await.unwrap().version.clone();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(version, "2.0");
+    }
+
+    #[test]
+    fn test_input_database() {
+        let tests = [
+            (r#"{"name": "a", "sequence": "MAGIC"}"#, "asdb"),
+            (
+                r#"{"name": "a", "sequence": "MAGIC", "database": "mibig"}"#,
+                "mibig",
+            ),
+        ];
+        for (input, expected) in tests {
+            let parsed: CompaRiPPsonInput = serde_json::from_str(input).unwrap();
+            assert_eq!(parsed.query.sequence, "MAGIC");
+            assert_eq!(parsed.database, expected, "{input}");
+        }
+
+        assert!(validate_database("asdb").is_ok());
+        assert!(validate_database("mibig").is_ok());
+        assert!(validate_database("uniprot").is_err());
+    }
 }
@@ -1,77 +1,271 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 
+use clap::ValueEnum;
+use futures::TryStreamExt;
 use git_version::git_version;
-use sqlx::PgPool;
-use tokio::time::{sleep, Duration, Instant};
+use sha2::{digest::Output, Digest, Sha256};
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, timeout, Duration, Instant};
 
+use crate::api::stream::Lines;
 use crate::models::{
     control::Control,
-    job::{JobEntry, JobStatus, JobType},
+    job::{JobEntry, JobKind, JobStatus, JobType},
 };
-use crate::Result;
+use crate::{Error, Result};
 
+pub mod assembly_download;
 pub mod blast;
+pub mod bundle;
 pub mod clusterblast;
 pub mod comparippson;
+pub mod paths;
 pub mod ping;
+pub mod sideload;
 pub mod stored_query;
+pub mod subscription;
+pub mod xlsx;
 
 const VERSION: &str = git_version!(cargo_prefix = "cargo:", fallback = "unknown");
+pub const DEFAULT_TIMEOUT: u64 = 3600;
+/// How often a job failing with a transient error is re-queued before it fails for good
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Delay before the first retry of a job, doubled for every further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+/// Longest delay between two attempts of a job
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+/// Upper limit of hits parsed from a single blast run, the tool is stopped once it is reached
+pub const MAX_HITS: usize = 10_000;
+pub const CONTAINER_IMAGE: &str = "docker.io/antismash/asdb-jobs:latest";
+/// Suffix of the file holding the SHA256 sum of a job's result file, in `sha256sum` format
+pub const CHECKSUM_SUFFIX: &str = ".sha256";
+/// Channel the database announces new jobs on, see the new job migration
+pub const NEW_JOB_CHANNEL: &str = "asdb_new_jobs";
+/// How often to look for pending jobs if new jobs aren't announced
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How often to look for pending jobs while listening for announcements, in case one is missed,
+/// and to notice scheduled stops
+const LISTEN_POLL_INTERVAL: Duration = Duration::from_secs(10);
 
 pub async fn dispatch(pool: PgPool, config: RunConfig) -> Result<()> {
     let mut control = Control::new(&pool, &config.name, "running", false, VERSION)
+        .with_jobtypes(&config.jobtypes)
         .commit()
         .await
         .expect("whoops");
+    let mut listener = match new_job_listener(&pool).await {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            eprintln!("->> Failed to listen for new jobs, polling instead: {e}");
+            None
+        }
+    };
     eprintln!("->> Starting loop");
+    let mut next_subscription_check = Instant::now();
     loop {
-        if let Some(mut job) = JobEntry::next_pending(&pool).await? {
-            job.runner = config.name.to_owned();
-            job.status = JobStatus::Running;
-            job.commit(&pool).await?;
+        if Instant::now() >= next_subscription_check {
+            if let Err(e) = subscription::schedule_due(&pool).await {
+                eprintln!("->> Failed to schedule subscriptions: {e}");
+            }
+            next_subscription_check = Instant::now() + subscription::CHECK_INTERVAL;
+        }
+
+        let claimed = JobEntry::claim_next_pending(&pool, &config.name, &config.jobtypes).await?;
+        let processed = claimed.is_some();
+        if let Some(mut job) = claimed {
+            control.current_job = Some(job.id.to_string());
+            control.heartbeat().await?;
             let start = Instant::now();
             job = run(job, &pool, &config).await?;
             let duration = start.elapsed();
             eprintln!("->> Processing job {} took {duration:?}", &job.id);
+            control.current_job = None;
         }
 
-        control.fetch().await?;
+        control.heartbeat().await?;
         if control.stop_scheduled {
             eprintln!("->> shutting down");
             return Ok(());
         }
 
-        sleep(Duration::from_secs(1)).await;
+        // More jobs might have been queued while this one ran
+        if !processed {
+            wait_for_new_job(&mut listener, next_subscription_check).await;
+        }
     }
 }
 
-async fn run(mut job: JobEntry, pool: &PgPool, config: &RunConfig) -> Result<JobEntry> {
-    match job.jobtype.clone() {
-        JobType::ClusterBlast(cb) => {
-            let completed = clusterblast::run(cb, config).await?;
-            job.jobtype = JobType::ClusterBlast(completed);
+async fn new_job_listener(pool: &PgPool) -> Result<PgListener> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(NEW_JOB_CHANNEL).await?;
+    Ok(listener)
+}
+
+/// Sleep until a new job is announced, the fallback poll is due or it's time to check the
+/// subscriptions. Without a listener, this is just the regular poll interval.
+async fn wait_for_new_job(listener: &mut Option<PgListener>, next_subscription_check: Instant) {
+    let Some(active) = listener else {
+        sleep(POLL_INTERVAL).await;
+        return;
+    };
+    let wait =
+        LISTEN_POLL_INTERVAL.min(next_subscription_check.saturating_duration_since(Instant::now()));
+    if let Ok(Err(e)) = timeout(wait, active.recv()).await {
+        eprintln!("->> Lost the new job announcements, polling instead: {e}");
+        *listener = None;
+    }
+}
+
+/// Validate the runner configuration without processing any jobs
+pub async fn check(pool: PgPool, config: RunConfig) -> Result<()> {
+    if !config.dbdir.is_dir() {
+        return Err(Error::ConfigError(format!(
+            "database directory {:?} does not exist",
+            config.dbdir
+        )));
+    }
+    eprintln!("->> Found database directory {:?}", config.dbdir);
+
+    if config.handles(JobKind::ClusterBlast) {
+        let clusterblast_db = config.dbdir.join(clusterblast::CLUSTERBLAST_DB);
+        if !clusterblast_db.is_file() {
+            return Err(Error::ConfigError(format!(
+                "ClusterBlast database {clusterblast_db:?} does not exist"
+            )));
         }
-        JobType::CompaRiPPson(cr) => {
-            let completed = comparippson::run(cr, config).await?;
-            job.jobtype = JobType::CompaRiPPson(completed);
+        eprintln!("->> Found ClusterBlast database {clusterblast_db:?}");
+    }
+
+    for (name, database) in &config.comparippson_config.databases {
+        let db = config.dbdir.join(&database.db);
+        if !db.is_file() {
+            return Err(Error::ConfigError(format!(
+                "CompaRiPPson database {db:?} does not exist"
+            )));
+        }
+        let metadata = database.metadata().await?;
+        eprintln!(
+            "->> Loaded CompaRiPPson {name} metadata for {} {} with {} entries",
+            metadata.name,
+            metadata.version,
+            metadata.entries.len()
+        );
+    }
+
+    let runs_tools = config.handles(JobKind::ClusterBlast) || config.handles(JobKind::CompaRiPPson);
+    if runs_tools {
+        for found in check_executor(config.executor).await? {
+            eprintln!("->> Found {found}");
         }
-        JobType::Ping(p) => {
-            let completed_p = ping::run(p).await?;
-            job.jobtype = JobType::Ping(completed_p);
+    }
+
+    Control::new(&pool, &config.name, "validated", false, VERSION)
+        .with_jobtypes(&config.jobtypes)
+        .commit()
+        .await?;
+    eprintln!("->> Registered {} as validated", config.name);
+    Ok(())
+}
+
+async fn run(mut job: JobEntry, pool: &PgPool, config: &RunConfig) -> Result<JobEntry> {
+    match run_jobtype(job.jobtype.clone(), pool, config).await {
+        Ok(completed) => {
+            job.jobtype = completed;
+            job.status = JobStatus::Done;
+            // Drop the error of an earlier failed attempt
+            job.error = None;
         }
-        JobType::StoredQuery(q) => {
-            let completed_q = stored_query::run(q, pool, config).await?;
-            job.jobtype = JobType::StoredQuery(completed_q);
+        Err(e) if e.is_transient() && job.retries < config.max_retries as i32 => {
+            job.retries += 1;
+            let delay = retry_delay(job.retries);
+            eprintln!(
+                "->> Job {} failed, retry {} in {delay:?}: {e}",
+                &job.id, job.retries
+            );
+            job.status = JobStatus::Pending;
+            job.error = Some(e.to_string());
+            job.retry_after = chrono::Duration::from_std(delay)
+                .ok()
+                .map(|delay| chrono::Utc::now() + delay);
+            job.commit(pool).await?;
+            return Ok(job);
+        }
+        Err(e) => {
+            eprintln!("->> Job {} failed: {e}", &job.id);
+            job.status = JobStatus::Error;
+            job.error = Some(e.to_string());
         }
     }
-    job.status = JobStatus::Done;
     job.commit(pool).await?;
+    subscription::notify(pool, &job).await;
     Ok(job)
 }
 
+/// Exponential backoff before the `retry`th attempt of a job, starting at 1
+pub fn retry_delay(retry: i32) -> Duration {
+    let doublings = retry.saturating_sub(1).clamp(0, 16) as u32;
+    RETRY_BASE_DELAY
+        .saturating_mul(2_u32.pow(doublings))
+        .min(MAX_RETRY_DELAY)
+}
+
+async fn run_jobtype(jobtype: JobType, pool: &PgPool, config: &RunConfig) -> Result<JobType> {
+    let completed = match jobtype {
+        JobType::AssemblyDownload(d) => {
+            JobType::AssemblyDownload(assembly_download::run(d, pool, config).await?)
+        }
+        JobType::ClusterBlast(cb) => JobType::ClusterBlast(clusterblast::run(cb, config).await?),
+        JobType::CompaRiPPson(cr) => JobType::CompaRiPPson(comparippson::run(cr, config).await?),
+        JobType::Ping(p) => JobType::Ping(ping::run(p).await?),
+        JobType::StoredQuery(q) => JobType::StoredQuery(stored_query::run(q, pool, config).await?),
+    };
+    Ok(completed)
+}
+
+/// Write a job's result file into its job directory, along with its checksum file
+pub async fn write_result(jobdir: &Path, filename: &str, data: &[u8]) -> Result<()> {
+    fs::write(jobdir.join(paths::component(filename)?), data).await?;
+    write_checksum(jobdir, filename, Sha256::digest(data)).await
+}
+
+/// Like [`write_result`], but write the lines to the file as they are generated
+pub async fn write_stream(jobdir: &Path, filename: &str, mut lines: Lines) -> Result<()> {
+    let path = jobdir.join(paths::component(filename)?);
+    let mut file = BufWriter::new(fs::File::create(path).await?);
+    let mut hasher = Sha256::new();
+    while let Some(line) = lines.try_next().await? {
+        hasher.update(line.as_bytes());
+        file.write_all(line.as_bytes()).await?;
+    }
+    file.flush().await?;
+    write_checksum(jobdir, filename, hasher.finalize()).await
+}
+
+async fn write_checksum(jobdir: &Path, filename: &str, digest: Output<Sha256>) -> Result<()> {
+    let checksum = format!("{digest:x}  {filename}\n");
+    fs::write(checksum_path(&jobdir.join(filename)), checksum).await?;
+    Ok(())
+}
+
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(CHECKSUM_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// The SHA256 sum of a result file, if its checksum file exists
+pub async fn read_checksum(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(checksum_path(path)).await.ok()?;
+    content.split_whitespace().next().map(|s| s.to_owned())
+}
+
 #[derive(Debug, Clone)]
 pub struct RunConfig {
     pub comparippson_config: comparippson::CompaRiPPsonConfig,
@@ -80,4 +274,269 @@ pub struct RunConfig {
     pub outdir: Option<PathBuf>,
     pub name: String,
     pub urlroot: String,
+    pub executor: Executor,
+    pub timeout: Duration,
+    /// How often jobs failing with transient errors are re-queued
+    pub max_retries: u32,
+    /// Job types the runner picks up, all of them if empty
+    pub jobtypes: Vec<JobKind>,
+}
+
+impl RunConfig {
+    /// Whether the runner picks up jobs of a type
+    pub fn handles(&self, kind: JobKind) -> bool {
+        self.jobtypes.is_empty() || self.jobtypes.contains(&kind)
+    }
+
+    /// Database directory as seen by the blast tools
+    pub fn tool_dbdir(&self) -> PathBuf {
+        match self.executor {
+            Executor::Container => PathBuf::from("/databases"),
+            Executor::Native => self.dbdir.clone(),
+        }
+    }
+
+    /// Set up a command running one of the blast tools with the configured executor
+    pub fn tool_command(&self, program: &str, args: &[&str]) -> Command {
+        match self.executor {
+            Executor::Container => {
+                // The dbdir should always convert to a str
+                let dbdir_mapping = format!("{}:/databases:ro", self.dbdir.to_str().unwrap());
+                #[rustfmt::skip]
+                let container_args = [
+                    "run", "--detach=false", "--rm", "--interactive",
+                    "--volume", dbdir_mapping.as_str(),
+                    "--name", self.name.as_str(),
+                    CONTAINER_IMAGE,
+                    program,
+                ];
+                let mut command = Command::new("podman");
+                command.args(container_args);
+                command.args(args);
+                command
+            }
+            Executor::Native => {
+                let mut command = Command::new(program);
+                command.args(args);
+                // Make sure a timed out tool doesn't linger
+                command.kill_on_drop(true);
+                command
+            }
+        }
+    }
+
+    /// Feed the output of a blast tool to `handle_line` line by line while the tool runs.
+    /// The tool is stopped early once `handle_line` returns `false` or fails,
+    /// and killed if it runs longer than the configured timeout.
+    pub async fn stream_tool<F>(&self, mut child: Child, mut handle_line: F) -> Result<()>
+    where
+        F: FnMut(&str) -> Result<bool>,
+    {
+        // stdout is always piped by the callers
+        let stdout = child.stdout.take().unwrap();
+        let mut lines = BufReader::new(stdout).lines();
+
+        let reading = async {
+            while let Some(line) = lines.next_line().await? {
+                if !handle_line(&line)? {
+                    return Ok(false);
+                }
+            }
+            child.wait().await?;
+            Ok::<bool, Error>(true)
+        };
+
+        match timeout(self.timeout, reading).await {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => self.stop_tool(&mut child).await,
+            Ok(Err(e)) => {
+                self.stop_tool(&mut child).await?;
+                Err(e)
+            }
+            Err(_) => {
+                self.stop_tool(&mut child).await?;
+                Err(Error::TimeoutError(self.timeout))
+            }
+        }
+    }
+
+    async fn stop_tool(&self, child: &mut Child) -> Result<()> {
+        if self.executor == Executor::Container {
+            // Killing the podman client doesn't stop the container itself
+            Command::new("podman")
+                .args(["rm", "-f", self.name.as_str()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await?;
+        }
+        child.kill().await?;
+        Ok(())
+    }
+}
+
+/// How the runner executes the blast tools
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Executor {
+    /// Run the tools inside the asdb-jobs container via podman
+    #[default]
+    Container,
+    /// Run the tools directly from PATH
+    Native,
+}
+
+/// Binaries needed in PATH when running with the native executor
+const NATIVE_TOOLS: [&str; 2] = ["diamond", "blastp"];
+
+/// Check that the executor can run the blast tools, returning what was found
+pub async fn check_executor(executor: Executor) -> Result<Vec<String>> {
+    match executor {
+        Executor::Container => {
+            let status = Command::new("podman")
+                .args(["image", "exists", CONTAINER_IMAGE])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await
+                .map_err(|e| Error::ConfigError(format!("podman is not available: {e}")))?;
+            if !status.success() {
+                return Err(Error::ConfigError(format!(
+                    "container image {CONTAINER_IMAGE} is not available"
+                )));
+            }
+            Ok(vec![format!("container image {CONTAINER_IMAGE}")])
+        }
+        Executor::Native => NATIVE_TOOLS
+            .iter()
+            .map(|tool| match find_in_path(tool) {
+                Some(path) => Ok(format!("{tool} at {path:?}")),
+                None => Err(Error::ConfigError(format!("{tool} not found in PATH"))),
+            })
+            .collect(),
+    }
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_retry_delay() {
+        let tests = [
+            (1, RETRY_BASE_DELAY),
+            (2, RETRY_BASE_DELAY * 2),
+            (3, RETRY_BASE_DELAY * 4),
+            (0, RETRY_BASE_DELAY),
+            (10, MAX_RETRY_DELAY),
+            (i32::MAX, MAX_RETRY_DELAY),
+        ];
+        for (retry, expected) in tests {
+            assert_eq!(retry_delay(retry), expected, "{retry}");
+        }
+    }
+
+    fn native_config() -> RunConfig {
+        RunConfig {
+            comparippson_config: comparippson::CompaRiPPsonConfig {
+                databases: BTreeMap::new(),
+                dbdir: PathBuf::new(),
+            },
+            dbdir: PathBuf::new(),
+            jobdir: PathBuf::new(),
+            outdir: None,
+            name: "test".to_string(),
+            urlroot: String::new(),
+            executor: Executor::Native,
+            timeout: Duration::from_secs(10),
+            max_retries: DEFAULT_MAX_RETRIES,
+            jobtypes: Vec::new(),
+        }
+    }
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_tool() {
+        let config = native_config();
+
+        let mut lines = Vec::new();
+        config
+            .stream_tool(spawn("printf 'a\\nb\\nc\\n'"), |line| {
+                lines.push(line.to_string());
+                Ok(true)
+            })
+            .await
+            .unwrap();
+        assert_eq!(lines, vec!["a", "b", "c"]);
+
+        // never finishes on its own, so this only returns if the tool gets stopped
+        let mut count = 0;
+        config
+            .stream_tool(spawn("yes hit"), |_| {
+                count += 1;
+                Ok(count < 5)
+            })
+            .await
+            .unwrap();
+        assert_eq!(count, 5);
+
+        let res = config
+            .stream_tool(spawn("yes hit"), |_| Err(Error::ParserError))
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_result() {
+        let dir = std::env::temp_dir().join(format!("asdb-result-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        write_result(&dir, "bob.csv", b"LOCUS").await.unwrap();
+
+        let checksum = fs::read_to_string(dir.join("bob.csv.sha256"))
+            .await
+            .unwrap();
+        let sha256 = read_checksum(&dir.join("bob.csv")).await;
+        let missing = read_checksum(&dir.join("alice.csv")).await;
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        let expected = "f510430eb3eb8aa324d7d050e826bffb31f8816c36dd57edea93d6fa6d1cec02";
+        assert_eq!(checksum, format!("{expected}  bob.csv\n"));
+        assert_eq!(sha256.as_deref(), Some(expected));
+        assert_eq!(missing, None);
+    }
+
+    #[tokio::test]
+    async fn test_write_stream() {
+        let dir = std::env::temp_dir().join(format!("asdb-stream-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let lines = futures::stream::iter(["LO".to_string(), "CUS".to_string()].map(Ok));
+        write_stream(&dir, "bob.csv", Box::pin(lines))
+            .await
+            .unwrap();
+        write_result(&dir, "alice.csv", b"LOCUS").await.unwrap();
+
+        let content = fs::read(dir.join("bob.csv")).await.unwrap();
+        let sha256 = read_checksum(&dir.join("bob.csv")).await;
+        let expected = read_checksum(&dir.join("alice.csv")).await;
+        fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(content, b"LOCUS");
+        assert!(sha256.is_some());
+        assert_eq!(sha256, expected);
+    }
 }
@@ -0,0 +1,110 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+use crate::{Error, Result};
+
+/// Check that a name taken from the database or a request is a single path component,
+/// so joining it to a directory can't point anywhere but into that directory
+pub fn component(name: &str) -> Result<&str> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        return Err(Error::InvalidRequest(format!(
+            "Invalid path component {name:?}"
+        )));
+    }
+    Ok(name)
+}
+
+/// Resolve symlinks and check that `path` is inside `base`, returning the resolved path.
+/// Paths that don't exist yet are resolved through their closest existing ancestor.
+pub async fn ensure_within(base: &Path, path: &Path) -> Result<PathBuf> {
+    let base = fs::canonicalize(base).await?;
+
+    let mut existing = path;
+    let mut missing = Vec::new();
+    let mut resolved = loop {
+        match fs::canonicalize(existing).await {
+            Ok(resolved) => break resolved,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                // file_name() is None for paths ending in "..", which can't be resolved
+                // without the parts that are missing
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(e.into());
+                };
+                missing.push(name);
+                existing = parent;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    resolved.extend(missing.iter().rev());
+
+    if !resolved.starts_with(&base) {
+        return Err(Error::InvalidRequest(format!(
+            "{path:?} is outside of {base:?}"
+        )));
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component() {
+        let valid = [
+            "GCF_000203835.1",
+            "NC_003888.3.region001.gbk",
+            "..hidden",
+            "a..b",
+        ];
+        for name in valid {
+            assert_eq!(component(name).unwrap(), name);
+        }
+
+        let invalid = ["", ".", "..", "../etc", "a/b", "/etc", "a\\b", "a\0b"];
+        for name in invalid {
+            assert!(component(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ensure_within() {
+        let dir = std::env::temp_dir().join(format!("asdb-paths-{}", std::process::id()));
+        let base = dir.join("outdir");
+        fs::create_dir_all(base.join("GCF_1")).await.unwrap();
+        fs::create_dir_all(dir.join("elsewhere")).await.unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("elsewhere"), base.join("link")).unwrap();
+
+        let inside = [
+            base.join("GCF_1"),
+            base.join("GCF_1").join("missing.gbk"),
+            base.join("GCF_2").join("missing.gbk"),
+            base.join("GCF_1").join("..").join("GCF_1"),
+        ];
+        for path in inside {
+            let resolved = ensure_within(&base, &path).await;
+            assert!(resolved.is_ok(), "{path:?}");
+        }
+
+        let mut outside = vec![
+            base.join("..").join("elsewhere"),
+            base.join("..").join("missing"),
+            base.join("missing").join(".."),
+        ];
+        if cfg!(unix) {
+            outside.push(base.join("link").join("file.gbk"));
+        }
+        for path in outside {
+            assert!(ensure_within(&base, &path).await.is_err(), "{path:?}");
+        }
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
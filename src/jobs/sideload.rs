@@ -0,0 +1,201 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::api::region::Region;
+use crate::Result;
+
+/// Top level of an antiSMASH sideloaded annotation file, as read by `antismash --sideload`
+#[derive(Debug, Serialize)]
+pub struct Sideload {
+    pub tool: SideloadTool,
+    pub records: Vec<SideloadRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SideloadTool {
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub configuration: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SideloadRecord {
+    /// Record ID including the version, has to match the record antiSMASH is run on
+    pub name: String,
+    pub subregions: Vec<SideloadSubregion>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SideloadSubregion {
+    pub start: i32,
+    pub end: i32,
+    pub label: String,
+    pub details: BTreeMap<String, Vec<String>>,
+}
+
+impl From<&Region> for SideloadSubregion {
+    fn from(region: &Region) -> Self {
+        let mut details = BTreeMap::new();
+        let types = if region.types.is_empty() {
+            vec![region.term.to_owned()]
+        } else {
+            region.types.to_owned()
+        };
+        details.insert("bgc_types".to_string(), types);
+        details.insert(
+            "database_id".to_string(),
+            vec![region.region_id.to_string()],
+        );
+
+        let organism = [&region.genus, &region.species, &region.strain]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        if !organism.is_empty() {
+            details.insert("organism".to_string(), vec![organism]);
+        }
+        if let Some(assembly_id) = &region.assembly_id {
+            details.insert("assembly_id".to_string(), vec![assembly_id.to_owned()]);
+        }
+        if let Some(acc) = &region.best_mibig_hit_acc {
+            let mut hit = vec![acc.to_owned()];
+            if let Some(description) = &region.best_mibig_hit_description {
+                hit.push(description.to_owned());
+            }
+            if let Some(similarity) = region.best_mibig_hit_similarity {
+                hit.push(format!("{similarity}% similarity"));
+            }
+            details.insert("most_similar_known_cluster".to_string(), hit);
+        }
+        details.insert(
+            "url".to_string(),
+            vec![format!(
+                "https://antismash-db.secondarymetabolites.org/area?record={}&start={}&end={}",
+                record_name(region),
+                region.start_pos,
+                region.end_pos
+            )],
+        );
+
+        Self {
+            start: region.start_pos,
+            end: region.end_pos,
+            label: format!("antiSMASH-DB {} region", region.term),
+            details,
+        }
+    }
+}
+
+fn record_name(region: &Region) -> String {
+    format!(
+        "{}.{}",
+        region.accession.as_deref().unwrap_or_default(),
+        region.version.unwrap_or(1)
+    )
+}
+
+/// Turn the matched regions into sideloaded subregions, grouped by record
+pub fn to_sideload(regions: &[Region], job_id: &str) -> Result<Vec<u8>> {
+    let mut records: BTreeMap<String, Vec<SideloadSubregion>> = BTreeMap::new();
+    for region in regions {
+        records
+            .entry(record_name(region))
+            .or_default()
+            .push(region.into());
+    }
+
+    let mut configuration = BTreeMap::new();
+    configuration.insert("job_id".to_string(), vec![job_id.to_owned()]);
+
+    let sideload = Sideload {
+        tool: SideloadTool {
+            name: "antiSMASH-DB".to_string(),
+            version: super::VERSION.to_string(),
+            description: "Regions matching an antiSMASH database search".to_string(),
+            configuration,
+        },
+        records: records
+            .into_iter()
+            .map(|(name, subregions)| SideloadRecord { name, subregions })
+            .collect(),
+    };
+
+    Ok(serde_json::to_vec_pretty(&sideload)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::models::location::Strand;
+
+    fn region(region_id: i32, accession: &str, start_pos: i32) -> Region {
+        Region {
+            region_id,
+            record_number: 1,
+            region_number: region_id,
+            start_pos,
+            end_pos: start_pos + 1000,
+            contig_edge: false,
+            strand: Strand::Unstranded,
+            accession: Some(accession.to_string()),
+            assembly_id: Some("GCF_000203835.1".to_string()),
+            version: Some(3),
+            record_length: Some(8_667_507),
+            record_definition: None,
+            genus: Some("Streptomyces".to_string()),
+            species: Some("coelicolor".to_string()),
+            strain: None,
+            term: "NRPS T1PKS hybrid".to_string(),
+            description: "".to_string(),
+            category: "".to_string(),
+            best_mibig_hit_similarity: Some(42),
+            best_mibig_hit_description: Some("actinorhodin".to_string()),
+            best_mibig_hit_acc: Some("BGC0000194".to_string()),
+            types: vec!["NRPS".to_string(), "T1PKS".to_string()],
+            categories: Vec::new(),
+            descriptions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_to_sideload() {
+        let regions = vec![
+            region(1, "NC_003888", 100),
+            region(2, "NC_003903", 50),
+            region(3, "NC_003888", 9000),
+        ];
+        let data = to_sideload(&regions, "bob").unwrap();
+        let sideload: Value = serde_json::from_slice(&data).unwrap();
+
+        assert_eq!(sideload["tool"]["name"], "antiSMASH-DB");
+        assert_eq!(sideload["tool"]["configuration"]["job_id"][0], "bob");
+
+        let records = sideload["records"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["name"], "NC_003888.3");
+        assert_eq!(records[0]["subregions"].as_array().unwrap().len(), 2);
+        assert_eq!(records[1]["name"], "NC_003903.3");
+
+        let subregion = &records[0]["subregions"][0];
+        assert_eq!(subregion["start"], 100);
+        assert_eq!(subregion["end"], 1100);
+        assert_eq!(subregion["details"]["bgc_types"][1], "T1PKS");
+        assert_eq!(
+            subregion["details"]["organism"][0],
+            "Streptomyces coelicolor"
+        );
+        assert_eq!(
+            subregion["details"]["most_similar_known_cluster"][2],
+            "42% similarity"
+        );
+    }
+}
@@ -1,10 +1,13 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use std::collections::HashSet;
 use std::io::{Cursor, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use tokio::fs;
 use tokio::io::{self, AsyncReadExt};
@@ -13,28 +16,185 @@ use zip::{write::FileOptions, CompressionMethod, ZipWriter};
 use crate::api::cds;
 use crate::api::domains;
 use crate::api::region;
+use crate::api::stream::{self, Lines};
+use crate::api::version::DatabaseVersion;
+use crate::models::job::JobId;
 use crate::query::{ReturnType, SearchType};
 use crate::{Error, Result};
 
+use super::bundle;
+use super::paths;
+use super::sideload::to_sideload;
+use super::xlsx::to_xlsx;
 use super::RunConfig;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct StoredQueryInput {
-    pub job_id: String,
+    pub job_id: JobId,
     pub ids: Vec<i32>,
     pub search_type: SearchType,
     pub return_type: ReturnType,
+    #[serde(default)]
+    pub csv_style: region::CsvStyle,
+    /// Only export the biosynthetic core genes in protein FASTA exports
+    #[serde(default)]
+    pub core_only: bool,
+    /// Extend the regions by this many bp on each side in DNA FASTA exports
+    #[serde(default)]
+    pub flank: u32,
+    /// Set for jobs started by a subscription
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_id: Option<String>,
+    /// The query the IDs were found with, recorded in exported archives
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<serde_json::Value>,
+}
+
+pub const DEFAULT_MAX_IDS: usize = 10_000;
+/// How many offending IDs are listed when rejecting a submission
+const MAX_LISTED_IDS: usize = 20;
+
+/// Limits for the ID lists of stored queries submitted through the API
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IdLimits {
+    pub max_ids: usize,
+}
+
+impl Default for IdLimits {
+    fn default() -> Self {
+        Self {
+            max_ids: DEFAULT_MAX_IDS,
+        }
+    }
+}
+
+impl StoredQueryInput {
+    /// Check the IDs are a non-empty set of positive IDs within the limit, listing the
+    /// offending entries otherwise
+    pub fn check_ids(&self, limits: &IdLimits) -> Result<()> {
+        if self.ids.is_empty() {
+            return Err(Error::InvalidRequest("No IDs given".to_string()));
+        }
+        if self.ids.len() > limits.max_ids {
+            return Err(Error::InvalidRequest(format!(
+                "Cannot export more than {} entries at once, {} requested",
+                limits.max_ids,
+                self.ids.len()
+            )));
+        }
+
+        let mut problems = Vec::new();
+        let non_positive: Vec<i32> = self.ids.iter().copied().filter(|id| *id < 1).collect();
+        if !non_positive.is_empty() {
+            problems.push(format!("non-positive IDs {}", list_ids(&non_positive)));
+        }
+        let mut sorted = self.ids.clone();
+        sorted.sort_unstable();
+        let mut duplicates: Vec<i32> = sorted
+            .windows(2)
+            .filter(|pair| pair[0] == pair[1])
+            .map(|pair| pair[0])
+            .collect();
+        duplicates.dedup();
+        if !duplicates.is_empty() {
+            problems.push(format!("duplicate IDs {}", list_ids(&duplicates)));
+        }
+        if !problems.is_empty() {
+            return Err(Error::InvalidRequest(format!(
+                "Invalid IDs: {}",
+                problems.join("; ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check all IDs exist for the search type, so no job is queued for entries that
+    /// aren't there
+    pub async fn check_ids_exist(&self, pool: &PgPool) -> Result<()> {
+        let known: HashSet<i32> = match self.search_type {
+            SearchType::Region => {
+                sqlx::query_scalar!(
+                    "SELECT region_id FROM antismash.regions WHERE region_id = ANY($1)",
+                    &self.ids,
+                )
+                .fetch_all(pool)
+                .await?
+            }
+            SearchType::Gene => {
+                sqlx::query_scalar!(
+                    "SELECT cds_id FROM antismash.cdss WHERE cds_id = ANY($1)",
+                    &self.ids,
+                )
+                .fetch_all(pool)
+                .await?
+            }
+            SearchType::Domain => {
+                sqlx::query_scalar!(
+                    "SELECT as_domain_id FROM antismash.as_domains WHERE as_domain_id = ANY($1)",
+                    &self.ids,
+                )
+                .fetch_all(pool)
+                .await?
+            }
+        }
+        .into_iter()
+        .collect();
+        let mut unknown: Vec<i32> = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| !known.contains(id))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort_unstable();
+            return Err(Error::InvalidRequest(format!(
+                "Unknown {} IDs: {}",
+                self.search_type.as_ref(),
+                list_ids(&unknown)
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Comma-separated IDs, shortened if there are many
+fn list_ids(ids: &[i32]) -> String {
+    let mut listed: Vec<String> = ids
+        .iter()
+        .take(MAX_LISTED_IDS)
+        .map(i32::to_string)
+        .collect();
+    if ids.len() > MAX_LISTED_IDS {
+        listed.push(format!("and {} more", ids.len() - MAX_LISTED_IDS));
+    }
+    listed.join(", ")
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct StoredQuery {
     pub input: StoredQueryInput,
     pub filename: Option<String>,
+    /// Regions whose GenBank files weren't found when packing the archive
+    #[serde(default)]
+    pub missing: Vec<ManifestFile>,
+}
+
+/// Results as stored with the job, jobs from before missing files were recorded only
+/// stored the filename
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredResults {
+    Filename(Option<String>),
+    Results {
+        filename: Option<String>,
+        #[serde(default)]
+        missing: Vec<ManifestFile>,
+    },
 }
 
 impl StoredQuery {
     pub fn new(
-        job_id: String,
+        job_id: JobId,
         ids: &[i32],
         search_type: SearchType,
         return_type: ReturnType,
@@ -45,63 +205,115 @@ impl StoredQuery {
                 ids: Vec::from(ids),
                 search_type,
                 return_type,
+                csv_style: region::CsvStyle::default(),
+                core_only: false,
+                flank: 0,
+                subscription_id: None,
+                query: None,
             },
             filename: None,
+            missing: Vec::new(),
+        }
+    }
+
+    /// Rebuild a stored query from the job's input and stored results
+    pub fn from_results(input: StoredQueryInput, results: serde_json::Value) -> Self {
+        let (filename, missing) = match serde_json::from_value(results) {
+            Ok(StoredResults::Filename(filename)) => (filename, Vec::new()),
+            Ok(StoredResults::Results { filename, missing }) => (filename, missing),
+            Err(_) => (None, Vec::new()),
+        };
+        Self {
+            input,
+            filename,
+            missing,
         }
     }
+
+    /// Results as stored with the job and shown in the job info
+    pub fn results(&self) -> serde_json::Value {
+        serde_json::json!({
+            "filename": self.filename,
+            "missing_count": self.missing.len(),
+            "missing": self.missing,
+        })
+    }
 }
 
 pub async fn run(mut query: StoredQuery, pool: &PgPool, config: &RunConfig) -> Result<StoredQuery> {
-    let job_id = query.input.job_id.as_str();
-    let jobdir = config.jobdir.join(job_id);
+    let job_id = query.input.job_id;
+    let jobdir = job_id.dir(&config.jobdir);
     let urlroot = &config.urlroot;
     fs::create_dir_all(&jobdir).await?;
+    let jobdir = paths::ensure_within(&config.jobdir, &jobdir).await?;
 
-    let (filename, data) = match query.input.search_type {
+    let (filename, output) = match query.input.search_type {
         SearchType::Region => run_region(&query, pool, config).await?,
-        SearchType::Gene => run_cds(&query, pool).await?,
-        SearchType::Domain => run_domain(&query, pool).await?,
+        SearchType::Gene => run_cds(&query, pool, config).await?,
+        SearchType::Domain => run_domain(&query, pool, config).await?,
     };
 
-    fs::write(jobdir.join(&filename), &data).await?;
+    match output {
+        Output::Data(data) => super::write_result(&jobdir, &filename, &data).await?,
+        Output::Lines(lines) => super::write_stream(&jobdir, &filename, lines).await?,
+        Output::Archive(data, missing) => {
+            super::write_result(&jobdir, &filename, &data).await?;
+            query.missing = missing;
+        }
+    }
 
     query.filename = Some(format!("/{urlroot}/{job_id}/{filename}"));
     Ok(query)
 }
 
+/// Content of a result file, text exports are written as they are generated
+enum Output {
+    Data(Vec<u8>),
+    Lines(Lines),
+    /// Zip archive of result files, with the files that were missing
+    Archive(Vec<u8>, Vec<ManifestFile>),
+}
+
 async fn run_region(
     query: &StoredQuery,
     pool: &PgPool,
     config: &RunConfig,
-) -> Result<(String, Vec<u8>)> {
+) -> Result<(String, Output)> {
     let filename: String;
-    let data = match query.input.return_type {
+    let output = match query.input.return_type {
         ReturnType::Json => {
             filename = format!("{}.json", &query.input.job_id);
             let regions = region::ids_to_regions(pool, &query.input.ids).await?;
-            serde_json::to_vec(&regions)?
+            Output::Data(serde_json::to_vec(&regions)?)
         }
         ReturnType::Csv => {
             filename = format!("{}.csv", &query.input.job_id);
-            let regions = region::ids_to_regions(pool, &query.input.ids)
-                .await?
-                .into_iter()
-                .map(|r| r.to_csv())
-                .collect::<Vec<String>>()
-                .join("\n");
-            Vec::from(format!("{}\n{regions}", region::Region::csv_header()))
+            let lines = stream::region_csv(pool, &query.input.ids, query.input.csv_style).await?;
+            Output::Lines(lines)
+        }
+        ReturnType::Xlsx => {
+            filename = format!("{}.xlsx", &query.input.job_id);
+            let regions = region::ids_to_regions(pool, &query.input.ids).await?;
+            Output::Data(to_xlsx("regions", &regions)?)
+        }
+        ReturnType::Sqlite => {
+            filename = format!("{}.sqlite", &query.input.job_id);
+            Output::Data(sqlite_bundle(query, pool, config).await?)
+        }
+        ReturnType::Sideload => {
+            filename = format!("{}_sideload.json", &query.input.job_id);
+            let regions = region::ids_to_regions(pool, &query.input.ids).await?;
+            Output::Data(to_sideload(&regions, &query.input.job_id.to_string())?)
         }
         ReturnType::Fasta => {
             filename = format!("{}.fa", &query.input.job_id);
-            let sequences = region::ids_to_fasta(pool, &query.input.ids)
-                .await?
-                .join("\n");
-            Vec::from(sequences)
+            let lines = stream::region_fasta(pool, &query.input.ids, query.input.flank);
+            Output::Lines(lines)
         }
         ReturnType::Fastaa => {
-            return Err(Error::InvalidRequest(
-                "Cannot request region in protein fasta format".to_string(),
-            ))
+            filename = format!("{}_proteins.fa", &query.input.job_id);
+            let lines = stream::region_faa(pool, &query.input.ids, query.input.core_only);
+            Output::Lines(lines)
         }
         ReturnType::Genbank => {
             let Some(outdir) = &config.outdir else {
@@ -110,9 +322,17 @@ async fn run_region(
                 ));
             };
 
+            // The region GenBank files are the antiSMASH output as is, they don't contain
+            // any sequence outside of the region
+            if query.input.flank > 0 {
+                return Err(Error::InvalidRequest(
+                    "Flanking sequences are only available for FASTA exports".to_string(),
+                ));
+            }
+
             filename = format!("{}.zip", &query.input.job_id);
             let regions = region::ids_to_regions(pool, &query.input.ids).await?;
-            let mut gbk_files: Vec<PathBuf> = Vec::with_capacity(regions.len());
+            let mut gbk_files: Vec<(PathBuf, i32)> = Vec::with_capacity(regions.len());
             for region in &regions {
                 let Some(assembly_id) = &region.assembly_id else {
                     continue;
@@ -123,29 +343,173 @@ async fn run_region(
                 let Some(version) = &region.version else {
                     continue;
                 };
-                let number = region.region_number;
-                let mut file_path = outdir.to_owned();
-                file_path.push(assembly_id);
-                file_path.push(format!("{accession}.{version}.region{number:03}.gbk",));
-                gbk_files.push(file_path);
+                let file_path = region_gbk_path(
+                    outdir,
+                    assembly_id,
+                    accession,
+                    *version,
+                    region.region_number,
+                )?;
+                gbk_files.push((file_path, region.region_id));
             }
 
-            zip_files(&gbk_files).await?
+            let mut manifest = Manifest::new(&query.input, &config.name);
+            manifest.database = Some(DatabaseVersion::fetch(pool).await?);
+            let (data, missing) = zip_files(outdir, &gbk_files, manifest).await?;
+            Output::Archive(data, missing)
         }
     };
-    Ok((filename, data))
+    Ok((filename, output))
+}
+
+/// Location of a region's GenBank file in the antiSMASH output directory, the IDs come
+/// from the database but still must not point outside of it
+pub fn region_gbk_path(
+    outdir: &Path,
+    assembly_id: &str,
+    accession: &str,
+    version: i32,
+    number: i32,
+) -> Result<PathBuf> {
+    let filename = format!(
+        "{}.{version}.region{number:03}.gbk",
+        paths::component(accession)?
+    );
+    Ok(outdir.join(paths::component(assembly_id)?).join(filename))
+}
+
+/// Machine-readable description of the contents of a multi-file archive
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub job_id: JobId,
+    pub search_type: SearchType,
+    pub return_type: ReturnType,
+    pub created: DateTime<Utc>,
+    pub runner: String,
+    pub version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<DatabaseVersion>,
+    pub files: Vec<ManifestFile>,
+    pub missing: Vec<ManifestFile>,
 }
 
-async fn zip_files(gbk_files: &[PathBuf]) -> Result<Vec<u8>> {
+impl Manifest {
+    pub fn new(input: &StoredQueryInput, runner: &str) -> Self {
+        Self {
+            job_id: input.job_id,
+            search_type: input.search_type.clone(),
+            return_type: input.return_type.clone(),
+            created: Utc::now(),
+            runner: runner.to_owned(),
+            version: super::VERSION,
+            query: input.query.to_owned(),
+            database: None,
+            files: Vec::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    /// The manifest in every form added to archives: the JSON manifest, a tab-separated
+    /// listing of the included files and, if any files are missing, a list of those
+    pub fn archive_entries(&self) -> Result<Vec<(&'static str, Vec<u8>)>> {
+        let mut entries = vec![
+            (MANIFEST_NAME, serde_json::to_vec_pretty(self)?),
+            (MANIFEST_TSV_NAME, self.to_tsv().into_bytes()),
+        ];
+        if !self.missing.is_empty() {
+            entries.push((MISSING_NAME, self.missing_text().into_bytes()));
+        }
+        Ok(entries)
+    }
+
+    /// Tab-separated listing of the included files, for checking them with standard tools
+    fn to_tsv(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(database) = &self.database {
+            lines.push(database.csv_comment());
+        }
+        lines.push(format!("# job: {}", self.job_id));
+        if let Some(query) = &self.query {
+            lines.push(format!("# query: {query}"));
+        }
+        lines.push(ManifestFile::TSV_HEADER.to_string());
+        lines.extend(self.files.iter().map(ManifestFile::tsv_row));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Human-readable list of the files missing from the archive
+    fn missing_text(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "{} GenBank files could not be found and are not included in this archive:",
+                self.missing.len()
+            ),
+            String::new(),
+            ManifestFile::TSV_HEADER.to_string(),
+        ];
+        lines.extend(self.missing.iter().map(ManifestFile::tsv_row));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ManifestFile {
+    pub filename: String,
+    pub region_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl ManifestFile {
+    const TSV_HEADER: &'static str = "filename\tregion_id\tsize\tsha256";
+
+    /// The file as a row of the tab-separated listings, missing files have no size and checksum
+    fn tsv_row(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.filename,
+            self.region_id,
+            self.size.map(|size| size.to_string()).unwrap_or_default(),
+            self.sha256.as_deref().unwrap_or_default()
+        )
+    }
+}
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+pub const MANIFEST_TSV_NAME: &str = "MANIFEST.tsv";
+/// Only added to archives with missing files, so they are noticed without a manifest parser
+pub const MISSING_NAME: &str = "MISSING.txt";
+
+/// Pack the GenBank files into a zip archive with the manifest, returning the archive and
+/// the files missing from the output directory. Missing files are listed in the manifest
+/// and in a MISSING.txt, files resolving outside of the output directory fail the job.
+pub async fn zip_files(
+    outdir: &Path,
+    gbk_files: &[(PathBuf, i32)],
+    mut manifest: Manifest,
+) -> Result<(Vec<u8>, Vec<ManifestFile>)> {
     let mut buffer = Cursor::new(Vec::new());
     {
         let mut zip = ZipWriter::new(&mut buffer);
         let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
 
-        for file_path in gbk_files {
+        for (file_path, region_id) in gbk_files {
             let name = get_filename(file_path)?;
-            let Ok(file) = fs::File::open(file_path).await else {
+            let file_path = paths::ensure_within(outdir, file_path).await?;
+            let Ok(file) = fs::File::open(&file_path).await else {
                 eprintln!("->> Failed to find file {name}");
+                manifest.missing.push(ManifestFile {
+                    filename: name.to_owned(),
+                    region_id: *region_id,
+                    size: None,
+                    sha256: None,
+                });
                 continue;
             };
             zip.start_file(name, options)?;
@@ -153,11 +517,45 @@ async fn zip_files(gbk_files: &[PathBuf]) -> Result<Vec<u8>> {
             let mut buf = Vec::new();
             io::copy(&mut file.take(u64::MAX), &mut buf).await?;
             zip.write_all(&buf)?;
+
+            manifest.files.push(ManifestFile {
+                filename: name.to_owned(),
+                region_id: *region_id,
+                size: Some(buf.len()),
+                sha256: Some(format!("{:x}", Sha256::digest(&buf))),
+            });
+        }
+
+        for (name, data) in manifest.archive_entries()? {
+            zip.start_file(name, options)?;
+            zip.write_all(&data)?;
         }
 
         zip.finish()?;
     }
-    Ok(buffer.into_inner())
+    Ok((buffer.into_inner(), manifest.missing))
+}
+
+/// Pack the matched entries into a SQLite file, see [`bundle::SCHEMA`] for the layout
+async fn sqlite_bundle(query: &StoredQuery, pool: &PgPool, config: &RunConfig) -> Result<Vec<u8>> {
+    let job_id = query.input.job_id;
+    let scratch = job_id
+        .dir(&config.jobdir)
+        .join(format!("{job_id}.sqlite.tmp"));
+    let info = [
+        ("job_id", job_id.to_string()),
+        ("search_type", query.input.search_type.as_ref().to_owned()),
+        ("created", Utc::now().to_rfc3339()),
+        ("version", super::VERSION.to_owned()),
+    ];
+    bundle::to_sqlite(
+        pool,
+        &scratch,
+        &query.input.search_type,
+        &query.input.ids,
+        &info,
+    )
+    .await
 }
 
 fn get_filename(path: &PathBuf) -> Result<&str> {
@@ -170,80 +568,319 @@ fn get_filename(path: &PathBuf) -> Result<&str> {
         .ok_or(Error::OsStringError(os_name.to_owned()))
 }
 
-async fn run_cds(query: &StoredQuery, pool: &PgPool) -> Result<(String, Vec<u8>)> {
+async fn run_cds(
+    query: &StoredQuery,
+    pool: &PgPool,
+    config: &RunConfig,
+) -> Result<(String, Output)> {
     let filename: String;
-    let data = match query.input.return_type {
+    let output = match query.input.return_type {
         ReturnType::Json => {
             filename = format!("{}.json", &query.input.job_id);
             let cdses = cds::ids_to_genes(pool, &query.input.ids).await?;
-            serde_json::to_vec(&cdses)?
+            Output::Data(serde_json::to_vec(&cdses)?)
         }
         ReturnType::Csv => {
             filename = format!("{}.csv", &query.input.job_id);
-            let cdses = cds::ids_to_genes(pool, &query.input.ids)
-                .await?
-                .into_iter()
-                .map(|c| c.to_csv())
-                .collect::<Vec<String>>()
-                .join("\n");
-            Vec::from(format!("{}\n{cdses}", cds::Cds::csv_header()))
+            let lines = stream::gene_csv(pool, &query.input.ids).await?;
+            Output::Lines(lines)
+        }
+        ReturnType::Xlsx => {
+            filename = format!("{}.xlsx", &query.input.job_id);
+            let cdses = cds::ids_to_genes(pool, &query.input.ids).await?;
+            Output::Data(to_xlsx("genes", &cdses)?)
+        }
+        ReturnType::Sqlite => {
+            filename = format!("{}.sqlite", &query.input.job_id);
+            Output::Data(sqlite_bundle(query, pool, config).await?)
         }
         ReturnType::Fasta => {
             filename = format!("{}.fa", &query.input.job_id);
-            let sequences = cds::ids_to_fna(pool, &query.input.ids).await?.join("\n");
-            Vec::from(sequences)
+            let lines = stream::gene_fna(pool, &query.input.ids);
+            Output::Lines(lines)
         }
         ReturnType::Fastaa => {
             filename = format!("{}.fa", &query.input.job_id);
-            let sequences = cds::ids_to_faa(pool, &query.input.ids).await?.join("\n");
-            Vec::from(sequences)
+            let lines = stream::gene_faa(pool, &query.input.ids, query.input.core_only);
+            Output::Lines(lines)
         }
-        ReturnType::Genbank => {
-            return Err(Error::InvalidRequest(
-                "Cannot request CDSes in Genbank format".to_string(),
-            ))
+        ReturnType::Genbank | ReturnType::Sideload => {
+            return Err(Error::InvalidRequest(format!(
+                "Cannot request CDSes in {:?} format",
+                query.input.return_type
+            )))
         }
     };
-    Ok((filename, data))
+    Ok((filename, output))
 }
 
-async fn run_domain(query: &StoredQuery, pool: &PgPool) -> Result<(String, Vec<u8>)> {
+async fn run_domain(
+    query: &StoredQuery,
+    pool: &PgPool,
+    config: &RunConfig,
+) -> Result<(String, Output)> {
     let filename: String;
-    let data = match query.input.return_type {
+    let output = match query.input.return_type {
         ReturnType::Json => {
             filename = format!("{}.json", &query.input.job_id);
             let domains = domains::ids_to_domains(pool, &query.input.ids).await?;
-            serde_json::to_vec(&domains)?
+            Output::Data(serde_json::to_vec(&domains)?)
         }
         ReturnType::Csv => {
             filename = format!("{}.csv", &query.input.job_id);
-            let domains = domains::ids_to_domains(pool, &query.input.ids)
-                .await?
-                .into_iter()
-                .map(|c| c.to_csv())
-                .collect::<Vec<String>>()
-                .join("\n");
-            Vec::from(domains)
+            let lines = stream::domain_csv(pool, &query.input.ids).await?;
+            Output::Lines(lines)
+        }
+        ReturnType::Xlsx => {
+            filename = format!("{}.xlsx", &query.input.job_id);
+            let domains = domains::ids_to_domains(pool, &query.input.ids).await?;
+            Output::Data(to_xlsx("domains", &domains)?)
+        }
+        ReturnType::Sqlite => {
+            filename = format!("{}.sqlite", &query.input.job_id);
+            Output::Data(sqlite_bundle(query, pool, config).await?)
         }
         ReturnType::Fasta => {
             filename = format!("{}.fa", &query.input.job_id);
-            let sequences = domains::ids_to_fna(pool, &query.input.ids)
-                .await?
-                .join("\n");
-            Vec::from(sequences)
+            let lines = stream::domain_fna(pool, &query.input.ids);
+            Output::Lines(lines)
         }
         ReturnType::Fastaa => {
             filename = format!("{}.fa", &query.input.job_id);
-            let sequences = domains::ids_to_faa(pool, &query.input.ids)
-                .await?
-                .join("\n");
-            Vec::from(sequences)
+            let lines = stream::domain_faa(pool, &query.input.ids);
+            Output::Lines(lines)
         }
-        ReturnType::Genbank => {
-            return Err(Error::InvalidRequest(
-                "Cannot request domains in Genbank format".to_string(),
-            ))
+        ReturnType::Genbank | ReturnType::Sideload => {
+            return Err(Error::InvalidRequest(format!(
+                "Cannot request domains in {:?} format",
+                query.input.return_type
+            )))
         }
     };
-    Ok((filename, data))
+    Ok((filename, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_check_ids() {
+        let limits = IdLimits { max_ids: 5 };
+        let tests = [
+            (vec![1, 2, 3], None),
+            (vec![], Some("No IDs given")),
+            (
+                vec![1, 2, 3, 4, 5, 6],
+                Some("Cannot export more than 5 entries at once, 6 requested"),
+            ),
+            (vec![3, 0, -2], Some("Invalid IDs: non-positive IDs 0, -2")),
+            (vec![4, 2, 4, 2, 4], Some("Invalid IDs: duplicate IDs 2, 4")),
+            (
+                vec![-1, 3, 3],
+                Some("Invalid IDs: non-positive IDs -1; duplicate IDs 3"),
+            ),
+        ];
+        for (ids, expected) in tests {
+            let query =
+                StoredQuery::new(JobId::generate(), &ids, SearchType::Region, ReturnType::Csv);
+            let result = query.input.check_ids(&limits);
+            match expected {
+                None => assert!(result.is_ok(), "{ids:?}"),
+                Some(message) => assert!(
+                    matches!(&result, Err(Error::InvalidRequest(m)) if m == message),
+                    "{ids:?}: {result:?}"
+                ),
+            }
+        }
+
+        let many: Vec<i32> = (-30..0).collect();
+        assert!(list_ids(&many).ends_with("-11, and 10 more"));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn test_check_ids_exist(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        let tests = [
+            (SearchType::Region, vec![1, 2, 3], None),
+            (
+                SearchType::Region,
+                vec![5, 1, 4],
+                Some("Unknown region IDs: 4, 5"),
+            ),
+            (SearchType::Gene, vec![4, 1], None),
+            (SearchType::Gene, vec![7], Some("Unknown gene IDs: 7")),
+            (SearchType::Domain, vec![6], None),
+            (
+                SearchType::Domain,
+                vec![6, 60],
+                Some("Unknown domain IDs: 60"),
+            ),
+        ];
+        for (search_type, ids, expected) in tests {
+            let query = StoredQuery::new(JobId::generate(), &ids, search_type, ReturnType::Csv);
+            let result = query.input.check_ids_exist(&pool).await;
+            match expected {
+                None => assert!(result.is_ok(), "{ids:?}: {result:?}"),
+                Some(message) => assert!(
+                    matches!(&result, Err(Error::InvalidRequest(m)) if m == message),
+                    "{ids:?}: {result:?}"
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_region_gbk_path() {
+        let outdir = Path::new("/data/antismash");
+        let path = region_gbk_path(outdir, "GCF_000203835.1", "NC_003888", 3, 1).unwrap();
+        assert_eq!(
+            path,
+            outdir.join("GCF_000203835.1/NC_003888.3.region001.gbk")
+        );
+
+        let invalid = [
+            ("..", "NC_003888"),
+            ("GCF_1/..", "NC_003888"),
+            ("GCF_1", "../NC"),
+        ];
+        for (assembly_id, accession) in invalid {
+            assert!(
+                region_gbk_path(outdir, assembly_id, accession, 1, 1).is_err(),
+                "{assembly_id} {accession}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zip_files_manifest() {
+        let dir = std::env::temp_dir().join(format!("asdb-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).await.unwrap();
+        let present = dir.join("NC_003888.3.region001.gbk");
+        fs::write(&present, b"LOCUS").await.unwrap();
+        let missing = dir.join("NC_003888.3.region002.gbk");
+
+        let input = StoredQueryInput {
+            job_id: "c0ffee00-0000-4000-8000-000000000b0b".parse().unwrap(),
+            ids: vec![1, 2],
+            search_type: SearchType::Region,
+            return_type: ReturnType::Genbank,
+            csv_style: region::CsvStyle::default(),
+            core_only: false,
+            flank: 0,
+            subscription_id: None,
+            query: Some(serde_json::json!({"terms": "{[type|NRPS]}"})),
+        };
+        let mut manifest = Manifest::new(&input, "alice");
+        manifest.database = Some(DatabaseVersion {
+            release: Some("4.0".to_string()),
+            ..Default::default()
+        });
+        let (data, missing_files) = zip_files(&dir, &[(present, 1), (missing, 2)], manifest)
+            .await
+            .unwrap();
+        fs::remove_dir_all(&dir).await.unwrap();
+        assert_eq!(missing_files.len(), 1);
+        assert_eq!(missing_files[0].region_id, 2);
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.len(), 4);
+        let mut raw = String::new();
+        archive
+            .by_name(MANIFEST_NAME)
+            .unwrap()
+            .read_to_string(&mut raw)
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&raw).unwrap();
+
+        assert_eq!(manifest["job_id"], "c0ffee00-0000-4000-8000-000000000b0b");
+        assert_eq!(manifest["runner"], "alice");
+        assert_eq!(manifest["files"][0]["region_id"], 1);
+        assert_eq!(manifest["files"][0]["size"], 5);
+        assert_eq!(
+            manifest["files"][0]["sha256"],
+            "f510430eb3eb8aa324d7d050e826bffb31f8816c36dd57edea93d6fa6d1cec02"
+        );
+        assert_eq!(
+            manifest["missing"][0]["filename"],
+            "NC_003888.3.region002.gbk"
+        );
+        assert_eq!(manifest["database"]["release"], "4.0");
+
+        let mut tsv = String::new();
+        archive
+            .by_name(MANIFEST_TSV_NAME)
+            .unwrap()
+            .read_to_string(&mut tsv)
+            .unwrap();
+        assert_eq!(
+            tsv,
+            "# antiSMASH-DB 4.0\n\
+             # job: c0ffee00-0000-4000-8000-000000000b0b\n\
+             # query: {\"terms\":\"{[type|NRPS]}\"}\n\
+             filename\tregion_id\tsize\tsha256\n\
+             NC_003888.3.region001.gbk\t1\t5\t\
+             f510430eb3eb8aa324d7d050e826bffb31f8816c36dd57edea93d6fa6d1cec02\n"
+        );
+
+        let mut text = String::new();
+        archive
+            .by_name(MISSING_NAME)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(
+            text,
+            "1 GenBank files could not be found and are not included in this archive:\n\n\
+             filename\tregion_id\tsize\tsha256\n\
+             NC_003888.3.region002.gbk\t2\t\t\n"
+        );
+    }
+
+    #[test]
+    fn test_stored_query_results() {
+        let input = StoredQuery::new(
+            JobId::generate(),
+            &[1, 2],
+            SearchType::Region,
+            ReturnType::Genbank,
+        )
+        .input;
+        let missing = ManifestFile {
+            filename: "NC_003888.3.region002.gbk".to_string(),
+            region_id: 2,
+            size: None,
+            sha256: None,
+        };
+        let tests = [
+            (serde_json::Value::Null, None, vec![]),
+            (
+                serde_json::json!("/jobs/a/a.zip"),
+                Some("/jobs/a/a.zip"),
+                vec![],
+            ),
+            (
+                serde_json::json!({
+                    "filename": "/jobs/a/a.zip",
+                    "missing_count": 1,
+                    "missing": [{"filename": "NC_003888.3.region002.gbk", "region_id": 2}],
+                }),
+                Some("/jobs/a/a.zip"),
+                vec![missing.clone()],
+            ),
+        ];
+        for (results, filename, missing) in tests {
+            let query = StoredQuery::from_results(input.clone(), results.clone());
+            assert_eq!(query.filename.as_deref(), filename, "{results}");
+            assert_eq!(query.missing, missing, "{results}");
+
+            let stored = query.results();
+            assert_eq!(stored["missing_count"], missing.len());
+            let reloaded = StoredQuery::from_results(input.clone(), stored);
+            assert_eq!(reloaded.filename, query.filename);
+            assert_eq!(reloaded.missing, query.missing);
+        }
+    }
 }
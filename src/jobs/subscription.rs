@@ -0,0 +1,234 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::{redirect, Url};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::net::lookup_host;
+use tokio::time::Duration;
+
+use super::stored_query::StoredQuery;
+use crate::api::{etag, region};
+use crate::models::job::{JobEntry, JobId, JobType};
+use crate::models::subscription::Subscription;
+use crate::{Error, Result};
+
+/// How often the runner looks for subscriptions that are due
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(300);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Most subscriptions a single session can register
+pub const MAX_SUBSCRIPTIONS_PER_SESSION: i64 = 20;
+
+/// Queue a stored query job for every subscription that hasn't seen the current database
+/// contents yet or whose interval has passed
+pub async fn schedule_due(pool: &PgPool) -> Result<()> {
+    let db_version = etag::fingerprint(pool).await?;
+    for id in Subscription::due(pool, &db_version).await? {
+        if !Subscription::claim(pool, &id, &db_version).await? {
+            continue;
+        }
+        let mut subscription = Subscription::from_db(pool, &id).await?;
+        // A failing query is only retried once it is due again
+        match queue(pool, &subscription).await {
+            Ok(job_id) => {
+                eprintln!("->> Queued job {job_id} for subscription {id}");
+                subscription.set_last_job(pool, &job_id.to_string()).await?;
+            }
+            Err(e) => eprintln!("->> Failed to run subscription {id}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+async fn queue(pool: &PgPool, subscription: &Subscription) -> Result<JobId> {
+    let mut query = subscription.query()?;
+    region::resolve_versions(pool, &mut query.terms, query.resolve_versions).await?;
+    let ids = region::query_ids(pool, &query).await?;
+
+    let job_id = JobId::generate();
+    let mut stored = StoredQuery::new(job_id, &ids, query.search_type, query.return_type);
+    stored.input.subscription_id = Some(subscription.id.to_owned());
+    stored.input.query = Some(subscription.query.to_owned());
+
+    let mut job = JobEntry::new(JobType::StoredQuery(stored));
+    job.id = job_id;
+    job.session_id = subscription.session_id.to_owned();
+    job.commit(pool).await?;
+    Ok(job_id)
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload {
+    pub subscription_id: String,
+    pub job_id: JobId,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Tell the subscriber about a finished subscription job.
+/// Notifications are best effort, failures are logged but don't fail the job.
+pub async fn notify(pool: &PgPool, job: &JobEntry) {
+    let JobType::StoredQuery(query) = &job.jobtype else {
+        return;
+    };
+    let Some(subscription_id) = &query.input.subscription_id else {
+        return;
+    };
+
+    let payload = WebhookPayload {
+        subscription_id: subscription_id.to_owned(),
+        job_id: job.id,
+        status: job.status.to_string(),
+        download: query.filename.to_owned(),
+        error: job.error.to_owned(),
+    };
+    if let Err(e) = send_webhook(pool, &payload).await {
+        eprintln!(
+            "->> Failed to notify subscription {}: {e}",
+            payload.subscription_id
+        );
+    }
+}
+
+async fn send_webhook(pool: &PgPool, payload: &WebhookPayload) -> Result<()> {
+    let subscription = Subscription::from_db(pool, &payload.subscription_id).await?;
+    let Some(url) = &subscription.webhook_url else {
+        return Ok(());
+    };
+
+    // Connect to the checked addresses only, so the host can't resolve somewhere else
+    // in between, and don't follow redirects to unchecked URLs
+    let (url, addrs) = resolve_webhook(url).await?;
+    let mut client = reqwest::Client::builder().redirect(redirect::Policy::none());
+    if let Some(host) = url.host_str() {
+        client = client.resolve_to_addrs(host, &addrs);
+    }
+    client
+        .build()?
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Parse a webhook URL and resolve its host, rejecting URLs that aren't http(s) or that
+/// point into the deployment, like loopback, private and link-local addresses
+pub async fn resolve_webhook(url: &str) -> Result<(Url, Vec<SocketAddr>)> {
+    let invalid =
+        |reason: &str| Error::InvalidRequest(format!("Invalid webhook URL {url:?}: {reason}"));
+    let parsed = Url::parse(url).map_err(|_| invalid("not a URL"))?;
+    if parsed.scheme() != "https" && parsed.scheme() != "http" {
+        return Err(invalid("only http and https are supported"));
+    }
+    let host = parsed.host_str().ok_or_else(|| invalid("no host"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let addrs: Vec<SocketAddr> = lookup_host((host, port))
+        .await
+        .map_err(|_| invalid("host not found"))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(invalid("host not found"));
+    }
+    if !addrs.iter().all(|addr| is_public_address(addr.ip())) {
+        return Err(invalid("host is not publicly reachable"));
+    }
+    Ok((parsed, addrs))
+}
+
+/// Whether an address is reachable on the internet, as opposed to the loopback, private,
+/// link-local, shared and otherwise reserved ranges
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8, shared address space 100.64.0.0/10 and reserved 240.0.0.0/4
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local fc00::/7 and link-local fe80::/10
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_address() {
+        let tests = [
+            ("93.184.216.34", true),
+            ("2606:2800:220:1:248:1893:25c8:1946", true),
+            ("127.0.0.1", false),
+            ("10.1.2.3", false),
+            ("172.16.0.1", false),
+            ("192.168.1.1", false),
+            ("169.254.169.254", false),
+            ("100.64.0.1", false),
+            ("0.0.0.0", false),
+            ("255.255.255.255", false),
+            ("::1", false),
+            ("::", false),
+            ("fd00::1", false),
+            ("fe80::1", false),
+            ("::ffff:127.0.0.1", false),
+            ("::ffff:93.184.216.34", true),
+        ];
+        for (input, expected) in tests {
+            let ip: IpAddr = input.parse().unwrap();
+            assert_eq!(is_public_address(ip), expected, "{input}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_webhook() {
+        let tests = [
+            "http://localhost:8080/hook",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "https://10.0.0.1/hook",
+            "ftp://93.184.216.34/hook",
+            "not a url",
+        ];
+        for input in tests {
+            assert!(resolve_webhook(input).await.is_err(), "{input}");
+        }
+
+        let (url, addrs) = resolve_webhook("https://93.184.216.34/hook").await.unwrap();
+        assert_eq!(url.as_str(), "https://93.184.216.34/hook");
+        assert_eq!(addrs, ["93.184.216.34:443".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,179 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use rust_xlsxwriter::{Format, Workbook};
+
+use crate::api::cds::Cds;
+use crate::api::domains::Domain;
+use crate::api::region::Region;
+use crate::Result;
+
+/// A typed spreadsheet cell, so numbers and flags don't end up as text
+#[derive(Debug, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Bool(bool),
+    Empty,
+}
+
+impl From<Option<String>> for Cell {
+    fn from(value: Option<String>) -> Self {
+        match value {
+            Some(text) => Cell::Text(text),
+            None => Cell::Empty,
+        }
+    }
+}
+
+pub trait XlsxRow {
+    fn xlsx_header() -> &'static [&'static str];
+    fn xlsx_row(&self) -> Vec<Cell>;
+}
+
+impl XlsxRow for Region {
+    fn xlsx_header() -> &'static [&'static str] {
+        &[
+            "Genus",
+            "Species",
+            "Strain",
+            "NCBI accession",
+            "From",
+            "To",
+            "BGC type",
+            "On contig edge",
+            "Most similar known cluster",
+            "Similarity in %",
+            "MIBiG BGC-ID",
+        ]
+    }
+
+    fn xlsx_row(&self) -> Vec<Cell> {
+        vec![
+            self.genus.to_owned().into(),
+            self.species.to_owned().into(),
+            self.strain.to_owned().into(),
+            Cell::Text(format!(
+                "{}.{}",
+                self.accession.as_deref().unwrap_or_default(),
+                self.version.unwrap_or_default()
+            )),
+            Cell::Number(self.start_pos.into()),
+            Cell::Number(self.end_pos.into()),
+            Cell::Text(self.term.to_owned()),
+            Cell::Bool(self.contig_edge),
+            self.best_mibig_hit_description.to_owned().into(),
+            match self.best_mibig_hit_similarity {
+                Some(similarity) => Cell::Number(similarity.into()),
+                None => Cell::Empty,
+            },
+            self.best_mibig_hit_acc.to_owned().into(),
+        ]
+    }
+}
+
+impl XlsxRow for Cds {
+    fn xlsx_header() -> &'static [&'static str] {
+        &["Locus tag", "Accession", "Location", "Translation"]
+    }
+
+    fn xlsx_row(&self) -> Vec<Cell> {
+        vec![
+            self.locus_tag.to_owned().into(),
+            Cell::Text(self.accession.to_owned()),
+            Cell::Text(self.location.to_owned()),
+            self.translation.to_owned().into(),
+        ]
+    }
+}
+
+impl XlsxRow for Domain {
+    fn xlsx_header() -> &'static [&'static str] {
+        &[
+            "Locus tag",
+            "Domain type",
+            "Accession",
+            "Location",
+            "Sequence",
+        ]
+    }
+
+    fn xlsx_row(&self) -> Vec<Cell> {
+        vec![
+            self.locus_tag.to_owned().into(),
+            Cell::Text(self.name.to_owned()),
+            Cell::Text(format!("{}.{}", self.accession, self.version.unwrap_or(1))),
+            Cell::Text(self.location.to_owned()),
+            self.translation.to_owned().into(),
+        ]
+    }
+}
+
+/// Write rows into a single worksheet with a bold, frozen header row
+pub fn to_xlsx<T: XlsxRow>(sheet_name: &str, rows: &[T]) -> Result<Vec<u8>> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(sheet_name)?;
+
+    let bold = Format::new().set_bold();
+    for (col, title) in T::xlsx_header().iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *title, &bold)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+
+    for (i, row) in rows.iter().enumerate() {
+        let row_num = i as u32 + 1;
+        for (col, cell) in row.xlsx_row().into_iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(text) => worksheet.write_string(row_num, col, text)?,
+                Cell::Number(number) => worksheet.write_number(row_num, col, number)?,
+                Cell::Bool(flag) => worksheet.write_boolean(row_num, col, flag)?,
+                Cell::Empty => continue,
+            };
+        }
+    }
+    worksheet.autofit();
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+
+    #[test]
+    fn test_to_xlsx() {
+        let cdses = vec![Cds {
+            cds_id: 1,
+            locus_tag: Some("SCO1234".to_string()),
+            translation: None,
+            accession: "NC_003888".to_string(),
+            location: "[10:20](+)".to_string(),
+        }];
+        let data = to_xlsx("genes", &cdses).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut sheet = String::new();
+        archive
+            .by_name("xl/worksheets/sheet1.xml")
+            .unwrap()
+            .read_to_string(&mut sheet)
+            .unwrap();
+        let mut strings = String::new();
+        archive
+            .by_name("xl/sharedStrings.xml")
+            .unwrap()
+            .read_to_string(&mut strings)
+            .unwrap();
+
+        // header plus one gene, the missing translation is left empty
+        assert_eq!(sheet.matches("<row ").count(), 2);
+        assert_eq!(sheet.matches("<c ").count(), 7);
+        for text in ["Locus tag", "Translation", "SCO1234", "NC_003888"] {
+            assert!(strings.contains(text), "{text}");
+        }
+    }
+}
@@ -4,23 +4,31 @@
 use std::env;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::{Parser, Subcommand};
+use axum::ServiceExt;
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenvy::dotenv;
 use gethostname::gethostname;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use tower::{util::MapRequestLayer, Layer};
 use tower_http::services::ServeDir;
 
 pub use self::error::{Error, Result};
 
+pub mod analysis;
 pub mod api;
 pub mod cleanup;
+pub mod compounds;
+pub mod config_check;
 pub mod error;
 pub mod jobs;
 pub mod models;
 pub mod query;
+pub mod scheduler;
 pub mod search;
-
-use jobs::comparippson::COMPARIPPSON_METADATA;
+#[cfg(test)]
+pub mod testutils;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -33,17 +41,94 @@ struct Cli {
     #[arg(long, short)]
     outdir: Option<PathBuf>,
 
+    /// Maximum number of database connections [env: DB_MAX_CONNECTIONS] [default: 10]
+    #[arg(long)]
+    max_connections: Option<u32>,
+
+    /// Number of database connections to keep open [env: DB_MIN_CONNECTIONS] [default: 0]
+    #[arg(long)]
+    min_connections: Option<u32>,
+
+    /// Seconds to wait for a free database connection [env: DB_ACQUIRE_TIMEOUT] [default: 30]
+    #[arg(long)]
+    acquire_timeout: Option<u64>,
+
+    /// Seconds after which idle database connections are closed [env: DB_IDLE_TIMEOUT] [default: 600]
+    #[arg(long)]
+    idle_timeout: Option<u64>,
+
+    /// Seconds after which the database cancels a statement, 0 to disable [env: DB_STATEMENT_TIMEOUT] [default: 0]
+    #[arg(long)]
+    statement_timeout: Option<u64>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
-#[derive(Debug, Subcommand)]
+#[derive(Debug, Subcommand, strum::AsRefStr)]
+#[strum(serialize_all = "lowercase")]
 pub enum Commands {
     /// Serve the web API
     Serve {
         /// Address to listen on
         #[arg(long, short, default_value = "[::]:5566")]
         address: String,
+
+        /// Token required to access the admin endpoints
+        #[arg(long)]
+        admin_token: Option<String>,
+
+        /// Periodically precompute the statistics instead of querying them per request
+        #[arg(long)]
+        refresh_stats: bool,
+
+        /// Seconds between statistics refreshes
+        #[arg(long, default_value_t = 3600)]
+        stats_interval: u64,
+
+        /// Periodically mark jobs stuck in the running state as failed
+        #[arg(long)]
+        check_stale_jobs: bool,
+
+        /// Seconds between stale job checks
+        #[arg(long, default_value_t = 600)]
+        stale_job_interval: u64,
+
+        /// Hours after which a running job is considered stale
+        #[arg(long, default_value_t = 24.0_f64)]
+        stale_job_age: f64,
+
+        /// Maximum random delay in seconds added to each scheduled task run
+        #[arg(long, default_value_t = 60)]
+        jitter: u64,
+
+        /// Comma-separated list of origins allowed to make cross-origin requests, or "*" for any
+        #[arg(long)]
+        allowed_origins: Option<String>,
+
+        /// Minimum length of query sequences submitted to blast jobs
+        #[arg(long)]
+        min_sequence_length: Option<usize>,
+
+        /// Maximum length of query sequences submitted to blast jobs
+        #[arg(long)]
+        max_sequence_length: Option<usize>,
+
+        /// Maximum length of search strings [env: MAX_QUERY_LENGTH]
+        #[arg(long)]
+        max_query_length: Option<usize>,
+
+        /// Maximum nesting depth of search strings [env: MAX_QUERY_DEPTH]
+        #[arg(long)]
+        max_query_depth: Option<usize>,
+
+        /// Maximum number of terms in search strings [env: MAX_QUERY_TERMS]
+        #[arg(long)]
+        max_query_terms: Option<usize>,
+
+        /// Maximum number of IDs exported by a stored query [env: MAX_STORED_QUERY_IDS]
+        #[arg(long)]
+        max_stored_query_ids: Option<usize>,
     },
     /// Run the background jobs
     Run {
@@ -58,12 +143,67 @@ pub enum Commands {
         /// Base directory for stored job URLs
         #[arg(long, short)]
         urlroot: Option<String>,
+
+        /// Validate the runner configuration and exit
+        #[arg(long)]
+        check: bool,
+
+        /// How to execute the blast tools
+        #[arg(long, value_enum)]
+        executor: Option<jobs::Executor>,
+
+        /// Seconds after which a running blast tool is killed
+        #[arg(long, short)]
+        timeout: Option<u64>,
+
+        /// How often a job failing with a transient error is re-queued
+        #[arg(long)]
+        max_retries: Option<u32>,
+
+        /// Only pick up jobs of these types, comma-separated, all types if unset
+        #[arg(long, value_enum, value_delimiter = ',')]
+        jobtypes: Vec<models::job::JobKind>,
+    },
+    /// Validate the configuration of the API and the job runners, exiting with an error
+    /// if any check fails
+    CheckConfig {
+        /// Base directory for the databases
+        #[arg(long, short = 'D')]
+        dbdir: Option<PathBuf>,
+
+        /// How the blast tools would be executed
+        #[arg(long, value_enum)]
+        executor: Option<jobs::Executor>,
+    },
+    /// Apply the embedded migrations for the job queue tables
+    Migrate {
+        /// Only list the migrations and whether they have been applied
+        #[arg(long)]
+        check: bool,
+    },
+    /// Load a table of known compounds and their MIBiG entries, replacing the earlier
+    /// compounds of the same source
+    ImportCompounds {
+        /// Tab-separated table with a header line, like an NPAtlas export
+        file: PathBuf,
+
+        /// Where the compounds come from
+        #[arg(long, value_enum, default_value_t)]
+        source: compounds::CompoundSource,
     },
     /// Clean up old jobs from the database and file system
     Cleanup {
         /// Days after which to cleanup jobs
         #[arg(long, short, default_value_t = 7.0_f64)]
         interval: f64,
+
+        /// Only print what would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Only clean up jobs in these states, comma-separated
+        #[arg(long, value_delimiter = ',')]
+        status: Vec<models::job::JobStatus>,
     },
 }
 
@@ -76,6 +216,10 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    // TODO: Maybe also add a CLI arg?
+    let url = env::var("DATABASE_URL")?;
+    let pool = create_pool(&url, &cli).await?;
+
     let jobdir = if let Some(d) = cli.jobdir {
         d
     } else {
@@ -90,13 +234,66 @@ async fn main() -> Result<()> {
 
     let outdir = cli.outdir;
 
-    // TODO: Maybe also add a CLI arg?
-    let url = env::var("DATABASE_URL")?;
-    let pool = sqlx::postgres::PgPool::connect(&url).await?;
-
     match &cli.command {
-        Commands::Serve { address } => {
-            let mut routes_all = api::init_routes(pool);
+        Commands::Serve {
+            address,
+            admin_token,
+            refresh_stats,
+            stats_interval,
+            check_stale_jobs,
+            stale_job_interval,
+            stale_job_age,
+            jitter,
+            allowed_origins,
+            min_sequence_length,
+            max_sequence_length,
+            max_query_length,
+            max_query_depth,
+            max_query_terms,
+            max_stored_query_ids,
+        } => {
+            let sequence_limits = jobs::blast::SequenceLimits {
+                min_length: arg_or_env(*min_sequence_length, "MIN_SEQUENCE_LENGTH")?
+                    .unwrap_or(jobs::blast::DEFAULT_MIN_SEQUENCE_LENGTH),
+                max_length: arg_or_env(*max_sequence_length, "MAX_SEQUENCE_LENGTH")?
+                    .unwrap_or(jobs::blast::DEFAULT_MAX_SEQUENCE_LENGTH),
+            };
+            let parse_limits = query::ParseLimits {
+                max_length: arg_or_env(*max_query_length, "MAX_QUERY_LENGTH")?
+                    .unwrap_or(query::limits::DEFAULT_MAX_QUERY_LENGTH),
+                max_depth: arg_or_env(*max_query_depth, "MAX_QUERY_DEPTH")?
+                    .unwrap_or(query::limits::DEFAULT_MAX_QUERY_DEPTH),
+                max_terms: arg_or_env(*max_query_terms, "MAX_QUERY_TERMS")?
+                    .unwrap_or(query::limits::DEFAULT_MAX_QUERY_TERMS),
+            };
+            let id_limits = jobs::stored_query::IdLimits {
+                max_ids: arg_or_env(*max_stored_query_ids, "MAX_STORED_QUERY_IDS")?
+                    .unwrap_or(jobs::stored_query::DEFAULT_MAX_IDS),
+            };
+            let config = api::ApiConfig {
+                admin_token: admin_token.to_owned().or(env::var("ADMIN_TOKEN").ok()),
+                sequence_limits,
+                parse_limits,
+                id_limits,
+                outdir: outdir.clone(),
+                jobdir: jobdir.clone(),
+                ..Default::default()
+            };
+            if config.admin_token.is_none() {
+                eprintln!("->> No admin token set, admin endpoints are disabled");
+            }
+
+            let scheduler_config = scheduler::SchedulerConfig {
+                stats_interval: refresh_stats.then(|| Duration::from_secs(*stats_interval)),
+                stale_job_interval: check_stale_jobs
+                    .then(|| Duration::from_secs(*stale_job_interval)),
+                stale_job_age: *stale_job_age,
+                jitter: Duration::from_secs(*jitter),
+            };
+            scheduler::start(pool.clone(), scheduler_config, config.stats_cache.clone());
+            config.job_events.listen(&pool);
+
+            let (mut routes_all, route_segments) = api::init_routes(pool, config);
 
             if let Some(o) = outdir {
                 let serve_dir = ServeDir::new(&o);
@@ -104,11 +301,24 @@ async fn main() -> Result<()> {
                 eprintln!("->> Serving files from {o:?}");
             }
 
+            let allowed_origins = api::cors::parse_origins(
+                &allowed_origins
+                    .to_owned()
+                    .or(env::var("ALLOWED_ORIGINS").ok())
+                    .unwrap_or(api::cors::DEFAULT_ALLOWED_ORIGINS.to_string()),
+            );
+            eprintln!("->> Allowing cross-origin requests from {allowed_origins:?}");
+            routes_all = routes_all.layer(api::cors::cors_layer(&allowed_origins)?);
+            routes_all = routes_all.layer(axum::middleware::from_fn(api::request_id::assign));
+
             let addr: SocketAddr = address.as_str().parse().unwrap();
             eprintln!("->> Listening on {addr}");
 
+            let app = MapRequestLayer::new(move |req| route_segments.normalize_request(req))
+                .layer(routes_all);
+
             axum::Server::bind(&addr)
-                .serve(routes_all.into_make_service())
+                .serve(app.into_make_service())
                 .await
                 .unwrap();
         }
@@ -116,12 +326,92 @@ async fn main() -> Result<()> {
             name,
             dbdir,
             urlroot,
+            check,
+            executor,
+            timeout,
+            max_retries,
+            jobtypes,
         } => {
-            let config = create_config(name, dbdir, &jobdir, &outdir, &urlroot).await?;
+            let mut config =
+                create_config(name, dbdir, &jobdir, &outdir, &urlroot, executor, timeout).await?;
+            config.max_retries =
+                arg_or_env(*max_retries, "MAX_JOB_RETRIES")?.unwrap_or(jobs::DEFAULT_MAX_RETRIES);
+            config.jobtypes = jobtypes.to_owned();
+            if config.jobtypes.is_empty() {
+                if let Ok(value) = env::var("JOBTYPES") {
+                    for jobtype in value.split(',').filter(|j| !j.trim().is_empty()) {
+                        config.jobtypes.push(
+                            models::job::JobKind::from_str(jobtype.trim(), true)
+                                .map_err(Error::ConfigError)?,
+                        );
+                    }
+                }
+            }
+            // Runners without CompaRiPPson jobs don't need its databases
+            if config.handles(models::job::JobKind::CompaRiPPson) {
+                config.comparippson_config =
+                    jobs::comparippson::CompaRiPPsonConfig::load(&config.dbdir).await?;
+            }
+            if *check {
+                eprintln!(
+                    "->> Validating the runner configuration for {}",
+                    config.name
+                );
+                jobs::check(pool, config).await?;
+                return Ok(());
+            }
             eprintln!("->> Running the background jobs as {}", config.name);
             jobs::dispatch(pool, config).await.unwrap();
         }
-        Commands::Cleanup { interval } => {
+        Commands::CheckConfig { dbdir, executor } => {
+            let config = config_check::CheckConfig {
+                jobdir,
+                outdir,
+                dbdir: resolve_dbdir(dbdir)?,
+                executor: resolve_executor(executor)?,
+            };
+            let report = config_check::run(&pool, &config).await;
+            println!("{report}");
+            if report.failures() > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Migrate { check } => {
+            let migrator = sqlx::migrate!();
+            if *check {
+                let applied: Vec<i64> =
+                    sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+                        .fetch_all(&pool)
+                        .await
+                        .unwrap_or_default();
+                for migration in migrator.iter() {
+                    let state = if applied.contains(&migration.version) {
+                        "applied"
+                    } else {
+                        "pending"
+                    };
+                    eprintln!(
+                        "->> {} {} ({state})",
+                        migration.version, migration.description
+                    );
+                }
+                return Ok(());
+            }
+
+            eprintln!("->> Applying database migrations");
+            migrator.run(&pool).await?;
+            eprintln!("->> Database is up to date");
+        }
+        Commands::ImportCompounds { file, source } => {
+            eprintln!("->> Importing {} compounds from {file:?}", source.as_ref());
+            let count = compounds::import(&pool, file, *source).await?;
+            eprintln!("->> Imported {count} compound references");
+        }
+        Commands::Cleanup {
+            interval,
+            dry_run,
+            status,
+        } => {
             let days = interval.to_owned();
             if days < 0.0 {
                 eprintln!("Can't use a negative interval");
@@ -131,19 +421,67 @@ async fn main() -> Result<()> {
             }
 
             eprintln!("->> Cleaning up outdated/deleted jobs older than {days} days");
-            cleanup::run(&pool, &jobdir, days).await.unwrap();
+            cleanup::run(&pool, &jobdir, days, status, *dry_run)
+                .await
+                .unwrap();
         }
     }
 
     Ok(())
 }
 
+/// Use the CLI value if given, otherwise the environment variable if set
+fn arg_or_env<T: std::str::FromStr>(arg: Option<T>, var: &str) -> Result<Option<T>> {
+    if arg.is_some() {
+        return Ok(arg);
+    }
+    match env::var(var) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| Error::ConfigError(format!("invalid value {value:?} for {var}"))),
+        Err(_) => Ok(None),
+    }
+}
+
+async fn create_pool(url: &str, cli: &Cli) -> Result<sqlx::PgPool> {
+    let mut connect_options: PgConnectOptions = url.parse()?;
+    connect_options =
+        connect_options.application_name(&format!("antismash-db {}", cli.command.as_ref()));
+    if let Some(secs) = arg_or_env(cli.statement_timeout, "DB_STATEMENT_TIMEOUT")? {
+        let timeout = format!("{}", secs * 1000);
+        connect_options = connect_options.options([("statement_timeout", timeout.as_str())]);
+    }
+
+    let mut pool_options = PgPoolOptions::new();
+    if let Some(max) = arg_or_env(cli.max_connections, "DB_MAX_CONNECTIONS")? {
+        pool_options = pool_options.max_connections(max);
+    }
+    if let Some(min) = arg_or_env(cli.min_connections, "DB_MIN_CONNECTIONS")? {
+        pool_options = pool_options.min_connections(min);
+    }
+    if let Some(secs) = arg_or_env(cli.acquire_timeout, "DB_ACQUIRE_TIMEOUT")? {
+        pool_options = pool_options.acquire_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = arg_or_env(cli.idle_timeout, "DB_IDLE_TIMEOUT")? {
+        pool_options = pool_options.idle_timeout(Duration::from_secs(secs));
+    }
+
+    // Checking the configuration reports an unreachable database instead of failing on it
+    if matches!(cli.command, Commands::CheckConfig { .. }) {
+        return Ok(pool_options.connect_lazy_with(connect_options));
+    }
+    Ok(pool_options.connect_with(connect_options).await?)
+}
+
 async fn create_config(
     name: &Option<String>,
     dbdir: &Option<PathBuf>,
     jobdir: &PathBuf,
     outdir: &Option<PathBuf>,
     urlroot: &Option<String>,
+    executor: &Option<jobs::Executor>,
+    timeout: &Option<u64>,
 ) -> Result<jobs::RunConfig> {
     let name_to_use = if let Some(n) = name {
         n.to_owned()
@@ -154,26 +492,10 @@ async fn create_config(
         }
     };
 
-    let db_base_dir = if let Some(d) = dbdir {
-        d.to_owned()
-    } else {
-        if let Ok(d) = env::var("DBDIR") {
-            PathBuf::from(d)
-        } else {
-            let mut d = env::current_dir()?;
-            d.push("databases");
-            d
-        }
-    };
-
-    let mut metadata_file = db_base_dir.clone();
-    metadata_file.push(COMPARIPPSON_METADATA);
-
-    let metadata =
-        jobs::comparippson::Metadata::from_json(&tokio::fs::read_to_string(&metadata_file).await?)?;
+    let db_base_dir = resolve_dbdir(dbdir)?;
 
     let comparippson_config = jobs::comparippson::CompaRiPPsonConfig {
-        metadata,
+        databases: Default::default(),
         dbdir: db_base_dir.clone(),
     };
 
@@ -183,6 +505,17 @@ async fn create_config(
         "job_downloads".to_string()
     };
 
+    let executor_to_use = resolve_executor(executor)?;
+
+    let timeout_secs = if let Some(t) = timeout {
+        t.to_owned()
+    } else {
+        match env::var("JOB_TIMEOUT") {
+            Ok(t) => t.parse()?,
+            Err(_) => jobs::DEFAULT_TIMEOUT,
+        }
+    };
+
     let config = jobs::RunConfig {
         comparippson_config,
         name: name_to_use,
@@ -190,6 +523,32 @@ async fn create_config(
         jobdir: jobdir.clone(),
         outdir: outdir.clone(),
         urlroot: job_dl_url_root,
+        executor: executor_to_use,
+        timeout: Duration::from_secs(timeout_secs),
+        max_retries: jobs::DEFAULT_MAX_RETRIES,
+        jobtypes: Vec::new(),
     };
     Ok(config)
 }
+
+fn resolve_dbdir(dbdir: &Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(d) = dbdir {
+        return Ok(d.to_owned());
+    }
+    if let Ok(d) = env::var("DBDIR") {
+        return Ok(PathBuf::from(d));
+    }
+    let mut d = env::current_dir()?;
+    d.push("databases");
+    Ok(d)
+}
+
+fn resolve_executor(executor: &Option<jobs::Executor>) -> Result<jobs::Executor> {
+    if let Some(e) = executor {
+        return Ok(e.to_owned());
+    }
+    match env::var("EXECUTOR") {
+        Ok(e) => jobs::Executor::from_str(&e, true).map_err(Error::ConfigError),
+        Err(_) => Ok(jobs::Executor::default()),
+    }
+}
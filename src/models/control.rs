@@ -1,8 +1,12 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use chrono::{DateTime, Utc};
+use gethostname::gethostname;
+use serde::Serialize;
 use sqlx::PgPool;
 
+use crate::models::job::JobKind;
 use crate::Result;
 
 #[derive(Debug)]
@@ -12,6 +16,26 @@ pub struct Control<'a> {
     pub status: String,
     pub stop_scheduled: bool,
     pub version: String,
+    /// Job types the runner picks up, all of them if unset
+    pub jobtypes: Option<Vec<String>>,
+    /// Machine the runner is running on
+    pub host: Option<String>,
+    /// Job the runner is working on
+    pub current_job: Option<String>,
+}
+
+/// A runner as listed for the admins
+#[derive(Debug, Serialize)]
+pub struct Worker {
+    pub name: String,
+    pub status: String,
+    pub stop_scheduled: bool,
+    pub version: String,
+    pub host: Option<String>,
+    /// Job types the runner picks up, all of them if unset
+    pub jobtypes: Option<Vec<String>>,
+    pub current_job: Option<String>,
+    pub last_heartbeat: Option<DateTime<Utc>>,
 }
 
 impl<'a> Control<'a> {
@@ -28,9 +52,19 @@ impl<'a> Control<'a> {
             status: status.to_owned(),
             stop_scheduled,
             version: version.to_owned(),
+            jobtypes: None,
+            host: gethostname().into_string().ok(),
+            current_job: None,
         }
     }
 
+    /// Advertise the job types the runner picks up, an empty list means all of them
+    pub fn with_jobtypes(mut self, jobtypes: &[JobKind]) -> Self {
+        self.jobtypes = (!jobtypes.is_empty())
+            .then(|| jobtypes.iter().map(|j| j.as_ref().to_owned()).collect());
+        self
+    }
+
     pub async fn from_db(pool: &'a PgPool, name: &str) -> Result<Control<'a>> {
         let row = sqlx::query!(
             r#"
@@ -47,9 +81,37 @@ impl<'a> Control<'a> {
             status: row.status.to_owned(),
             stop_scheduled: row.stop_scheduled,
             version: row.version.to_owned(),
+            jobtypes: row.jobtypes,
+            host: row.host,
+            current_job: row.current_job,
         })
     }
 
+    /// All registered runners, ordered by name
+    pub async fn list_workers(pool: &PgPool) -> Result<Vec<Worker>> {
+        let workers = sqlx::query!(
+            r#"
+        SELECT name, status, stop_scheduled, version, host, jobtypes, current_job, last_heartbeat
+            FROM asdb_jobs.controls
+            ORDER BY name"#,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| Worker {
+            name: row.name,
+            status: row.status,
+            stop_scheduled: row.stop_scheduled,
+            version: row.version,
+            host: row.host,
+            jobtypes: row.jobtypes,
+            current_job: row.current_job,
+            last_heartbeat: row.last_heartbeat.map(|d| d.and_utc()),
+        })
+        .collect();
+        Ok(workers)
+    }
+
     pub async fn fetch(&mut self) -> Result<&mut Control<'a>> {
         let row = sqlx::query!(
             r#"
@@ -64,18 +126,40 @@ impl<'a> Control<'a> {
         Ok(self)
     }
 
+    /// Record that the runner is alive and what it is working on, and fetch its status
+    pub async fn heartbeat(&mut self) -> Result<&mut Control<'a>> {
+        let row = sqlx::query!(
+            r#"
+        UPDATE asdb_jobs.controls SET last_heartbeat = now(), current_job = $2
+            WHERE name = $1
+            RETURNING status, stop_scheduled"#,
+            self.name,
+            self.current_job,
+        )
+        .fetch_one(self.pool)
+        .await?;
+        self.status = row.status.to_owned();
+        self.stop_scheduled = row.stop_scheduled;
+        Ok(self)
+    }
+
     pub async fn commit(self) -> Result<Control<'a>> {
         sqlx::query!(
             r#"
-        INSERT INTO asdb_jobs.controls (name, status, stop_scheduled, version)
-            VALUES ($1, $2, $3, $4)
+        INSERT INTO asdb_jobs.controls
+            (name, status, stop_scheduled, version, jobtypes, host, current_job, last_heartbeat)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, now())
         ON CONFLICT (name)
         DO UPDATE
-            SET status = $2, stop_scheduled = $3, version = $4"#,
+            SET status = $2, stop_scheduled = $3, version = $4, jobtypes = $5, host = $6,
+                current_job = $7, last_heartbeat = now()"#,
             self.name,
             self.status,
             self.stop_scheduled,
-            self.version
+            self.version,
+            self.jobtypes.as_deref(),
+            self.host,
+            self.current_job,
         )
         .execute(self.pool)
         .await?;
@@ -1,28 +1,95 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::string::ToString;
 
 use chrono::prelude::*;
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::jobs::{blast, clusterblast, comparippson, ping, stored_query};
+use crate::jobs::{assembly_download, blast, clusterblast, comparippson, ping, stored_query};
 use crate::{Error, Result};
 
+/// ID of a job. Job IDs name the job directories, so only UUIDs are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct JobId(Uuid);
+
+impl JobId {
+    /// A new random job ID
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// Directory of the job's files below the job base directory
+    pub fn dir(&self, jobdir: &Path) -> PathBuf {
+        jobdir.join(self.to_string())
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+impl FromStr for JobId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Uuid::parse_str(s)
+            .map(Self)
+            .map_err(|_| Error::InvalidRequest(format!("Invalid job ID {s:?}")))
+    }
+}
+
+impl From<Uuid> for JobId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, strum::Display)]
 #[strum(serialize_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum JobType {
+    AssemblyDownload(assembly_download::AssemblyDownload),
     ClusterBlast(clusterblast::ClusterBlast),
     CompaRiPPson(comparippson::CompaRiPPson),
     Ping(ping::Ping),
     StoredQuery(stored_query::StoredQuery),
 }
 
+impl JobType {
+    /// URL of the result file of job types producing downloads, once it exists
+    pub fn download(&self) -> Option<&str> {
+        match self {
+            JobType::AssemblyDownload(d) => d.filename.as_deref(),
+            JobType::StoredQuery(q) => q.filename.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Names of the job types as stored in the database, for runners only handling some of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, strum::AsRefStr, strum::EnumIter)]
+#[value(rename_all = "lower")]
+#[strum(serialize_all = "lowercase")]
+pub enum JobKind {
+    AssemblyDownload,
+    ClusterBlast,
+    CompaRiPPson,
+    Ping,
+    StoredQuery,
+}
+
 #[derive(
     Debug, Deserialize, Serialize, Clone, strum::Display, strum::AsRefStr, strum::EnumString,
 )]
@@ -38,34 +105,106 @@ pub enum JobStatus {
 
 #[derive(Debug)]
 pub struct JobEntry {
-    pub id: String,
+    pub id: JobId,
     pub jobtype: JobType,
     pub status: JobStatus,
     pub runner: String,
     pub submitted_date: DateTime<Utc>,
+    /// Set by the database when the job is first committed as running
+    pub started_date: Option<DateTime<Utc>>,
+    /// Set by the database when the job is first committed as done or failed
+    pub finished_date: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    /// Front-end session the job was submitted from, if any
+    pub session_id: Option<String>,
+    /// How often the job was re-queued after a transient failure
+    pub retries: i32,
+    /// Re-queued jobs aren't picked up again before this time
+    pub retry_after: Option<DateTime<Utc>>,
     version: i32,
 }
 
+pub const DEFAULT_LIST_LIMIT: i64 = 50;
+pub const MAX_LIST_LIMIT: i64 = 500;
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobOrder {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+/// Filters and paging for listing jobs
+#[derive(Debug, Default, Deserialize)]
+pub struct JobFilter {
+    pub status: Option<JobStatus>,
+    #[serde(rename = "type")]
+    pub jobtype: Option<String>,
+    #[serde(default)]
+    pub order: JobOrder,
+    pub session: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl JobFilter {
+    pub fn limit(&self) -> i64 {
+        self.limit
+            .unwrap_or(DEFAULT_LIST_LIMIT)
+            .clamp(1, MAX_LIST_LIMIT)
+    }
+
+    pub fn offset(&self) -> i64 {
+        self.offset.unwrap_or_default().max(0)
+    }
+}
+
+/// Overview of the job queue health
+#[derive(Debug, Default, Serialize)]
+pub struct QueueStats {
+    pub by_status: BTreeMap<String, i64>,
+    pub by_type: BTreeMap<String, i64>,
+    /// Average runtime in seconds of the jobs that finished in the last 24 hours
+    pub average_runtime: Option<f64>,
+    pub counters: BTreeMap<String, i64>,
+}
+
+/// Job activity of a single job type on a single day
+#[derive(Debug, Default, Serialize)]
+pub struct DailyJobStats {
+    pub date: NaiveDate,
+    pub jobtype: String,
+    pub submitted: i64,
+    pub done: i64,
+    pub error: i64,
+}
+
 impl JobEntry {
     pub fn new(jobtype: JobType) -> Self {
-        let id = Uuid::new_v4().to_string();
         Self {
-            id,
+            id: JobId::generate(),
             jobtype,
             status: JobStatus::Pending,
             runner: "".to_owned(),
             submitted_date: Utc::now(),
+            started_date: None,
+            finished_date: None,
+            error: None,
+            session_id: None,
+            retries: 0,
+            retry_after: None,
             version: 0,
         }
     }
 
-    pub async fn from_db(pool: &PgPool, id: &str) -> Result<Self> {
+    pub async fn from_db(pool: &PgPool, id: &JobId) -> Result<Self> {
         let job = sqlx::query_as!(
             DbJob,
             r#"
             SELECT * FROM asdb_jobs.jobs
                 WHERE id = $1"#,
-            id,
+            id.to_string(),
         )
         .fetch_one(pool)
         .await?;
@@ -73,14 +212,35 @@ impl JobEntry {
         Ok(job.try_into()?)
     }
 
-    pub async fn next_pending(pool: &PgPool) -> Result<Option<Self>> {
+    /// Atomically mark the oldest pending job of one of the `jobtypes` as running on `runner`
+    /// and return it, any job type if the list is empty. Jobs being claimed by other runners
+    /// at the same time are skipped, so every job is only picked up once even with several
+    /// runners.
+    pub async fn claim_next_pending(
+        pool: &PgPool,
+        runner: &str,
+        jobtypes: &[JobKind],
+    ) -> Result<Option<Self>> {
+        let jobtypes: Vec<&str> = jobtypes.iter().map(JobKind::as_ref).collect();
         let job_opt = sqlx::query_as!(
             DbJob,
             r#"
-            SELECT * FROM asdb_jobs.jobs
-                WHERE status = 'pending'
-                ORDER BY submitted_date
-                LIMIT 1"#,
+            UPDATE asdb_jobs.jobs SET
+                status = 'running',
+                runner = $1,
+                started_date = COALESCE(started_date, now()),
+                version = version + 1
+            WHERE id = (
+                SELECT id FROM asdb_jobs.jobs
+                    WHERE status = 'pending' AND (retry_after IS NULL OR retry_after <= now())
+                        AND (cardinality($2::text[]) = 0 OR jobtype = ANY($2))
+                    ORDER BY submitted_date
+                    LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *"#,
+            runner,
+            &jobtypes as &[&str],
         )
         .fetch_optional(pool)
         .await?;
@@ -92,24 +252,124 @@ impl JobEntry {
         Ok(None)
     }
 
-    pub async fn next_to_clean(pool: &PgPool, days: f64) -> Result<Option<Self>> {
-        let job_opt = sqlx::query_as!(
+    pub async fn next_to_clean(
+        pool: &PgPool,
+        days: f64,
+        statuses: &[JobStatus],
+    ) -> Result<Option<Self>> {
+        let mut jobs = Self::to_clean(pool, days, statuses, 1).await?;
+        Ok(jobs.pop())
+    }
+
+    /// Get up to `limit` jobs that are older than `days` or marked for deletion,
+    /// restricted to the given statuses if there are any
+    pub async fn to_clean(
+        pool: &PgPool,
+        days: f64,
+        statuses: &[JobStatus],
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let statuses: Vec<String> = statuses.iter().map(|s| s.to_string()).collect();
+        let jobs = sqlx::query_as!(
             DbJob,
             r#"
             SELECT * FROM asdb_jobs.jobs
-                WHERE submitted_date < now() - interval '1 day' * $1 OR status = 'delete'
+                WHERE (submitted_date < now() - interval '1 day' * $1 OR status = 'delete')
+                    AND (cardinality($2::text[]) = 0 OR status = ANY($2))
                 ORDER BY submitted_date
-                LIMIT 1"#,
+                LIMIT $3"#,
             days,
+            &statuses,
+            limit,
         )
-        .fetch_optional(pool)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(JobEntry::try_from)
+        .collect::<Result<Vec<Self>>>()?;
+
+        Ok(jobs)
+    }
+
+    /// Get the subset of `ids` that belong to jobs in the database
+    pub async fn existing_ids(pool: &PgPool, ids: &[JobId]) -> Result<Vec<JobId>> {
+        let ids: Vec<String> = ids.iter().map(JobId::to_string).collect();
+        sqlx::query_scalar!("SELECT id FROM asdb_jobs.jobs WHERE id = ANY($1)", &ids)
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|id| id.parse())
+            .collect()
+    }
+
+    pub async fn list(pool: &PgPool, filter: &JobFilter) -> Result<Vec<Self>> {
+        let jobs = sqlx::query_as!(
+            DbJob,
+            r#"
+            SELECT * FROM asdb_jobs.jobs
+                WHERE ($1::text IS NULL OR status = $1)
+                    AND ($2::text IS NULL OR jobtype = $2)
+                    AND ($6::text IS NULL OR session_id = $6)
+                ORDER BY
+                    CASE WHEN $3 THEN submitted_date END ASC,
+                    CASE WHEN NOT $3 THEN submitted_date END DESC,
+                    id
+                LIMIT $4 OFFSET $5"#,
+            filter.status.as_ref().map(|s| s.to_string()),
+            filter.jobtype.as_ref().map(|t| t.to_lowercase()),
+            filter.order == JobOrder::Oldest,
+            filter.limit(),
+            filter.offset(),
+            filter.session,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(JobEntry::try_from)
+        .collect::<Result<Vec<Self>>>()?;
+
+        Ok(jobs)
+    }
+
+    /// Number of jobs matching the filter, ignoring its paging
+    pub async fn count(pool: &PgPool, filter: &JobFilter) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM asdb_jobs.jobs
+                WHERE ($1::text IS NULL OR status = $1)
+                    AND ($2::text IS NULL OR jobtype = $2)
+                    AND ($3::text IS NULL OR session_id = $3)"#,
+            filter.status.as_ref().map(|s| s.to_string()),
+            filter.jobtype.as_ref().map(|t| t.to_lowercase()),
+            filter.session,
+        )
+        .fetch_one(pool)
         .await?;
+        Ok(count)
+    }
 
-        if let Some(job) = job_opt {
-            return Ok(Some(JobEntry::try_from(job)?));
-        }
+    /// Mark jobs that have been running for longer than `hours` as failed, returning their IDs.
+    /// Time spent waiting in the queue doesn't count.
+    pub async fn fail_stale(pool: &PgPool, hours: f64) -> Result<Vec<String>> {
+        let ids = sqlx::query!(
+            r#"
+            UPDATE asdb_jobs.jobs SET
+                status = 'error',
+                error = 'Job went stale while running',
+                finished_date = now(),
+                version = version + 1
+            WHERE status = 'running'
+                AND COALESCE(started_date, submitted_date) < now() - interval '1 hour' * $1
+            RETURNING id"#,
+            hours,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.id)
+        .collect();
 
-        Ok(None)
+        Ok(ids)
     }
 
     pub async fn fetch(&mut self, pool: &PgPool) -> Result<&mut Self> {
@@ -118,7 +378,7 @@ impl JobEntry {
             r#"
             SELECT * FROM asdb_jobs.jobs
                 WHERE id = $1"#,
-            self.id,
+            self.id.to_string(),
         )
         .fetch_one(pool)
         .await?
@@ -126,6 +386,11 @@ impl JobEntry {
 
         self.jobtype = job.jobtype;
         self.status = job.status;
+        self.started_date = job.started_date;
+        self.finished_date = job.finished_date;
+        self.error = job.error;
+        self.retries = job.retries;
+        self.retry_after = job.retry_after;
         Ok(self)
     }
 
@@ -138,7 +403,7 @@ impl JobEntry {
             SELECT COUNT(*) FROM asdb_jobs.jobs
                 WHERE id = $1
             "#,
-            self.id,
+            db_job.id,
         )
         .fetch_one(pool)
         .await?
@@ -147,8 +412,8 @@ impl JobEntry {
         if count == 0 {
             sqlx::query!(
                 r#"
-                INSERT INTO asdb_jobs.jobs (id, jobtype, status, runner, submitted_date, data, results, version)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                INSERT INTO asdb_jobs.jobs (id, jobtype, status, runner, submitted_date, data, results, version, error, session_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             "#,
             db_job.id,
             db_job.jobtype,
@@ -157,23 +422,33 @@ impl JobEntry {
             db_job.submitted_date,
             db_job.data,
             db_job.results,
-            db_job.version
+            db_job.version,
+            db_job.error,
+            db_job.session_id,
             )
             .execute(pool)
             .await?;
+            self.record_event(pool, "submitted").await?;
             tx.commit().await?;
             return Ok(self);
         }
-        let new_version = sqlx::query!(
+        let updated = sqlx::query!(
             r#"
             UPDATE asdb_jobs.jobs SET
                 status = $3,
                 runner = $4,
                 data = $5,
                 results = $6,
+                error = $7,
+                retries = $8,
+                retry_after = $9,
+                started_date = CASE WHEN $3 = 'running'
+                    THEN COALESCE(started_date, now()) ELSE started_date END,
+                finished_date = CASE WHEN $3 IN ('done', 'error')
+                    THEN COALESCE(finished_date, now()) ELSE finished_date END,
                 version = ($2 + 1)
             WHERE id = $1 AND version = $2
-            RETURNING version
+            RETURNING version, started_date, finished_date
             "#,
             db_job.id,
             db_job.version,
@@ -181,26 +456,117 @@ impl JobEntry {
             db_job.runner,
             db_job.data,
             db_job.results,
+            db_job.error,
+            db_job.retries,
+            db_job.retry_after,
         )
         .fetch_one(pool)
-        .await?
-        .version;
+        .await?;
+        if self.finished_date.is_none() && updated.finished_date.is_some() {
+            self.record_event(pool, &db_job.status).await?;
+        }
         tx.commit().await?;
-        self.version = new_version;
+        self.version = updated.version;
+        self.started_date = updated.started_date.map(|d| d.and_utc());
+        self.finished_date = updated.finished_date.map(|d| d.and_utc());
 
         Ok(self)
     }
 
     pub async fn delete(&self, pool: &PgPool) -> Result<()> {
         let tx = pool.begin().await?;
-        sqlx::query!("DELETE FROM asdb_jobs.jobs WHERE id = $1", self.id)
-            .execute(pool)
-            .await?;
+        sqlx::query!(
+            "DELETE FROM asdb_jobs.jobs WHERE id = $1",
+            self.id.to_string()
+        )
+        .execute(pool)
+        .await?;
         self.update_stats(pool).await?;
         tx.commit().await?;
         Ok(())
     }
 
+    pub async fn queue_stats(pool: &PgPool) -> Result<QueueStats> {
+        let mut stats = QueueStats::default();
+
+        for row in sqlx::query!(
+            r#"
+            SELECT status, jobtype, COUNT(*) AS "count!"
+                FROM asdb_jobs.jobs
+                GROUP BY status, jobtype"#,
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            *stats.by_status.entry(row.status).or_default() += row.count;
+            *stats.by_type.entry(row.jobtype).or_default() += row.count;
+        }
+
+        stats.average_runtime = sqlx::query_scalar!(
+            r#"
+            SELECT AVG(EXTRACT(EPOCH FROM finished_date - started_date))::float8
+                FROM asdb_jobs.jobs
+                WHERE finished_date > now() - interval '24 hours'
+                    AND started_date IS NOT NULL"#,
+        )
+        .fetch_one(pool)
+        .await?;
+
+        stats.counters = sqlx::query!("SELECT name, value FROM asdb_jobs.counters")
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|row| (row.name, row.value))
+            .collect();
+
+        Ok(stats)
+    }
+
+    /// Daily job counts of the last `days` days, only days with any activity are listed
+    pub async fn timeseries(pool: &PgPool, days: i32) -> Result<Vec<DailyJobStats>> {
+        let mut series: BTreeMap<(NaiveDate, String), DailyJobStats> = BTreeMap::new();
+        for row in sqlx::query!(
+            r#"
+            SELECT day, jobtype, event, count FROM asdb_jobs.counters_history
+                WHERE day > CURRENT_DATE - $1::int"#,
+            days,
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            let entry = series
+                .entry((row.day, row.jobtype.to_owned()))
+                .or_insert_with(|| DailyJobStats {
+                    date: row.day,
+                    jobtype: row.jobtype,
+                    ..Default::default()
+                });
+            match row.event.as_str() {
+                "submitted" => entry.submitted += row.count,
+                "done" => entry.done += row.count,
+                "error" => entry.error += row.count,
+                _ => {}
+            }
+        }
+        Ok(series.into_values().collect())
+    }
+
+    /// Count a submission, completion or failure in today's job history
+    async fn record_event(&self, pool: &PgPool, event: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO asdb_jobs.counters_history (day, jobtype, event, count)
+                VALUES (CURRENT_DATE, $1, $2, 1)
+            ON CONFLICT (day, jobtype, event) DO UPDATE SET count = counters_history.count + 1
+            "#,
+            self.jobtype.to_string(),
+            event,
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
     async fn update_stats(&self, pool: &PgPool) -> Result<()> {
         sqlx::query!(
             r#"UPDATE asdb_jobs.counters SET value = value + 1 WHERE name = 'total_jobs'"#,
@@ -225,6 +591,12 @@ impl TryFrom<DbJob> for JobEntry {
 
     fn try_from(value: DbJob) -> std::result::Result<Self, Self::Error> {
         let jobtype = match value.jobtype.as_ref() {
+            "assemblydownload" => {
+                let input: assembly_download::AssemblyDownloadInput =
+                    serde_json::from_value(value.data)?;
+                let filename: Option<String> = serde_json::from_value(value.results).ok();
+                JobType::AssemblyDownload(assembly_download::AssemblyDownload { input, filename })
+            }
             "clusterblast" => {
                 let input: blast::BlastInput = serde_json::from_value(value.data)?;
                 let results: clusterblast::ClusterBlastResults =
@@ -232,7 +604,7 @@ impl TryFrom<DbJob> for JobEntry {
                 JobType::ClusterBlast(clusterblast::ClusterBlast { input, results })
             }
             "comparippson" => {
-                let input: blast::BlastInput = serde_json::from_value(value.data)?;
+                let input: comparippson::CompaRiPPsonInput = serde_json::from_value(value.data)?;
                 let results: comparippson::CompaRiPPsonResults =
                     serde_json::from_value(value.results)?;
                 JobType::CompaRiPPson(comparippson::CompaRiPPson { input, results })
@@ -244,8 +616,10 @@ impl TryFrom<DbJob> for JobEntry {
             }
             "storedquery" => {
                 let input: stored_query::StoredQueryInput = serde_json::from_value(value.data)?;
-                let filename: Option<String> = serde_json::from_value(value.results).ok();
-                JobType::StoredQuery(stored_query::StoredQuery { input, filename })
+                JobType::StoredQuery(stored_query::StoredQuery::from_results(
+                    input,
+                    value.results,
+                ))
             }
             _ => {
                 return Err(Error::InvalidRequest(format!(
@@ -255,11 +629,17 @@ impl TryFrom<DbJob> for JobEntry {
             }
         };
         Ok(Self {
-            id: value.id,
+            id: value.id.parse()?,
             jobtype,
             status: JobStatus::from_str(&value.status).or(Err(Error::ParserError))?,
             runner: value.runner.unwrap_or_default(),
             submitted_date: value.submitted_date.and_utc(),
+            started_date: value.started_date.map(|d| d.and_utc()),
+            finished_date: value.finished_date.map(|d| d.and_utc()),
+            error: value.error,
+            session_id: value.session_id,
+            retries: value.retries,
+            retry_after: value.retry_after.map(|d| d.and_utc()),
             version: value.version,
         })
     }
@@ -275,6 +655,12 @@ struct DbJob {
     pub data: sqlx::types::JsonValue,
     pub results: sqlx::types::JsonValue,
     pub version: i32,
+    pub error: Option<String>,
+    pub started_date: Option<NaiveDateTime>,
+    pub finished_date: Option<NaiveDateTime>,
+    pub session_id: Option<String>,
+    pub retries: i32,
+    pub retry_after: Option<NaiveDateTime>,
 }
 
 impl TryFrom<&JobEntry> for DbJob {
@@ -282,6 +668,11 @@ impl TryFrom<&JobEntry> for DbJob {
 
     fn try_from(value: &JobEntry) -> std::result::Result<Self, Self::Error> {
         let (jobtype, data, results) = match value.jobtype.clone() {
+            JobType::AssemblyDownload(d) => (
+                "assemblydownload".to_string(),
+                serde_json::to_value(d.input)?,
+                serde_json::to_value(d.filename)?,
+            ),
             JobType::Ping(ping) => (
                 "ping".to_string(),
                 serde_json::to_value(ping.greeting)?,
@@ -299,13 +690,13 @@ impl TryFrom<&JobEntry> for DbJob {
             ),
             JobType::StoredQuery(q) => (
                 "storedquery".to_string(),
-                serde_json::to_value(q.input)?,
-                serde_json::to_value(q.filename)?,
+                serde_json::to_value(&q.input)?,
+                q.results(),
             ),
         };
 
         Ok(Self {
-            id: value.id.to_owned(),
+            id: value.id.to_string(),
             jobtype,
             status: value.status.to_string(),
             runner: Some(value.runner.to_owned()),
@@ -313,6 +704,121 @@ impl TryFrom<&JobEntry> for DbJob {
             data,
             results,
             version: value.version,
+            error: value.error.to_owned(),
+            started_date: value.started_date.map(|d| d.naive_utc()),
+            finished_date: value.finished_date.map(|d| d.naive_utc()),
+            session_id: value.session_id.to_owned(),
+            retries: value.retries,
+            retry_after: value.retry_after.map(|d| d.naive_utc()),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_fail_stale(pool: PgPool) {
+        crate::testutils::seed(&pool).await.unwrap();
+        let mut job = JobEntry::new(JobType::Ping(ping::Ping::new("hello")));
+        job.commit(&pool).await.unwrap();
+        sqlx::query(
+            "UPDATE asdb_jobs.jobs SET submitted_date = now() - interval '3 hours' WHERE id = $1",
+        )
+        .bind(job.id.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // Waiting in the queue for longer than the limit doesn't make a job stale
+        JobEntry::claim_next_pending(&pool, "runner", &[])
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(JobEntry::fail_stale(&pool, 1.0).await.unwrap().is_empty());
+
+        sqlx::query(
+            "UPDATE asdb_jobs.jobs SET started_date = now() - interval '2 hours' WHERE id = $1",
+        )
+        .bind(job.id.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            JobEntry::fail_stale(&pool, 1.0).await.unwrap(),
+            [job.id.to_string()]
+        );
+    }
+
+    #[test]
+    fn test_job_id() {
+        let id: JobId = "0E5C1B3A-7F2D-4C8E-9A1B-2C3D4E5F6A7B".parse().unwrap();
+        assert_eq!(id.to_string(), "0e5c1b3a-7f2d-4c8e-9a1b-2c3d4e5f6a7b");
+        assert_eq!(
+            id.dir(Path::new("/jobs")),
+            Path::new("/jobs/0e5c1b3a-7f2d-4c8e-9a1b-2c3d4e5f6a7b")
+        );
+        assert_eq!(
+            serde_json::to_value(id).unwrap(),
+            "0e5c1b3a-7f2d-4c8e-9a1b-2c3d4e5f6a7b"
+        );
+
+        let invalid = [
+            "",
+            "bob",
+            "../etc",
+            "../../0e5c1b3a-7f2d-4c8e-9a1b-2c3d4e5f6a7b",
+            "0e5c1b3a-7f2d-4c8e-9a1b-2c3d4e5f6a7b/..",
+        ];
+        for input in invalid {
+            assert!(input.parse::<JobId>().is_err(), "{input}");
+            assert!(
+                serde_json::from_value::<JobId>(serde_json::json!(input)).is_err(),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_job_filter_paging() {
+        let tests = [
+            (None, None, DEFAULT_LIST_LIMIT, 0),
+            (Some(10), Some(20), 10, 20),
+            (Some(0), Some(-5), 1, 0),
+            (Some(100_000), None, MAX_LIST_LIMIT, 0),
+        ];
+        for (limit, offset, expected_limit, expected_offset) in tests {
+            let filter = JobFilter {
+                limit,
+                offset,
+                ..Default::default()
+            };
+            assert_eq!(filter.limit(), expected_limit, "{limit:?}");
+            assert_eq!(filter.offset(), expected_offset, "{offset:?}");
+        }
+    }
+
+    #[test]
+    fn test_job_kind_names() {
+        use strum::IntoEnumIterator;
+
+        let names: Vec<String> = JobKind::iter()
+            .map(|kind| kind.as_ref().to_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "assemblydownload",
+                "clusterblast",
+                "comparippson",
+                "ping",
+                "storedquery"
+            ]
+        );
+        for kind in JobKind::iter() {
+            let parsed = JobKind::from_str(kind.as_ref(), false).unwrap();
+            assert_eq!(parsed, kind);
+        }
+    }
+}
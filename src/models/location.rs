@@ -14,10 +14,11 @@ use serde::{Deserialize, Serialize};
 
 use crate::{Error, Result};
 
-#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum Strand {
     Forward,
     Reverse,
+    #[default]
     Unstranded,
 }
 
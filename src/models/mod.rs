@@ -4,3 +4,4 @@
 pub mod control;
 pub mod job;
 pub mod location;
+pub mod subscription;
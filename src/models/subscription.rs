@@ -0,0 +1,155 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::query::Query;
+use crate::{Error, Result};
+
+/// A saved query that gets re-run whenever the database changes
+#[derive(Debug, Clone, Serialize)]
+pub struct Subscription {
+    pub id: String,
+    pub query: serde_json::Value,
+    /// Also re-run the query after this many hours, even if the database didn't change
+    pub interval_hours: Option<i32>,
+    pub webhook_url: Option<String>,
+    pub session_id: Option<String>,
+    pub created_date: DateTime<Utc>,
+    /// Database fingerprint the query last ran against
+    pub db_version: Option<String>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_job_id: Option<String>,
+}
+
+impl Subscription {
+    pub fn new(
+        query: &Query,
+        interval_hours: Option<i32>,
+        webhook_url: Option<String>,
+        session_id: Option<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            id: Uuid::new_v4().to_string(),
+            query: serde_json::to_value(query)?,
+            interval_hours,
+            webhook_url,
+            session_id,
+            created_date: Utc::now(),
+            db_version: None,
+            last_run: None,
+            last_job_id: None,
+        })
+    }
+
+    pub fn query(&self) -> Result<Query> {
+        Ok(serde_json::from_value(self.query.clone())?)
+    }
+
+    pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO asdb_jobs.subscriptions
+                (id, query, interval_hours, webhook_url, session_id, created_date)
+            VALUES ($1, $2, $3, $4, $5, $6)"#,
+            self.id,
+            self.query,
+            self.interval_hours,
+            self.webhook_url,
+            self.session_id,
+            self.created_date.naive_utc(),
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn from_db(pool: &PgPool, id: &str) -> Result<Self> {
+        let row = sqlx::query!("SELECT * FROM asdb_jobs.subscriptions WHERE id = $1", id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        Ok(Self {
+            id: row.id,
+            query: row.query,
+            interval_hours: row.interval_hours,
+            webhook_url: row.webhook_url,
+            session_id: row.session_id,
+            created_date: row.created_date.and_utc(),
+            db_version: row.db_version,
+            last_run: row.last_run.map(|d| d.and_utc()),
+            last_job_id: row.last_job_id,
+        })
+    }
+
+    /// IDs of subscriptions that haven't run against the database version `db_version` yet,
+    /// or whose interval has passed since their last run
+    pub async fn due(pool: &PgPool, db_version: &str) -> Result<Vec<String>> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM asdb_jobs.subscriptions
+                WHERE db_version IS DISTINCT FROM $1
+                    OR last_run < now() - interval '1 hour' * interval_hours
+                ORDER BY created_date"#,
+            db_version,
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(ids)
+    }
+
+    /// Record that the subscription is running against `db_version`. Returns false if it isn't
+    /// due anymore, e.g. because another runner claimed it first.
+    pub async fn claim(pool: &PgPool, id: &str, db_version: &str) -> Result<bool> {
+        let claimed = sqlx::query_scalar!(
+            r#"
+            UPDATE asdb_jobs.subscriptions SET
+                db_version = $2,
+                last_run = now()
+            WHERE id = $1 AND (
+                db_version IS DISTINCT FROM $2
+                OR last_run < now() - interval '1 hour' * interval_hours
+            )
+            RETURNING id"#,
+            id,
+            db_version,
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(claimed.is_some())
+    }
+
+    /// Number of subscriptions registered by a session
+    pub async fn count_for_session(pool: &PgPool, session_id: &str) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM asdb_jobs.subscriptions WHERE session_id = $1"#,
+            session_id,
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(count)
+    }
+
+    pub async fn set_last_job(&mut self, pool: &PgPool, job_id: &str) -> Result<()> {
+        sqlx::query!(
+            "UPDATE asdb_jobs.subscriptions SET last_job_id = $2 WHERE id = $1",
+            self.id,
+            job_id,
+        )
+        .execute(pool)
+        .await?;
+        self.last_job_id = Some(job_id.to_owned());
+        Ok(())
+    }
+
+    pub async fn delete(&self, pool: &PgPool) -> Result<()> {
+        sqlx::query!("DELETE FROM asdb_jobs.subscriptions WHERE id = $1", self.id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use std::borrow::Cow;
+use std::fmt;
+
 use nom::{
     bytes::complete::tag,
     sequence::{delimited, terminated},
@@ -10,7 +13,10 @@ use serde::{Deserialize, Serialize};
 
 use super::{
     filters::Filter,
-    parser::{contrib::take_until_unbalanced, parse_number, with_mustache},
+    parser::{
+        contrib::{quoted_length, take_until_unbalanced},
+        parse_number, with_mustache,
+    },
 };
 use crate::search::Category;
 use crate::Error;
@@ -37,6 +43,25 @@ impl Expression {
             count,
         }
     }
+
+    /// Check if a boolean filter with the given name is set
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.filters
+            .iter()
+            .any(|f| matches!(f, Filter::Boolean(b) if b.name == name))
+    }
+
+    /// Check that the expression only has a count if its category can be counted
+    pub fn check_count(&self) -> Result<(), Error> {
+        if self.count != 1 && !self.category.is_countable() {
+            return Err(Error::InvalidRequest(format!(
+                "{} queries can't be counted, remove the {}* prefix",
+                self.category, self.count
+            )));
+        }
+        Ok(())
+    }
+
     pub fn parse(input: &str) -> IResult<&str, Self, Error> {
         let count: i64;
         let remaining: &str;
@@ -63,16 +88,75 @@ impl Expression {
             filters.push(filter);
         }
 
-        let parts: Vec<&str> = term.split("|").collect();
-        let (_, category) = Category::parse(parts[0])?;
-        let value = match parts.len() {
-            1 => None,
-            2 => Some(parts[1]),
-            _ => return Err(nom::Err::Failure(Error::ParserError)),
+        let (category, value) = match term.split_once('|') {
+            Some((category, raw)) => (category, Some(parse_value(raw)?)),
+            None => (term, None),
         };
+        let (_, category) = Category::parse(category)?;
+
+        let expr = Expression::new(category, value.as_deref(), &filters, count);
+        expr.check_count().map_err(nom::Err::Failure)?;
+        Ok((remaining, expr))
+    }
+}
+
+/// Renders the canonical search string form, e.g. `2*{[type|NRPS] WITH [rank|<=:3]}`
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count != 1 {
+            write!(f, "{}*", self.count)?;
+        }
+        let category: &'static str = (&self.category).into();
+        write!(f, "{{[{category}")?;
+        if !self.value.is_empty() {
+            write!(f, "|{}", quote_value(&self.value))?;
+        }
+        write!(f, "]")?;
+        for filter in &self.filters {
+            write!(f, " WITH {filter}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Characters that need a value to be quoted
+const SPECIAL_CHARACTERS: &[char] = &['{', '}', '[', ']', '(', ')', '|', '"', '\\'];
+
+/// Parse an expression value, either as is or as a double-quoted string in which
+/// `\"` and `\\` stand for a literal quote and backslash
+fn parse_value(raw: &str) -> Result<String, nom::Err<Error>> {
+    let Some(quoted) = raw.strip_prefix('"') else {
+        if raw.contains(['|', '"']) {
+            return Err(nom::Err::Failure(Error::ParserError));
+        }
+        return Ok(raw.to_owned());
+    };
+    if quoted_length(quoted) != Some(quoted.len()) {
+        return Err(nom::Err::Failure(Error::ParserError));
+    }
 
-        Ok((remaining, Expression::new(category, value, &filters, count)))
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted[..quoted.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                _ => return Err(nom::Err::Failure(Error::ParserError)),
+            },
+            c => value.push(c),
+        }
     }
+    Ok(value)
+}
+
+/// Render a value for a search string, quoting and escaping it if it contains brackets,
+/// pipes, quotes, backslashes or leading or trailing whitespace
+pub fn quote_value(value: &str) -> Cow<'_, str> {
+    if !value.contains(SPECIAL_CHARACTERS) && value.trim() == value {
+        return Cow::Borrowed(value);
+    }
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    Cow::Owned(format!("\"{escaped}\""))
 }
 
 #[cfg(test)]
@@ -88,7 +172,15 @@ mod tests {
                 "{[acc|bob]}",
                 Expression::new(Category::Acc, Some("bob"), &[], 1),
             ),
-            ("3*{[acc]}", Expression::new(Category::Acc, None, &[], 3)),
+            ("3*{[type]}", Expression::new(Category::Type, None, &[], 3)),
+            (
+                "2*{[knowncluster|BGC0000315]}",
+                Expression::new(Category::KnownCluster, Some("BGC0000315"), &[], 2),
+            ),
+            (
+                "1*{[genus]}",
+                Expression::new(Category::Genus, None, &[], 1),
+            ),
             (
                 "{[acc] WITH [charlie|==:30]}",
                 Expression::new(
@@ -108,4 +200,73 @@ mod tests {
             assert_eq!(output, expected_output);
         }
     }
+
+    #[test]
+    fn test_parse_uncountable() {
+        for input in ["3*{[acc]}", "2*{[genus|Streptomyces]}", "2*{[contigedge]}"] {
+            match Expression::parse(input) {
+                Err(nom::Err::Failure(Error::InvalidRequest(message))) => {
+                    assert!(message.contains("can't be counted"), "{message}")
+                }
+                other => panic!("{input}: unexpected result {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_quoted_value() {
+        let tests = [
+            (
+                r#"{[species|"Streptomyces coelicolor A3(2)"]}"#,
+                "Streptomyces coelicolor A3(2)",
+            ),
+            (r#"{[strain|"A3(2"]}"#, "A3(2"),
+            (r#"{[strain|"a|b]c"]}"#, "a|b]c"),
+            (r#"{[strain|"say \"hi\" \\o/"]}"#, r#"say "hi" \o/"#),
+            (r#"{[strain|""]}"#, ""),
+        ];
+        for (input, expected) in tests {
+            let (remaining, output) = Expression::parse(input).unwrap();
+            assert_eq!(remaining, "", "{input}");
+            assert_eq!(output.value, expected, "{input}");
+        }
+
+        let invalid = [
+            r#"{[strain|"unterminated]}"#,
+            r#"{[strain|"quoted" trailing]}"#,
+            r#"{[strain|"bad \escape"]}"#,
+            r#"{[strain|un"quoted]}"#,
+            "{[strain|a|b]}",
+        ];
+        for input in invalid {
+            assert!(Expression::parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_quote_value_round_trip() {
+        let tests = [
+            ("NC_003888", "NC_003888"),
+            ("Streptomyces coelicolor", "Streptomyces coelicolor"),
+            (
+                "Streptomyces coelicolor A3(2)",
+                r#""Streptomyces coelicolor A3(2)""#,
+            ),
+            (" padded", r#"" padded""#),
+            (r#"a "b" \c"#, r#""a \"b\" \\c""#),
+            ("x|y", r#""x|y""#),
+        ];
+        for (value, expected) in tests {
+            let quoted = quote_value(value);
+            assert_eq!(quoted, expected);
+            let input = format!("{{[strain|{quoted}]}}");
+            let (_, output) = Expression::parse(&input).unwrap();
+            assert_eq!(output.value, value, "{input}");
+
+            // values survive the JSON form of a query unchanged
+            let json = serde_json::to_string(&output).unwrap();
+            let restored: Expression = serde_json::from_str(&json).unwrap();
+            assert_eq!(restored, output);
+        }
+    }
 }
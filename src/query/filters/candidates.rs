@@ -0,0 +1,83 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use sqlx::{postgres::PgArguments, Arguments, PgPool};
+
+use crate::api::region::RegionId;
+use crate::query::{filters::Filter, Expression};
+use crate::search::Category;
+
+use crate::{Error, Result};
+
+/// Filter regions on the BGC types or the number of protoclusters of the candidate clusters
+/// matching the expression. A candidate cluster consists of the protoclusters it contains.
+pub async fn apply_filter(
+    pool: &PgPool,
+    regions_to_filter: &[RegionId],
+    expr: &Expression,
+    filter: &Filter,
+) -> Result<Vec<RegionId>> {
+    if expr.category != Category::CandidateKind {
+        return Err(Error::InvalidRequest(format!(
+            "{} query does not support candidate filters",
+            expr.category
+        )));
+    }
+
+    let ids: Vec<i32> = regions_to_filter.iter().map(|r| r.region_id).collect();
+    let mut args = PgArguments::default();
+    args.add(ids);
+    args.add(format!("%{}%", expr.value));
+    args.add(expr.count);
+
+    let condition = match (filter.name(), filter) {
+        ("bgctype", Filter::Text(f)) => {
+            args.add(f.value.to_owned());
+            "bool_or(t.term ILIKE $4)".to_string()
+        }
+        ("bgctype", _) => {
+            return Err(Error::InvalidRequest(
+                "bgctype filter needs a BGC type".to_string(),
+            ))
+        }
+        ("numprotoclusters", Filter::Numerical(f)) => {
+            args.add(f.value as f64);
+            "COUNT(DISTINCT p.protocluster_id) = $4".to_string()
+        }
+        ("numprotoclusters", _) => {
+            let (condition, values) =
+                filter.numeric_condition("COUNT(DISTINCT p.protocluster_id)", 4)?;
+            for value in values {
+                args.add(value);
+            }
+            condition
+        }
+        (name, _) => {
+            return Err(Error::InvalidRequest(format!(
+                "{} query does not support the {name} filter",
+                expr.category
+            )))
+        }
+    };
+
+    let sql = format!(
+        r#"
+    SELECT region_id FROM (
+        SELECT c.region_id FROM antismash.candidates AS c
+        JOIN antismash.candidate_types AS k USING (candidate_type_id)
+        JOIN antismash.protoclusters AS p ON (p.region_id = c.region_id
+            AND p.start_pos >= c.start_pos AND p.end_pos <= c.end_pos)
+        JOIN antismash.bgc_types AS t ON (t.bgc_type_id = p.bgc_type_id)
+        WHERE c.region_id = ANY($1) AND k.description ILIKE $2
+        GROUP BY c.region_id, c.candidate_id
+        HAVING {condition}
+    ) AS matching
+    GROUP BY region_id HAVING COUNT(*) >= $3
+        "#
+    );
+
+    let regions = sqlx::query_as_with::<_, RegionId, _>(&sql, args)
+        .fetch_all(pool)
+        .await?;
+    Ok(regions)
+}
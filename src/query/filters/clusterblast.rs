@@ -0,0 +1,107 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use sqlx::{postgres::PgArguments, Arguments, PgPool};
+
+use crate::api::region::{expression::ClusterBlastAlgorithm, RegionId};
+use crate::query::{filters::Filter, Expression};
+use crate::search::Category;
+
+use crate::{Error, Result};
+
+/// Filter regions on the similarity or rank of the ClusterBlast hits matching the expression
+pub async fn apply_filter(
+    pool: &PgPool,
+    regions_to_filter: &[RegionId],
+    expr: &Expression,
+    filter: &Filter,
+) -> Result<Vec<RegionId>> {
+    let Some(algorithm) = ClusterBlastAlgorithm::for_category(&expr.category) else {
+        return Err(Error::InvalidRequest(format!(
+            "{} query does not support clusterblast filters",
+            expr.category
+        )));
+    };
+
+    let (condition, values) = match (filter.name(), &expr.category) {
+        ("best", Category::KnownCluster) => match filter {
+            Filter::Boolean(_) => ("best_mibig_hit_acc ILIKE $2".to_string(), Vec::new()),
+            _ => {
+                return Err(Error::InvalidRequest(
+                    "best filter does not take a value".to_string(),
+                ))
+            }
+        },
+        ("similarity", category) => filter.numeric_condition(similarity_column(category), 4)?,
+        ("rank", _) => filter.numeric_condition("rank", 4)?,
+        (name, _) => {
+            return Err(Error::InvalidRequest(format!(
+                "{} query does not support the {name} filter",
+                expr.category
+            )))
+        }
+    };
+    let sql = format!(
+        r#"
+    SELECT DISTINCT region_id FROM antismash.regions
+    JOIN antismash.clusterblast_hits USING (region_id)
+    JOIN antismash.clusterblast_algorithms USING (algorithm_id)
+    WHERE region_id = ANY($1) AND acc ILIKE $2 AND name = $3 AND {condition}
+        "#
+    );
+
+    let ids: Vec<i32> = regions_to_filter.iter().map(|r| r.region_id).collect();
+
+    let mut args = PgArguments::default();
+    args.add(ids);
+    args.add(expr.value.to_owned());
+    args.add(algorithm.as_ref().to_owned());
+    for value in values {
+        args.add(value);
+    }
+
+    let regions = sqlx::query_as_with::<_, RegionId, _>(&sql, args)
+        .fetch_all(pool)
+        .await?;
+    Ok(regions)
+}
+
+/// The similarity shown for the most similar known cluster is stored on the region,
+/// prefer that one so search results match the region overview
+fn similarity_column(category: &Category) -> &'static str {
+    match category {
+        Category::KnownCluster => {
+            "COALESCE(CASE WHEN best_mibig_hit_acc ILIKE $2 THEN best_mibig_hit_similarity END, similarity)"
+        }
+        _ => "similarity",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::filters::{Operator, QualitativeFilter};
+
+    #[test]
+    fn test_similarity_condition() {
+        let filter = Filter::Qualitative(QualitativeFilter::new(
+            "similarity",
+            70.0,
+            Operator::GreaterOrEqual,
+        ));
+        let tests = [
+            (Category::ClusterBlast, "similarity >= $4"),
+            (
+                Category::KnownCluster,
+                "COALESCE(CASE WHEN best_mibig_hit_acc ILIKE $2 THEN best_mibig_hit_similarity END, similarity) >= $4",
+            ),
+        ];
+        for (category, expected) in tests {
+            let (condition, values) = filter
+                .numeric_condition(similarity_column(&category), 4)
+                .unwrap();
+            assert_eq!(condition, expected);
+            assert_eq!(values, vec![70.0]);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use sqlx::{postgres::PgArguments, Arguments, PgPool};
+
+use crate::api::region::RegionId;
+use crate::query::{filters::Filter, Expression};
+use crate::search::Category;
+
+use crate::{Error, Result};
+
+/// Filter regions on the score or e-value of the Pfam/TIGRFAM domains matching the expression
+pub async fn apply_filter(
+    pool: &PgPool,
+    regions_to_filter: &[RegionId],
+    expr: &Expression,
+    filter: &Filter,
+) -> Result<Vec<RegionId>> {
+    let (table, prefix) = match expr.category {
+        Category::Pfam => ("pfam", "pfam"),
+        Category::Tigrfam => ("tigrfam", "tigrfam"),
+        _ => {
+            return Err(Error::InvalidRequest(format!(
+                "{} query does not support domain filters",
+                expr.category
+            )))
+        }
+    };
+
+    let column = match filter.name() {
+        "score" => "score",
+        "evalue" => "evalue",
+        name => {
+            return Err(Error::InvalidRequest(format!(
+                "{} query does not support the {name} filter",
+                expr.category
+            )))
+        }
+    };
+
+    let (condition, values) = filter.numeric_condition(&format!("d.{column}"), 4)?;
+    let sql = format!(
+        r#"
+    SELECT region_id FROM antismash.regions
+    JOIN antismash.cdss USING (region_id)
+    JOIN antismash.{table}_domains AS d USING (cds_id)
+    JOIN antismash.{table}s AS p USING ({table}_id)
+    WHERE region_id = ANY($1)
+        AND (p.{table}_id ILIKE $2 OR p.name ILIKE $2 OR p.description ILIKE $2)
+        AND {condition}
+    GROUP BY region_id HAVING COUNT(*) >= $3
+        "#
+    );
+
+    let pattern = if expr.value.to_lowercase().starts_with(prefix) {
+        expr.value.to_owned()
+    } else {
+        format!("%{}%", expr.value)
+    };
+    let ids: Vec<i32> = regions_to_filter.iter().map(|r| r.region_id).collect();
+
+    let mut args = PgArguments::default();
+    args.add(ids);
+    args.add(pattern);
+    args.add(expr.count);
+    for value in values {
+        args.add(value);
+    }
+
+    let regions = sqlx::query_as_with::<_, RegionId, _>(&sql, args)
+        .fetch_all(pool)
+        .await?;
+    Ok(regions)
+}
@@ -1,6 +1,8 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use std::fmt;
+
 use nom::{
     bytes::complete::tag,
     sequence::{delimited, tuple},
@@ -9,8 +11,11 @@ use nom::{
 use serde::{Deserialize, Serialize};
 
 use super::parser::contrib::take_until_unbalanced;
-use crate::Error;
+use crate::{Error, Result};
 
+pub mod candidates;
+pub mod clusterblast;
+pub mod domains;
 pub mod tfbs;
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, strum::AsRefStr)]
@@ -54,6 +59,29 @@ impl Operator {
         }
         Ok((remaining, op))
     }
+
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Equal => "=",
+            Operator::LessOrEqual => "<=",
+            Operator::Less => "<",
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Equal => "==",
+            Operator::LessOrEqual => "<=",
+            Operator::Less => "<",
+        };
+        write!(f, "{op}")
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -101,6 +129,23 @@ impl QualitativeFilter {
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct RangeFilter {
+    pub name: String,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl RangeFilter {
+    pub fn new(name: &str, min: f32, max: f32) -> Self {
+        Self {
+            name: name.to_owned(),
+            min,
+            max,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct TextFilter {
     pub name: String,
@@ -121,6 +166,7 @@ impl TextFilter {
 #[strum(serialize_all = "lowercase")]
 pub enum Filter {
     Qualitative(QualitativeFilter),
+    Range(RangeFilter),
     Numerical(NumericalFilter),
     Text(TextFilter),
     Boolean(BooleanFilter),
@@ -139,12 +185,26 @@ impl Filter {
             if let Some((operator_raw, value)) = value_raw.split_once(":") {
                 let (_, op) = Operator::parse(operator_raw)?;
                 let Ok(val) = value.parse::<f32>() else {
-                    return Err(nom::Err::Failure(Error::InvalidRequest(format!("failed to parse filter value {value_raw}"))))
+                    return Err(nom::Err::Failure(Error::InvalidRequest(format!(
+                        "failed to parse filter value {value_raw}"
+                    ))));
                 };
                 filter = Filter::Qualitative(QualitativeFilter::new(name, val, op));
+            } else if let Some((min_raw, max_raw)) = value_raw.split_once("..") {
+                let (Ok(min), Ok(max)) = (min_raw.parse::<f32>(), max_raw.parse::<f32>()) else {
+                    return Err(nom::Err::Failure(Error::InvalidRequest(format!(
+                        "failed to parse filter range {value_raw}"
+                    ))));
+                };
+                if min > max {
+                    return Err(nom::Err::Failure(Error::InvalidRequest(format!(
+                        "invalid filter range {value_raw}, minimum is larger than maximum"
+                    ))));
+                }
+                filter = Filter::Range(RangeFilter::new(name, min, max));
             } else {
                 let Ok(value) = value_raw.parse::<f32>() else {
-                    return Ok((remaining, Filter::Text(TextFilter::new(name, value_raw))))
+                    return Ok((remaining, Filter::Text(TextFilter::new(name, value_raw))));
                 };
                 filter = Filter::Numerical(NumericalFilter::new(name, value));
             }
@@ -154,6 +214,53 @@ impl Filter {
 
         Ok((remaining, filter))
     }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Filter::Qualitative(f) => &f.name,
+            Filter::Range(f) => &f.name,
+            Filter::Numerical(f) => &f.name,
+            Filter::Text(f) => &f.name,
+            Filter::Boolean(f) => &f.name,
+        }
+    }
+
+    /// Build the SQL condition for a numeric filter on `column`,
+    /// with the values to bind starting at parameter `$first_param`
+    pub fn numeric_condition(
+        &self,
+        column: &str,
+        first_param: usize,
+    ) -> Result<(String, Vec<f64>)> {
+        match self {
+            Filter::Qualitative(f) => Ok((
+                format!("{column} {} ${first_param}", f.operator.as_sql()),
+                vec![f.value as f64],
+            )),
+            Filter::Range(f) => Ok((
+                format!("{column} BETWEEN ${first_param} AND ${}", first_param + 1),
+                vec![f.min as f64, f.max as f64],
+            )),
+            invalid => Err(Error::InvalidRequest(format!(
+                "{} filter needs an operator or a range, not a {} filter",
+                invalid.name(),
+                invalid.as_ref()
+            ))),
+        }
+    }
+}
+
+/// Renders the filter as it appears after `WITH` in a search string
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::Qualitative(q) => write!(f, "[{}|{}:{}]", q.name, q.operator, q.value),
+            Filter::Range(r) => write!(f, "[{}|{}..{}]", r.name, r.min, r.max),
+            Filter::Numerical(n) => write!(f, "[{}|{}]", n.name, n.value),
+            Filter::Text(t) => write!(f, "[{}|{}]", t.name, t.value),
+            Filter::Boolean(b) => write!(f, "[{}]", b.name),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -187,6 +294,14 @@ mod tests {
                 " WITH [alice|==:30]",
                 Filter::Qualitative(QualitativeFilter::new("alice", 30.0, Operator::Equal)),
             ),
+            (
+                " WITH [score|10..30]",
+                Filter::Range(RangeFilter::new("score", 10.0, 30.0)),
+            ),
+            (
+                " WITH [score|-1.5..2.5]",
+                Filter::Range(RangeFilter::new("score", -1.5, 2.5)),
+            ),
         ];
         for (input, expected_output) in tests {
             let (_, filter) = Filter::parse(input).unwrap();
@@ -194,16 +309,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_filter_invalid_range() {
+        let tests = [
+            " WITH [score|30..10]",
+            " WITH [score|a..10]",
+            " WITH [score|10..]",
+        ];
+        for input in tests {
+            assert!(Filter::parse(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_numeric_condition() {
+        let tests = [
+            (
+                Filter::Qualitative(QualitativeFilter::new(
+                    "score",
+                    30.0,
+                    Operator::GreaterOrEqual,
+                )),
+                ("score >= $3".to_string(), vec![30.0]),
+            ),
+            (
+                Filter::Range(RangeFilter::new("score", 10.0, 30.0)),
+                ("score BETWEEN $3 AND $4".to_string(), vec![10.0, 30.0]),
+            ),
+        ];
+        for (filter, expected) in tests {
+            assert_eq!(filter.numeric_condition("score", 3).unwrap(), expected);
+        }
+
+        assert!(Filter::Boolean(BooleanFilter::new("score"))
+            .numeric_condition("score", 3)
+            .is_err());
+    }
+
     #[test]
     fn test_filter_from_json() {
-        let tests = [(
-            r#"{"name": "quality", "operator": ">=", "value": 30}"#,
-            Filter::Qualitative(QualitativeFilter::new(
-                "quality",
-                30.0,
-                Operator::GreaterOrEqual,
-            )),
-        )];
+        let tests = [
+            (
+                r#"{"name": "quality", "operator": ">=", "value": 30}"#,
+                Filter::Qualitative(QualitativeFilter::new(
+                    "quality",
+                    30.0,
+                    Operator::GreaterOrEqual,
+                )),
+            ),
+            (
+                r#"{"name": "score", "min": 10, "max": 30}"#,
+                Filter::Range(RangeFilter::new("score", 10.0, 30.0)),
+            ),
+        ];
 
         for (input, expected) in tests {
             let result: Filter = serde_json::from_str(input).unwrap();
@@ -4,17 +4,39 @@
 use sqlx::PgPool;
 
 use crate::api::region::RegionId;
-use crate::query::filters::Filter;
+use crate::query::filters::{Filter, Operator};
 
 use crate::{Error, Result};
 
+pub async fn apply_filter(
+    pool: &PgPool,
+    regions_to_filter: &[RegionId],
+    filter: &Filter,
+) -> Result<Vec<RegionId>> {
+    match filter {
+        Filter::Qualitative(f) if f.name == "score" => {
+            let (min, max) = match f.operator {
+                Operator::Greater | Operator::GreaterOrEqual => (f.value as f64, f64::MAX),
+                Operator::Less | Operator::LessOrEqual => (f64::MIN, f.value as f64),
+                Operator::Equal => (f.value as f64, f.value as f64),
+            };
+            tfbs_score(pool, regions_to_filter, min, max).await
+        }
+        Filter::Range(f) if f.name == "score" => {
+            tfbs_score(pool, regions_to_filter, f.min as f64, f.max as f64).await
+        }
+        _ => tfbs_quality(pool, regions_to_filter, filter).await,
+    }
+}
+
 pub async fn tfbs_quality(
     pool: &PgPool,
     regions_to_filter: &[RegionId],
     filter: &Filter,
 ) -> Result<Vec<RegionId>> {
-    let f = match filter {
-        Filter::Qualitative(f) => f,
+    let (min, max) = match filter {
+        Filter::Qualitative(f) => (f.value.round() as i16, i16::MAX),
+        Filter::Range(f) => (f.min.round() as i16, f.max.round() as i16),
         invalid => {
             return Err(Error::InvalidRequest(format!(
                 "tfbs query does not support {} filters",
@@ -23,7 +45,6 @@ pub async fn tfbs_quality(
         }
     };
 
-    let value = f.value.round() as i16;
     let r: Vec<i32> = regions_to_filter.iter().map(|r| r.region_id).collect();
 
     let regions: Vec<RegionId> = sqlx::query_as!(
@@ -32,10 +53,36 @@ pub async fn tfbs_quality(
     SELECT region_id FROM antismash.regions
     JOIN antismash.binding_sites USING (region_id)
     JOIN antismash.regulator_confidence USING (confidence_id)
-    WHERE region_id = ANY($1) AND strength >= $2
+    WHERE region_id = ANY($1) AND strength BETWEEN $2 AND $3
+        "#,
+        &r,
+        min,
+        max,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(regions)
+}
+
+async fn tfbs_score(
+    pool: &PgPool,
+    regions_to_filter: &[RegionId],
+    min: f64,
+    max: f64,
+) -> Result<Vec<RegionId>> {
+    let r: Vec<i32> = regions_to_filter.iter().map(|r| r.region_id).collect();
+
+    let regions: Vec<RegionId> = sqlx::query_as!(
+        RegionId,
+        r#"
+    SELECT DISTINCT region_id FROM antismash.regions
+    JOIN antismash.binding_sites USING (region_id)
+    WHERE region_id = ANY($1) AND score BETWEEN $2 AND $3
         "#,
         &r,
-        value,
+        min,
+        max,
     )
     .fetch_all(pool)
     .await?;
@@ -0,0 +1,92 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use crate::Error;
+
+pub const DEFAULT_MAX_QUERY_LENGTH: usize = 50_000;
+pub const DEFAULT_MAX_QUERY_DEPTH: usize = 32;
+pub const DEFAULT_MAX_QUERY_TERMS: usize = 1000;
+
+/// Limits on search strings, keeping the recursive parser and the search from
+/// running out of stack on huge or deeply nested input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParseLimits {
+    /// Maximum length of the search string in bytes
+    pub max_length: usize,
+    /// Maximum nesting of parentheses and NOTs
+    pub max_depth: usize,
+    /// Maximum number of expressions
+    pub max_terms: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_length: DEFAULT_MAX_QUERY_LENGTH,
+            max_depth: DEFAULT_MAX_QUERY_DEPTH,
+            max_terms: DEFAULT_MAX_QUERY_TERMS,
+        }
+    }
+}
+
+impl ParseLimits {
+    pub fn check_length(&self, input: &str) -> Result<(), nom::Err<Error>> {
+        if input.len() > self.max_length {
+            return Err(limit_error(format!(
+                "Search string is longer than {} characters",
+                self.max_length
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a single parse, checked against the limits as the parser descends
+#[derive(Debug)]
+pub struct ParseState<'a> {
+    limits: &'a ParseLimits,
+    depth: usize,
+    terms: usize,
+}
+
+impl<'a> ParseState<'a> {
+    pub fn new(limits: &'a ParseLimits) -> Self {
+        Self {
+            limits,
+            depth: 0,
+            terms: 0,
+        }
+    }
+
+    /// Enter a parenthesised operation or negation
+    pub fn descend(&mut self) -> Result<(), nom::Err<Error>> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(limit_error(format!(
+                "Search string is nested deeper than {} levels",
+                self.limits.max_depth
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn ascend(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    pub fn count_term(&mut self) -> Result<(), nom::Err<Error>> {
+        self.terms += 1;
+        if self.terms > self.limits.max_terms {
+            return Err(limit_error(format!(
+                "Search string has more than {} terms",
+                self.limits.max_terms
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Limit violations are failures, so the parser does not backtrack and try alternatives
+fn limit_error(message: String) -> nom::Err<Error> {
+    nom::Err::Failure(Error::InvalidRequest(message))
+}
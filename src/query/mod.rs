@@ -1,11 +1,14 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
-use nom::IResult;
+use std::fmt;
+
+use nom::{character::complete::multispace1, IResult};
 use serde::{Deserialize, Serialize};
 
 pub mod expression;
 pub mod filters;
+pub mod limits;
 pub mod module;
 pub mod operation;
 pub mod parser;
@@ -14,24 +17,59 @@ pub use crate::search::Category;
 use crate::{Error, Result};
 pub use expression::Expression;
 pub use filters::Filter;
-pub use operation::{Operation, Operator};
+pub use limits::{ParseLimits, ParseState};
+pub use operation::{Negation, Operation, Operator};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, strum::AsRefStr)]
 #[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
 pub enum SearchType {
     Region,
     Gene,
     Domain,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone, strum::AsRefStr)]
 #[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
 pub enum ReturnType {
     Json,
     Csv,
     Fasta,
     Fastaa,
     Genbank,
+    Xlsx,
+    Sqlite,
+    Sideload,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy, strum::AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SortBy {
+    #[default]
+    RegionId,
+    Taxonomy,
+    StartPos,
+    Similarity,
+    Length,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How to order region results
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, Copy)]
+pub struct Sort {
+    #[serde(default)]
+    pub sort_by: SortBy,
+    #[serde(default)]
+    pub sort_order: SortOrder,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
@@ -39,17 +77,93 @@ pub enum ReturnType {
 pub enum Term {
     Expr(Expression),
     Op(Operation),
+    Not(Negation),
 }
 
 impl Term {
+    /// Parse a search string with the default [`ParseLimits`]
     pub fn parse(input: &str) -> IResult<&str, Self, Error> {
+        Term::parse_with(input, &ParseLimits::default())
+    }
+
+    /// Parse a search string, failing with [`Error::InvalidRequest`] if it exceeds the limits
+    pub fn parse_with<'i>(input: &'i str, limits: &ParseLimits) -> IResult<&'i str, Self, Error> {
+        limits.check_length(input)?;
+        Term::parse_nested(input, &mut ParseState::new(limits))
+    }
+
+    /// Parse a term, chaining further terms joined by the same operator left to right,
+    /// so `{[a]} AND {[b]} AND {[c]}` needs no extra parentheses.
+    /// Mixing operators without parentheses is ambiguous and thus rejected.
+    pub fn parse_nested<'i>(
+        input: &'i str,
+        state: &mut ParseState,
+    ) -> IResult<&'i str, Self, Error> {
+        let (mut remaining, mut term) = Term::parse_single(input, state)?;
+        let mut chain_op: Option<Operator> = None;
+
+        loop {
+            let Ok((partial, _)) = multispace1::<&str, Error>(remaining) else {
+                break;
+            };
+            let Ok((partial, op)) = Operator::parse(partial) else {
+                break;
+            };
+            if chain_op.as_ref().is_some_and(|chained| *chained != op) {
+                return Err(nom::Err::Failure(Error::ParserError));
+            }
+            let (partial, _) = multispace1(partial)?;
+            let (partial, right) = Term::parse_single(partial, state)?;
+
+            term = Term::Op(Operation::new(op.clone(), term, right));
+            chain_op = Some(op);
+            remaining = partial;
+        }
+
+        Ok((remaining, term))
+    }
+
+    /// Parse a single expression, parenthesised operation or negation
+    pub fn parse_single<'i>(
+        input: &'i str,
+        state: &mut ParseState,
+    ) -> IResult<&'i str, Self, Error> {
         if input.starts_with('(') {
-            let (remaining, op) = Operation::parse(input)?;
+            let (remaining, op) = Operation::parse(input, state)?;
             return Ok((remaining, Term::Op(op)));
         }
+        match Negation::parse(input, state) {
+            Ok((remaining, not)) => return Ok((remaining, Term::Not(not))),
+            Err(nom::Err::Failure(e)) => return Err(nom::Err::Failure(e)),
+            Err(_) => (),
+        }
+        state.count_term()?;
         let (remaining, expr) = Expression::parse(input)?;
         Ok((remaining, Term::Expr(expr)))
     }
+
+    /// All expressions in the term, left to right
+    pub fn expressions(&self) -> Vec<&Expression> {
+        match self {
+            Term::Expr(e) => vec![e],
+            Term::Op(o) => {
+                let mut exprs = o.left.expressions();
+                exprs.extend(o.right.expressions());
+                exprs
+            }
+            Term::Not(n) => n.term.expressions(),
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Expr(expr) => write!(f, "{expr}"),
+            Term::Op(op) => write!(f, "{op}"),
+            Term::Not(not) => write!(f, "{not}"),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -60,16 +174,39 @@ pub struct Query {
     pub return_type: ReturnType,
     #[serde(default)]
     pub verbose: bool,
+    /// Also return results from assemblies that have been tombstoned
+    #[serde(default)]
+    pub include_tombstoned: bool,
+    /// Replace accession versions missing from the database by the closest stored version
+    #[serde(default)]
+    pub resolve_versions: bool,
 }
 
 impl Query {
     pub fn from_str(input: &str) -> Result<Self> {
-        let (_, term) = Term::parse(input).or_else(|_| return Err(Error::ParserError))?;
+        Query::parse(input, &ParseLimits::default())
+    }
+
+    /// The canonical search string of the query's terms, which parses back into the same terms
+    pub fn to_search_string(&self) -> String {
+        self.terms.to_string()
+    }
+
+    /// Parse a region query from a search string, limit violations are reported as
+    /// [`Error::InvalidRequest`], any other problem as [`Error::ParserError`]
+    pub fn parse(input: &str, limits: &ParseLimits) -> Result<Self> {
+        let term = match Term::parse_with(input, limits) {
+            Ok((_, term)) => term,
+            Err(nom::Err::Failure(e @ Error::InvalidRequest(_))) => return Err(e),
+            Err(_) => return Err(Error::ParserError),
+        };
         Ok(Self {
             terms: term,
             search_type: SearchType::Region,
             return_type: ReturnType::Json,
             verbose: false,
+            include_tombstoned: false,
+            resolve_versions: false,
         })
     }
 }
@@ -93,10 +230,168 @@ mod tests {
                     Term::Expr(Expression::new(Category::Type, None, &[], 1)),
                 )),
             ),
+            (
+                "{[acc]} OR {[type]} OR {[tfbs]}",
+                Term::Op(Operation::new(
+                    Operator::Or,
+                    Term::Op(Operation::new(
+                        Operator::Or,
+                        Term::Expr(Expression::new(Category::Acc, None, &[], 1)),
+                        Term::Expr(Expression::new(Category::Type, None, &[], 1)),
+                    )),
+                    Term::Expr(Expression::new(Category::Tfbs, None, &[], 1)),
+                )),
+            ),
+            (
+                "NOT {[acc]} AND {[type]}",
+                Term::Op(Operation::new(
+                    Operator::And,
+                    Term::Not(Negation::new(Term::Expr(Expression::new(
+                        Category::Acc,
+                        None,
+                        &[],
+                        1,
+                    )))),
+                    Term::Expr(Expression::new(Category::Type, None, &[], 1)),
+                )),
+            ),
         ];
         for (input, expected_output) in tests {
             let (_, output) = Term::parse(input).unwrap();
             assert_eq!(output, expected_output);
         }
     }
+
+    #[test]
+    fn test_to_search_string() {
+        let tests = [
+            ("{[acc]}", "{[acc]}"),
+            ("{[ACC|NC_003888]}", "{[acc|NC_003888]}"),
+            ("3*{[type|NRPS]}", "3*{[type|NRPS]}"),
+            ("({[acc]} AND {[type]})", "{[acc]} AND {[type]}"),
+            (
+                "{[acc]} OR {[type]} OR {[tfbs]}",
+                "{[acc]} OR {[type]} OR {[tfbs]}",
+            ),
+            (
+                "{[acc]} OR ({[type]} OR {[tfbs]})",
+                "{[acc]} OR ({[type]} OR {[tfbs]})",
+            ),
+            (
+                "({[acc]} OR {[type]}) AND {[tfbs]}",
+                "({[acc]} OR {[type]}) AND {[tfbs]}",
+            ),
+            ("NOT {[acc]} AND {[type]}", "NOT {[acc]} AND {[type]}"),
+            (
+                "NOT ({[acc]} EXCEPT {[type]})",
+                "NOT ({[acc]} EXCEPT {[type]})",
+            ),
+            (
+                r#"{[species|"Streptomyces coelicolor A3(2)"]}"#,
+                r#"{[species|"Streptomyces coelicolor A3(2)"]}"#,
+            ),
+            (
+                "{[pfam|PF00109] WITH [evalue|<=:0.5] WITH [score|10..20.5]}",
+                "{[pfam|PF00109] WITH [evalue|<=:0.5] WITH [score|10..20.5]}",
+            ),
+            (
+                "{[tfbs|ZuR] WITH [quality|30] WITH [name|foo] WITH [strong]}",
+                "{[tfbs|ZuR] WITH [quality|30] WITH [name|foo] WITH [strong]}",
+            ),
+        ];
+        for (input, expected) in tests {
+            let query = Query::from_str(input).unwrap();
+            let rendered = query.to_search_string();
+            assert_eq!(rendered, expected, "{input}");
+            let reparsed = Query::from_str(&rendered).unwrap();
+            assert_eq!(reparsed.terms, query.terms, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = ParseLimits {
+            max_length: 100,
+            max_depth: 2,
+            max_terms: 3,
+        };
+        let deep = format!("{}{{[acc]}}{}", "NOT (".repeat(5_000), ")".repeat(5_000));
+        let tests = [
+            ("{[acc]} OR {[type]} OR {[tfbs]}", None),
+            ("NOT ({[acc]} OR {[type]})", None),
+            (
+                "{[acc]} OR {[type]} OR {[tfbs]} OR {[acc]}",
+                Some("more than 3 terms"),
+            ),
+            ("NOT NOT NOT {[acc]}", Some("deeper than 2 levels")),
+            (
+                "({[acc]} OR ({[type]} AND NOT {[tfbs]}))",
+                Some("deeper than 2 levels"),
+            ),
+            (&deep, Some("longer than 100 characters")),
+            ("{[acc", Some("Parser error")),
+        ];
+        for (input, expected) in tests {
+            let result = Query::parse(input, &limits);
+            match expected {
+                None => assert!(result.is_ok(), "{input}"),
+                Some(message) => {
+                    let error = result.unwrap_err().to_string();
+                    assert!(error.contains(message), "{input}: {error}");
+                }
+            }
+        }
+
+        let error = Query::from_str(&deep).unwrap_err().to_string();
+        assert!(error.contains("deeper than 32 levels"), "{error}");
+    }
+
+    #[test]
+    fn test_expressions() {
+        let tests = [
+            ("{[acc]}", vec![Category::Acc]),
+            (
+                "{[acc]} OR {[type]} OR {[tfbs]}",
+                vec![Category::Acc, Category::Type, Category::Tfbs],
+            ),
+            (
+                "NOT {[acc]} AND ({[type]} OR {[tfbs]})",
+                vec![Category::Acc, Category::Type, Category::Tfbs],
+            ),
+        ];
+        for (input, expected) in tests {
+            let (_, term) = Term::parse(input).unwrap();
+            let categories: Vec<Category> = term
+                .expressions()
+                .iter()
+                .map(|e| e.category.clone())
+                .collect();
+            assert_eq!(categories, expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_sort_from_json() {
+        let tests = [
+            ("{}", Sort::default()),
+            (
+                r#"{"sort_by": "start_pos", "sort_order": "desc"}"#,
+                Sort {
+                    sort_by: SortBy::StartPos,
+                    sort_order: SortOrder::Desc,
+                },
+            ),
+            (
+                r#"{"sort_by": "length"}"#,
+                Sort {
+                    sort_by: SortBy::Length,
+                    sort_order: SortOrder::Asc,
+                },
+            ),
+        ];
+        for (input, expected) in tests {
+            let sort: Sort = serde_json::from_str(input).unwrap();
+            assert_eq!(sort, expected);
+        }
+    }
 }
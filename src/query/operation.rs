@@ -1,6 +1,8 @@
 // License: GNU Affero General Public License v3 or later
 // A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
 
+use std::fmt;
+
 use nom::{
     branch::alt,
     bytes::complete::{tag, tag_no_case},
@@ -11,10 +13,10 @@ use nom::{
 use serde::{Deserialize, Serialize};
 
 use super::parser::contrib::take_until_unbalanced;
-use super::Term;
+use super::{ParseState, Term};
 use crate::Error;
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum Operator {
     And,
@@ -39,6 +41,47 @@ impl Operator {
     }
 }
 
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Operator::And => "AND",
+            Operator::Or => "OR",
+            Operator::Except => "EXCEPT",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// A unary NOT, matching all regions the wrapped term does not match
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Negation {
+    pub term: Box<Term>,
+}
+
+impl Negation {
+    pub fn new(term: Term) -> Self {
+        Negation { term: term.into() }
+    }
+
+    pub fn parse<'i>(input: &'i str, state: &mut ParseState) -> IResult<&'i str, Self, Error> {
+        let (remaining, _) = tag_no_case("not")(input)?;
+        let (remaining, _) = multispace1(remaining)?;
+        state.descend()?;
+        let (remaining, term) = Term::parse_single(remaining, state)?;
+        state.ascend();
+        Ok((remaining, Negation::new(term)))
+    }
+}
+
+impl fmt::Display for Negation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.term.as_ref() {
+            Term::Op(op) => write!(f, "NOT ({op})"),
+            term => write!(f, "NOT {term}"),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Operation {
     #[serde(rename = "operation")]
@@ -56,22 +99,39 @@ impl Operation {
         }
     }
 
-    pub fn parse(input: &str) -> IResult<&str, Self, Error> {
+    pub fn parse<'i>(input: &'i str, state: &mut ParseState) -> IResult<&'i str, Self, Error> {
         let (remaining, partial) =
             delimited(tag("("), take_until_unbalanced('(', ')'), tag(")"))(input)?;
 
+        state.descend()?;
         let (partial, _) = multispace0(partial)?;
-        let (partial, left) = Term::parse(partial)?;
-        let (partial, _) = multispace1(partial)?;
-        let (partial, op) = Operator::parse(partial)?;
-        let (partial, _) = multispace1(partial)?;
-        let (partial, right) = Term::parse(partial)?;
+        let (partial, term) = Term::parse_nested(partial, state)?;
+        state.ascend();
         let (partial, _) = multispace0(partial)?;
         if partial.len() > 0 {
             return Err(nom::Err::Failure(Error::ParserError));
         }
 
-        return Ok((remaining, Operation::new(op, left, right)));
+        match term {
+            Term::Op(op) => Ok((remaining, op)),
+            _ => Err(nom::Err::Failure(Error::ParserError)),
+        }
+    }
+}
+
+/// Renders the left operand of a chain like `{[a]} AND {[b]} AND {[c]}` without parentheses,
+/// so the string parses back into the same tree
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.left.as_ref() {
+            Term::Op(left) if left.operator != self.operator => write!(f, "({left})")?,
+            left => write!(f, "{left}")?,
+        }
+        write!(f, " {} ", self.operator)?;
+        match self.right.as_ref() {
+            Term::Op(right) => write!(f, "({right})"),
+            right => write!(f, "{right}"),
+        }
     }
 }
 
@@ -92,7 +152,7 @@ impl PartialEq for Operation {
 
 #[cfg(test)]
 mod tests {
-    use super::super::Expression;
+    use super::super::{Expression, ParseLimits};
     use super::*;
     use crate::search::Category;
 
@@ -148,9 +208,82 @@ mod tests {
                     )),
                 ),
             ),
+            (
+                "({[acc]} AND {[type]} AND {[tfbs]})",
+                Operation::new(
+                    Operator::And,
+                    Term::Op(Operation::new(
+                        Operator::And,
+                        Term::Expr(Expression::new(Category::Acc, None, &[], 1)),
+                        Term::Expr(Expression::new(Category::Type, None, &[], 1)),
+                    )),
+                    Term::Expr(Expression::new(Category::Tfbs, None, &[], 1)),
+                ),
+            ),
+            (
+                "({[acc]} AND NOT {[type]})",
+                Operation::new(
+                    Operator::And,
+                    Term::Expr(Expression::new(Category::Acc, None, &[], 1)),
+                    Term::Not(Negation::new(Term::Expr(Expression::new(
+                        Category::Type,
+                        None,
+                        &[],
+                        1,
+                    )))),
+                ),
+            ),
+        ];
+        for (input, expected_output) in tests {
+            let (_, output) =
+                Operation::parse(input, &mut ParseState::new(&ParseLimits::default())).unwrap();
+            assert_eq!(output, expected_output);
+        }
+    }
+
+    #[test]
+    fn test_parse_operation_invalid() {
+        let tests = [
+            "({[acc]})",
+            "({[acc]} AND)",
+            "({[acc]} AND {[type]} OR {[tfbs]})",
+        ];
+        for input in tests {
+            assert!(
+                Operation::parse(input, &mut ParseState::new(&ParseLimits::default())).is_err(),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_negation() {
+        let tests = [
+            (
+                "NOT {[acc]}",
+                Negation::new(Term::Expr(Expression::new(Category::Acc, None, &[], 1))),
+            ),
+            (
+                "not ({[acc]} OR {[type]})",
+                Negation::new(Term::Op(Operation::new(
+                    Operator::Or,
+                    Term::Expr(Expression::new(Category::Acc, None, &[], 1)),
+                    Term::Expr(Expression::new(Category::Type, None, &[], 1)),
+                ))),
+            ),
+            (
+                "NOT NOT {[acc]}",
+                Negation::new(Term::Not(Negation::new(Term::Expr(Expression::new(
+                    Category::Acc,
+                    None,
+                    &[],
+                    1,
+                ))))),
+            ),
         ];
         for (input, expected_output) in tests {
-            let (_, output) = Operation::parse(input).unwrap();
+            let (_, output) =
+                Negation::parse(input, &mut ParseState::new(&ParseLimits::default())).unwrap();
             assert_eq!(output, expected_output);
         }
     }
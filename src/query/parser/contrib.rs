@@ -4,7 +4,8 @@ use nom::IResult;
 use crate::Error;
 
 // This function is borrowed from the parse-hyperlinks crate at https://crates.io/crates/parse-hyperlinks
-// under an MIT/Apache-2 dual licence
+// under an MIT/Apache-2 dual licence.
+// Changed to also skip double-quoted strings, so quoted values may contain brackets.
 pub fn take_until_unbalanced(
     opening_bracket: char,
     closing_bracket: char,
@@ -12,7 +13,7 @@ pub fn take_until_unbalanced(
     move |i: &str| {
         let mut index = 0;
         let mut bracket_counter = 0;
-        while let Some(n) = &i[index..].find(&[opening_bracket, closing_bracket, '\\'][..]) {
+        while let Some(n) = &i[index..].find(&[opening_bracket, closing_bracket, '\\', '"'][..]) {
             index += n;
             let mut it = i[index..].chars();
             match it.next().unwrap_or_default() {
@@ -23,6 +24,13 @@ pub fn take_until_unbalanced(
                     let c = it.next().unwrap_or_default();
                     index += c.len_utf8();
                 }
+                '"' => {
+                    index += '"'.len_utf8();
+                    let Some(len) = quoted_length(&i[index..]) else {
+                        return Err(Err::Failure(Error::ParserError));
+                    };
+                    index += len;
+                }
                 c if c == opening_bracket => {
                     bracket_counter += 1;
                     index += opening_bracket.len_utf8();
@@ -51,6 +59,21 @@ pub fn take_until_unbalanced(
     }
 }
 
+/// Length of a double-quoted string up to and including the closing quote, with the
+/// opening quote already consumed, or `None` if the string isn't terminated
+pub fn quoted_length(input: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index + '"'.len_utf8()),
+            _ => (),
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,5 +108,10 @@ mod tests {
             take_until_unbalanced('€', 'ü')("€uü€€üürlüabc").unwrap(),
             ("üabc", "€uü€€üürl")
         );
+        assert_eq!(
+            take_until_unbalanced('(', ')')(r#"u"(\")"r)abc"#).unwrap(),
+            (")abc", r#"u"(\")"r"#)
+        );
+        assert!(take_until_unbalanced('(', ')')(r#"u"r)abc"#).is_err());
     }
 }
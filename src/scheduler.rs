@@ -0,0 +1,92 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+
+use sqlx::PgPool;
+use tokio::time::{sleep, Duration};
+
+use crate::api::stats::StatsCache;
+use crate::models::job::JobEntry;
+use crate::Result;
+
+/// Periodic maintenance tasks run inside the Serve process
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Interval for refreshing the cached statistics, disabled if None
+    pub stats_interval: Option<Duration>,
+    /// Interval for failing stale running jobs, disabled if None
+    pub stale_job_interval: Option<Duration>,
+    /// Hours after which a running job counts as stale
+    pub stale_job_age: f64,
+    /// Maximum random delay added to each task run
+    pub jitter: Duration,
+}
+
+pub fn start(pool: PgPool, config: SchedulerConfig, stats_cache: StatsCache) {
+    if let Some(interval) = config.stats_interval {
+        eprintln!("->> Refreshing stats every {interval:?}");
+        let pool = pool.clone();
+        spawn_task("stats", interval, config.jitter, move || {
+            let pool = pool.clone();
+            let cache = stats_cache.clone();
+            async move { cache.refresh(&pool).await }
+        });
+    }
+
+    if let Some(interval) = config.stale_job_interval {
+        eprintln!("->> Checking for stale jobs every {interval:?}");
+        let hours = config.stale_job_age;
+        spawn_task("stale_jobs", interval, config.jitter, move || {
+            let pool = pool.clone();
+            async move {
+                for id in JobEntry::fail_stale(&pool, hours).await? {
+                    eprintln!("->> Marked stale job {id} as failed");
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+fn spawn_task<F, Fut>(name: &'static str, interval: Duration, jitter: Duration, task: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>> + Send,
+{
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = task().await {
+                eprintln!("->> Scheduled task {name} failed: {e}");
+            }
+            sleep(interval + random_jitter(jitter)).await;
+        }
+    });
+}
+
+/// Pick a random delay up to `max`, so tasks of several servers don't all fire at once
+fn random_jitter(max: Duration) -> Duration {
+    let millis = max.as_millis() as u64;
+    if millis == 0 {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_jitter() {
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+
+        let max = Duration::from_secs(5);
+        for _ in 0..100 {
+            assert!(random_jitter(max) < max);
+        }
+    }
+}
@@ -5,10 +5,11 @@ use std::str::FromStr;
 
 use nom::IResult;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use strum::EnumMessage;
+use serde_json::{self, json};
+use strum::{EnumMessage, EnumProperty, IntoEnumIterator};
 
 use super::filters::{get_filters_by_category, AvailableFilter};
+use crate::query::Expression;
 use crate::Error;
 
 pub trait CategoryMetadata {
@@ -44,7 +45,9 @@ pub enum CategoryGroup {
 
 // We're abusing the strum::EnumMessage message to set the CategoryGroup,
 // the doc string comment to set the label,
-// and the detailed message for the description
+// the detailed message for the description,
+// and the "example" strum::EnumProperty for an example search value.
+// Alternative names accepted when parsing are set as serde aliases.
 #[derive(
     Debug,
     Deserialize,
@@ -53,6 +56,7 @@ pub enum CategoryGroup {
     Clone,
     strum::EnumIter,
     strum::EnumMessage,
+    strum::EnumProperty,
     strum::Display,
     strum::IntoStaticStr,
 )]
@@ -61,101 +65,137 @@ pub enum CategoryGroup {
 pub enum Category {
     // uncategorised
     /// NCBI RefSeq Accession
-    #[strum(detailed_message = "DNA record accession from RefSeq")]
+    #[strum(
+        detailed_message = "DNA record accession from RefSeq",
+        props(example = "NC_003888")
+    )]
+    #[serde(alias = "accession")]
     Acc,
 
     /// NCBI Assembly ID
-    #[strum(detailed_message = "NCBI assembly ID")]
+    #[strum(
+        detailed_message = "NCBI assembly ID",
+        props(example = "GCF_000203835.1")
+    )]
     Assembly,
 
+    /// Protein motif
+    #[strum(
+        detailed_message = "Regions containing a gene whose translation matches a PROSITE-style motif",
+        props(example = "C-x(2,4)-C-x(3)-H")
+    )]
+    ProteinMotif,
+
     /// BGC type
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "BGC type as predicted by antiSMASH"
+        detailed_message = "BGC type as predicted by antiSMASH",
+        props(example = "nrps")
     )]
     Type,
 
     /// BGC category
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "BGC type category (e.g. PKS, Terpene)"
+        detailed_message = "BGC type category (e.g. PKS, Terpene)",
+        props(example = "PKS")
     )]
     TypeCategory,
 
     /// Candidate cluster type
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "A specific kind of CandidateCluster"
+        detailed_message = "A specific kind of CandidateCluster",
+        props(example = "neighbouring")
     )]
     CandidateKind,
 
     /// Substrate
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Substrate integrated into the cluster product"
+        detailed_message = "Substrate integrated into the cluster product",
+        props(example = "ala")
     )]
     Substrate,
 
     /// Monomer
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Monomer contained in the cluster product"
+        detailed_message = "Monomer contained in the cluster product",
+        props(example = "ala")
     )]
     Monomer,
 
     /// Biosynthetic profile
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a specific antiSMASH BGC detection profile hit"
+        detailed_message = "Regions containing a specific antiSMASH BGC detection profile hit",
+        props(example = "PKS_KS")
     )]
     Profile,
 
     /// ResFam profile
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a hit to the given ResFams ID"
+        detailed_message = "Regions containing a hit to the given ResFams ID",
+        props(example = "RF0001")
     )]
     Resfam,
 
     /// Pfam profile
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a hit to the given PFAM ID"
+        detailed_message = "Regions containing a hit to the given PFAM ID",
+        props(example = "PF00109")
     )]
     Pfam,
 
     /// TIGRFAM profile
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a hit to the given TIGRFam ID"
+        detailed_message = "Regions containing a hit to the given TIGRFam ID",
+        props(example = "TIGR01720")
     )]
     Tigrfam,
 
     /// GO term
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a hit to the given GO term (based on PFAM hits)"
+        detailed_message = "Regions containing a hit to the given GO term (based on PFAM hits)",
+        props(example = "GO:0004315")
     )]
+    #[serde(alias = "go")]
     GOTerm,
 
     /// NRPS/PKS domain
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a specific aSDomain by name"
+        detailed_message = "Regions containing a specific aSDomain by name",
+        props(example = "PKS_KS")
     )]
     AsDomain,
 
     /// NRPS/PKS domain subtype
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containig a specific aSDomain subtype"
+        detailed_message = "Regions containig a specific aSDomain subtype",
+        props(example = "Trans-AT-KS")
     )]
     AsDomainSubtype,
 
+    /// NRPS/PKS domain architecture
+    #[strum(
+        message = "AntismashPrediction",
+        detailed_message = "Regions containing a CDS whose aSDomains, in order, match a pattern, with ? for any single domain and * for any number of domains",
+        props(example = "C-A-PCP")
+    )]
+    DomainArchitecture,
+
     /// NRPS/PKS module query
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a module with the requested component domains"
+        detailed_message = "Regions containing a module with the requested component domains",
+        props(example = "L=PKS_AT+PKS_KS")
     )]
     ModuleQuery,
 
@@ -169,59 +209,83 @@ pub enum Category {
     /// PKS type II profile
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions with a specific PKS type II detection profile"
+        detailed_message = "Regions with a specific PKS type II detection profile",
+        props(example = "CLF")
     )]
     T2pksProfile,
 
     /// PKS type II product class
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions with a specific PKS type II product class"
+        detailed_message = "Regions with a specific PKS type II product class",
+        props(example = "angucycline")
     )]
     T2pksProductClass,
 
     /// PKS type II starter moiety
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions with a specific PKS type II starter"
+        detailed_message = "Regions with a specific PKS type II starter",
+        props(example = "acetyl-CoA")
     )]
     T2pksStarter,
 
     /// PKS type II elongation
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions with PKS type II elongations of a specific size"
+        detailed_message = "Regions with PKS type II elongations of a specific size",
+        props(example = "7")
     )]
     T2pksElongation,
 
     /// smCoG hit
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a specific smCoG hit"
+        detailed_message = "Regions containing a specific smCoG hit",
+        props(example = "SMCOG1001")
     )]
     SmCoG,
 
     /// Binding site regulator
     #[strum(
         message = "AntismashPrediction",
-        detailed_message = "Regions containing a TFBS regulator of the given name"
+        detailed_message = "Regions containing a TFBS regulator of the given name",
+        props(example = "LexA")
     )]
     Tfbs,
 
     /// Compound sequence
     #[strum(
         message = "CompoundProperty",
-        detailed_message = "RiPP BGC containing a compound with a sequence containing this string"
+        detailed_message = "RiPP BGC containing a compound with a sequence containing this string",
+        props(example = "GGIGD")
     )]
     CompoundSeq,
 
     /// RiPP compound class
     #[strum(
         message = "CompoundProperty",
-        detailed_message = "RiPP BGC containing a given compound class"
+        detailed_message = "RiPP BGC containing a given compound class",
+        props(example = "Class I")
     )]
     CompoundClass,
 
+    /// Predicted structure
+    #[strum(
+        message = "CompoundProperty",
+        detailed_message = "Regions with a predicted core structure whose SMILES contains this string",
+        props(example = "NCC(=O)")
+    )]
+    Smiles,
+
+    /// Known compound
+    #[strum(
+        message = "CompoundProperty",
+        detailed_message = "Regions with a KnownClusterBlast hit to the gene cluster of a known compound, by name or NPAtlas ID",
+        props(example = "erythromycin")
+    )]
+    Compound,
+
     /// Region on contig edge
     #[strum(
         message = "QualityFilter",
@@ -231,92 +295,116 @@ pub enum Category {
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By strain according to NCBI taxonomy"
+        detailed_message = "By strain according to NCBI taxonomy",
+        props(example = "A3(2)")
     )]
     Strain,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By species according to NCBI taxonomy"
+        detailed_message = "By species according to NCBI taxonomy",
+        props(example = "coelicolor")
     )]
     Species,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By genus according to NCBI taxonomy"
+        detailed_message = "By genus according to NCBI taxonomy",
+        props(example = "Streptomyces")
     )]
     Genus,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By family according to NCBI taxonomy"
+        detailed_message = "By family according to NCBI taxonomy",
+        props(example = "Streptomycetaceae")
     )]
     Family,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By order according to NCBI taxonomy"
+        detailed_message = "By order according to NCBI taxonomy",
+        props(example = "Streptomycetales")
     )]
     Order,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By class according to NCBI taxonomy"
+        detailed_message = "By class according to NCBI taxonomy",
+        props(example = "Actinomycetia")
     )]
     Class,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By phylum according to NCBI taxonomy"
+        detailed_message = "By phylum according to NCBI taxonomy",
+        props(example = "Actinomycetota")
     )]
     Phylum,
 
     #[strum(
         message = "Taxonomy",
-        detailed_message = "By superkingdom according to NCBI taxonomy"
+        detailed_message = "By superkingdom according to NCBI taxonomy",
+        props(example = "Bacteria")
     )]
     Superkingdom,
 
+    /// Taxonomy tree node
+    #[strum(
+        message = "Taxonomy",
+        detailed_message = "Within the taxonomy tree node of the given ID",
+        props(example = "phylum_bacteria_actinomycetota")
+    )]
+    TaxNode,
+
     /// CompaRiPPson MIBiG hit
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions containing a CompaRiPPson hit against the given MIBiG ID"
+        detailed_message = "Regions containing a CompaRiPPson hit against the given MIBiG ID",
+        props(example = "BGC0000535")
     )]
     CompaRiPPsonMibig,
 
     /// ClusterCompare by region
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions with ClusterCompare hits matching the given MIBiG ID"
+        detailed_message = "Regions with ClusterCompare hits matching the given MIBiG ID",
+        props(example = "BGC0000315")
     )]
     ClusterCompareRegion,
 
     /// ClusterCompare by protocluster
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions with protoclusters with ClusterCompare hits matching the given MIBiG ID"
+        detailed_message = "Regions with protoclusters with ClusterCompare hits matching the given MIBiG ID",
+        props(example = "BGC0000315")
     )]
     ClusterCompareProtocluster,
 
     /// ClusterBlast hit
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions containing a hit to the given ClusterBlast entry"
+        detailed_message = "Regions containing a hit to the given ClusterBlast entry",
+        props(example = "NC_003888_c5")
     )]
     ClusterBlast,
 
     /// KnownClusterBlast hit
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions containing a hit to the given KnownClusterBlast entry"
+        detailed_message = "Regions containing a hit to the given KnownClusterBlast entry",
+        props(example = "BGC0000315")
     )]
+    #[serde(alias = "knownclusterblast")]
     KnownCluster,
 
     /// SubClusterBlast hit
     #[strum(
         message = "SimilarClusters",
-        detailed_message = "Regions containing a hit to the given SubClusterBlast entry"
+        detailed_message = "Regions containing a hit to the given SubClusterBlast entry",
+        props(example = "AB050629")
     )]
+    #[serde(alias = "subclusterblast")]
     SubCluster,
 }
 
@@ -362,6 +450,15 @@ impl Category {
         }
     }
 
+    pub fn get_example(&self) -> Option<&'static str> {
+        self.get_str("example")
+    }
+
+    /// A ready-to-use query string searching for the example value
+    pub fn get_snippet(&self) -> String {
+        Expression::new(self.clone(), self.get_example(), &[], 1).to_string()
+    }
+
     pub fn is_countable(&self) -> bool {
         match self {
             Category::Strain
@@ -372,15 +469,13 @@ impl Category {
             | Category::Class
             | Category::Phylum
             | Category::Superkingdom
+            | Category::TaxNode
             | Category::Acc
             | Category::Assembly
             | Category::CompoundClass
             | Category::ClusterCompareRegion
             | Category::ContigEdge
-            | Category::ClusterBlast
-            | Category::KnownCluster
-            | Category::SubCluster
-            | Category::CompaRiPPsonMibig => false,
+            | Category::Compound => false,
             _ => true,
         }
     }
@@ -392,10 +487,18 @@ impl Category {
 
 impl FromStr for Category {
     type Err = Error;
+    /// Parse a category name or alias, ignoring case and surrounding whitespace
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         // serde expects the value to be quoted
-        let quoted = format!("\"{s}\"");
-        Ok(serde_json::from_str::<Self>(&quoted)?)
+        let quoted = json!(s.trim().to_lowercase()).to_string();
+        serde_json::from_str::<Self>(&quoted).map_err(|_| {
+            let valid: Vec<&'static str> = Category::iter().map(|c| c.into()).collect();
+            Error::InvalidRequest(format!(
+                "Invalid category {:?}, valid categories are: {}",
+                s.trim(),
+                valid.join(", ")
+            ))
+        })
     }
 }
 
@@ -411,8 +514,28 @@ mod tests {
 
     #[test]
     fn test_from_str() {
-        let c: Category = "acc".parse().unwrap();
-        assert_eq!(c, Category::Acc);
+        let tests = [
+            ("acc", Category::Acc),
+            ("PFAM", Category::Pfam),
+            (" knowncluster ", Category::KnownCluster),
+            ("KnownClusterBlast", Category::KnownCluster),
+            ("accession", Category::Acc),
+            ("GOTerm", Category::GOTerm),
+            ("go", Category::GOTerm),
+        ];
+        for (input, expected) in tests {
+            let c: Category = input.parse().unwrap();
+            assert_eq!(c, expected, "{input}");
+        }
+
+        let error = "nosuchcategory".parse::<Category>().unwrap_err();
+        let Error::InvalidRequest(message) = error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert!(message.starts_with(
+            "Invalid category \"nosuchcategory\", valid categories are: acc, assembly,"
+        ));
+        assert!(message.contains(", knowncluster, "));
     }
 
     #[test]
@@ -465,9 +588,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_snippet() {
+        let tests = [
+            (Category::Acc, Some("NC_003888"), "{[acc|NC_003888]}"),
+            (Category::ContigEdge, None, "{[contigedge]}"),
+            (Category::Strain, Some("A3(2)"), r#"{[strain|"A3(2)"]}"#),
+        ];
+        for (cat, example, snippet) in tests {
+            assert_eq!(cat.get_example(), example);
+            assert_eq!(cat.get_snippet(), snippet);
+        }
+    }
+
+    #[test]
+    fn test_snippets_parse() {
+        use crate::query::{Query, Term};
+        use strum::IntoEnumIterator;
+
+        for cat in Category::iter() {
+            let snippet = cat.get_snippet();
+            let query = Query::from_str(&snippet).unwrap_or_else(|e| panic!("{snippet}: {e}"));
+            let Term::Expr(expr) = query.terms else {
+                panic!("{snippet} is not a single expression");
+            };
+            assert_eq!(expr.category, cat);
+            assert_eq!(expr.value, cat.get_example().unwrap_or_default());
+        }
+    }
+
     #[test]
     fn test_countable() {
-        let tests = [(Category::Acc, false), (Category::ModuleQuery, true)];
+        let tests = [
+            (Category::Acc, false),
+            (Category::Genus, false),
+            (Category::ModuleQuery, true),
+            (Category::ClusterBlast, true),
+        ];
         for (cat, expected) in tests {
             assert_eq!(cat.is_countable(), expected);
         }
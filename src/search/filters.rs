@@ -54,6 +54,24 @@ pub fn get_filters_by_category(category: &Category) -> Vec<AvailableFilter> {
             );
             filters
         }
+        Category::CompoundSeq => vec![AvailableFilter::new(
+            "regex",
+            "Search as regular expression",
+            "boolean",
+        )],
+        Category::Pfam | Category::Tigrfam => vec![
+            AvailableFilter::new("score", "Bitscore", "numeric"),
+            AvailableFilter::new("evalue", "E-value", "numeric"),
+        ],
+        Category::KnownCluster => vec![
+            AvailableFilter::new("similarity", "Similarity (% of genes with hits)", "numeric"),
+            AvailableFilter::new("rank", "Hit rank", "numeric"),
+            AvailableFilter::new("best", "Most similar known cluster only", "boolean"),
+        ],
+        Category::ClusterBlast | Category::SubCluster => vec![
+            AvailableFilter::new("similarity", "Similarity (% of genes with hits)", "numeric"),
+            AvailableFilter::new("rank", "Hit rank", "numeric"),
+        ],
         _ => return Vec::new(),
     }
 }
@@ -0,0 +1,21 @@
+// License: GNU Affero General Public License v3 or later
+// A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+use sqlx::{Executor, PgPool};
+
+use crate::Result;
+
+/// Miniature antiSMASH schema, see the file for details
+pub const SCHEMA: &str = include_str!("schema.sql");
+
+/// Three regions on two records touching every search category
+pub const REGIONS: &str = include_str!("regions.sql");
+
+/// Set up the antiSMASH and job tables and load the seeded regions into an empty database,
+/// like the ones created for `#[sqlx::test(migrations = false)]` tests
+pub async fn seed(pool: &PgPool) -> Result<()> {
+    pool.execute(SCHEMA).await?;
+    sqlx::migrate!().run(pool).await?;
+    pool.execute(REGIONS).await?;
+    Ok(())
+}
@@ -0,0 +1,181 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- A handful of regions for the integration tests, loaded into a fresh database so the
+-- serial IDs start at 1:
+--   region 1: NC_003888.3 (Streptomyces), NRPS, KnownClusterBlast hit to streptomycin
+--   region 2: NC_003888.3 (Streptomyces), lanthipeptide on a contig edge
+--   region 3: NC_004129.6 (Pseudomonas), T1PKS/NRPS/T2PKS hybrid
+
+SET search_path TO antismash;
+
+INSERT INTO taxa (ncbi_taxid, superkingdom, kingdom, phylum, class, taxonomic_order, family, genus, species, strain, name) VALUES
+    (100226, 'Bacteria', NULL, 'Actinomycetota', 'Actinomycetes', 'Kitasatosporales', 'Streptomycetaceae', 'Streptomyces', 'coelicolor', 'A3(2)', 'Streptomyces coelicolor A3(2)'),
+    (220664, 'Bacteria', NULL, 'Pseudomonadota', 'Gammaproteobacteria', 'Pseudomonadales', 'Pseudomonadaceae', 'Pseudomonas', 'fluorescens', 'Pf-5', 'Pseudomonas fluorescens Pf-5');
+
+INSERT INTO genomes (tax_id, assembly_id) VALUES
+    (1, 'GCF_000203835.1'),
+    (2, 'GCF_000012265.1');
+
+INSERT INTO dna_sequences (accession, version, definition, genome_id, record_number, dna) VALUES
+    ('NC_003888', 3, 'Streptomyces coelicolor A3(2) complete genome', 1, 1,
+        'ATGAGCACCAACGGCGCCGCCGGCCTGTGAATGAAGAAGCTGTGCAGCAGCTGCTGGTGA'),
+    ('NC_004129', 6, 'Pseudomonas fluorescens Pf-5 complete genome', 2, 1,
+        'ATGCCGAAAGGCCTGGCCGCGCTGCTGGCGGCCCTGGGCAAGTGACCGGCATGA');
+
+INSERT INTO regions (accession, region_number, location, start_pos, end_pos, contig_edge) VALUES
+    ('NC_003888', 1, '[0:30]', 0, 30, false),
+    ('NC_003888', 2, '[30:60]', 30, 60, true),
+    ('NC_004129', 1, '[0:54]', 0, 54, false);
+
+INSERT INTO bgc_categories (category, description) VALUES
+    ('NRPS', 'Non-ribosomal peptide'),
+    ('PKS', 'Polyketide'),
+    ('RiPP', 'Ribosomally synthesised and post-translationally modified peptide');
+
+INSERT INTO bgc_types (term, description, category) VALUES
+    ('NRPS', 'Non-ribosomal peptide synthetase', 'NRPS'),
+    ('T1PKS', 'Type I PKS', 'PKS'),
+    ('lanthipeptide-class-i', 'Class I lanthipeptide', 'RiPP'),
+    ('T2PKS', 'Type II PKS', 'PKS');
+
+INSERT INTO rel_regions_types (region_id, bgc_type_id) VALUES
+    (1, 1), (2, 3), (3, 1), (3, 2), (3, 4);
+
+INSERT INTO candidate_types (description) VALUES
+    ('single'), ('neighbouring'), ('chemical hybrid');
+
+INSERT INTO candidates (region_id, candidate_type_id, candidate_number, location, start_pos, end_pos, smiles) VALUES
+    (1, 1, 1, '[0:30]', 0, 30, 'NC(C)C(=O)O'),
+    (3, 3, 1, '[0:54]', 0, 54, 'c1ccccc1C(=O)O'),
+    (3, 2, 2, '[0:54]', 0, 54, NULL);
+
+INSERT INTO protoclusters (region_id, protocluster_number, bgc_type_id, location, start_pos, end_pos, core_location, core_start, core_end) VALUES
+    (1, 1, 1, '[0:30]', 0, 30, '[0:30]', 0, 30),
+    (2, 1, 3, '[30:60]', 30, 60, '[30:60]', 30, 60),
+    (3, 1, 2, '[0:54]', 0, 54, '[0:30]', 0, 30),
+    (3, 2, 4, '[0:54]', 0, 54, '[20:54]', 20, 54);
+
+INSERT INTO substrates (name, description) VALUES
+    ('ala', 'Alanine'),
+    ('mal', 'Malonyl-CoA');
+
+INSERT INTO monomers (substrate_id, name, description) VALUES
+    (1, 'ala', 'Alanine'),
+    (2, 'mal', 'Malonyl-CoA'),
+    (2, 'ohmal', 'Hydroxymalonyl-CoA');
+
+INSERT INTO cdss (region_id, locus_tag, protein_id, product, translation, location) VALUES
+    (1, 'SCO0001', 'NP_000001.1', 'peptide synthetase', 'MSTNGAAGL', '[0:30](+)'),
+    (2, 'SCO0002', 'NP_000002.1', 'lanthipeptide', 'MKKLCSSCW', '[30:60](-)'),
+    (3, 'PFL_0001', 'YP_000001.1', 'polyketide synthase', 'MPKGLAALLAALGK', '[0:45](+)'),
+    (3, 'PFL_0002', 'YP_000002.1', 'peptide synthetase', 'MTGMQ', '[10:40](-)');
+
+INSERT INTO modules (region_id, location, start_pos, end_pos, type, complete, iterative, multi_gene) VALUES
+    (1, '[0:30](+)', 0, 30, 'nrps', true, false, false),
+    (3, '[0:45](+)', 0, 45, 'pks', true, false, true);
+
+INSERT INTO rel_modules_monomers (module_id, substrate, monomer) VALUES
+    (1, 1, 1),
+    (2, 2, 3);
+
+INSERT INTO profiles (name, description) VALUES
+    ('AMP-binding', 'AMP-binding enzyme'),
+    ('PKS_KS', 'Ketosynthase domain'),
+    ('LANC_like', 'Lanthionine synthetase C-like protein');
+
+INSERT INTO profile_hits (cds_id, name, evalue, bitscore, seeds) VALUES
+    (1, 'AMP-binding', 1e-50, 180.5, 10),
+    (2, 'LANC_like', 1e-20, 75.0, 5),
+    (3, 'AMP-binding', 1e-40, 150.0, 10),
+    (3, 'PKS_KS', 1e-60, 210.0, 20);
+
+INSERT INTO resfams (accession, name, description) VALUES
+    ('RF0001', 'ABC_efflux', 'ABC efflux pump');
+
+INSERT INTO resfam_domains (cds_id, resfam_id, score, evalue, location) VALUES
+    (4, 1, 120.0, 1e-30, '[0:5]');
+
+INSERT INTO pfams (pfam_id, name, description) VALUES
+    ('PF00501', 'AMP-binding', 'AMP-binding enzyme'),
+    ('PF00109', 'ketoacyl-synt', 'Beta-ketoacyl synthase, N-terminal domain');
+
+INSERT INTO pfam_domains (cds_id, pfam_id, score, evalue, location) VALUES
+    (1, 'PF00501', 180.0, 1e-50, '[0:9]'),
+    (3, 'PF00501', 150.0, 1e-40, '[0:7]'),
+    (3, 'PF00109', 200.0, 1e-60, '[7:14]');
+
+INSERT INTO tigrfams (tigrfam_id, name, description) VALUES
+    ('TIGR01720', 'NRPS-para261', 'non-ribosomal peptide synthase domain');
+
+INSERT INTO tigrfam_domains (cds_id, tigrfam_id, score, evalue, location) VALUES
+    (1, 'TIGR01720', 90.0, 1e-25, '[0:9]');
+
+INSERT INTO gene_ontologies (identifier, description) VALUES
+    ('GO:0003824', 'catalytic activity');
+
+INSERT INTO pfam_go_entries (pfam_domain_id, go_id) VALUES
+    (3, 1);
+
+INSERT INTO as_domain_profiles (name, description) VALUES
+    ('AMP-binding', 'Adenylation domain'),
+    ('PCP', 'Peptidyl carrier protein'),
+    ('PKS_KS', 'Ketosynthase'),
+    ('PKS_AT', 'Acyltransferase');
+
+-- The domains of the reverse strand CDS 4 are listed from the C to the N terminus
+INSERT INTO as_domains (cds_id, module_id, as_domain_profile_id, location, start_pos, end_pos) VALUES
+    (1, 1, 1, '[0:4](+)', 10, 100),
+    (1, 1, 2, '[4:9](+)', 200, 260),
+    (3, 2, 3, '[0:7](+)', 10, 120),
+    (3, 2, 4, '[7:14](+)', 300, 400),
+    (4, NULL, 2, '[0:2](-)', 10, 70),
+    (4, NULL, 1, '[2:5](-)', 300, 390);
+
+INSERT INTO as_domain_subtypes (subtype, description) VALUES
+    ('Trans-AT-KS', 'trans-AT PKS ketosynthase');
+
+INSERT INTO rel_as_domain_to_subtype (as_domain_id, subtype) VALUES
+    (3, 'Trans-AT-KS');
+
+INSERT INTO t2pks (protocluster_id) VALUES (4);
+INSERT INTO t2pks_starters (t2pks_id, name) VALUES (1, 'acetyl-CoA');
+INSERT INTO t2pks_starter_elongation (domain_id, elongation) VALUES (1, 7);
+INSERT INTO t2pks_product_classes (t2pks_id, product_class) VALUES (1, 'angucycline');
+INSERT INTO t2pks_profiles (name, description) VALUES ('KSIII', 'Ketosynthase III');
+INSERT INTO t2pks_cds_domain (t2pks_id, profile_id, cds_id) VALUES (1, 1, 4);
+
+INSERT INTO smcogs (name, description) VALUES
+    ('SMCOG1002', 'AMP-dependent synthetase and ligase');
+
+INSERT INTO smcog_hits (cds_id, smcog_id, score, evalue) VALUES
+    (1, 1, 300.0, 1e-90);
+
+INSERT INTO regulators (name, description) VALUES ('ArgR', 'Arginine repressor');
+INSERT INTO regulator_confidence (strength) VALUES (2);
+INSERT INTO binding_sites (region_id, regulator_id, confidence_id, score, start_pos) VALUES
+    (2, 1, 1, 18.5, 35);
+
+INSERT INTO ripps (protocluster_id, peptide_sequence, subclass, cds_id) VALUES
+    (2, 'MSKKITGAIGCGSCVTCS', 'Class I', 2);
+
+INSERT INTO comparippson_mibig_references (accession, name, compound, product) VALUES
+    ('BGC0000535', 'nisA', 'nisin A', 'lanthipeptide');
+
+INSERT INTO comparippson_hits (region_id, comparippson_mibig_id, similarity) VALUES
+    (2, 1, 0.95);
+
+INSERT INTO clusterblast_algorithms (name) VALUES
+    ('clusterblast'), ('knownclusterblast'), ('subclusterblast');
+
+INSERT INTO clusterblast_hits (region_id, algorithm_id, rank, acc, description, similarity) VALUES
+    (1, 2, 1, 'BGC0000315', 'streptomycin', 40),
+    (2, 2, 1, 'BGC0000535', 'nisin', 60),
+    (3, 1, 1, 'NC_003888_c1', 'Streptomyces coelicolor A3(2) region 1', 20),
+    (3, 3, 1, 'AJ007731_c1', 'methylmalonyl-CoA biosynthesis', 50);
+
+-- Created by a migration, so the seed is loaded after migrating
+INSERT INTO compound_references (source, accession, name, smiles, mibig_accession) VALUES
+    ('npatlas', 'NPA000001', 'streptomycin', NULL, 'BGC0000315');
+
+RESET search_path;
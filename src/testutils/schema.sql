@@ -0,0 +1,94 @@
+-- License: GNU Affero General Public License v3 or later
+-- A copy of GNU AGPL v3 should have been included in this software package in LICENSE.txt.
+
+-- Miniature antiSMASH schema with the tables and columns the API queries, for tests and
+-- local development. The full schema comes with the import tooling. Load it before
+-- running the migrations, some of them extend the antiSMASH tables.
+
+CREATE SCHEMA IF NOT EXISTS antismash;
+SET search_path TO antismash;
+
+CREATE TABLE taxa (
+    tax_id serial PRIMARY KEY,
+    ncbi_taxid int,
+    superkingdom text, kingdom text, phylum text, class text, taxonomic_order text,
+    family text, genus text, species text, strain text, name text
+);
+CREATE TABLE genomes (
+    genome_id serial PRIMARY KEY,
+    tax_id int NOT NULL REFERENCES taxa,
+    bio_project text, bio_sample text,
+    assembly_id text NOT NULL
+);
+CREATE TABLE dna_sequences (
+    accession text PRIMARY KEY,
+    version int,
+    definition text,
+    genome_id int NOT NULL REFERENCES genomes,
+    record_number int NOT NULL,
+    dna text,
+    md5 text
+);
+CREATE TABLE regions (
+    region_id serial PRIMARY KEY,
+    accession text NOT NULL REFERENCES dna_sequences,
+    region_number int NOT NULL,
+    location text NOT NULL,
+    start_pos int NOT NULL,
+    end_pos int NOT NULL,
+    contig_edge bool NOT NULL,
+    best_mibig_hit_similarity int,
+    best_mibig_hit_description text,
+    best_mibig_hit_acc text
+);
+CREATE TABLE bgc_categories (category text PRIMARY KEY, description text NOT NULL, parent_category text);
+CREATE TABLE bgc_types (bgc_type_id serial PRIMARY KEY, term text NOT NULL, description text NOT NULL, category text NOT NULL REFERENCES bgc_categories);
+CREATE TABLE rel_regions_types (region_id int NOT NULL REFERENCES regions, bgc_type_id int NOT NULL REFERENCES bgc_types);
+CREATE TABLE candidate_types (candidate_type_id serial PRIMARY KEY, description text NOT NULL);
+CREATE TABLE candidates (candidate_id serial PRIMARY KEY, region_id int NOT NULL REFERENCES regions, candidate_type_id int NOT NULL REFERENCES candidate_types, candidate_number int NOT NULL, location text NOT NULL, start_pos int NOT NULL, end_pos int NOT NULL, smiles text, polymer text);
+CREATE TABLE protoclusters (protocluster_id serial PRIMARY KEY, region_id int NOT NULL REFERENCES regions, protocluster_number int NOT NULL, bgc_type_id int NOT NULL REFERENCES bgc_types, location text NOT NULL, start_pos int NOT NULL, end_pos int NOT NULL, core_location text NOT NULL, core_start int NOT NULL, core_end int NOT NULL);
+CREATE TABLE substrates (substrate_id serial PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE monomers (monomer_id serial PRIMARY KEY, substrate_id int REFERENCES substrates, name text NOT NULL, description text);
+CREATE TABLE cdss (
+    cds_id serial PRIMARY KEY,
+    region_id int NOT NULL REFERENCES regions,
+    locus_tag text, protein_id text, gene_id text, product text,
+    functional_class text,
+    translation text,
+    location text NOT NULL
+);
+CREATE TABLE modules (module_id serial PRIMARY KEY, region_id int NOT NULL REFERENCES regions, location text NOT NULL, start_pos int NOT NULL, end_pos int NOT NULL, type text NOT NULL, complete bool NOT NULL, iterative bool NOT NULL, multi_gene bool NOT NULL);
+CREATE TABLE rel_modules_monomers (module_id int NOT NULL REFERENCES modules, substrate int NOT NULL REFERENCES substrates, monomer int NOT NULL REFERENCES monomers);
+CREATE TABLE profiles (name text PRIMARY KEY, description text);
+CREATE TABLE profile_hits (cds_id int NOT NULL REFERENCES cdss, name text NOT NULL, evalue float8, bitscore float8, seeds int);
+CREATE TABLE resfams (resfam_id serial PRIMARY KEY, accession text NOT NULL, name text NOT NULL, description text);
+CREATE TABLE resfam_domains (resfam_domain_id serial PRIMARY KEY, cds_id int NOT NULL REFERENCES cdss, resfam_id int NOT NULL REFERENCES resfams, score float8, evalue float8, location text);
+CREATE TABLE pfams (pfam_id text PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE pfam_domains (pfam_domain_id serial PRIMARY KEY, cds_id int NOT NULL REFERENCES cdss, pfam_id text NOT NULL REFERENCES pfams, score float8, evalue float8, location text NOT NULL, translation text);
+CREATE TABLE tigrfams (tigrfam_id text PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE tigrfam_domains (tigrfam_domain_id serial PRIMARY KEY, cds_id int NOT NULL REFERENCES cdss, tigrfam_id text NOT NULL REFERENCES tigrfams, score float8, evalue float8, location text NOT NULL);
+CREATE TABLE gene_ontologies (go_id serial PRIMARY KEY, identifier text NOT NULL, description text NOT NULL);
+CREATE TABLE pfam_go_entries (pfam_domain_id int NOT NULL REFERENCES pfam_domains, go_id int NOT NULL REFERENCES gene_ontologies);
+CREATE TABLE as_domain_profiles (as_domain_profile_id serial PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE as_domains (as_domain_id serial PRIMARY KEY, cds_id int NOT NULL REFERENCES cdss, module_id int REFERENCES modules, as_domain_profile_id int NOT NULL REFERENCES as_domain_profiles, location text NOT NULL, start_pos int, end_pos int, score float8, evalue float8, translation text);
+CREATE TABLE as_domain_subtypes (subtype text PRIMARY KEY, description text);
+CREATE TABLE rel_as_domain_to_subtype (as_domain_id int NOT NULL REFERENCES as_domains, subtype text NOT NULL REFERENCES as_domain_subtypes);
+CREATE TABLE t2pks (t2pks_id serial PRIMARY KEY, protocluster_id int NOT NULL REFERENCES protoclusters);
+CREATE TABLE t2pks_starters (domain_id serial PRIMARY KEY, t2pks_id int NOT NULL REFERENCES t2pks, name text NOT NULL);
+CREATE TABLE t2pks_starter_elongation (domain_id int NOT NULL REFERENCES t2pks_starters, elongation int NOT NULL);
+CREATE TABLE t2pks_product_classes (t2pks_id int NOT NULL REFERENCES t2pks, product_class text NOT NULL);
+CREATE TABLE t2pks_profiles (profile_id serial PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE t2pks_cds_domain (t2pks_id int NOT NULL REFERENCES t2pks, profile_id int NOT NULL REFERENCES t2pks_profiles, cds_id int REFERENCES cdss);
+CREATE TABLE smcogs (smcog_id serial PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE smcog_hits (cds_id int NOT NULL REFERENCES cdss, smcog_id int NOT NULL REFERENCES smcogs, score float8, evalue float8);
+CREATE TABLE regulators (regulator_id serial PRIMARY KEY, name text NOT NULL, description text);
+CREATE TABLE regulator_confidence (confidence_id serial PRIMARY KEY, strength smallint NOT NULL);
+CREATE TABLE binding_sites (binding_site_id serial PRIMARY KEY, region_id int NOT NULL REFERENCES regions, regulator_id int NOT NULL REFERENCES regulators, confidence_id int NOT NULL REFERENCES regulator_confidence, score float8 NOT NULL, start_pos int NOT NULL);
+CREATE TABLE ripps (protocluster_id int NOT NULL REFERENCES protoclusters, peptide_sequence text NOT NULL, subclass text, monoisotopic_mass float8, molecular_weight float8, cds_id int REFERENCES cdss);
+CREATE TABLE comparippson_mibig_references (comparippson_mibig_id serial PRIMARY KEY, accession text NOT NULL, name text NOT NULL, compound text, product text);
+CREATE TABLE comparippson_hits (region_id int NOT NULL REFERENCES regions, comparippson_mibig_id int NOT NULL REFERENCES comparippson_mibig_references, similarity float8);
+CREATE TABLE cluster_compare_hits (cluster_compare_hit_id serial PRIMARY KEY, region_id int REFERENCES regions, protocluster_id int REFERENCES protoclusters, reference_accession text NOT NULL, description text NOT NULL, score float8 NOT NULL, identity_metric float8, order_metric float8, components_metric float8);
+CREATE TABLE clusterblast_algorithms (algorithm_id serial PRIMARY KEY, name text NOT NULL);
+CREATE TABLE clusterblast_hits (clusterblast_hit_id serial PRIMARY KEY, region_id int NOT NULL REFERENCES regions, algorithm_id int NOT NULL REFERENCES clusterblast_algorithms, rank int NOT NULL, acc text NOT NULL, description text NOT NULL, similarity int);
+
+RESET search_path;